@@ -0,0 +1,923 @@
+// src-tauri/src/agent.rs
+// Backend agent executor: the model works on a task by calling workspace tools
+// in a loop until it answers without tool calls.
+//
+// - Writes and shell commands wait for a human decision (diff / command preview);
+//   the run pauses as AwaitingApproval and continues on resume.
+// - Every run keeps a full transcript and is checkpointed to
+//   {app_data}/agent_runs/{id}.json, so it can be resumed after a restart.
+// - The model is any `model_router::ChatModel`: a remote provider, a local GGUF
+//   model, a per-task fallback chain or a test script.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::commands;
+use crate::model_router::{self, ChatModel, ModelTarget};
+use crate::providers::{ChatRequest, Message, Role, ToolCall, ToolDefinition};
+use crate::usage::{self, UsageEvent};
+
+const MAX_READ_CHARS: usize = 64 * 1024;
+const MAX_OUTPUT_CHARS: usize = 16 * 1024;
+const MAX_LISTED_FILES: usize = 500;
+
+// --------------------
+// RUN STATE
+// --------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentConfig {
+    /// Model turns before the run stops with StepLimit
+    #[serde(default = "default_max_steps")]
+    pub max_steps: u32,
+    #[serde(default = "default_max_tool_calls")]
+    pub max_tool_calls: u32,
+    #[serde(default)]
+    pub auto_approve_writes: bool,
+    #[serde(default)]
+    pub auto_approve_commands: bool,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Replaces the default agent system prompt
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+fn default_max_steps() -> u32 { 20 }
+fn default_max_tool_calls() -> u32 { 60 }
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            max_steps: default_max_steps(),
+            max_tool_calls: default_max_tool_calls(),
+            auto_approve_writes: false,
+            auto_approve_commands: false,
+            temperature: None,
+            system_prompt: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentStatus {
+    Running,
+    AwaitingApproval,
+    Completed,
+    StepLimit,
+    Cancelled,
+    Failed,
+}
+
+/// A gated tool call waiting for (or holding) a human decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub call: ToolCall,
+    pub summary: String,
+    /// Unified diff for write_file
+    pub diff: Option<String>,
+    pub approved: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptEvent {
+    Task { text: String },
+    Assistant { text: String },
+    ToolCall { call: ToolCall },
+    ToolResult { call_id: String, name: String, output: String, is_error: bool },
+    ApprovalRequested { call_id: String, summary: String },
+    Decision { call_id: String, approved: bool },
+    Status { status: AgentStatus, message: Option<String> },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptEntry {
+    pub step: u32,
+    pub at: u64,
+    #[serde(flatten)]
+    pub event: TranscriptEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentRun {
+    pub id: String,
+    pub task: String,
+    pub workspace: String,
+    pub config: AgentConfig,
+    pub status: AgentStatus,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    pub steps: u32,
+    pub tool_calls: u32,
+    pub messages: Vec<Message>,
+    pub pending: Vec<PendingAction>,
+    pub transcript: Vec<TranscriptEntry>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentRunSummary {
+    pub id: String,
+    pub task: String,
+    pub workspace: String,
+    pub status: AgentStatus,
+    pub steps: u32,
+    pub pending: usize,
+    pub updated_at: u64,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn default_system_prompt(workspace: &str) -> String {
+    format!(
+        "You are a coding agent working in the workspace at {}. Use the tools to inspect and change files; \
+         paths are relative to the workspace root. Read files before editing them and write complete file contents. \
+         When the task is done, reply with a short summary and no tool calls.",
+        workspace
+    )
+}
+
+impl AgentRun {
+    pub fn new(id: impl Into<String>, task: impl Into<String>, workspace: impl Into<String>, config: AgentConfig) -> Self {
+        let (task, workspace) = (task.into(), workspace.into());
+        let system = config.system_prompt.clone().unwrap_or_else(|| default_system_prompt(&workspace));
+        let mut run = Self {
+            id: id.into(),
+            task: task.clone(),
+            workspace,
+            config,
+            status: AgentStatus::Running,
+            result: None,
+            error: None,
+            steps: 0,
+            tool_calls: 0,
+            messages: vec![Message::new(Role::System, system), Message::new(Role::User, task.clone())],
+            pending: Vec::new(),
+            transcript: Vec::new(),
+            created_at: now_secs(),
+            updated_at: now_secs(),
+        };
+        run.transcript.push(TranscriptEntry { step: 0, at: now_secs(), event: TranscriptEvent::Task { text: task } });
+        run
+    }
+
+    pub fn summary(&self) -> AgentRunSummary {
+        AgentRunSummary {
+            id: self.id.clone(),
+            task: self.task.clone(),
+            workspace: self.workspace.clone(),
+            status: self.status,
+            steps: self.steps,
+            pending: self.pending.iter().filter(|p| p.approved.is_none()).count(),
+            updated_at: self.updated_at,
+        }
+    }
+
+    fn record(&mut self, observer: &mut dyn AgentObserver, event: TranscriptEvent) {
+        let entry = TranscriptEntry { step: self.steps, at: now_secs(), event };
+        observer.entry(&self.id, &entry);
+        self.transcript.push(entry);
+        self.updated_at = now_secs();
+    }
+
+    fn finish(&mut self, observer: &mut dyn AgentObserver, status: AgentStatus, message: Option<String>) {
+        self.status = status;
+        if status == AgentStatus::Failed {
+            self.error = message.clone();
+        }
+        self.record(observer, TranscriptEvent::Status { status, message });
+        observer.checkpoint(self);
+    }
+
+    /// Approve or reject a pending action; takes effect on the next `drive`
+    pub fn decide(&mut self, call_id: &str, approved: bool) -> Result<(), String> {
+        let action = self.pending.iter_mut()
+            .find(|p| p.call.id == call_id)
+            .ok_or_else(|| format!("Bekleyen işlem bulunamadı: {}", call_id))?;
+        action.approved = Some(approved);
+        let entry = TranscriptEntry {
+            step: self.steps,
+            at: now_secs(),
+            event: TranscriptEvent::Decision { call_id: call_id.to_string(), approved },
+        };
+        self.transcript.push(entry);
+        self.updated_at = now_secs();
+        Ok(())
+    }
+
+    /// Answer tool calls that never got a result (app closed mid-step), so the
+    /// conversation stays valid for every provider
+    pub fn repair_interrupted(&mut self) -> usize {
+        let Some(last) = self.messages.iter().rposition(|m| m.role == Role::Assistant && !m.tool_calls.is_empty()) else {
+            return 0;
+        };
+        let answered: HashSet<String> = self.messages[last + 1..].iter()
+            .filter_map(|m| m.tool_call_id.clone())
+            .chain(self.pending.iter().map(|p| p.call.id.clone()))
+            .collect();
+        let missing: Vec<String> = self.messages[last].tool_calls.iter()
+            .filter(|c| !answered.contains(&c.id))
+            .map(|c| c.id.clone())
+            .collect();
+        for id in &missing {
+            self.messages.push(Message::tool_result(id.clone(), "Hata: araç çalışırken işlem yarıda kesildi"));
+        }
+        missing.len()
+    }
+}
+
+/// Hooks for live progress; `()` ignores everything
+pub trait AgentObserver: Send {
+    fn entry(&mut self, _run_id: &str, _entry: &TranscriptEntry) {}
+    /// Called at step boundaries with a consistent run state
+    fn checkpoint(&mut self, _run: &AgentRun) {}
+}
+
+impl AgentObserver for () {}
+
+// --------------------
+// WORKSPACE TOOLS
+// --------------------
+
+fn tool(name: &str, description: &str, parameters: Value) -> ToolDefinition {
+    ToolDefinition { name: name.to_string(), description: description.to_string(), parameters }
+}
+
+pub fn workspace_tools() -> Vec<ToolDefinition> {
+    let path = json!({ "type": "string", "description": "Path relative to the workspace root" });
+    vec![
+        tool("read_file", "Read a text file from the workspace.", json!({
+            "type": "object", "properties": { "path": path }, "required": ["path"],
+        })),
+        tool("write_file", "Create or overwrite a file with the given full content. Needs user approval.", json!({
+            "type": "object",
+            "properties": { "path": path, "content": { "type": "string" } },
+            "required": ["path", "content"],
+        })),
+        tool("get_all_files", "List files in the workspace (or a sub-directory), skipping build and dependency folders.", json!({
+            "type": "object", "properties": { "path": path },
+        })),
+        tool("semantic_search", "Search the indexed codebase by meaning.", json!({
+            "type": "object",
+            "properties": { "query": { "type": "string" }, "limit": { "type": "integer" }, "path": path },
+            "required": ["query"],
+        })),
+        tool("parse_file_ast", "List symbols, imports and exports of a source file.", json!({
+            "type": "object", "properties": { "path": path }, "required": ["path"],
+        })),
+        tool("execute_command", "Run an allow-listed command (no shell) in the workspace root. Needs user approval.", json!({
+            "type": "object",
+            "properties": { "command": { "type": "string" }, "args": { "type": "array", "items": { "type": "string" } } },
+            "required": ["command"],
+        })),
+        tool("git_status", "Show staged, modified and untracked files.", json!({ "type": "object", "properties": {} })),
+    ]
+}
+
+fn arg_str<'a>(call: &'a ToolCall, key: &str) -> Result<&'a str, String> {
+    call.arguments[key].as_str().ok_or_else(|| format!("`{}` argümanı eksik", key))
+}
+
+fn truncate(mut text: String, max: usize) -> String {
+    if text.len() > max {
+        let mut cut = max;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        text.truncate(cut);
+        text.push_str("\n… (kısaltıldı)");
+    }
+    text
+}
+
+/// Tools bound to one workspace root; every path is confined to it
+pub struct Workspace {
+    root: PathBuf,
+    app: Option<AppHandle>,
+}
+
+impl Workspace {
+    pub fn new(root: &str, app: Option<AppHandle>) -> Result<Self, String> {
+        let root = std::fs::canonicalize(root).map_err(|e| format!("Çalışma alanı bulunamadı ({}): {}", root, e))?;
+        Ok(Self { root, app })
+    }
+
+    /// Resolve `path` under the root, refusing anything that escapes it either
+    /// lexically or through a symlink
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let joined = if Path::new(path).is_absolute() { PathBuf::from(path) } else { self.root.join(path) };
+        let mut out = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !out.pop() {
+                        return Err(format!("Geçersiz yol: {}", path));
+                    }
+                }
+                other => out.push(other),
+            }
+        }
+        if !out.starts_with(&self.root) {
+            return Err(format!("Güvenlik: '{}' çalışma alanının dışında", path));
+        }
+
+        // Canonicalize the deepest existing ancestor (the path itself unless it is a
+        // new file) so symlinks inside the workspace can't point outside it
+        let mut existing = out.as_path();
+        while std::fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().ok_or_else(|| format!("Geçersiz yol: {}", path))?;
+        }
+        let real = std::fs::canonicalize(existing).map_err(|e| format!("Yol çözümlenemedi ({}): {}", path, e))?;
+        if !real.starts_with(&self.root) {
+            return Err(format!("Güvenlik: '{}' bir bağlantı üzerinden çalışma alanının dışını gösteriyor", path));
+        }
+        let rest = out.strip_prefix(existing).map_err(|e| e.to_string())?;
+        Ok(real.join(rest))
+    }
+
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root).unwrap_or(path).to_string_lossy().replace('\\', "/")
+    }
+
+    pub fn needs_approval(call: &ToolCall, config: &AgentConfig) -> bool {
+        match call.name.as_str() {
+            "write_file" => !config.auto_approve_writes,
+            "execute_command" => !config.auto_approve_commands,
+            _ => false,
+        }
+    }
+
+    /// Human-readable description and, for writes, a diff against the current file
+    pub fn preview(&self, call: &ToolCall) -> (String, Option<String>) {
+        match call.name.as_str() {
+            "write_file" => {
+                let path = call.arguments["path"].as_str().unwrap_or("?");
+                let new = call.arguments["content"].as_str().unwrap_or("");
+                let old = self.resolve(path).ok().and_then(|p| std::fs::read_to_string(p).ok());
+                let summary = match &old {
+                    Some(_) => format!("{} dosyasını güncelle", path),
+                    None => format!("{} dosyasını oluştur", path),
+                };
+                (summary, Some(line_diff(old.as_deref().unwrap_or(""), new, path)))
+            }
+            "execute_command" => {
+                let args: Vec<&str> = call.arguments["args"].as_array().into_iter().flatten().filter_map(|a| a.as_str()).collect();
+                let command = call.arguments["command"].as_str().unwrap_or("?");
+                (format!("Komut çalıştır: {} {}", command, args.join(" ")).trim_end().to_string(), None)
+            }
+            _ => (call.name.clone(), None),
+        }
+    }
+
+    pub async fn run(&self, call: &ToolCall) -> Result<String, String> {
+        match call.name.as_str() {
+            "read_file" => {
+                let path = self.resolve(arg_str(call, "path")?)?;
+                let content = commands::read_file(path.to_string_lossy().into_owned())?;
+                Ok(truncate(content, MAX_READ_CHARS))
+            }
+            "write_file" => {
+                let path = self.resolve(arg_str(call, "path")?)?;
+                let content = arg_str(call, "content")?;
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                commands::write_file(path.to_string_lossy().into_owned(), content.to_string())?;
+                Ok(format!("{} yazıldı ({} bayt)", self.relative(&path), content.len()))
+            }
+            "get_all_files" => {
+                let dir = self.resolve(call.arguments["path"].as_str().unwrap_or("."))?;
+                let files = commands::get_all_files(dir.to_string_lossy().into_owned()).await?;
+                let total = files.len();
+                let mut listed: Vec<String> = files.iter().map(|f| self.relative(Path::new(f))).collect();
+                listed.sort();
+                listed.truncate(MAX_LISTED_FILES);
+                let mut out = listed.join("\n");
+                if total > MAX_LISTED_FILES {
+                    out.push_str(&format!("\n… ({} dosyadan ilk {})", total, MAX_LISTED_FILES));
+                }
+                Ok(out)
+            }
+            "semantic_search" => {
+                let app = self.app.clone().ok_or("Semantic search bu ortamda kullanılamıyor")?;
+                let filter = match call.arguments["path"].as_str() {
+                    Some(p) => Some(self.resolve(p)?.to_string_lossy().into_owned()),
+                    None => None,
+                };
+                let limit = call.arguments["limit"].as_u64().map(|n| n as usize);
+                let results = commands::semantic_search(arg_str(call, "query")?.to_string(), limit, filter, app).await?;
+                Ok(truncate(results["results"].to_string(), MAX_OUTPUT_CHARS))
+            }
+            "parse_file_ast" => {
+                let path = self.resolve(arg_str(call, "path")?)?;
+                let analysis = commands::parse_file_ast(path.to_string_lossy().into_owned()).await?;
+                Ok(truncate(serde_json::to_string(&analysis).map_err(|e| e.to_string())?, MAX_OUTPUT_CHARS))
+            }
+            "execute_command" => {
+                let command = arg_str(call, "command")?.to_string();
+                let args = call.arguments["args"].as_array().into_iter().flatten()
+                    .filter_map(|a| a.as_str().map(String::from))
+                    .collect();
+                let output = commands::execute_command(command, args, Some(self.root.to_string_lossy().into_owned())).await?;
+                Ok(truncate(output.to_string(), MAX_OUTPUT_CHARS))
+            }
+            "git_status" => {
+                let status = commands::git_status(self.root.to_string_lossy().into_owned()).await?;
+                Ok(status.to_string())
+            }
+            other => Err(format!("Bilinmeyen araç: {}", other)),
+        }
+    }
+}
+
+/// Unified diff of two texts with 3 lines of context
+pub fn line_diff(old: &str, new: &str, path: &str) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // LCS table; huge files fall back to a full replace
+    let ops: Vec<(char, &str)> = if a.len().saturating_mul(b.len()) > 4_000_000 {
+        a.iter().map(|l| ('-', *l)).chain(b.iter().map(|l| ('+', *l))).collect()
+    } else {
+        let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+            }
+        }
+        let (mut i, mut j, mut ops) = (0, 0, Vec::new());
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((' ', a[i]));
+                i += 1;
+                j += 1;
+            } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                ops.push(('-', a[i]));
+                i += 1;
+            } else {
+                ops.push(('+', b[j]));
+                j += 1;
+            }
+        }
+        ops
+    };
+
+    let mut out = format!("--- a/{}\n+++ b/{}\n", path, path);
+    let changed: Vec<usize> = ops.iter().enumerate().filter(|(_, (op, _))| *op != ' ').map(|(i, _)| i).collect();
+    let mut k = 0;
+    while k < changed.len() {
+        // Grow the hunk while the next change is within 6 lines
+        let start = changed[k].saturating_sub(3);
+        let mut end = changed[k];
+        while k + 1 < changed.len() && changed[k + 1] <= end + 7 {
+            k += 1;
+            end = changed[k];
+        }
+        let end = (end + 4).min(ops.len());
+        let old_start = ops[..start].iter().filter(|(op, _)| *op != '+').count() + 1;
+        let new_start = ops[..start].iter().filter(|(op, _)| *op != '-').count() + 1;
+        let old_len = ops[start..end].iter().filter(|(op, _)| *op != '+').count();
+        let new_len = ops[start..end].iter().filter(|(op, _)| *op != '-').count();
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+        for (op, line) in &ops[start..end] {
+            out.push(*op);
+            out.push_str(line);
+            out.push('\n');
+        }
+        k += 1;
+    }
+    out
+}
+
+// --------------------
+// EXECUTOR
+// --------------------
+
+async fn run_tool(run: &mut AgentRun, workspace: &Workspace, call: &ToolCall, observer: &mut dyn AgentObserver) {
+    let (output, is_error) = match workspace.run(call).await {
+        Ok(output) => (output, false),
+        Err(e) => (e, true),
+    };
+    let content = if is_error { format!("Hata: {}", output) } else { output.clone() };
+    run.messages.push(Message::tool_result(call.id.clone(), content));
+    run.record(observer, TranscriptEvent::ToolResult { call_id: call.id.clone(), name: call.name.clone(), output, is_error });
+}
+
+/// Advance `run` until it finishes, hits a limit or needs approval
+pub async fn drive(
+    run: &mut AgentRun,
+    model: &dyn ChatModel,
+    workspace: &Workspace,
+    cancel: &AtomicBool,
+    observer: &mut dyn AgentObserver,
+) {
+    run.status = AgentStatus::Running;
+    run.repair_interrupted();
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return run.finish(observer, AgentStatus::Cancelled, None);
+        }
+
+        // Settle decided approvals; stop while any are still open
+        if !run.pending.is_empty() {
+            if run.pending.iter().any(|p| p.approved.is_none()) {
+                return run.finish(observer, AgentStatus::AwaitingApproval, None);
+            }
+            for action in std::mem::take(&mut run.pending) {
+                if action.approved == Some(true) {
+                    run_tool(run, workspace, &action.call, observer).await;
+                } else {
+                    let output = "Kullanıcı bu işlemi reddetti".to_string();
+                    run.messages.push(Message::tool_result(action.call.id.clone(), output.clone()));
+                    run.record(observer, TranscriptEvent::ToolResult {
+                        call_id: action.call.id.clone(),
+                        name: action.call.name.clone(),
+                        output,
+                        is_error: true,
+                    });
+                }
+            }
+            observer.checkpoint(run);
+        }
+
+        if run.steps >= run.config.max_steps || run.tool_calls >= run.config.max_tool_calls {
+            let message = format!("{} adım / {} araç çağrısı sınırına ulaşıldı", run.steps, run.tool_calls);
+            return run.finish(observer, AgentStatus::StepLimit, Some(message));
+        }
+
+        if let Err(e) = usage::check_budget(None, "agent", Some(&run.workspace)) {
+            return run.finish(observer, AgentStatus::Failed, Some(e));
+        }
+        let request = ChatRequest {
+            messages: run.messages.clone(),
+            temperature: run.config.temperature,
+            tools: workspace_tools(),
+            ..Default::default()
+        };
+        let response = match model.complete(&request).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("⚠️ Agent model hatası ({}): {}", run.id, e);
+                return run.finish(observer, AgentStatus::Failed, Some(e));
+            }
+        };
+        usage::record(UsageEvent::from_response("agent", &model.label(), &request, &response).with_project(run.workspace.clone()));
+        run.steps += 1;
+        run.messages.push(Message::assistant_tool_calls(response.text.clone(), response.tool_calls.clone()));
+        if !response.text.is_empty() {
+            run.record(observer, TranscriptEvent::Assistant { text: response.text.clone() });
+        }
+
+        if response.tool_calls.is_empty() {
+            run.result = Some(response.text);
+            info!("✅ Agent tamamlandı: {} ({} adım)", run.id, run.steps);
+            return run.finish(observer, AgentStatus::Completed, None);
+        }
+
+        for call in &response.tool_calls {
+            run.tool_calls += 1;
+            run.record(observer, TranscriptEvent::ToolCall { call: call.clone() });
+            if Workspace::needs_approval(call, &run.config) {
+                let (summary, diff) = workspace.preview(call);
+                run.record(observer, TranscriptEvent::ApprovalRequested { call_id: call.id.clone(), summary: summary.clone() });
+                run.pending.push(PendingAction { call: call.clone(), summary, diff, approved: None });
+            } else {
+                run_tool(run, workspace, call, observer).await;
+            }
+        }
+        observer.checkpoint(run);
+    }
+}
+
+// --------------------
+// PERSISTENCE & COMMANDS
+// --------------------
+
+/// Cancel flags of runs currently being driven
+static ACTIVE_RUNS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn runs_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("agent_runs");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Agent klasörü oluşturulamadı: {}", e))?;
+    Ok(dir)
+}
+
+pub fn save_run(dir: &Path, run: &AgentRun) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(run).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(format!("{}.json", run.id)), json).map_err(|e| format!("Agent kaydı yazılamadı: {}", e))
+}
+
+pub fn load_run(dir: &Path, run_id: &str) -> Result<AgentRun, String> {
+    if run_id.contains(['/', '\\']) || run_id.contains("..") {
+        return Err(format!("Geçersiz run id: {}", run_id));
+    }
+    let text = std::fs::read_to_string(dir.join(format!("{}.json", run_id)))
+        .map_err(|_| format!("Agent çalıştırması bulunamadı: {}", run_id))?;
+    serde_json::from_str(&text).map_err(|e| format!("Agent kaydı okunamadı: {}", e))
+}
+
+pub fn agent_event_name(run_id: &str) -> String {
+    format!("agent-run:{}", run_id)
+}
+
+/// Emits transcript entries on `agent-run:{id}` and checkpoints to disk
+struct TauriObserver {
+    app: AppHandle,
+    dir: PathBuf,
+}
+
+impl AgentObserver for TauriObserver {
+    fn entry(&mut self, run_id: &str, entry: &TranscriptEntry) {
+        let _ = self.app.emit(&agent_event_name(run_id), entry);
+    }
+
+    fn checkpoint(&mut self, run: &AgentRun) {
+        if let Err(e) = save_run(&self.dir, run) {
+            warn!("⚠️ {}", e);
+        }
+    }
+}
+
+/// Removes a run from `ACTIVE_RUNS` however `drive_registered` exits
+struct ActiveRunGuard(String);
+
+impl Drop for ActiveRunGuard {
+    fn drop(&mut self) {
+        ACTIVE_RUNS.lock().unwrap_or_else(|p| p.into_inner()).remove(&self.0);
+    }
+}
+
+async fn drive_registered(app: &AppHandle, mut run: AgentRun, model: Option<ModelTarget>) -> Result<AgentRun, String> {
+    let target = model.unwrap_or_else(|| ModelTarget::Chain { task: "agent".to_string() });
+    let model = model_router::build_target(app, &target)?;
+    let workspace = Workspace::new(&run.workspace, Some(app.clone()))?;
+    let mut observer = TauriObserver { app: app.clone(), dir: runs_dir(app)? };
+    let (cancel, _active) = {
+        let mut active = ACTIVE_RUNS.lock().unwrap_or_else(|p| p.into_inner());
+        if active.contains_key(&run.id) {
+            return Err(format!("Agent zaten çalışıyor: {}", run.id));
+        }
+        let flag = Arc::new(AtomicBool::new(false));
+        active.insert(run.id.clone(), flag.clone());
+        (flag, ActiveRunGuard(run.id.clone()))
+    };
+    drive(&mut run, model.as_ref(), &workspace, &cancel, &mut observer).await;
+    observer.checkpoint(&run);
+    Ok(run)
+}
+
+/// Start a task; listen on `agent-run:{run_id}` for live transcript entries.
+/// Returns when the run completes, fails or pauses for approval. Without
+/// `model` the "agent" fallback chain is used.
+#[tauri::command]
+pub async fn start_agent_run(
+    app: AppHandle,
+    run_id: Option<String>,
+    task: String,
+    workspace: String,
+    model: Option<ModelTarget>,
+    config: Option<AgentConfig>,
+) -> Result<AgentRun, String> {
+    let run_id = run_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    info!("🤖 Agent başlatılıyor: {} ({})", run_id, workspace);
+    let run = AgentRun::new(run_id, task, workspace, config.unwrap_or_default());
+    drive_registered(&app, run, model).await
+}
+
+/// Record a decision for a pending write/command
+#[tauri::command]
+pub fn decide_agent_action(app: AppHandle, run_id: String, call_id: String, approved: bool) -> Result<AgentRun, String> {
+    let dir = runs_dir(&app)?;
+    let mut run = load_run(&dir, &run_id)?;
+    run.decide(&call_id, approved)?;
+    save_run(&dir, &run)?;
+    Ok(run)
+}
+
+/// Continue a paused, limited or interrupted run. `max_steps` raises the step limit.
+#[tauri::command]
+pub async fn resume_agent_run(
+    app: AppHandle,
+    run_id: String,
+    model: Option<ModelTarget>,
+    max_steps: Option<u32>,
+) -> Result<AgentRun, String> {
+    let mut run = load_run(&runs_dir(&app)?, &run_id)?;
+    if let Some(max_steps) = max_steps {
+        run.config.max_steps = max_steps;
+    }
+    info!("🤖 Agent devam ediyor: {} (adım {})", run_id, run.steps);
+    drive_registered(&app, run, model).await
+}
+
+#[tauri::command]
+pub fn get_agent_run(app: AppHandle, run_id: String) -> Result<AgentRun, String> {
+    load_run(&runs_dir(&app)?, &run_id)
+}
+
+#[tauri::command]
+pub fn list_agent_runs(app: AppHandle) -> Result<Vec<AgentRunSummary>, String> {
+    let dir = runs_dir(&app)?;
+    let mut runs: Vec<AgentRunSummary> = std::fs::read_dir(&dir)
+        .map_err(|e| e.to_string())?
+        .flatten()
+        .filter_map(|e| e.path().file_stem().map(|s| s.to_string_lossy().into_owned()))
+        .filter_map(|id| load_run(&dir, &id).ok())
+        .map(|run| run.summary())
+        .collect();
+    runs.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(runs)
+}
+
+/// Stop a running agent at the next step boundary; false if it isn't running
+#[tauri::command]
+pub fn cancel_agent_run(run_id: String) -> bool {
+    match ACTIVE_RUNS.lock().unwrap_or_else(|p| p.into_inner()).get(&run_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            info!("🛑 Agent iptal edildi: {}", run_id);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::ChatResponse;
+    use std::collections::VecDeque;
+
+    fn temp_workspace() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corex_agent_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+        dir
+    }
+
+    fn call(id: &str, name: &str, arguments: Value) -> ToolCall {
+        ToolCall { id: id.into(), name: name.into(), arguments }
+    }
+
+    fn reply(text: &str, tool_calls: Vec<ToolCall>) -> ChatResponse {
+        ChatResponse { text: text.into(), tool_calls, ..ChatResponse::empty("scripted") }
+    }
+
+    /// Plays back canned responses and records every request it saw
+    struct ScriptedModel {
+        replies: Mutex<VecDeque<ChatResponse>>,
+        seen: Mutex<Vec<ChatRequest>>,
+    }
+
+    impl ScriptedModel {
+        fn new(replies: Vec<ChatResponse>) -> Self {
+            Self { replies: Mutex::new(replies.into()), seen: Mutex::new(Vec::new()) }
+        }
+    }
+
+    impl ChatModel for ScriptedModel {
+        fn label(&self) -> String {
+            "scripted".to_string()
+        }
+
+        fn complete<'a>(&'a self, request: &'a ChatRequest) -> model_router::ModelFuture<'a> {
+            self.seen.lock().unwrap().push(request.clone());
+            let next = self.replies.lock().unwrap().pop_front().ok_or_else(|| "script bitti".to_string());
+            Box::pin(async move { next })
+        }
+    }
+
+    #[test]
+    fn test_resolve_stays_in_workspace() {
+        let dir = temp_workspace();
+        let ws = Workspace::new(dir.to_str().unwrap(), None).unwrap();
+        assert!(ws.resolve("src/../src/lib.rs").unwrap().ends_with("src/lib.rs"));
+        assert!(ws.resolve("../outside.txt").is_err());
+        assert!(ws.resolve("/etc/passwd").is_err());
+        assert!(ws.resolve("src/new/file.rs").unwrap().ends_with("src/new/file.rs"));
+
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("corex_agent_out_{}", uuid::Uuid::new_v4().simple()));
+            std::fs::create_dir_all(&outside).unwrap();
+            std::fs::write(outside.join("secret.txt"), "x").unwrap();
+            std::os::unix::fs::symlink(&outside, dir.join("link")).unwrap();
+            assert!(ws.resolve("link/secret.txt").is_err());
+            assert!(ws.resolve("link/new.txt").is_err());
+            assert!(ws.resolve("link").is_err());
+            std::fs::remove_dir_all(outside).ok();
+        }
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let diff = line_diff("a\nb\nc\n", "a\nB\nc\nd\n", "x.txt");
+        assert_eq!(diff, "--- a/x.txt\n+++ b/x.txt\n@@ -1,3 +1,4 @@\n a\n-b\n+B\n c\n+d\n");
+        assert_eq!(line_diff("same\n", "same\n", "x"), "--- a/x\n+++ b/x\n");
+    }
+
+    #[tokio::test]
+    async fn test_agent_reads_then_answers() {
+        let dir = temp_workspace();
+        let ws = Workspace::new(dir.to_str().unwrap(), None).unwrap();
+        let model = ScriptedModel::new(vec![
+            reply("", vec![call("c1", "get_all_files", json!({})), call("c2", "read_file", json!({ "path": "src/lib.rs" }))]),
+            reply("lib.rs has two functions.", vec![]),
+        ]);
+        let mut run = AgentRun::new("r1", "Describe lib.rs", dir.to_str().unwrap(), AgentConfig::default());
+        drive(&mut run, &model, &ws, &AtomicBool::new(false), &mut ()).await;
+
+        assert_eq!(run.status, AgentStatus::Completed);
+        assert_eq!(run.result.as_deref(), Some("lib.rs has two functions."));
+        assert_eq!((run.steps, run.tool_calls), (2, 2));
+        let second = &model.seen.lock().unwrap()[1];
+        assert_eq!(second.messages.last().unwrap().content, "fn a() {}\nfn b() {}\n");
+        assert_eq!(second.messages[second.messages.len() - 2].content, "src/lib.rs");
+        assert_eq!(second.tools.len(), workspace_tools().len());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_write_waits_for_approval_and_resumes_from_disk() {
+        let dir = temp_workspace();
+        let ws = Workspace::new(dir.to_str().unwrap(), None).unwrap();
+        let write = call("w1", "write_file", json!({ "path": "src/lib.rs", "content": "fn a() {}\nfn c() {}\n" }));
+        let model = ScriptedModel::new(vec![reply("Renaming b.", vec![write]), reply("Done.", vec![])]);
+        let mut run = AgentRun::new("r2", "Rename b to c", dir.to_str().unwrap(), AgentConfig::default());
+        drive(&mut run, &model, &ws, &AtomicBool::new(false), &mut ()).await;
+
+        assert_eq!(run.status, AgentStatus::AwaitingApproval);
+        let diff = run.pending[0].diff.as_deref().unwrap();
+        assert!(diff.contains("-fn b() {}\n+fn c() {}"));
+        assert_eq!(std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(), "fn a() {}\nfn b() {}\n");
+
+        // Round-trip through JSON the way a checkpoint would
+        let runs = dir.join("runs");
+        std::fs::create_dir_all(&runs).unwrap();
+        save_run(&runs, &run).unwrap();
+        let mut run = load_run(&runs, "r2").unwrap();
+        assert!(run.decide("missing", true).is_err());
+        run.decide("w1", true).unwrap();
+        drive(&mut run, &model, &ws, &AtomicBool::new(false), &mut ()).await;
+
+        assert_eq!(run.status, AgentStatus::Completed);
+        assert_eq!(std::fs::read_to_string(dir.join("src/lib.rs")).unwrap(), "fn a() {}\nfn c() {}\n");
+        assert!(run.transcript.iter().any(|e| matches!(&e.event, TranscriptEvent::Decision { approved: true, .. })));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_rejection_step_limit_and_cancel() {
+        let dir = temp_workspace();
+        let ws = Workspace::new(dir.to_str().unwrap(), None).unwrap();
+        let model = ScriptedModel::new(vec![
+            reply("", vec![call("x1", "execute_command", json!({ "command": "rm", "args": ["-rf", "src"] }))]),
+            reply("", vec![call("g1", "read_file", json!({ "path": "../secret" }))]),
+            reply("never reached", vec![]),
+        ]);
+        let config = AgentConfig { max_steps: 2, ..Default::default() };
+        let mut run = AgentRun::new("r3", "Clean up", dir.to_str().unwrap(), config);
+        drive(&mut run, &model, &ws, &AtomicBool::new(false), &mut ()).await;
+        assert_eq!(run.pending[0].summary, "Komut çalıştır: rm -rf src");
+
+        run.decide("x1", false).unwrap();
+        drive(&mut run, &model, &ws, &AtomicBool::new(false), &mut ()).await;
+        assert_eq!(run.status, AgentStatus::StepLimit);
+        assert!(dir.join("src/lib.rs").exists());
+        let results: Vec<&str> = run.messages.iter().filter(|m| m.role == Role::Tool).map(|m| m.content.as_str()).collect();
+        assert_eq!(results[0], "Kullanıcı bu işlemi reddetti");
+        assert!(results[1].starts_with("Hata: Güvenlik"));
+
+        drive(&mut run, &model, &ws, &AtomicBool::new(true), &mut ()).await;
+        assert_eq!(run.status, AgentStatus::Cancelled);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_repair_interrupted_step() {
+        let mut run = AgentRun::new("r4", "t", "/tmp", AgentConfig::default());
+        run.messages.push(Message::assistant_tool_calls("", vec![
+            call("a", "git_status", json!({})),
+            call("b", "git_status", json!({})),
+        ]));
+        run.messages.push(Message::tool_result("a", "{}"));
+        assert_eq!(run.repair_interrupted(), 1);
+        assert_eq!(run.messages.last().unwrap().tool_call_id.as_deref(), Some("b"));
+        assert_eq!(run.repair_interrupted(), 0);
+    }
+}
@@ -3,11 +3,16 @@ use std::path::Path;
 use std::process::Command;
 use std::env;
 
-use reqwest::Client;
 use serde_json::json;
 use log::{info, error};
-use tauri::{AppHandle, Manager, Emitter};
+use tauri::{AppHandle, Manager};
+use crate::conversations::{self, NewMessage};
+use crate::credentials;
+use crate::http_client;
 use crate::process_monitor::MonitorState;
+use crate::providers;
+use crate::response_cache::{self, CacheOptions};
+use crate::usage;
 
 // --------------------
 // SYSTEM UTILITIES
@@ -192,10 +197,11 @@ pub async fn chat_with_specific_ai(message: String, model_type: String) -> Resul
         _ => (1234, "qwen2.5-coder-7b-instruct"),
     };
 
+    usage::check_budget(Some("openai"), "chat", None)?;
     info!("🔵 {}:{} modeline istek gönderiliyor...", model_type, port);
     info!("📤 Mesaj: {}", message);
 
-    let client = Client::new();
+    let client = http_client::client();
     let endpoint = format!("http://127.0.0.1:{}/v1/chat/completions", port);
 
     let body = json!({
@@ -225,10 +231,10 @@ pub async fn chat_with_specific_ai(message: String, model_type: String) -> Resul
 
     info!("📡 Endpoint: {}", endpoint);
 
-    let res = client
-        .post(&endpoint)
-        .json(&body)
-        .send()
+    let key = http_client::circuit_key("openai", &endpoint);
+    let res = http_client::send_with_retry(&key, &http_client::RetryPolicy::default(), || {
+        client.post(&endpoint).json(&body).timeout(http_client::REQUEST_TIMEOUT)
+    })
         .await
         .map_err(|e| {
             error!("❌ İstek hatası ({}:{}): {}", model_type, port, e);
@@ -255,6 +261,7 @@ pub async fn chat_with_specific_ai(message: String, model_type: String) -> Resul
         .to_string();
 
     info!("📥 AI Yanıtı ({}): {}", model_type, ai_response);
+    usage::record(usage::UsageEvent::from_openai_json("chat", "openai", model_name, &json, &message, &ai_response));
 
     Ok(ai_response)
 }
@@ -264,141 +271,139 @@ pub async fn chat_with_specific_ai(message: String, model_type: String) -> Resul
 // --------------------
 #[derive(serde::Deserialize)]
 pub struct ProviderConfig {
+    /// Provider id from `providers::providers()`; guessed from base_url when absent
+    #[serde(default)]
+    pub provider: Option<String>,
     pub base_url: String,
     #[allow(dead_code)]
     pub host: Option<String>,
     #[allow(dead_code)]
     pub port: Option<u16>,
     pub api_key: Option<String>,
+    /// Vault credential used instead of `api_key`
+    #[serde(default)]
+    pub credential_id: Option<String>,
     pub model_name: String,
     pub temperature: f32,
     pub max_tokens: i32,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// Opt-in response cache (temperature 0 or a seed only)
+    #[serde(default)]
+    pub cache: Option<CacheOptions>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+impl ProviderConfig {
+    pub fn provider_id(&self) -> String {
+        self.provider.clone().unwrap_or_else(|| providers::detect_provider(&self.base_url).to_string())
+    }
+
+    pub fn settings(&self) -> providers::ProviderSettings {
+        providers::ProviderSettings {
+            base_url: self.base_url.clone(),
+            api_key: self.api_key.clone(),
+            model: self.model_name.clone(),
+            credential_id: self.credential_id.clone(),
+        }
+    }
+
+    /// Normalised request for `messages`, using this config's sampling settings
+    pub fn chat_request(&self, messages: Vec<providers::Message>) -> providers::ChatRequest {
+        providers::ChatRequest {
+            messages,
+            temperature: Some(self.temperature),
+            max_tokens: (self.max_tokens > 0).then_some(self.max_tokens as u32),
+            seed: self.seed,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ChatMessage {
     pub role: String,
     pub content: String,
 }
 
+impl ChatMessage {
+    pub fn to_message(&self) -> providers::Message {
+        providers::Message::new(providers::Role::parse(&self.role), self.content.clone())
+    }
+}
+
+/// Reply text plus `cache` info when it came from (or went into) the response cache
 #[tauri::command]
 pub async fn chat_with_dynamic_ai(
+    app: AppHandle,
     message: String, 
     conversation_history: Vec<ChatMessage>,
-    provider_config: ProviderConfig
-) -> Result<String, String> {
-    info!("🔵 Dinamik AI çağrısı: {} -> {}", provider_config.model_name, provider_config.base_url);
+    provider_config: ProviderConfig,
+    conversation_id: Option<String>,
+) -> Result<providers::ChatResponse, String> {
+    let provider_id = provider_config.provider_id();
+    info!("🔵 Dinamik AI çağrısı: {} ({}) -> {}", provider_config.model_name, provider_id, provider_config.base_url);
     info!("📤 Mesaj: {}", message);
     info!("📚 History: {} mesaj", conversation_history.len());
 
-    let client = Client::new();
-    let endpoint = format!("{}/chat/completions", provider_config.base_url);
-
-    // Headers oluştur
-    let mut headers = reqwest::header::HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse().unwrap());
-    
-    if let Some(api_key) = &provider_config.api_key {
-        if !api_key.is_empty() {
-            let auth_header = format!("Bearer {}", api_key);
-            headers.insert("Authorization", auth_header.parse().unwrap());
-        }
-    }
-
-    // 🔥 Conversation history kullan (eğer varsa), yoksa sadece user message
-    let messages: Vec<serde_json::Value> = if !conversation_history.is_empty() {
-        conversation_history.iter().map(|msg| {
-            json!({
-                "role": msg.role,
-                "content": msg.content
-            })
-        }).collect()
+    // 💬 Backend'de saklanan sohbet: geçmişi oradan al, mesaj yanıtla birlikte kaydedilir
+    // 🔥 Yoksa conversation history kullan (eğer varsa), yoksa sadece user message
+    let messages: Vec<providers::Message> = if let Some(id) = &conversation_id {
+        let mut history = conversations::load_conversation(&conversations::conversations_dir(&app)?, id)?.to_messages();
+        history.push(providers::Message::new(providers::Role::User, message.clone()));
+        history
+    } else if !conversation_history.is_empty() {
+        conversation_history.iter().map(ChatMessage::to_message).collect()
     } else {
-        vec![json!({
-            "role": "user",
-            "content": message
-        })]
+        vec![providers::Message::new(providers::Role::User, message.clone())]
     };
 
-    let body = json!({
-        "model": provider_config.model_name,
-        "messages": messages,
-        "temperature": provider_config.temperature,
-        "max_tokens": if provider_config.max_tokens > 0 { 
-            serde_json::Value::Number(serde_json::Number::from(provider_config.max_tokens)) 
-        } else { 
-            serde_json::Value::Null 
-        },
-        "stream": false
-    });
-
-    info!("📡 Endpoint: {}", endpoint);
-    info!("🔧 Model: {}, Temp: {}, MaxTokens: {}, Messages: {}", 
-          provider_config.model_name, 
-          provider_config.temperature, 
-          provider_config.max_tokens,
-          messages.len());
-
-    let res = client
-        .post(&endpoint)
-        .headers(headers)
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| {
-            error!("❌ Dinamik AI istek hatası: {}", e);
-            format!("Bağlantı hatası: {}", e)
-        })?;
-
-    info!("✅ Yanıt alındı, durum: {}", res.status());
-
-    if !res.status().is_success() {
-        let status_code = res.status();
-        let error_text = res.text().await.unwrap_or_default();
-        error!("❌ API hatası: {}", error_text);
-        return Err(format!("API hatası ({}): {}", status_code, error_text));
-    }
-
-    let response_text = res.text().await.map_err(|e| {
-        error!("❌ Response okuma hatası: {}", e);
-        e.to_string()
-    })?;
-
-    info!("📥 Raw Response: {}", response_text);
-
-    let json: serde_json::Value = serde_json::from_str(&response_text).map_err(|e| {
-        error!("❌ JSON parse hatası: {}", e);
-        format!("JSON parse hatası: {}", e)
-    })?;
-
-    // OpenAI format response
-    if let Some(choices) = json["choices"].as_array() {
-        if let Some(first_choice) = choices.first() {
-            if let Some(content) = first_choice["message"]["content"].as_str() {
-                info!("📥 AI Yanıtı: {}", content);
-                return Ok(content.to_string());
-            }
-        }
+    let request = provider_config.chat_request(messages);
+    let cache = &provider_config.cache;
+    let key = response_cache::chat_key(&provider_id, &provider_config.base_url, &provider_config.model_name, &request);
+    if let Some((mut cached, info)) = response_cache::lookup::<providers::ChatResponse>(cache, key.as_ref()) {
+        info!("📥 AI Yanıtı (önbellek, {} sn önce): {}", info.age_secs, cached.text);
+        cached.cache = Some(info);
+        record_reply(&app, conversation_id.as_deref(), &message, &provider_config, &cached).await?;
+        return Ok(cached);
     }
 
-    // Anthropic format response
-    if let Some(content) = json["content"].as_array() {
-        if let Some(first_content) = content.first() {
-            if let Some(text) = first_content["text"].as_str() {
-                info!("📥 AI Yanıtı (Anthropic): {}", text);
-                return Ok(text.to_string());
-            }
-        }
-    }
+    usage::check_budget(Some(&provider_id), "chat", None)?;
+    let provider = providers::provider(&provider_id)?;
+    let mut response = providers::send(&http_client::client(), provider.as_ref(), &provider_config.settings(), &request).await?;
+    usage::record(usage::UsageEvent::from_response("chat", &provider_config.model_name, &request, &response));
+    response.cache = response_cache::store(cache, key.as_ref(), &response);
 
-    // Fallback - try to find any text content
-    if let Some(text) = json["text"].as_str() {
-        info!("📥 AI Yanıtı (Fallback): {}", text);
-        return Ok(text.to_string());
+    if let Some(usage) = &response.usage {
+        info!("📊 Tokens: {} prompt + {} completion", usage.prompt_tokens, usage.completion_tokens);
     }
+    info!("📥 AI Yanıtı: {}", response.text);
+    record_reply(&app, conversation_id.as_deref(), &message, &provider_config, &response).await?;
+    Ok(response)
+}
 
-    error!("❌ AI yanıtı parse edilemedi: {}", response_text);
-    Err("AI yanıtı formatı tanınmıyor".to_string())
+/// Append the user message and the assistant reply to the stored conversation, if there
+/// is one; only called once the provider answered, so failed turns leave no trace
+async fn record_reply(
+    app: &AppHandle,
+    conversation_id: Option<&str>,
+    message: &str,
+    provider_config: &ProviderConfig,
+    response: &providers::ChatResponse,
+) -> Result<(), String> {
+    let Some(id) = conversation_id else {
+        return Ok(());
+    };
+    let user = NewMessage { role: "user".into(), content: message.to_string(), ..Default::default() };
+    let reply = NewMessage {
+        role: "assistant".into(),
+        content: response.text.clone(),
+        model: Some(provider_config.model_name.clone()),
+        provider: Some(provider_config.provider_id()),
+        tool_calls: response.tool_calls.clone(),
+        usage: response.usage.clone(),
+        ..Default::default()
+    };
+    conversations::append_messages(app, id, vec![user, reply]).await.map(|_| ())
 }
 
 // --------------------
@@ -578,7 +583,7 @@ pub fn execute_terminal_command(command: String, path: String, app: AppHandle) -
 pub async fn create_embedding_bge(text: String, endpoint: Option<String>) -> Result<Vec<f32>, String> {
     info!("🧩 BGE Embedding oluşturuluyor...");
     
-    let client = Client::new();
+    let client = http_client::client();
     let final_endpoint = endpoint.unwrap_or_else(|| "http://127.0.0.1:1234/v1/embeddings".to_string());
     info!("📡 Embedding endpoint: {}", final_endpoint);
 
@@ -616,6 +621,16 @@ pub async fn create_embedding_bge(text: String, endpoint: Option<String>) -> Res
         .collect::<Vec<f32>>();
 
     info!("✅ BGE Embedding oluşturuldu: {} boyut", embedding.len());
+    let mut event = usage::UsageEvent::from_openai_json(
+        "embedding",
+        providers::detect_provider(&final_endpoint),
+        body["model"].as_str().unwrap_or_default(),
+        &json,
+        &text,
+        "",
+    );
+    event.completion_tokens = 0;
+    usage::record(event);
 
     Ok(embedding)
 }
@@ -653,6 +668,59 @@ pub async fn semantic_search(
 }
 
 
+/// Choose the embedding model used by semantic search, indexing and RAG.
+/// `model_path` = pooled GGUF embedding model (offline); None = fastembed default.
+/// Applies to the app VectorDB and to the one opened by `init_vector_db`.
+#[tauri::command]
+pub async fn set_vector_embedding_model(
+    model_path: Option<String>,
+    options: Option<crate::gguf_embedding::EmbeddingOptions>,
+    gguf_state: tauri::State<'_, std::sync::Arc<std::sync::Mutex<crate::gguf::GgufState>>>,
+    app: AppHandle,
+) -> Result<serde_json::Value, String> {
+    let vector_db = app.state::<crate::vector_db::VectorDB>();
+    let global_db = VECTOR_DB.lock().await;
+    let targets = std::iter::once(vector_db.inner()).chain(global_db.as_ref());
+
+    match model_path {
+        Some(model_path) => {
+            let options = options.unwrap_or_default();
+            // Generative models have no pooling_type; they need an explicit pooling choice
+            if options.pooling.is_none() {
+                let read = crate::gguf_reader::GgufReadOptions { max_array_len: Some(0), read_tensors: false };
+                let gguf = crate::gguf_reader::read_gguf_file(&model_path, &read)?;
+                if !crate::gguf_embedding::is_embedding_model(&gguf.metadata) {
+                    return Err(format!(
+                        "{} bir embedding modeli değil (pooling_type yok). Üretken bir model için options.pooling belirtin.",
+                        model_path
+                    ));
+                }
+            }
+            info!("🧩 VectorDB embedding modeli: {}", model_path);
+            for db in targets {
+                db.use_gguf_embeddings(gguf_state.inner().clone(), model_path.clone(), options.clone()).await;
+            }
+        }
+        None => {
+            info!("🧩 VectorDB embedding modeli: fastembed (varsayılan)");
+            for db in targets {
+                db.use_fastembed().await;
+            }
+        }
+    }
+    drop(global_db);
+
+    // Probe once so a missing/unsuitable model fails here rather than mid-indexing
+    let probe = vector_db.generate_embedding("dimension probe").await.map_err(|e| e.to_string())?;
+
+    Ok(json!({
+        "success": true,
+        "source": vector_db.embedding_source().await,
+        "table": vector_db.table_name().await,
+        "dimensions": probe.len()
+    }))
+}
+
 /// Index a document/file into the vector database
 #[tauri::command]
 pub async fn vector_index_file(
@@ -1050,96 +1118,7 @@ pub async fn execute_command(command: String, args: Vec<String>, cwd: Option<Str
 }
 // GGUF MODEL DOWNLOAD
 // --------------------
-
-#[tauri::command]
-pub async fn download_gguf_model(
-    url: String,
-    destination: String,
-    app: AppHandle
-) -> Result<String, String> {
-    info!("🔵 GGUF model indiriliyor: {}", url);
-    info!("📁 Hedef: {}", destination);
-
-    let client = Client::new();
-    
-    // İndirme başlat
-    let response = client.get(&url)
-        .send()
-        .await
-        .map_err(|e| format!("İndirme başlatılamadı: {}", e))?;
-
-    if !response.status().is_success() {
-        return Err(format!("HTTP hatası: {}", response.status()));
-    }
-
-    let total_size = response.content_length().unwrap_or(0);
-    info!("📊 Toplam boyut: {} bytes", total_size);
-
-    // Dosyayı oluştur
-    let dest_path = Path::new(&destination);
-    if let Some(parent) = dest_path.parent() {
-        fs::create_dir_all(parent).map_err(|e| format!("Klasör oluşturulamadı: {}", e))?;
-    }
-
-    let mut file = fs::File::create(&destination)
-        .map_err(|e| format!("Dosya oluşturulamadı: {}", e))?;
-
-    // Stream ile indir
-    let mut downloaded: u64 = 0;
-    let mut stream = response.bytes_stream();
-    let mut last_progress_update: u64 = 0;
-
-    use futures_util::StreamExt;
-    use std::io::Write;
-
-    info!("📥 İndirme başladı, toplam: {} bytes", total_size);
-
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result.map_err(|e| format!("İndirme hatası: {}", e))?;
-        file.write_all(&chunk).map_err(|e| format!("Yazma hatası: {}", e))?;
-        
-        downloaded += chunk.len() as u64;
-        
-        // Progress event gönder (her 1MB'de bir veya %1 değişimde)
-        let progress = if total_size > 0 {
-            downloaded as f64 / total_size as f64 * 100.0
-        } else {
-            0.0
-        };
-
-        // Her 1MB'de bir veya ilk chunk'ta progress gönder
-        if downloaded - last_progress_update >= 1024 * 1024 || last_progress_update == 0 {
-            last_progress_update = downloaded;
-            
-            let progress_data = json!({
-                "url": url.clone(),
-                "downloaded": downloaded,
-                "total": total_size,
-                "progress": progress
-            });
-
-            info!("📊 Progress: {:.1}% ({} / {} bytes)", progress, downloaded, total_size);
-
-            // Frontend'e progress gönder
-            match app.emit("download-progress", progress_data) {
-                Ok(_) => {},
-                Err(e) => error!("❌ Event emit hatası: {}", e)
-            }
-        }
-    }
-
-    // Son progress'i gönder (100%)
-    let _ = app.emit("download-progress", json!({
-        "url": url.clone(),
-        "downloaded": total_size,
-        "total": total_size,
-        "progress": 100.0
-    }));
-
-    info!("✅ İndirme tamamlandı: {}", destination);
-    Ok(destination)
-}
-
+// model_download.rs (resumable, verified, Hugging Face aware)
 
 // --------------------
 // VECTOR DATABASE COMMANDS (AI-Native IDE Evolution)
@@ -1156,12 +1135,16 @@ lazy_static::lazy_static! {
 
 /// Initialize vector database
 #[tauri::command]
-pub async fn init_vector_db(db_path: String) -> Result<(), String> {
+pub async fn init_vector_db(db_path: String, app: AppHandle) -> Result<(), String> {
     info!("🔵 Vector DB başlatılıyor: {}", db_path);
     
-    let db = VectorDB::init(&db_path)
+    let mut db = VectorDB::init(&db_path)
         .await
         .map_err(|e| format!("Vector DB başlatılamadı: {}", e))?;
+    // Use the embedding model chosen with set_vector_embedding_model
+    if let Some(app_db) = app.try_state::<VectorDB>() {
+        db = db.share_embedder(&app_db);
+    }
     
     let mut global_db: tokio::sync::MutexGuard<Option<VectorDB>> = VECTOR_DB.lock().await;
     *global_db = Some(db);
@@ -1172,17 +1155,17 @@ pub async fn init_vector_db(db_path: String) -> Result<(), String> {
 
 /// Search vector database for similar code chunks
 #[tauri::command]
-pub async fn vector_search(query: String, top_k: u32, endpoint: Option<String>) -> Result<Vec<CodeChunk>, String> {
+pub async fn vector_search(query: String, top_k: u32) -> Result<Vec<CodeChunk>, String> {
     info!("🔍 Vector search: {} (top_k: {})", query, top_k);
     
-    // Create embedding for query
-    let query_embedding = create_embedding_bge(query, endpoint).await?;
-    
     // Get VectorDB instance
     let global_db: tokio::sync::MutexGuard<Option<VectorDB>> = VECTOR_DB.lock().await;
     let db = global_db.as_ref()
         .ok_or("Vector DB başlatılmamış. Önce init_vector_db çağırın.")?;
     
+    // Create embedding for query with the configured embedder
+    let query_embedding = db.generate_embedding(&query).await.map_err(|e| format!("Embedding hatası: {}", e))?;
+    
     // Search
     let results = db.query(query_embedding, top_k as usize, None)
         .await
@@ -1194,14 +1177,19 @@ pub async fn vector_search(query: String, top_k: u32, endpoint: Option<String>)
 
 /// Index a file in the vector database
 #[tauri::command]
-pub async fn index_file_vector(file_path: String, endpoint: Option<String>) -> Result<(), String> {
+pub async fn index_file_vector(file_path: String) -> Result<(), String> {
     info!("📇 Dosya indeksleniyor: {}", file_path);
     
     // Read file content
     let content = read_file(file_path.clone())?;
     
-    // Create embedding
-    let embedding = create_embedding_bge(content.clone(), endpoint).await?;
+    // Get VectorDB instance
+    let global_db: tokio::sync::MutexGuard<Option<VectorDB>> = VECTOR_DB.lock().await;
+    let db = global_db.as_ref()
+        .ok_or("Vector DB başlatılmamış. Önce init_vector_db çağırın.")?;
+    
+    // Create embedding with the configured embedder
+    let embedding = db.generate_embedding(&content).await.map_err(|e| format!("Embedding hatası: {}", e))?;
     
     // Create chunk
     let chunk = CodeChunk {
@@ -1217,11 +1205,6 @@ pub async fn index_file_vector(file_path: String, endpoint: Option<String>) -> R
             .as_secs(),
     };
     
-    // Get VectorDB instance
-    let global_db: tokio::sync::MutexGuard<Option<VectorDB>> = VECTOR_DB.lock().await;
-    let db = global_db.as_ref()
-        .ok_or("Vector DB başlatılmamış. Önce init_vector_db çağırın.")?;
-    
     // Upsert to vector DB
     db.upsert(vec![chunk])
         .await
@@ -1239,12 +1222,16 @@ pub async fn index_manual_vector(
     content: String,
     chunk_type: String,
     symbol_name: Option<String>,
-    endpoint: Option<String>
 ) -> Result<(), String> {
     info!("📇 Manuel veri indeksleniyor: {} ({})", id, chunk_type);
     
-    // Create embedding
-    let embedding = create_embedding_bge(content.clone(), endpoint).await?;
+    // Get VectorDB instance
+    let global_db = VECTOR_DB.lock().await;
+    let db = global_db.as_ref()
+        .ok_or("Vector DB başlatılmamış.")?;
+    
+    // Create embedding with the configured embedder
+    let embedding = db.generate_embedding(&content).await.map_err(|e| format!("Embedding hatası: {}", e))?;
     
     // Create chunk
     let chunk = CodeChunk {
@@ -1260,11 +1247,6 @@ pub async fn index_manual_vector(
             .as_secs(),
     };
     
-    // Get VectorDB instance
-    let global_db = VECTOR_DB.lock().await;
-    let db = global_db.as_ref()
-        .ok_or("Vector DB başlatılmamış.")?;
-    
     // Upsert
     db.upsert(vec![chunk])
         .await
@@ -1491,7 +1473,11 @@ pub async fn git_blame(
 
 /// 🆕 FIX-41: Test AI Provider Connection securely from backend
 #[tauri::command]
-pub async fn test_provider_connection(base_url: String, api_key: String) -> Result<bool, String> {
+pub async fn test_provider_connection(base_url: String, api_key: String, credential_id: Option<String>) -> Result<bool, String> {
+    let api_key = match credential_id {
+        Some(id) => credentials::secret_for(&id).await?,
+        None => api_key,
+    };
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(5))
         .build()
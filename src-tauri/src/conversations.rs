@@ -0,0 +1,664 @@
+// src-tauri/src/conversations.rs
+// Backend conversation store.
+//
+// Each conversation is one JSON file in {app_data}/conversations/{id}.json holding
+// its messages with model/provider metadata, attached RAG sources and tool calls.
+// A conversation can be forked at any message: the fork copies the messages up to
+// that point and records where it came from. Messages are also embedded into the
+// VectorDB "conversations" collection for semantic search; a fork only indexes the
+// messages added after the fork point, since the shared prefix is already indexed
+// under its parent. Full-text search scans the files directly.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+use crate::providers::{Message, Role, ToolCall, Usage};
+use crate::rag_pipeline::ContextSource;
+use crate::vector_db::{CodeChunk, VectorDB, CONVERSATION_COLLECTION};
+
+/// Characters of context on each side of a search match
+const SNIPPET_RADIUS: usize = 60;
+/// Generated titles are cut to this many characters
+const TITLE_MAX_CHARS: usize = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub id: String,
+    pub role: Role,
+    pub content: String,
+    pub created_at: u64,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    /// RAG context the answer was built from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<ContextSource>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+}
+
+/// A message as sent by the frontend; roles are parsed leniently
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NewMessage {
+    pub role: String,
+    pub content: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+    #[serde(default)]
+    pub sources: Vec<ContextSource>,
+    #[serde(default)]
+    pub usage: Option<Usage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    pub conversation_id: String,
+    /// Last message copied into the fork
+    pub message_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// Workspace the conversation belongs to
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub forked_from: Option<ForkOrigin>,
+    pub messages: Vec<StoredMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    pub updated_at: u64,
+    pub project: Option<String>,
+    pub message_count: usize,
+    pub forked_from: Option<ForkOrigin>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub conversation_id: String,
+    pub title: String,
+    pub message_id: String,
+    pub role: Role,
+    pub snippet: String,
+    /// Match count for full-text hits, cosine similarity (0..1) for semantic ones
+    pub score: f32,
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn role_name(role: Role) -> &'static str {
+    match role {
+        Role::System => "system",
+        Role::User => "user",
+        Role::Assistant => "assistant",
+        Role::Tool => "tool",
+    }
+}
+
+impl Conversation {
+    pub fn new(id: String, title: Option<String>, project: Option<String>, now: u64) -> Self {
+        Self {
+            id,
+            title: title.unwrap_or_default(),
+            created_at: now,
+            updated_at: now,
+            project,
+            forked_from: None,
+            messages: Vec::new(),
+        }
+    }
+
+    /// Append a message; an untitled conversation is named after its first user message
+    pub fn push(&mut self, message: NewMessage, now: u64) -> &StoredMessage {
+        let role = Role::parse(&message.role);
+        if self.title.trim().is_empty() && role == Role::User {
+            let line = message.content.lines().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+            self.title = line.chars().take(TITLE_MAX_CHARS).collect();
+        }
+        self.messages.push(StoredMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            role,
+            content: message.content,
+            created_at: now,
+            model: message.model,
+            provider: message.provider,
+            tool_calls: message.tool_calls,
+            tool_call_id: message.tool_call_id,
+            sources: message.sources,
+            usage: message.usage,
+        });
+        self.updated_at = now;
+        self.messages.last().expect("message just pushed")
+    }
+
+    /// New conversation with the messages up to and including `message_id`
+    pub fn fork(&self, message_id: &str, new_id: String, title: Option<String>, now: u64) -> Result<Conversation, String> {
+        let index = self.messages.iter()
+            .position(|m| m.id == message_id)
+            .ok_or_else(|| format!("Mesaj bulunamadı: {}", message_id))?;
+        Ok(Conversation {
+            id: new_id,
+            title: title.unwrap_or_else(|| format!("{} (fork)", self.title)),
+            created_at: now,
+            updated_at: now,
+            project: self.project.clone(),
+            forked_from: Some(ForkOrigin { conversation_id: self.id.clone(), message_id: message_id.to_string() }),
+            messages: self.messages[..=index].to_vec(),
+        })
+    }
+
+    /// History in the normalised provider format
+    pub fn to_messages(&self) -> Vec<Message> {
+        self.messages.iter()
+            .map(|m| Message {
+                role: m.role,
+                content: m.content.clone(),
+                tool_calls: m.tool_calls.clone(),
+                tool_call_id: m.tool_call_id.clone(),
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> ConversationSummary {
+        ConversationSummary {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            updated_at: self.updated_at,
+            project: self.project.clone(),
+            message_count: self.messages.len(),
+            forked_from: self.forked_from.clone(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let title = if self.title.is_empty() { "Untitled conversation" } else { &self.title };
+        let mut out = format!("# {}\n\n", title);
+        if let Some(project) = &self.project {
+            out.push_str(&format!("- Project: `{}`\n", project));
+        }
+        if let Some(origin) = &self.forked_from {
+            out.push_str(&format!("- Forked from `{}` at message `{}`\n", origin.conversation_id, origin.message_id));
+        }
+        out.push_str(&format!("- Messages: {}\n", self.messages.len()));
+
+        for m in &self.messages {
+            let mut heading = match m.role {
+                Role::System => "System".to_string(),
+                Role::User => "User".to_string(),
+                Role::Assistant => "Assistant".to_string(),
+                Role::Tool => format!("Tool result ({})", m.tool_call_id.as_deref().unwrap_or("?")),
+            };
+            if let Some(model) = &m.model {
+                let provider = m.provider.as_deref().map(|p| format!("{}:", p)).unwrap_or_default();
+                heading.push_str(&format!(" — {}{}", provider, model));
+            }
+            out.push_str(&format!("\n## {}\n\n", heading));
+            if !m.content.trim().is_empty() {
+                out.push_str(m.content.trim_end());
+                out.push('\n');
+            }
+            for call in &m.tool_calls {
+                let args = serde_json::to_string_pretty(&call.arguments).unwrap_or_default();
+                out.push_str(&format!("\n**Tool call** `{}` (`{}`):\n\n```json\n{}\n```\n", call.name, call.id, args));
+            }
+            if !m.sources.is_empty() {
+                out.push_str("\n**Sources:**\n\n");
+                for source in &m.sources {
+                    out.push_str(&format!("- `{}` ({}, {:.2})\n", source.file_path, source.source_type, source.relevance_score));
+                }
+            }
+        }
+        out
+    }
+
+    pub fn export(&self, format: ExportFormat) -> Result<String, String> {
+        match format {
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+            ExportFormat::Json => serde_json::to_string_pretty(self).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// --------------------
+// SEARCH
+// --------------------
+
+/// Byte offset of the char boundary at or before `index`
+fn floor_boundary(text: &str, mut index: usize) -> usize {
+    index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn snippet(text: &str, at: usize, len: usize) -> String {
+    let start = floor_boundary(text, at.saturating_sub(SNIPPET_RADIUS));
+    let end = floor_boundary(text, at + len + SNIPPET_RADIUS);
+    let mut out = text[start..end].split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        out.insert(0, '…');
+    }
+    if end < text.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// Messages containing every term of `query` (case-insensitive), most matches first
+pub fn search_text(conversations: &[Conversation], query: &str, limit: usize) -> Vec<SearchHit> {
+    let terms: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    if terms.is_empty() {
+        return Vec::new();
+    }
+    let mut hits = Vec::new();
+    for conversation in conversations {
+        for message in &conversation.messages {
+            // Lowercasing can change byte lengths; only search ASCII-insensitively then
+            let lower = message.content.to_lowercase();
+            let haystack = if lower.len() == message.content.len() {
+                lower
+            } else {
+                message.content.to_ascii_lowercase()
+            };
+            if !terms.iter().all(|t| haystack.contains(t.as_str())) {
+                continue;
+            }
+            let count: usize = terms.iter().map(|t| haystack.matches(t.as_str()).count()).sum();
+            let first = haystack.find(terms[0].as_str()).unwrap_or(0);
+            hits.push(SearchHit {
+                conversation_id: conversation.id.clone(),
+                title: conversation.title.clone(),
+                message_id: message.id.clone(),
+                role: message.role,
+                snippet: snippet(&message.content, first, terms[0].len()),
+                score: count as f32,
+            });
+        }
+    }
+    hits.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    hits.truncate(limit);
+    hits
+}
+
+/// VectorDB "file path" grouping one conversation's messages
+pub fn vector_path(conversation_id: &str) -> String {
+    format!("conversation://{}", conversation_id)
+}
+
+/// One VectorDB chunk per message with text; embeddings are generated on upsert
+pub fn message_chunks(conversation: &Conversation, messages: &[StoredMessage]) -> Vec<CodeChunk> {
+    messages.iter()
+        .filter(|m| !m.content.trim().is_empty() && m.role != Role::System)
+        .map(|m| CodeChunk {
+            id: format!("{}:{}", conversation.id, m.id),
+            file_path: vector_path(&conversation.id),
+            content: m.content.clone(),
+            embedding: Vec::new(),
+            symbol_name: Some(m.id.clone()),
+            chunk_type: role_name(m.role).to_string(),
+            timestamp: m.created_at,
+        })
+        .collect()
+}
+
+/// 0 for mismatched or zero-length vectors
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 { dot / norm } else { 0.0 }
+}
+
+// --------------------
+// PERSISTENCE
+// --------------------
+
+/// Serialises read-modify-write cycles on conversation files
+static STORE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+pub fn conversations_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("conversations");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Sohbet klasörü oluşturulamadı: {}", e))?;
+    Ok(dir)
+}
+
+pub fn save_conversation(dir: &Path, conversation: &Conversation) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(conversation).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join(format!("{}.json", conversation.id)), json).map_err(|e| format!("Sohbet kaydedilemedi: {}", e))
+}
+
+pub fn load_conversation(dir: &Path, id: &str) -> Result<Conversation, String> {
+    if id.contains(['/', '\\']) || id.contains("..") {
+        return Err(format!("Geçersiz sohbet id: {}", id));
+    }
+    let text = std::fs::read_to_string(dir.join(format!("{}.json", id)))
+        .map_err(|_| format!("Sohbet bulunamadı: {}", id))?;
+    serde_json::from_str(&text).map_err(|e| format!("Sohbet okunamadı: {}", e))
+}
+
+pub fn load_all(dir: &Path) -> Vec<Conversation> {
+    std::fs::read_dir(dir)
+        .map(|entries| {
+            entries.flatten()
+                .filter_map(|e| e.path().file_stem().map(|s| s.to_string_lossy().into_owned()))
+                .filter_map(|id| load_conversation(dir, &id).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Load, change and save one conversation under the store lock
+pub fn update<T>(dir: &Path, id: &str, f: impl FnOnce(&mut Conversation) -> Result<T, String>) -> Result<(T, Conversation), String> {
+    let _guard = STORE_LOCK.lock().unwrap_or_else(|p| p.into_inner());
+    let mut conversation = load_conversation(dir, id)?;
+    let value = f(&mut conversation)?;
+    save_conversation(dir, &conversation)?;
+    Ok((value, conversation))
+}
+
+/// Embed messages for semantic search; skipped when the VectorDB isn't ready
+async fn index_messages(app: &AppHandle, conversation: &Conversation, messages: &[StoredMessage]) {
+    let Some(vector_db) = app.try_state::<VectorDB>() else {
+        return;
+    };
+    let chunks = message_chunks(conversation, messages);
+    if chunks.is_empty() {
+        return;
+    }
+    if let Err(e) = vector_db.upsert_into(CONVERSATION_COLLECTION, chunks).await {
+        warn!("⚠️ Sohbet mesajları indekslenemedi: {}", e);
+    }
+}
+
+/// Append messages to a stored conversation and index them
+pub async fn append_messages(app: &AppHandle, conversation_id: &str, messages: Vec<NewMessage>) -> Result<Vec<StoredMessage>, String> {
+    let dir = conversations_dir(app)?;
+    let (stored, conversation) = update(&dir, conversation_id, |conversation| {
+        let now = now_secs();
+        Ok(messages.into_iter().map(|m| conversation.push(m, now).clone()).collect::<Vec<_>>())
+    })?;
+    index_messages(app, &conversation, &stored).await;
+    Ok(stored)
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub fn create_conversation(app: AppHandle, title: Option<String>, project: Option<String>) -> Result<Conversation, String> {
+    let conversation = Conversation::new(uuid::Uuid::new_v4().to_string(), title, project, now_secs());
+    save_conversation(&conversations_dir(&app)?, &conversation)?;
+    info!("💬 Sohbet oluşturuldu: {}", conversation.id);
+    Ok(conversation)
+}
+
+#[tauri::command]
+pub async fn append_conversation_message(app: AppHandle, conversation_id: String, message: NewMessage) -> Result<StoredMessage, String> {
+    let mut stored = append_messages(&app, &conversation_id, vec![message]).await?;
+    stored.pop().ok_or_else(|| "Mesaj eklenemedi".to_string())
+}
+
+#[tauri::command]
+pub fn get_conversation(app: AppHandle, conversation_id: String) -> Result<Conversation, String> {
+    load_conversation(&conversations_dir(&app)?, &conversation_id)
+}
+
+/// Most recently updated first, optionally only one project's
+#[tauri::command]
+pub fn list_conversations(app: AppHandle, project: Option<String>) -> Result<Vec<ConversationSummary>, String> {
+    let mut summaries: Vec<ConversationSummary> = load_all(&conversations_dir(&app)?)
+        .iter()
+        .filter(|c| project.is_none() || c.project == project)
+        .map(Conversation::summary)
+        .collect();
+    summaries.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    Ok(summaries)
+}
+
+#[tauri::command]
+pub fn rename_conversation(app: AppHandle, conversation_id: String, title: String) -> Result<ConversationSummary, String> {
+    let (_, conversation) = update(&conversations_dir(&app)?, &conversation_id, |c| {
+        c.title = title;
+        Ok(())
+    })?;
+    Ok(conversation.summary())
+}
+
+#[tauri::command]
+pub async fn delete_conversation(app: AppHandle, conversation_id: String) -> Result<(), String> {
+    let dir = conversations_dir(&app)?;
+    load_conversation(&dir, &conversation_id)?;
+    std::fs::remove_file(dir.join(format!("{}.json", conversation_id))).map_err(|e| format!("Sohbet silinemedi: {}", e))?;
+    if let Some(vector_db) = app.try_state::<VectorDB>() {
+        if let Err(e) = vector_db.delete_file_in(CONVERSATION_COLLECTION, &vector_path(&conversation_id)).await {
+            warn!("⚠️ Sohbet indeksi silinemedi: {}", e);
+        }
+    }
+    info!("🗑️ Sohbet silindi: {}", conversation_id);
+    Ok(())
+}
+
+/// New conversation continuing from `message_id` of an existing one
+#[tauri::command]
+pub fn fork_conversation(app: AppHandle, conversation_id: String, message_id: String, title: Option<String>) -> Result<Conversation, String> {
+    let dir = conversations_dir(&app)?;
+    let fork = load_conversation(&dir, &conversation_id)?.fork(&message_id, uuid::Uuid::new_v4().to_string(), title, now_secs())?;
+    save_conversation(&dir, &fork)?;
+    info!("🌿 Sohbet çatallandı: {} -> {} ({})", conversation_id, fork.id, message_id);
+    Ok(fork)
+}
+
+/// Full-text search, or semantic search through the VectorDB when `semantic` is set
+#[tauri::command]
+pub async fn search_conversations(
+    app: AppHandle,
+    query: String,
+    semantic: Option<bool>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>, String> {
+    let dir = conversations_dir(&app)?;
+    let limit = limit.unwrap_or(20);
+    if !semantic.unwrap_or(false) {
+        return Ok(search_text(&load_all(&dir), &query, limit));
+    }
+
+    let vector_db = app.try_state::<VectorDB>().ok_or("VectorDB henüz hazır değil")?;
+    let embedding = vector_db.generate_embedding(&query).await.map_err(|e| e.to_string())?;
+    let chunks = vector_db.query_in(CONVERSATION_COLLECTION, embedding.clone(), limit, None).await.map_err(|e| e.to_string())?;
+
+    let mut hits = Vec::new();
+    for chunk in &chunks {
+        let Some(conversation_id) = chunk.file_path.strip_prefix("conversation://") else {
+            continue;
+        };
+        // Deleted conversations may linger in the index until their next cleanup
+        let Ok(conversation) = load_conversation(&dir, conversation_id) else {
+            continue;
+        };
+        let message_id = chunk.symbol_name.clone().unwrap_or_default();
+        let Some(message) = conversation.messages.iter().find(|m| m.id == message_id) else {
+            continue;
+        };
+        hits.push(SearchHit {
+            conversation_id: conversation.id.clone(),
+            title: conversation.title.clone(),
+            message_id,
+            role: message.role,
+            snippet: snippet(&message.content, 0, 0),
+            score: cosine_similarity(&embedding, &chunk.embedding).max(0.0),
+        });
+    }
+    Ok(hits)
+}
+
+#[tauri::command]
+pub fn export_conversation(app: AppHandle, conversation_id: String, format: ExportFormat) -> Result<String, String> {
+    load_conversation(&conversations_dir(&app)?, &conversation_id)?.export(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn msg(role: &str, content: &str) -> NewMessage {
+        NewMessage { role: role.into(), content: content.into(), ..Default::default() }
+    }
+
+    fn sample() -> Conversation {
+        let mut c = Conversation::new("c1".into(), None, Some("/work/app".into()), 10);
+        c.push(msg("system", "Be brief."), 10);
+        c.push(msg("user", "How do I read a file in Rust?\nThanks"), 11);
+        c.push(NewMessage {
+            model: Some("qwen".into()),
+            provider: Some("openai".into()),
+            tool_calls: vec![ToolCall { id: "call_0".into(), name: "read_file".into(), arguments: json!({ "path": "src/main.rs" }) }],
+            sources: vec![ContextSource {
+                source_type: "vector_db".into(),
+                file_path: "src/io.rs".into(),
+                relevance_score: 0.82,
+                reason: "similar".into(),
+            }],
+            ..msg("assistant", "Use std::fs::read_to_string.")
+        }, 12);
+        c.push(msg("user", "And how do I write one?"), 13);
+        c
+    }
+
+    #[test]
+    fn test_push_titles_and_history() {
+        let c = sample();
+        assert_eq!(c.title, "How do I read a file in Rust?");
+        assert_eq!(c.updated_at, 13);
+        let history = c.to_messages();
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[2].role, Role::Assistant);
+        assert_eq!(history[2].tool_calls[0].name, "read_file");
+    }
+
+    #[test]
+    fn test_fork_at_message() {
+        let c = sample();
+        let at = c.messages[2].id.clone();
+        let fork = c.fork(&at, "c2".into(), None, 20).unwrap();
+        assert_eq!(fork.messages.len(), 3);
+        assert_eq!(fork.messages.last().unwrap().id, at);
+        assert_eq!(fork.forked_from, Some(ForkOrigin { conversation_id: "c1".into(), message_id: at }));
+        assert_eq!(fork.title, "How do I read a file in Rust? (fork)");
+        assert_eq!(fork.project.as_deref(), Some("/work/app"));
+        assert!(c.fork("missing", "c3".into(), None, 20).is_err());
+    }
+
+    #[test]
+    fn test_search_text() {
+        let mut other = Conversation::new("c9".into(), Some("Unrelated".into()), None, 1);
+        other.push(msg("user", "Files, files and more FILES"), 1);
+        let all = vec![sample(), other];
+
+        let hits = search_text(&all, "file rust", 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].conversation_id, "c1");
+        assert!(hits[0].snippet.contains("read a file in Rust"));
+
+        let hits = search_text(&all, "FILE", 10);
+        assert_eq!(hits[0].conversation_id, "c9");
+        assert_eq!(hits[0].score, 3.0);
+        assert!(search_text(&all, "   ", 10).is_empty());
+        assert_eq!(search_text(&all, "file", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 2.0]), 0.0);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_snippet_is_char_safe() {
+        let text = format!("{}needle{}", "ğ".repeat(80), "ü".repeat(80));
+        let at = text.find("needle").unwrap();
+        let s = snippet(&text, at, 6);
+        assert!(s.starts_with('…') && s.ends_with('…') && s.contains("needle"));
+    }
+
+    #[test]
+    fn test_export_markdown_and_json() {
+        let c = sample();
+        let md = c.export(ExportFormat::Markdown).unwrap();
+        assert!(md.starts_with("# How do I read a file in Rust?\n"));
+        assert!(md.contains("## Assistant — openai:qwen"));
+        assert!(md.contains("**Tool call** `read_file`"));
+        assert!(md.contains("- `src/io.rs` (vector_db, 0.82)"));
+
+        let json = c.export(ExportFormat::Json).unwrap();
+        let back: Conversation = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.messages.len(), 4);
+        assert_eq!(back.messages[2].sources[0].file_path, "src/io.rs");
+    }
+
+    #[test]
+    fn test_message_chunks_skip_system_and_empty() {
+        let mut c = sample();
+        c.push(msg("assistant", "  "), 14);
+        let chunks = message_chunks(&c, &c.messages);
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|ch| ch.file_path == "conversation://c1"));
+        assert_eq!(chunks[0].chunk_type, "user");
+        assert_eq!(chunks[0].symbol_name.as_ref(), Some(&c.messages[1].id));
+    }
+
+    #[test]
+    fn test_persistence_and_update() {
+        let dir = std::env::temp_dir().join(format!("corex_conv_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        save_conversation(&dir, &sample()).unwrap();
+        let (count, updated) = update(&dir, "c1", |c| {
+            c.push(msg("assistant", "Use std::fs::write."), 30);
+            Ok(c.messages.len())
+        }).unwrap();
+        assert_eq!(count, 5);
+        assert_eq!(load_conversation(&dir, "c1").unwrap().messages.len(), updated.messages.len());
+        assert_eq!(load_all(&dir).len(), 1);
+        assert!(load_conversation(&dir, "../c1").is_err());
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
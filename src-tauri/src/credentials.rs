@@ -0,0 +1,735 @@
+// src-tauri/src/credentials.rs
+// Credential vault for provider API keys and OAuth tokens.
+//
+// Secrets live in the backend only: the webview stores and sends a credential id,
+// and `ProviderSettings.credential_id` is resolved to the key right before a
+// request is built. The default store is one XChaCha20-Poly1305 encrypted file,
+// {app_data}/credentials.vault, whose key is derived with Argon2id from either a
+// passphrase or a device secret (machine id + OS user). Other stores, e.g. an OS
+// keyring, implement `CredentialStore`. OAuth access tokens are refreshed shortly
+// before they expire, on use and by a background task.
+//
+// Known secrets and common key shapes are masked in every log line (see `redact`).
+
+use argon2::Argon2;
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+use crate::oauth_backend::{self, TokenResponse};
+use crate::providers::ProviderSettings;
+
+/// Refresh access tokens this long before they expire
+pub const REFRESH_MARGIN_SECS: u64 = 120;
+/// How often the background task looks for expiring tokens
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const VAULT_VERSION: u32 = 1;
+const VAULT_AAD: &[u8] = b"corex-credential-vault-v1";
+
+// --------------------
+// CREDENTIALS
+// --------------------
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Secret {
+    ApiKey {
+        key: String,
+    },
+    OAuth {
+        access_token: String,
+        refresh_token: Option<String>,
+        /// Unix seconds; None when the provider did not say
+        expires_at: Option<u64>,
+        token_type: String,
+    },
+}
+
+// Hand-written so secrets never end up in a `{:?}`
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Secret::ApiKey { .. } => write!(f, "ApiKey([REDACTED])"),
+            Secret::OAuth { expires_at, .. } => write!(f, "OAuth([REDACTED], expires_at: {:?})", expires_at),
+        }
+    }
+}
+
+impl Secret {
+    /// The value sent to the provider
+    pub fn token(&self) -> &str {
+        match self {
+            Secret::ApiKey { key } => key,
+            Secret::OAuth { access_token, .. } => access_token,
+        }
+    }
+
+    fn values(&self) -> Vec<&str> {
+        match self {
+            Secret::ApiKey { key } => vec![key.as_str()],
+            Secret::OAuth { access_token, refresh_token, .. } => {
+                std::iter::once(access_token.as_str()).chain(refresh_token.as_deref()).collect()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credential {
+    pub id: String,
+    pub label: String,
+    /// Chat provider id ("openai", "anthropic", ...) or OAuth provider ("github", "microsoft")
+    pub provider: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub secret: Secret,
+}
+
+impl Credential {
+    /// OAuth credential that expires within `margin` seconds and can be refreshed
+    pub fn needs_refresh(&self, now: u64, margin: u64) -> bool {
+        matches!(
+            &self.secret,
+            Secret::OAuth { refresh_token: Some(_), expires_at: Some(expires_at), .. } if *expires_at <= now + margin
+        )
+    }
+
+    pub fn info(&self) -> CredentialInfo {
+        let token = self.secret.token();
+        let tail: String = token.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+        CredentialInfo {
+            id: self.id.clone(),
+            label: self.label.clone(),
+            provider: self.provider.clone(),
+            kind: match self.secret {
+                Secret::ApiKey { .. } => "api_key",
+                Secret::OAuth { .. } => "oauth",
+            }.to_string(),
+            hint: if token.chars().count() > 8 { format!("…{}", tail) } else { "…".to_string() },
+            expires_at: match &self.secret {
+                Secret::OAuth { expires_at, .. } => *expires_at,
+                Secret::ApiKey { .. } => None,
+            },
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+/// What the webview sees of a credential
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CredentialInfo {
+    pub id: String,
+    pub label: String,
+    pub provider: String,
+    /// "api_key" or "oauth"
+    pub kind: String,
+    /// Last characters of the secret, e.g. "…3f9a"
+    pub hint: String,
+    pub expires_at: Option<u64>,
+    pub updated_at: u64,
+}
+
+// --------------------
+// STORES
+// --------------------
+
+/// Where credentials are persisted. The encrypted file is the default; an OS
+/// keyring backend only has to implement these three methods.
+pub trait CredentialStore: Send {
+    fn name(&self) -> &'static str;
+    fn load(&self) -> Result<BTreeMap<String, Credential>, String>;
+    fn save(&self, credentials: &BTreeMap<String, Credential>) -> Result<(), String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    Passphrase,
+    /// Bound to this machine and OS user; protects a copied file, not a local attacker
+    Device,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    key_source: KeySource,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    key_source: KeySource,
+    salt: Vec<u8>,
+    key: [u8; 32],
+}
+
+fn derive_key(secret: &[u8], salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(secret, salt, &mut key)
+        .map_err(|e| format!("Anahtar türetilemedi: {}", e))?;
+    Ok(key)
+}
+
+fn b64(bytes: &[u8]) -> String {
+    general_purpose::STANDARD.encode(bytes)
+}
+
+fn unb64(text: &str) -> Result<Vec<u8>, String> {
+    general_purpose::STANDARD.decode(text).map_err(|e| format!("Kasa dosyası bozuk: {}", e))
+}
+
+fn read_vault_file(path: &Path) -> Result<Option<VaultFile>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).map(Some).map_err(|e| format!("Kasa dosyası okunamadı: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(format!("Kasa dosyası okunamadı: {}", e)),
+    }
+}
+
+impl EncryptedFileStore {
+    /// Open the vault at `path` (creating it on first save). Fails on a wrong
+    /// secret or when `key_source` differs from the one the vault was created with.
+    pub fn open(path: &Path, key_source: KeySource, secret: &[u8]) -> Result<Self, String> {
+        let existing = read_vault_file(path)?;
+        let salt = match &existing {
+            Some(file) => {
+                if file.key_source != key_source {
+                    return Err(format!("Kasa {:?} anahtarıyla oluşturulmuş", file.key_source));
+                }
+                unb64(&file.salt)?
+            }
+            None => {
+                let mut salt = vec![0u8; 16];
+                rand::thread_rng().fill_bytes(&mut salt);
+                salt
+            }
+        };
+        let store = Self { path: path.to_path_buf(), key_source, key: derive_key(secret, &salt)?, salt };
+        if existing.is_some() {
+            store.load()?;
+        }
+        Ok(store)
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.key))
+    }
+}
+
+impl CredentialStore for EncryptedFileStore {
+    fn name(&self) -> &'static str {
+        "encrypted_file"
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, Credential>, String> {
+        let Some(file) = read_vault_file(&self.path)? else {
+            return Ok(BTreeMap::new());
+        };
+        if file.version != VAULT_VERSION {
+            return Err(format!("Desteklenmeyen kasa sürümü: {}", file.version));
+        }
+        let nonce = unb64(&file.nonce)?;
+        if nonce.len() != 24 {
+            return Err("Kasa dosyası bozuk: geçersiz nonce".to_string());
+        }
+        let plaintext = self.cipher()
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &unb64(&file.ciphertext)?, aad: VAULT_AAD })
+            .map_err(|_| "Kasa açılamadı: parola yanlış veya dosya değiştirilmiş".to_string())?;
+        serde_json::from_slice(&plaintext).map_err(|e| format!("Kasa içeriği okunamadı: {}", e))
+    }
+
+    fn save(&self, credentials: &BTreeMap<String, Credential>) -> Result<(), String> {
+        let plaintext = serde_json::to_vec(credentials).map_err(|e| e.to_string())?;
+        let mut nonce = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.cipher()
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: &plaintext, aad: VAULT_AAD })
+            .map_err(|_| "Kasa şifrelenemedi".to_string())?;
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            key_source: self.key_source,
+            salt: b64(&self.salt),
+            nonce: b64(&nonce),
+            ciphertext: b64(&ciphertext),
+        };
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        // Write then rename, so a crash never leaves a half-written vault
+        let tmp = self.path.with_extension("vault.tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&file).map_err(|e| e.to_string())?)
+            .map_err(|e| format!("Kasa yazılamadı: {}", e))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600));
+        }
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("Kasa yazılamadı: {}", e))
+    }
+}
+
+/// Machine id plus OS user name, the secret behind `KeySource::Device`
+pub fn device_secret() -> Result<String, String> {
+    let mut machine_id = String::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id"].iter()
+            .find_map(|p| std::fs::read_to_string(p).ok())
+            .unwrap_or_default();
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Ok(output) = std::process::Command::new("ioreg").args(["-rd1", "-c", "IOPlatformExpertDevice"]).output() {
+            machine_id = String::from_utf8_lossy(&output.stdout).lines()
+                .find(|l| l.contains("IOPlatformUUID"))
+                .and_then(|l| l.split('"').nth(3))
+                .unwrap_or_default()
+                .to_string();
+        }
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        if let Ok(output) = std::process::Command::new("reg")
+            .args(["query", r"HKLM\SOFTWARE\Microsoft\Cryptography", "/v", "MachineGuid"])
+            .output()
+        {
+            machine_id = String::from_utf8_lossy(&output.stdout).split_whitespace().last().unwrap_or_default().to_string();
+        }
+    }
+
+    let machine_id = machine_id.trim();
+    if machine_id.is_empty() {
+        return Err("Cihaz kimliği okunamadı; kasa için bir parola kullanın".to_string());
+    }
+    let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+    Ok(format!("{}:{}", machine_id, user))
+}
+
+// --------------------
+// VAULT
+// --------------------
+
+pub struct Vault {
+    store: Box<dyn CredentialStore>,
+    credentials: BTreeMap<String, Credential>,
+}
+
+impl Vault {
+    pub fn open(store: Box<dyn CredentialStore>) -> Result<Self, String> {
+        let credentials = store.load()?;
+        remember_secrets(credentials.values());
+        Ok(Self { store, credentials })
+    }
+
+    pub fn get(&self, id: &str) -> Result<&Credential, String> {
+        self.credentials.get(id).ok_or_else(|| format!("Kimlik bilgisi bulunamadı: {}", id))
+    }
+
+    pub fn list(&self) -> Vec<CredentialInfo> {
+        self.credentials.values().map(Credential::info).collect()
+    }
+
+    /// Insert or replace a credential, keeping its creation time
+    pub fn put(&mut self, mut credential: Credential) -> Result<CredentialInfo, String> {
+        if let Some(existing) = self.credentials.get(&credential.id) {
+            credential.created_at = existing.created_at;
+        }
+        remember_secrets([&credential]);
+        let info = credential.info();
+        self.credentials.insert(credential.id.clone(), credential);
+        self.store.save(&self.credentials)?;
+        Ok(info)
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<bool, String> {
+        let removed = self.credentials.remove(id).is_some();
+        if removed {
+            self.store.save(&self.credentials)?;
+        }
+        Ok(removed)
+    }
+
+    /// Ids of OAuth credentials to refresh now
+    pub fn expiring(&self, now: u64, margin: u64) -> Vec<String> {
+        self.credentials.values().filter(|c| c.needs_refresh(now, margin)).map(|c| c.id.clone()).collect()
+    }
+}
+
+/// New OAuth secret from a token response; keeps the old refresh token when none is returned
+pub fn oauth_secret(token: TokenResponse, previous_refresh: Option<String>, now: u64) -> Secret {
+    Secret::OAuth {
+        access_token: token.access_token,
+        refresh_token: token.refresh_token.or(previous_refresh),
+        expires_at: token.expires_in.map(|s| now + s),
+        token_type: token.token_type,
+    }
+}
+
+// --------------------
+// GLOBAL VAULT
+// --------------------
+
+static VAULT: Lazy<Mutex<Option<Vault>>> = Lazy::new(|| Mutex::new(None));
+static VAULT_PATH: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn vault_path() -> Result<PathBuf, String> {
+    VAULT_PATH.lock().unwrap_or_else(|p| p.into_inner()).clone().ok_or_else(|| "Kimlik kasası başlatılmadı".to_string())
+}
+
+fn with_vault<T>(f: impl FnOnce(&mut Vault) -> Result<T, String>) -> Result<T, String> {
+    let mut guard = VAULT.lock().unwrap_or_else(|p| p.into_inner());
+    let vault = guard.as_mut().ok_or("Kimlik kasası kilitli")?;
+    f(vault)
+}
+
+fn unlock(key_source: KeySource, secret: &[u8]) -> Result<usize, String> {
+    let store = EncryptedFileStore::open(&vault_path()?, key_source, secret)?;
+    let vault = Vault::open(Box::new(store))?;
+    let count = vault.credentials.len();
+    *VAULT.lock().unwrap_or_else(|p| p.into_inner()) = Some(vault);
+    Ok(count)
+}
+
+/// Remember the vault location, open a device-key vault right away and start the
+/// refresh task. Passphrase vaults wait for `unlock_credential_vault`.
+pub fn init_for_app(app: &AppHandle) {
+    let path = match app.path().app_data_dir() {
+        Ok(dir) => dir.join("credentials.vault"),
+        Err(e) => {
+            warn!("⚠️ Kimlik kasası klasörü bulunamadı: {}", e);
+            return;
+        }
+    };
+    let existing = read_vault_file(&path).ok().flatten().map(|f| f.key_source);
+    *VAULT_PATH.lock().unwrap_or_else(|p| p.into_inner()) = Some(path);
+    if existing == Some(KeySource::Device) {
+        match device_secret().and_then(|secret| unlock(KeySource::Device, secret.as_bytes())) {
+            Ok(count) => info!("🔐 Kimlik kasası açıldı: {} kayıt", count),
+            Err(e) => warn!("⚠️ Kimlik kasası açılamadı: {}", e),
+        }
+    }
+
+    tauri::async_runtime::spawn(async {
+        loop {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            let ids = with_vault(|vault| Ok(vault.expiring(now_secs(), REFRESH_MARGIN_SECS))).unwrap_or_default();
+            for id in ids {
+                if let Err(e) = refresh(&id).await {
+                    warn!("⚠️ OAuth yenilemesi başarısız ({}): {}", id, e);
+                }
+            }
+        }
+    });
+}
+
+/// One refresh in flight per credential id
+static REFRESH_FLIGHTS: Lazy<Mutex<BTreeMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Exchange the stored refresh token for a new access token. Concurrent calls for
+/// the same id wait for the first one and reuse its token.
+async fn refresh(id: &str) -> Result<(), String> {
+    let seen = with_vault(|vault| Ok(vault.get(id)?.secret.token().to_string()))?;
+    let flight = REFRESH_FLIGHTS.lock().unwrap_or_else(|p| p.into_inner()).entry(id.to_string()).or_default().clone();
+    let _flight = flight.lock().await;
+
+    let pending = with_vault(|vault| {
+        let credential = vault.get(id)?;
+        if credential.secret.token() != seen {
+            return Ok(None);
+        }
+        match &credential.secret {
+            Secret::OAuth { refresh_token: Some(token), .. } => Ok(Some((credential.provider.clone(), token.clone()))),
+            _ => Err(format!("Yenilenebilir bir OAuth kaydı değil: {}", id)),
+        }
+    })?;
+    let Some((provider, refresh_token)) = pending else {
+        return Ok(());
+    };
+    let token = oauth_backend::refresh_token(&refresh_token, &provider).await?;
+    let now = now_secs();
+    with_vault(|vault| {
+        // Re-read under the lock so a credential deleted or edited meanwhile isn't overwritten
+        let current = vault.get(id)
+            .map_err(|_| format!("Kimlik bilgisi yenileme sırasında silindi: {}", id))?
+            .clone();
+        vault.put(Credential { secret: oauth_secret(token, Some(refresh_token), now), updated_at: now, ..current })
+    })?;
+    info!("🔄 OAuth token yenilendi: {}", id);
+    Ok(())
+}
+
+/// The secret for `id`, refreshing an OAuth token that is about to expire
+pub async fn secret_for(id: &str) -> Result<String, String> {
+    let needs_refresh = with_vault(|vault| Ok(vault.get(id)?.needs_refresh(now_secs(), REFRESH_MARGIN_SECS)))?;
+    if needs_refresh {
+        if let Err(e) = refresh(id).await {
+            warn!("⚠️ OAuth yenilemesi başarısız ({}): {}", id, e);
+        }
+    }
+    with_vault(|vault| Ok(vault.get(id)?.secret.token().to_string()))
+}
+
+/// `settings` with `credential_id` resolved into `api_key`
+pub async fn resolve_settings(settings: &ProviderSettings) -> Result<ProviderSettings, String> {
+    let mut resolved = settings.clone();
+    if let Some(id) = &settings.credential_id {
+        resolved.api_key = Some(secret_for(id).await?);
+    }
+    Ok(resolved)
+}
+
+/// Store tokens from an OAuth exchange as a new credential
+pub fn store_oauth_tokens(provider: &str, label: Option<String>, token: TokenResponse) -> Result<CredentialInfo, String> {
+    let now = now_secs();
+    with_vault(|vault| vault.put(Credential {
+        id: uuid::Uuid::new_v4().to_string(),
+        label: label.unwrap_or_else(|| provider.to_string()),
+        provider: provider.to_string(),
+        created_at: now,
+        updated_at: now,
+        secret: oauth_secret(token, None, now),
+    }))
+}
+
+// --------------------
+// REDACTION
+// --------------------
+
+/// Secret values seen by the vault; masked verbatim wherever they show up
+static KNOWN_SECRETS: Lazy<RwLock<Vec<String>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Key shapes masked even when the vault never saw them
+static SECRET_PATTERNS: Lazy<Vec<(Regex, &'static str)>> = Lazy::new(|| {
+    [
+        (r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]{8,}", "${1}[REDACTED]"),
+        (r"\bsk-[A-Za-z0-9_-]{16,}", "[REDACTED]"),
+        (r"\bAIza[0-9A-Za-z_-]{30,}", "[REDACTED]"),
+        (r"\bgh[pousr]_[A-Za-z0-9]{20,}", "[REDACTED]"),
+        (r"(?i)([?&](?:key|api_key|access_token|refresh_token)=)[^&\s]+", "${1}[REDACTED]"),
+        (
+            r#"(?i)("(?:api_?key|access_token|refresh_token|client_secret|x-api-key|authorization)"\s*:\s*")[^"]*""#,
+            "${1}[REDACTED]\"",
+        ),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).expect("valid redaction pattern"), replacement))
+    .collect()
+});
+
+fn remember_secrets<'a>(credentials: impl IntoIterator<Item = &'a Credential>) {
+    let mut known = KNOWN_SECRETS.write().unwrap_or_else(|p| p.into_inner());
+    for value in credentials.into_iter().flat_map(|c| c.secret.values()) {
+        // Very short values would mask ordinary words
+        if value.len() >= 8 && !known.iter().any(|k| k == value) {
+            known.push(value.to_string());
+        }
+    }
+}
+
+/// `text` with known secrets and key-like strings replaced by [REDACTED]
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    for secret in KNOWN_SECRETS.read().unwrap_or_else(|p| p.into_inner()).iter() {
+        if out.contains(secret.as_str()) {
+            out = out.replace(secret.as_str(), "[REDACTED]");
+        }
+    }
+    for (pattern, replacement) in SECRET_PATTERNS.iter() {
+        if pattern.is_match(&out) {
+            out = pattern.replace_all(&out, *replacement).into_owned();
+        }
+    }
+    out
+}
+
+/// env_logger with every message passed through `redact`
+pub fn init_logging() {
+    use std::io::Write;
+    env_logger::Builder::from_default_env()
+        .format(|buf, record| {
+            writeln!(buf, "[{} {} {}] {}", buf.timestamp(), record.level(), record.target(), redact(&record.args().to_string()))
+        })
+        .init();
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultStatus {
+    /// A vault file exists
+    pub created: bool,
+    pub unlocked: bool,
+    pub key_source: Option<KeySource>,
+    pub backend: Option<String>,
+    pub credentials: usize,
+}
+
+#[tauri::command]
+pub fn get_credential_vault_status() -> Result<VaultStatus, String> {
+    let file = read_vault_file(&vault_path()?)?;
+    let guard = VAULT.lock().unwrap_or_else(|p| p.into_inner());
+    Ok(VaultStatus {
+        created: file.is_some(),
+        unlocked: guard.is_some(),
+        key_source: file.map(|f| f.key_source),
+        backend: guard.as_ref().map(|v| v.store.name().to_string()),
+        credentials: guard.as_ref().map_or(0, |v| v.credentials.len()),
+    })
+}
+
+/// Open (or create) the vault with a passphrase, or with the device secret when omitted
+#[tauri::command]
+pub fn unlock_credential_vault(passphrase: Option<String>) -> Result<VaultStatus, String> {
+    let count = match passphrase {
+        Some(passphrase) if !passphrase.is_empty() => unlock(KeySource::Passphrase, passphrase.as_bytes())?,
+        _ => unlock(KeySource::Device, device_secret()?.as_bytes())?,
+    };
+    info!("🔐 Kimlik kasası açıldı: {} kayıt", count);
+    get_credential_vault_status()
+}
+
+#[tauri::command]
+pub fn lock_credential_vault() -> Result<(), String> {
+    *VAULT.lock().unwrap_or_else(|p| p.into_inner()) = None;
+    info!("🔒 Kimlik kasası kilitlendi");
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_credentials() -> Result<Vec<CredentialInfo>, String> {
+    with_vault(|vault| Ok(vault.list()))
+}
+
+/// Store an API key; passing an existing id replaces its key
+#[tauri::command]
+pub fn save_api_key_credential(
+    id: Option<String>,
+    label: String,
+    provider: String,
+    api_key: String,
+) -> Result<CredentialInfo, String> {
+    let api_key = api_key.trim().to_string();
+    if api_key.is_empty() {
+        return Err("API anahtarı boş olamaz".to_string());
+    }
+    let now = now_secs();
+    with_vault(|vault| vault.put(Credential {
+        id: id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        label,
+        provider,
+        created_at: now,
+        updated_at: now,
+        secret: Secret::ApiKey { key: api_key },
+    }))
+}
+
+#[tauri::command]
+pub fn delete_credential(id: String) -> Result<bool, String> {
+    with_vault(|vault| vault.remove(&id))
+}
+
+/// Refresh an OAuth credential now
+#[tauri::command]
+pub async fn refresh_credential(id: String) -> Result<CredentialInfo, String> {
+    refresh(&id).await?;
+    with_vault(|vault| Ok(vault.get(&id)?.info()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_vault_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("corex_vault_{}", uuid::Uuid::new_v4().simple()))
+            .join("credentials.vault")
+    }
+
+    fn api_key(id: &str, key: &str) -> Credential {
+        Credential {
+            id: id.into(),
+            label: id.into(),
+            provider: "openai".into(),
+            created_at: 1,
+            updated_at: 1,
+            secret: Secret::ApiKey { key: key.into() },
+        }
+    }
+
+    #[test]
+    fn test_encrypted_roundtrip_and_wrong_passphrase() {
+        let path = temp_vault_path();
+        let store = EncryptedFileStore::open(&path, KeySource::Passphrase, b"correct horse").unwrap();
+        let mut vault = Vault::open(Box::new(store)).unwrap();
+        let info = vault.put(api_key("work", "sk-test-0123456789abcdef")).unwrap();
+        assert_eq!((info.kind.as_str(), info.hint.as_str()), ("api_key", "…cdef"));
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains("sk-test-0123456789abcdef"));
+
+        let reopened = EncryptedFileStore::open(&path, KeySource::Passphrase, b"correct horse").unwrap();
+        assert_eq!(Vault::open(Box::new(reopened)).unwrap().get("work").unwrap().secret.token(), "sk-test-0123456789abcdef");
+        assert!(EncryptedFileStore::open(&path, KeySource::Passphrase, b"wrong").is_err());
+        assert!(EncryptedFileStore::open(&path, KeySource::Device, b"correct horse").is_err());
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_oauth_refresh_window() {
+        let token = TokenResponse {
+            access_token: "access-1".into(),
+            refresh_token: None,
+            expires_in: Some(3600),
+            token_type: "Bearer".into(),
+        };
+        let credential = Credential {
+            secret: oauth_secret(token, Some("refresh-1".into()), 1_000),
+            ..api_key("ms", "unused")
+        };
+        assert!(matches!(&credential.secret, Secret::OAuth { refresh_token: Some(r), expires_at: Some(4_600), .. } if r == "refresh-1"));
+        assert!(!credential.needs_refresh(4_000, REFRESH_MARGIN_SECS));
+        assert!(credential.needs_refresh(4_500, REFRESH_MARGIN_SECS));
+        assert!(!api_key("k", "sk-whatever-long-key").needs_refresh(u64::MAX / 2, 0));
+        assert!(!format!("{:?}", credential).contains("access-1"));
+    }
+
+    #[test]
+    fn test_redact_known_secrets_and_key_shapes() {
+        remember_secrets([&api_key("local", "lm-studio-secret-value")]);
+        let line = "POST http://localhost:1234/v1 key lm-studio-secret-value";
+        assert_eq!(redact(line), "POST http://localhost:1234/v1 key [REDACTED]");
+
+        assert_eq!(redact("Authorization: Bearer abcdefghijkl"), "Authorization: Bearer [REDACTED]");
+        assert_eq!(
+            redact("gemini -> https://x.googleapis.com/v1beta/models/g:generateContent?key=AIzaSyA-123&alt=sse"),
+            "gemini -> https://x.googleapis.com/v1beta/models/g:generateContent?key=[REDACTED]&alt=sse"
+        );
+        assert_eq!(redact(r#"{"api_key": "abc", "model": "m"}"#), r#"{"api_key": "[REDACTED]", "model": "m"}"#);
+        assert_eq!(redact("using sk-ant-REDACTED"), "using [REDACTED]");
+        assert_eq!(redact("nothing secret here"), "nothing secret here");
+    }
+}
@@ -1,31 +1,70 @@
 // GGUF System - Complete implementation in one file
 use llama_cpp_2::context::params::LlamaContextParams;
+use llama_cpp_2::context::LlamaContext;
 use llama_cpp_2::llama_backend::LlamaBackend;
 use llama_cpp_2::llama_batch::LlamaBatch;
 use llama_cpp_2::model::params::LlamaModelParams;
 use llama_cpp_2::model::{LlamaModel, AddBos};
+use llama_cpp_2::token::LlamaToken;
+use llama_cpp_2::token::data::LlamaTokenData;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use log::{info, error, warn};
-use tauri::State;
+use tauri::{AppHandle, State};
 use serde_json::json;
-use base64::{Engine as _, engine::general_purpose}; // 🆕 Base64 decoding
 use rand::Rng; // 🆕 Random number generation for sampling
+use std::time::Instant;
+
+use crate::gguf_pool::{self, HardwareBudget, KvCacheType, LoadPlan, ModelArch, ModelFootprint, PoolBudget, PoolEntry, PoolPolicy};
+use crate::gguf_reader::{self, GgufFile, GgufReadOptions};
+use crate::gguf_vision::{self, VisionProjector};
+use crate::gguf_lora::{self, ActiveLora, AdapterContext};
+use crate::gguf_speculative::SpeculativePairing;
+use crate::hardware;
+use crate::inference::{GenerateRequest, InferenceEngine, LoadRequest};
+use crate::model_library;
+use crate::response_cache::{self, CacheOptions};
+use crate::usage::UsageEvent;
+use crate::gguf_completion::{
+    compute_logprobs, plan_context_shift, shift_prompt, CompletionOptions, CompletionTimings,
+    ContextShiftStats, FinishReason, GgufCompletion, JsonPrefix, StopMatch, StopSequenceMatcher, TokenLogprob,
+    TopLogprob,
+};
 
 use std::collections::HashMap;
 
 // State structure
 pub struct LoadedModel {
+    // 🆕 mmproj projektörü modele bağlı; model'den önce drop edilmesi için ilk alan
+    pub vision: Mutex<Option<VisionProjector>>,
+    // 🆕 Ekli LoRA adapter'ları (her yeni context'e ölçekleriyle uygulanır)
+    pub loras: Mutex<Vec<ActiveLora>>,
     pub model: LlamaModel,
     pub model_path: String,
     pub n_ctx: u32,
     pub n_gpu_layers: u32,
+    pub footprint: ModelFootprint, // 🆕 Tahmini RAM/VRAM kullanımı
+    /// Decoded token text, filled lazily (JSON mode and logprobs look pieces up every step)
+    pub token_pieces: Mutex<HashMap<i32, String>>,
+}
+
+impl LoadedModel {
+    /// Lossy text of one token, decoded once per loaded model
+    pub fn token_piece(&self, token: LlamaToken) -> String {
+        let mut pieces = self.token_pieces.lock().unwrap_or_else(|p| p.into_inner());
+        pieces.entry(token.0).or_insert_with(|| token_piece_lossy(&self.model, token)).clone()
+    }
 }
 
 pub struct GgufState {
     pub backend: Option<Arc<LlamaBackend>>,
     pub models: HashMap<String, Arc<LoadedModel>>, // Model path -> Model info
     pub backend_initialized: bool,
+    pub budget: PoolBudget, // 🆕 Havuz bellek bütçesi
+    pub speculative: HashMap<String, SpeculativePairing>, // 🆕 Target path -> draft model eşleşmesi
+    last_used: HashMap<String, u64>, // Model path -> LRU sayacı
+    use_counter: u64,
+    reservations: HashMap<String, ModelFootprint>, // Yüklenmekte olan modellerin bütçe payı
 }
 
 impl Default for GgufState {
@@ -34,24 +73,130 @@ impl Default for GgufState {
             backend: None,
             models: HashMap::new(),
             backend_initialized: false,
+            budget: PoolBudget::default(),
+            speculative: HashMap::new(),
+            last_used: HashMap::new(),
+            use_counter: 0,
+            reservations: HashMap::new(),
         }
     }
 }
 
+impl GgufState {
+    /// Get a pooled model + backend and mark the model as recently used
+    pub fn checkout(&mut self, model_path: &str) -> Result<(Arc<LoadedModel>, Arc<LlamaBackend>), String> {
+        let model = self.models.get(model_path)
+            .cloned() // Arc cloning is cheap
+            .ok_or_else(|| format!("Model havuzda bulunamadı: {}", model_path))?;
+        let backend = self.backend.as_ref()
+            .cloned()
+            .ok_or_else(|| "Backend not initialized".to_string())?;
+        self.touch(model_path);
+        Ok((model, backend))
+    }
+
+    fn touch(&mut self, model_path: &str) {
+        self.use_counter += 1;
+        self.last_used.insert(model_path.to_string(), self.use_counter);
+    }
+
+    /// Remove one model from the pool (memory is freed once in-flight requests finish)
+    pub fn remove_model(&mut self, model_path: &str) -> bool {
+        self.last_used.remove(model_path);
+        // Eşleşmeler her iki model de havuzdayken geçerli
+        self.speculative.retain(|target, p| target != model_path && p.draft_model_path != model_path);
+        self.models.remove(model_path).is_some()
+    }
+
+    /// Drop every model and the backend (frees all GPU memory)
+    pub fn unload_all(&mut self) {
+        self.models.clear();
+        self.last_used.clear();
+        self.speculative.clear();
+        self.backend = None;
+        self.backend_initialized = false;
+    }
+
+    /// Pool keys, most recently used first
+    pub fn models_by_recency(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.models.keys().cloned().collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(self.last_used.get(k).copied().unwrap_or(0)));
+        keys
+    }
+
+    pub fn pool_entries(&self) -> Vec<PoolEntry> {
+        self.models.iter()
+            .map(|(key, m)| PoolEntry {
+                key: key.clone(),
+                last_used: self.last_used.get(key).copied().unwrap_or(0),
+                footprint: m.footprint.clone(),
+            })
+            .collect()
+    }
+
+    /// Models to evict so `footprint` fits. `model_path` itself (a reload replaces it)
+    /// is left out, and loads still in flight keep their reserved share of the budget.
+    fn plan_admission(&self, model_path: &str, footprint: &ModelFootprint) -> Result<Vec<String>, String> {
+        let entries: Vec<PoolEntry> = self.pool_entries().into_iter().filter(|e| e.key != model_path).collect();
+        let (reserved_ram, reserved_vram) = self.reservations.iter()
+            .filter(|(key, _)| key.as_str() != model_path)
+            .fold((0u64, 0u64), |(ram, vram), (_, f)| (ram + f.ram_bytes, vram + f.vram_bytes));
+        let budget = PoolBudget {
+            max_ram_bytes: self.budget.max_ram_bytes.saturating_sub(reserved_ram),
+            max_vram_bytes: self.budget.max_vram_bytes.saturating_sub(reserved_vram),
+            policy: self.budget.policy,
+        };
+        gguf_pool::plan_admission(&entries, footprint, &budget)
+    }
+
+    /// Admit `footprint` for `model_path`: evict what the plan requires and reserve
+    /// the budget until the load finishes
+    fn admit(&mut self, model_path: &str, footprint: &ModelFootprint) -> Result<(), String> {
+        let evict = self.plan_admission(model_path, footprint)?;
+        for key in evict {
+            warn!("♻️ LRU eviction: {}", key);
+            self.remove_model(&key);
+        }
+        self.reservations.insert(model_path.to_string(), footprint.clone());
+        Ok(())
+    }
+
+    pub fn used_bytes(&self) -> (u64, u64) {
+        self.models.values().fold((0, 0), |(ram, vram), m| {
+            (ram + m.footprint.ram_bytes, vram + m.footprint.vram_bytes)
+        })
+    }
+}
+
 // Commands
 #[tauri::command]
 pub async fn load_gguf_model(
+    app: AppHandle,
     state: State<'_, Arc<Mutex<GgufState>>>,
     model_path: String,
     n_ctx: u32,
     n_gpu_layers: u32,
+) -> Result<String, String> {
+    let engine = InferenceEngine::llama_cpp(state.inner().clone());
+    let model_path = engine.load(&LoadRequest { model_path, n_ctx, n_gpu_layers })?;
+    model_library::mark_used(&app, &model_path);
+    Ok(format!("✅ Model başarıyla yüklendi: {}", model_path))
+}
+
+/// Load a model into the pool (evicting LRU models if the budget requires it).
+/// Returns the pool key, i.e. the resolved model path.
+pub fn load_into_pool(
+    state: &Mutex<GgufState>,
+    model_path: &str,
+    n_ctx: u32,
+    n_gpu_layers: u32,
 ) -> Result<String, String> {
     info!("🔵 GGUF model loading: {}", model_path);
     info!("📊 Context: {}, GPU Layers: {}", n_ctx, n_gpu_layers);
     
     // Split GGUF dosyalari icin ilk parcaya yonlendir
     // Ornek: model-00003-of-00004.gguf -> model-00001-of-00004.gguf
-    let model_path = resolve_split_gguf_path(&model_path);
+    let model_path = resolve_split_gguf_path(model_path);
     info!("📂 Resolved model path: {}", model_path);
     
     if !Path::new(&model_path).exists() {
@@ -65,17 +210,36 @@ pub async fn load_gguf_model(
     let file_size_mb = metadata.len() / (1024 * 1024);
     info!("📦 Model dosyası boyutu: {} MB", file_size_mb);
     
-    if file_size_mb < 10 {
-        // Split GGUF parcalari kucuk olabilir, kontrol et
-        let is_split = regex::Regex::new(r"-\d{5}-of-\d{5}\.gguf$")
-            .map(|re| re.is_match(&model_path))
-            .unwrap_or(false);
-        if !is_split {
-            error!("❌ Model dosyası çok küçük ({}MB), bozuk olabilir", file_size_mb);
-            return Err(format!("Model dosyası çok küçük ({}MB), muhtemelen bozuk veya eksik indirilmiş. Lütfen modeli yeniden indirin.", file_size_mb));
+    // Boyut yerine GGUF başlığı ve tensör tablosu doğrulanır (küçük modeller de geçerli)
+    for part in split_part_paths(&model_path) {
+        if let Err(e) = gguf_reader::validate_gguf_file(&part) {
+            error!("❌ Geçersiz GGUF dosyası {}: {}", part, e);
+            return Err(format!("Model dosyası geçersiz ({}): {}. Muhtemelen bozuk veya eksik indirilmiş, lütfen modeli yeniden indirin.", part, e));
         }
     }
 
+    // 🆕 Bellek tahmini ve havuz bütçesi kontrolü (yüklemeden önce)
+    let arch = model_arch_for(&model_path);
+    let planned_gpu_layers = offload_layers(n_gpu_layers);
+    let footprint = gguf_pool::estimate_footprint(&arch, n_ctx, planned_gpu_layers, KvCacheType::default());
+    info!("🧮 Tahmini bellek: RAM {:.2} GB, VRAM {:.2} GB (KV cache {:.2} GB)",
+        footprint.ram_bytes as f64 / 1_073_741_824.0,
+        footprint.vram_bytes as f64 / 1_073_741_824.0,
+        footprint.kv_cache_bytes as f64 / 1_073_741_824.0);
+    {
+        let mut guard = state.lock().unwrap();
+        if guard.reservations.contains_key(&model_path) {
+            return Err(format!("Model zaten yükleniyor: {}", model_path));
+        }
+        // Aynı model yeniden yükleniyorsa eskisi yeni model yüklenene kadar havuzda kalır
+        guard.admit(&model_path, &footprint).map_err(|e| {
+            error!("❌ {}", e);
+            e
+        })?;
+    }
+    // Rezervasyon, yükleme nasıl biterse bitsin bırakılır
+    let _reservation = Reservation { state, model_path: &model_path };
+
     // Initialize backend only once
     let backend = {
         let mut guard = state.lock().unwrap();
@@ -106,25 +270,24 @@ pub async fn load_gguf_model(
         "CPU"
     };
     
-    let safe_gpu_layers = if has_gpu {
-        info!("🎮 {} enabled - GPU Layers: {}", backend_name, n_gpu_layers);
-        n_gpu_layers
+    let safe_gpu_layers = offload_layers(n_gpu_layers);
+    if has_gpu {
+        info!("🎮 {} enabled - GPU Layers: {}", backend_name, safe_gpu_layers);
     } else {
         info!("⚠️ No GPU backend - Forcing CPU-only (GPU layers = 0)");
-        0
-    };
+    }
     
     let model_params = LlamaModelParams::default()
         .with_n_gpu_layers(safe_gpu_layers);
 
     info!("🔄 Loading model to GPU... (this may take a while)");
-    info!("📋 Model params: n_gpu_layers={}", n_gpu_layers);
+    info!("📋 Model params: n_gpu_layers={}", safe_gpu_layers);
 
     // 🆕 2025 Güncelleme: GPU/boyut kısıtlamaları kaldırıldı
     // Tüm GGUF modelleri yüklenmeye çalışılır
     // Bellek yetersiz ise CPU'ya otomatik fallback yapılır
     
-    let mut final_gpu_layers = n_gpu_layers;
+    let mut final_gpu_layers = safe_gpu_layers;
     let model = match LlamaModel::load_from_file(&backend, &model_path, &model_params) {
         Ok(m) => m,
         Err(e) => {
@@ -172,18 +335,56 @@ pub async fn load_gguf_model(
         info!("⚠️ GPU offload kapalı - Model CPU'da çalışacak");
     }
 
-    // Save model to state pool
     let mut guard = state.lock().unwrap();
+
+    // CPU fallback olduysa tahmini yeniden hesapla ve bütçeyi yeniden kontrol et
+    let footprint = if final_gpu_layers != planned_gpu_layers {
+        let cpu_footprint = gguf_pool::estimate_footprint(&arch, n_ctx, final_gpu_layers, KvCacheType::default());
+        guard.admit(&model_path, &cpu_footprint).map_err(|e| {
+            error!("❌ CPU fallback bütçeye sığmıyor: {}", e);
+            e
+        })?;
+        cpu_footprint
+    } else {
+        footprint
+    };
+
+    // Save model to state pool (replaces the previous instance on reload)
+    guard.remove_model(&model_path);
     guard.models.insert(model_path.clone(), Arc::new(LoadedModel {
+        vision: Mutex::new(None),
+        loras: Mutex::new(Vec::new()),
         model,
         model_path: model_path.clone(),
         n_ctx,
         n_gpu_layers: final_gpu_layers,
+        footprint,
+        token_pieces: Mutex::new(HashMap::new()),
     }));
+    guard.touch(&model_path);
+    guard.reservations.remove(&model_path);
     
     info!("✅ Model saved to pool! Total models: {}", guard.models.len());
+    drop(guard);
 
-    Ok(format!("✅ Model başarıyla yüklendi: {}", model_path))
+    Ok(model_path)
+}
+
+/// GPU layers this build can offload; always 0 without CUDA or Vulkan
+pub(crate) fn offload_layers(n_gpu_layers: u32) -> u32 {
+    if cfg!(feature = "cuda") || cfg!(feature = "vulkan") { n_gpu_layers } else { 0 }
+}
+
+/// Releases a load's budget reservation however `load_into_pool` returns
+struct Reservation<'a> {
+    state: &'a Mutex<GgufState>,
+    model_path: &'a str,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.state.lock().unwrap_or_else(|p| p.into_inner()).reservations.remove(self.model_path);
+    }
 }
 
 #[tauri::command]
@@ -193,58 +394,79 @@ pub async fn chat_with_gguf_model(
     prompt: String,
     max_tokens: u32,
     temperature: f32,
+    stop: Option<Vec<String>>,
 ) -> Result<String, String> {
-    info!("🔵 Starting inference...");
-    info!("📝 Prompt length: {} chars", prompt.len());
-    info!("⚙️ Max tokens: {}, Temperature: {}", max_tokens, temperature);
-
-    // 🆕 Get model and backend from pool with minimum lock time
-    let (loaded_model, backend) = {
-        let guard = match state.lock() {
-            Ok(g) => g,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        
-        let model = guard.models.get(&model_path)
-            .cloned() // Arc cloing is cheap
-            .ok_or_else(|| format!("Model havuzda bulunamadı: {}", model_path))?;
-            
-        let backend = guard.backend.as_ref()
-            .cloned()
-            .ok_or_else(|| "Backend not initialized".to_string())?;
-            
-        (model, backend)
+    let options = CompletionOptions {
+        stop: stop.unwrap_or_default(),
+        top_logprobs: None,
+        ..Default::default()
     };
+    let completion = complete_with_gguf_model(state, model_path, prompt, max_tokens, temperature, Some(options), None).await?;
+    Ok(completion.text)
+}
 
-    let model = &loaded_model.model;
-    let n_ctx = loaded_model.n_ctx;
+/// 🆕 Structured completion: finish reason, token counts, timings and optional logprobs
+#[tauri::command]
+pub async fn complete_with_gguf_model(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    prompt: String,
+    max_tokens: u32,
+    temperature: f32,
+    options: Option<CompletionOptions>,
+    cache: Option<CacheOptions>,
+) -> Result<GgufCompletion, String> {
+    let options = options.unwrap_or_default();
+    let variant = response_cache::gguf_variant(state.inner(), &model_path);
+    let key = response_cache::gguf_key("completion", &model_path, &variant, json!(prompt), max_tokens, temperature, &options);
+    if let Some((mut completion, info)) = response_cache::lookup::<GgufCompletion>(&cache, key.as_ref()) {
+        completion.cache = Some(info);
+        return Ok(completion);
+    }
 
-    info!("📦 Using model from pool: {}", model_path);
+    crate::usage::check_budget(Some("gguf"), "completion", None)?;
+    let engine = InferenceEngine::llama_cpp(state.inner().clone());
+    let request = GenerateRequest { prompt, max_tokens, temperature, options };
+    let model_id = model_path.clone();
+    let mut completion = tokio::task::spawn_blocking(move || engine.generate(&model_id, &request))
+        .await
+        .map_err(|e| format!("Inference görevi başarısız: {}", e))??;
+    crate::usage::record(UsageEvent::local("completion", &model_path, completion.prompt_tokens, completion.completion_tokens));
+    completion.cache = response_cache::store(&cache, key.as_ref(), &completion);
+    Ok(completion)
+}
 
-    // Create context with proper KV cache size (FIX-31)
-    let kv_cache_size = (n_ctx + max_tokens).max(4096);
-    
-    // 🔥 FIXED: n_batch context'in tek seferde işleyebileceği max token sayısıdır.
-    // LlamaBatch boyutu n_batch'den büyük olamaz.
-    let n_batch = 8192; // Max batch size increase
-    
-    info!("📊 Context Params: n_ctx={}, n_batch={}", kv_cache_size, n_batch);
-    
-    let ctx_params = LlamaContextParams::default()
-        .with_n_ctx(std::num::NonZeroU32::new(kv_cache_size as u32))
-        .with_n_batch(n_batch as u32);
+/// Run a full completion against a pooled model
+pub fn run_completion(
+    loaded_model: &LoadedModel,
+    backend: &LlamaBackend,
+    prompt: &str,
+    max_tokens: u32,
+    temperature: f32,
+    options: &CompletionOptions,
+) -> Result<GgufCompletion, String> {
+    run_completion_with_callback(loaded_model, backend, prompt, max_tokens, temperature, options, &mut |_| true)
+}
 
-    let mut context = model.new_context(&backend, ctx_params)
-        .map_err(|e| {
-            error!("❌ Context creation failed: {:?}", e);
-            format!("Context creation failed: {:?}", e)
-        })?;
+/// Same as `run_completion`, but hands every emitted text piece to `on_text`.
+/// Returning `false` from the callback cancels generation.
+pub fn run_completion_with_callback(
+    loaded_model: &LoadedModel,
+    backend: &LlamaBackend,
+    prompt: &str,
+    max_tokens: u32,
+    temperature: f32,
+    options: &CompletionOptions,
+    on_text: &mut dyn FnMut(&str) -> bool,
+) -> Result<GgufCompletion, String> {
+    let model = &loaded_model.model;
+    let n_ctx = loaded_model.n_ctx;
 
-    info!("✅ Context created with KV cache size: {}", kv_cache_size);
+    let mut context = new_inference_context(loaded_model, backend)?;
 
     // Tokenize prompt with BOS token
     info!("🔤 Tokenizing prompt with BOS token...");
-    let tokens = model.str_to_token(&prompt, AddBos::Always)
+    let mut tokens = model.str_to_token(prompt, AddBos::Always)
         .map_err(|e| {
             error!("❌ Tokenization failed: {:?}", e);
             format!("Tokenization failed: {:?}", e)
@@ -258,9 +480,17 @@ pub async fn chat_with_gguf_model(
     }
     
     // Check if prompt is too long
+    let mut prompt_tokens_discarded = 0;
     if tokens.len() > n_ctx as usize {
-        error!("❌ Prompt too long: {} tokens (max: {})", tokens.len(), n_ctx);
-        return Err(format!("Prompt too long: {} tokens (max: {})", tokens.len(), n_ctx));
+        let Some(n_keep) = options.n_keep else {
+            error!("❌ Prompt too long: {} tokens (max: {})", tokens.len(), n_ctx);
+            return Err(format!("Prompt too long: {} tokens (max: {})", tokens.len(), n_ctx));
+        };
+        // 🆕 Context shift: baştaki n_keep token korunur, en eski bloklar atılır
+        let (shifted, erased) = shift_prompt(&tokens, n_ctx as usize, n_keep);
+        warn!("✂️ Context shift: prompt {} -> {} tokens (n_keep {})", tokens.len(), shifted.len(), n_keep);
+        tokens = shifted;
+        prompt_tokens_discarded = erased;
     }
 
     // Create batch - MUST be at least as large as the number of prompt tokens
@@ -272,6 +502,7 @@ pub async fn chat_with_gguf_model(
     
     // Create batch outside if/else so it's available later
     let mut batch = LlamaBatch::new(batch_size, 1);
+    let prompt_start = Instant::now();
     
     // Process prompt in chunks if necessary
     if tokens.len() > max_batch_size {
@@ -321,103 +552,190 @@ pub async fn chat_with_gguf_model(
         info!("✅ Prompt processed!");
     }
 
+    let prompt_eval_ms = prompt_start.elapsed().as_secs_f64() * 1000.0;
+
+    // 🔥 FIXED: n_cur her zaman tüm prompt tokenlarının sayısı olmalı (chunking olsa bile)
+    let n_past = tokens.len() as i32;
+    let mut completion = generate_from_context(
+        loaded_model, &mut context, &mut batch, &tokens, n_past, tokens.len(), prompt_eval_ms,
+        max_tokens, temperature, options, on_text,
+    )?;
+    if prompt_tokens_discarded > 0 {
+        let stats = completion.context_shift.get_or_insert_with(|| ContextShiftStats {
+            n_keep: options.n_keep.unwrap_or(0),
+            ..Default::default()
+        });
+        stats.prompt_tokens_discarded = prompt_tokens_discarded;
+    }
+    Ok(completion)
+}
+
+/// Create an inference context whose KV cache holds exactly the model's n_ctx, the
+/// size the pool budget was estimated for. Prompt and reply share that window.
+pub(crate) fn new_inference_context<'a>(
+    loaded_model: &'a LoadedModel,
+    backend: &LlamaBackend,
+) -> Result<AdapterContext<'a>, String> {
+    let kv_cache_size = loaded_model.n_ctx;
+    
+    // 🔥 FIXED: n_batch context'in tek seferde işleyebileceği max token sayısıdır.
+    // LlamaBatch boyutu n_batch'den büyük olamaz.
+    let n_batch = 8192; // Max batch size increase
+    
+    // 🆕 Thread sayıları donanım taramasından (llama.cpp varsayılanı yerine)
+    let threads = hardware::thread_counts();
+    info!("📊 Context Params: n_ctx={}, n_batch={}, n_threads={}/{}", kv_cache_size, n_batch, threads.n_threads, threads.n_threads_batch);
+    
+    let ctx_params = LlamaContextParams::default()
+        .with_n_ctx(std::num::NonZeroU32::new(kv_cache_size as u32))
+        .with_n_batch(n_batch as u32)
+        .with_n_threads(threads.n_threads)
+        .with_n_threads_batch(threads.n_threads_batch);
+
+    let context = loaded_model.model.new_context(backend, ctx_params)
+        .map_err(|e| {
+            error!("❌ Context creation failed: {:?}", e);
+            format!("Context creation failed: {:?}", e)
+        })?;
+
+    info!("✅ Context created with KV cache size: {}", kv_cache_size);
+    gguf_lora::apply_adapters(loaded_model, context)
+}
+
+/// Sample and decode tokens from an already-evaluated prompt.
+///
+/// `history` holds the prompt tokens used for the repetition penalty (image
+/// embeddings have no token ids, so multimodal prompts pass only their text).
+/// `n_past` is the next KV position and `prompt_tokens` the count reported in usage.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn generate_from_context(
+    loaded_model: &LoadedModel,
+    context: &mut LlamaContext<'_>,
+    batch: &mut LlamaBatch,
+    history: &[LlamaToken],
+    n_past: i32,
+    prompt_tokens: usize,
+    prompt_eval_ms: f64,
+    max_tokens: u32,
+    temperature: f32,
+    options: &CompletionOptions,
+    on_text: &mut dyn FnMut(&str) -> bool,
+) -> Result<GgufCompletion, String> {
+    let tokens = history;
+    let model = &loaded_model.model;
+
     // Token generation
     let mut response_tokens = Vec::new();
+    let mut response = String::new();
+    let mut decoder = encoding_rs::UTF_8.new_decoder();
+    let mut stop_matcher = StopSequenceMatcher::new(&options.stop);
+    let mut logprobs: Option<Vec<TokenLogprob>> = options.top_logprobs.map(|_| Vec::new());
+    let mut finish_reason = FinishReason::Length;
+    let mut matched_stop = None;
+    let mut decode_errors = 0;
     
-    // 🔥 FIXED: n_cur her zaman tüm prompt tokenlarının sayısı olmalı (chunking olsa bile)
-    let mut n_cur = tokens.len() as i32;
+    let mut n_cur = n_past;
+    let n_ctx_window = context.n_ctx() as i32;
+    let mut context_shift: Option<ContextShiftStats> = None;
+    let mut json = options.json.then(JsonPrefix::new);
+    let generation_start = Instant::now();
     
     info!("🎲 Starting token generation from position {}", n_cur);
 
     for i in 0..max_tokens {
         // Get candidates
-        let candidates = context.candidates();
-        
-        // 🆕 Repetition Penalty ve Temperature Sampling
-        let candidates_vec: Vec<_> = candidates.into_iter().collect();
-        
-        // 🔄 Repetition Penalty Uygulama
-        let repeat_penalty = 1.15_f32;
-        let penalty_last_n = 64; 
+        let candidates_vec: Vec<LlamaTokenData> = context.candidates().collect();
+        // Distribution the token is actually drawn from (penalised and temperature-scaled)
+        let mut sampled_logits: Vec<(LlamaToken, f32)> = Vec::new();
         
-        let mut recent_tokens = Vec::new();
-        let total_recent = tokens.len() + response_tokens.len();
-        let start_idx = if total_recent > penalty_last_n { total_recent - penalty_last_n } else { 0 };
-        
-        if tokens.len() > start_idx {
-            recent_tokens.extend_from_slice(&tokens[start_idx..]);
-            recent_tokens.extend_from_slice(&response_tokens);
-        } else {
-            let resp_start = start_idx - tokens.len();
-            recent_tokens.extend_from_slice(&response_tokens[resp_start..]);
-        }
-        
-        let adjusted_logits: Vec<(llama_cpp_2::token::LlamaToken, f32)> = candidates_vec.iter()
-            .map(|c| {
-                let id = c.id();
-                let mut logit = c.logit();
-                
-                if recent_tokens.contains(&id) {
-                    if logit <= 0.0 {
-                        logit *= repeat_penalty;
-                    } else {
-                        logit /= repeat_penalty;
-                    }
+        let new_token_id = match &json {
+            Some(grammar) => match sample_json_token(loaded_model, &candidates_vec, grammar) {
+                Some(token) => {
+                    sampled_logits = candidates_vec.iter().map(|c| (c.id(), c.logit())).collect();
+                    token
                 }
-                
-                (id, logit)
-            })
-            .collect();
-        
-        // Temperature-based sampling
-        let new_token_id = if temperature > 0.0 && temperature != 1.0 {
-            // Apply temperature scaling to logits
-            let scaled_logits: Vec<_> = adjusted_logits.iter()
-                .map(|(id, logit)| (*id, logit / temperature))
-                .collect();
-            
-            // Convert to probabilities using softmax
-            let max_logit = scaled_logits.iter()
-                .map(|(_, logit)| logit)
-                .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
-            
-            let exp_sum: f32 = scaled_logits.iter()
-                .map(|(_, logit)| (logit - max_logit).exp())
-                .sum();
-            
-            let probs: Vec<_> = scaled_logits.iter()
-                .map(|(id, logit)| (*id, (logit - max_logit).exp() / exp_sum))
-                .collect();
-            
-            // Sample from distribution
-            let mut rng = rand::thread_rng();
-            let random_val: f32 = rng.gen();
-            let mut cumulative = 0.0;
-            
-            let mut selected_id = probs[0].0;
-            for (id, prob) in probs.iter() {
-                cumulative += prob;
-                if random_val <= cumulative {
-                    selected_id = *id;
+                None => {
+                    warn!("⚠️ No candidate fits the JSON grammar at token {}, stopping", i);
                     break;
                 }
+            },
+            None => {
+                let recent_tokens = recent_token_window(tokens, &response_tokens);
+                let adjusted = sampling_logits(&candidates_vec, &recent_tokens, temperature);
+                let token = sample_from_logits(&adjusted, temperature).unwrap_or(candidates_vec[0].id());
+                sampled_logits = adjusted;
+                token
             }
-            selected_id
-        } else {
-            // No temperature, just pick highest probability
-            adjusted_logits.into_iter()
-                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
-                .map(|(id, _)| id)
-                .unwrap_or(candidates_vec[0].id())
         };
 
         // Check for EOS (End of Sequence)
         if model.is_eog_token(new_token_id) {
             info!("✅ EOS token found at position {}, stopping", i);
+            finish_reason = FinishReason::Stop;
             break;
         }
 
         response_tokens.push(new_token_id);
+
+        // 🆕 Token'ı hemen decode et (stop sequence'lar token sınırlarını aşabilir)
+        let piece = match model.token_to_piece(new_token_id, &mut decoder, false, None) {
+            Ok(piece) => piece,
+            Err(e) => {
+                decode_errors += 1;
+                if decode_errors <= 10 {
+                    info!("⏭️ Token {}: decode failed: {:?}", i, e);
+                }
+                String::new()
+            }
+        };
+
+        if let (Some(entries), Some(top_n)) = (logprobs.as_mut(), options.top_logprobs) {
+            let logits: Vec<(i32, f32)> = sampled_logits.iter().map(|(id, l)| (id.0, *l)).collect();
+            let (logprob, top) = compute_logprobs(&logits, new_token_id.0, top_n as usize);
+            entries.push(TokenLogprob {
+                token: piece.clone(),
+                token_id: new_token_id.0,
+                logprob,
+                top_logprobs: top.into_iter()
+                    .map(|(id, lp)| TopLogprob {
+                        token: loaded_model.token_piece(LlamaToken(id)),
+                        token_id: id,
+                        logprob: lp,
+                    })
+                    .collect(),
+            });
+        }
+
+        match stop_matcher.push(&piece) {
+            StopMatch::Continue(text) => {
+                response.push_str(&text);
+                if !text.is_empty() && !on_text(&text) {
+                    info!("⏹️ Generation cancelled by caller at token {}", i);
+                    finish_reason = FinishReason::Cancelled;
+                    break;
+                }
+            }
+            StopMatch::Matched { emit, stop } => {
+                response.push_str(&emit);
+                if !emit.is_empty() {
+                    on_text(&emit);
+                }
+                info!("🛑 Stop sequence matched: {:?}", stop);
+                finish_reason = FinishReason::StopSequence;
+                matched_stop = Some(stop);
+                break;
+            }
+        }
+
+        // 🆕 JSON modu: nesne kapandığında üretimi bitir
+        if let Some(grammar) = json.as_mut() {
+            grammar.push_str(&piece);
+            if grammar.is_complete() {
+                info!("✅ JSON object complete at token {}", i);
+                finish_reason = FinishReason::Stop;
+                break;
+            }
+        }
         
         // Log first few tokens to debug
         if i < 5 {
@@ -429,46 +747,187 @@ pub async fn chat_with_gguf_model(
             info!("📊 Generated {}/{} tokens", i, max_tokens);
         }
 
+        // 🆕 KV cache doldu: n_keep varsa kaydır, yoksa pencere sonunda dur
+        if n_cur >= n_ctx_window {
+            let Some(n_keep) = options.n_keep else {
+                warn!("⚠️ Context window full at {} tokens, stopping", n_cur);
+                break;
+            };
+            let (n_keep, n_discard) = plan_context_shift(&mut context_shift, n_cur as usize, n_keep);
+            context.clear_kv_cache_seq(Some(0), Some(n_keep as u32), Some((n_keep + n_discard) as u32))
+                .map_err(|e| format!("Context shift failed: {:?}", e))?;
+            context.kv_cache_seq_add(0, Some((n_keep + n_discard) as u32), None, -(n_discard as i32))
+                .map_err(|e| format!("Context shift failed: {:?}", e))?;
+            n_cur -= n_discard as i32;
+            info!("↪️ Context shift: {} tokens discarded after the first {}", n_discard, n_keep);
+        }
+
         // Create new batch
         batch.clear();
         batch.add(new_token_id, n_cur, &[0], true)
             .map_err(|e| format!("Batch add failed: {:?}", e))?;
 
         // Decode
-        context.decode(&mut batch)
+        context.decode(batch)
             .map_err(|e| format!("Decode failed at token {}: {:?}", i, e))?;
 
         n_cur += 1;
     }
 
+    if matches!(finish_reason, FinishReason::Stop | FinishReason::Length) {
+        let rest = stop_matcher.flush();
+        if !rest.is_empty() {
+            on_text(&rest);
+        }
+        response.push_str(&rest);
+    }
+
+    let generation_ms = generation_start.elapsed().as_secs_f64() * 1000.0;
     let total_tokens = response_tokens.len();
-    info!("✅ Token generation completed: {} tokens", total_tokens);
+    info!("✅ Token generation completed: {} tokens ({:?})", total_tokens, finish_reason);
+    info!("✅ Decoded: {} characters from {} tokens ({} decode errors)", response.len(), total_tokens, decode_errors);
     
-    // 🆕 Token'ları tek tek decode et ve birleştir
-    info!("🔤 Decoding tokens...");
-    let mut response = String::new();
-    let mut decode_errors = 0;
+    let cleaned_response = clean_response(&response);
     
-    let mut decoder = encoding_rs::UTF_8.new_decoder();
-    for (idx, token_id) in response_tokens.iter().enumerate() {
-        // Use modern token_to_piece with 4 arguments as required by llama-cpp-2 v0.1.133
-        match model.token_to_piece(*token_id, &mut decoder, false, None) {
-            Ok(token_str) => {
-                response.push_str(&token_str);
-            },
-            Err(e) => {
-                decode_errors += 1;
-                if decode_errors <= 10 {
-                    info!("⏭️ Token {}: decode failed: {:?}", idx, e);
+    info!("📤 Final response length: {} characters", cleaned_response.len());
+    if cleaned_response.len() > 0 {
+        let preview: String = cleaned_response.chars().take(200).collect();
+        info!("📤 Response preview: {}", preview);
+    }
+
+    let timings = CompletionTimings::new(prompt_tokens, prompt_eval_ms, total_tokens, generation_ms);
+    info!("⏱️ Prompt: {:.1} tok/s, Generation: {:.1} tok/s", timings.prompt_tokens_per_sec, timings.tokens_per_sec);
+
+    Ok(GgufCompletion {
+        text: cleaned_response,
+        finish_reason,
+        stop_sequence: matched_stop,
+        prompt_tokens,
+        completion_tokens: total_tokens,
+        timings,
+        logprobs,
+        speculative: None,
+        context_shift,
+        cache: None,
+    })
+}
+
+/// Repetition-penalty window: the last 64 prompt + response tokens
+pub(crate) fn recent_token_window(history: &[LlamaToken], response_tokens: &[LlamaToken]) -> Vec<LlamaToken> {
+    let penalty_last_n = 64;
+    let total_recent = history.len() + response_tokens.len();
+    let start_idx = total_recent.saturating_sub(penalty_last_n);
+
+    let mut recent_tokens = Vec::with_capacity(penalty_last_n);
+    if history.len() > start_idx {
+        recent_tokens.extend_from_slice(&history[start_idx..]);
+        recent_tokens.extend_from_slice(response_tokens);
+    } else {
+        let resp_start = start_idx - history.len();
+        recent_tokens.extend_from_slice(&response_tokens[resp_start..]);
+    }
+    recent_tokens
+}
+
+/// Candidates JSON mode checks before falling back to the whole vocabulary
+const JSON_TOP_K: usize = 256;
+
+/// JSON mode: the highest-logit token whose text keeps the output a valid JSON
+/// prefix. End-of-generation tokens only qualify once the object is complete;
+/// tokens that decode to nothing on their own (partial UTF-8) are skipped.
+/// The best `JSON_TOP_K` are tried first; the rest of the vocabulary only if none fits.
+pub(crate) fn sample_json_token(loaded_model: &LoadedModel, candidates: &[LlamaTokenData], grammar: &JsonPrefix) -> Option<LlamaToken> {
+    let by_logit = |a: &&LlamaTokenData, b: &&LlamaTokenData| b.logit().partial_cmp(&a.logit()).unwrap_or(std::cmp::Ordering::Equal);
+    let fits = |c: &&LlamaTokenData| {
+        if loaded_model.model.is_eog_token(c.id()) {
+            return grammar.is_complete();
+        }
+        let piece = loaded_model.token_piece(c.id());
+        !piece.is_empty() && grammar.accepts(&piece)
+    };
+
+    let mut ranked: Vec<&LlamaTokenData> = candidates.iter().collect();
+    let k = JSON_TOP_K.min(ranked.len());
+    if k > 0 && k < ranked.len() {
+        ranked.select_nth_unstable_by(k - 1, by_logit);
+    }
+    let (head, tail) = ranked.split_at_mut(k);
+    head.sort_by(by_logit);
+    if let Some(c) = head.iter().find(|c| fits(c)) {
+        return Some(c.id());
+    }
+    tail.sort_by(by_logit);
+    tail.iter().find(|c| fits(c)).map(|c| c.id())
+}
+
+/// Repetition penalty + temperature sampling (greedy when temperature is 0 or 1)
+pub(crate) fn sample_next_token(candidates_vec: &[LlamaTokenData], recent_tokens: &[LlamaToken], temperature: f32) -> LlamaToken {
+    let adjusted = sampling_logits(candidates_vec, recent_tokens, temperature);
+    sample_from_logits(&adjusted, temperature).unwrap_or(candidates_vec[0].id())
+}
+
+/// Logits after the repetition penalty and, when sampling, temperature scaling.
+/// Logprobs are reported from these, since they are what the token is drawn from.
+pub(crate) fn sampling_logits(candidates_vec: &[LlamaTokenData], recent_tokens: &[LlamaToken], temperature: f32) -> Vec<(LlamaToken, f32)> {
+    // 🔄 Repetition Penalty Uygulama
+    let repeat_penalty = 1.15_f32;
+    let scale = if temperature > 0.0 && temperature != 1.0 { temperature } else { 1.0 };
+
+    candidates_vec.iter()
+        .map(|c| {
+            let id = c.id();
+            let mut logit = c.logit();
+            
+            if recent_tokens.contains(&id) {
+                if logit <= 0.0 {
+                    logit *= repeat_penalty;
+                } else {
+                    logit /= repeat_penalty;
                 }
             }
+            
+            (id, logit / scale)
+        })
+        .collect()
+}
+
+/// Draw from softmax(`logits`) when temperature is set (not 0 or 1), else take the highest
+pub(crate) fn sample_from_logits(logits: &[(LlamaToken, f32)], temperature: f32) -> Option<LlamaToken> {
+    if temperature > 0.0 && temperature != 1.0 {
+        // Convert to probabilities using softmax
+        let max_logit = logits.iter()
+            .map(|(_, logit)| logit)
+            .fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+        
+        let exp_sum: f32 = logits.iter()
+            .map(|(_, logit)| (logit - max_logit).exp())
+            .sum();
+        
+        // Sample from distribution
+        let mut rng = rand::thread_rng();
+        let random_val: f32 = rng.gen();
+        let mut cumulative = 0.0;
+        
+        let mut selected_id = logits.first()?.0;
+        for (id, logit) in logits {
+            cumulative += (logit - max_logit).exp() / exp_sum;
+            if random_val <= cumulative {
+                selected_id = *id;
+                break;
+            }
         }
+        Some(selected_id)
+    } else {
+        // No temperature, just pick highest probability
+        logits.iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(id, _)| *id)
     }
-    
-    info!("✅ Decoded: {} characters from {} tokens ({} decode errors)", response.len(), total_tokens, decode_errors);
-    
-    // Clean up response (remove special tokens if any)
-    let cleaned_response = response
+}
+
+/// Strip chat-template special tokens that leaked into the generated text
+pub(crate) fn clean_response(response: &str) -> String {
+    response
         .replace("<|im_start|>", "")
         .replace("<|im_end|>", "")
         .replace("<|endoftext|>", "")
@@ -476,35 +935,59 @@ pub async fn chat_with_gguf_model(
         .replace("<|user|>", "")
         .replace("<|assistant|>", "")
         .trim()
-        .to_string();
-    
-    info!("📤 Final response length: {} characters", cleaned_response.len());
-    if cleaned_response.len() > 0 {
-        let preview_len = cleaned_response.len().min(200);
-        info!("📤 Response preview: {}", &cleaned_response[..preview_len]);
+        .to_string()
+}
+
+/// Render chat messages with the model's embedded chat template.
+/// Falls back to ChatML when the GGUF has no usable template.
+pub fn format_chat_prompt(model: &LlamaModel, messages: &[crate::commands::ChatMessage]) -> String {
+    use llama_cpp_2::model::LlamaChatMessage;
+
+    let rendered = model.chat_template(None).ok().and_then(|template| {
+        let chat: Vec<LlamaChatMessage> = messages.iter()
+            .filter_map(|m| LlamaChatMessage::new(m.role.clone(), m.content.clone()).ok())
+            .collect();
+        model.apply_chat_template(&template, &chat, true).ok()
+    });
+
+    match rendered {
+        Some(prompt) => prompt,
+        None => {
+            warn!("⚠️ Chat template bulunamadı, ChatML formatı kullanılıyor");
+            chatml_prompt(messages)
+        }
+    }
+}
+
+fn chatml_prompt(messages: &[crate::commands::ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for m in messages {
+        prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", m.role, m.content));
     }
+    prompt.push_str("<|im_start|>assistant\n");
+    prompt
+}
 
-    Ok(cleaned_response)
+/// Decode a single token without touching the caller's UTF-8 decoder state
+fn token_piece_lossy(model: &LlamaModel, token: llama_cpp_2::token::LlamaToken) -> String {
+    let mut decoder = encoding_rs::UTF_8.new_decoder();
+    model.token_to_piece(token, &mut decoder, false, None).unwrap_or_default()
 }
 
 #[tauri::command]
 pub async fn unload_gguf_model(
     state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: Option<String>, // 🆕 Verilirse sadece bu model kaldırılır
 ) -> Result<String, String> {
+    let engine = InferenceEngine::llama_cpp(state.inner().clone());
+    if let Some(path) = model_path {
+        engine.unload(Some(&path))?;
+        info!("✅ Model havuzdan kaldırıldı: {} (kalan: {})", path, engine.loaded_models().len());
+        return Ok(format!("✅ Model kaldırıldı: {}", path));
+    }
+
     info!("🔵 Unloading GGUF model - Starting cleanup...");
-    
-    let mut state_guard = state.lock().unwrap();
-    
-    state_guard.models.clear();
-    state_guard.backend = None;
-    state_guard.backend_initialized = false;
-    
-    // Clear state
-    // Clear state done above
-    
-    // Force garbage collection hint (Rust will handle it)
-    drop(state_guard);
-    
+    engine.unload(None)?;
     info!("✅ GGUF model fully unloaded - GPU memory should be freed");
     Ok("✅ Model unloaded - GPU memory freed".to_string())
 }
@@ -515,13 +998,119 @@ pub async fn get_gguf_model_status(
 ) -> Result<serde_json::Value, String> {
     let state_guard = state.lock().unwrap();
     let loaded_models: Vec<String> = state_guard.models.keys().cloned().collect();
+    let footprints: serde_json::Map<String, serde_json::Value> = state_guard.models.iter()
+        .map(|(k, m)| (k.clone(), json!({
+            "n_ctx": m.n_ctx,
+            "n_gpu_layers": m.n_gpu_layers,
+            "footprint": m.footprint,
+            // try_lock: vision çıkarımı sürerken status çağrısı beklemesin
+            "vision_projector": m.vision.try_lock().ok()
+                .and_then(|v| v.as_ref().map(|p| p.mmproj_path.clone())),
+            "lora_adapters": m.loras.try_lock().ok()
+                .map(|l| l.iter().map(|a| a.info.clone()).collect::<Vec<_>>()),
+        })))
+        .collect();
+    let (used_ram, used_vram) = state_guard.used_bytes();
     
     Ok(json!({
         "loaded": !loaded_models.is_empty(),
-        "loaded_models": loaded_models
+        "loaded_models": loaded_models,
+        "models": footprints,
+        "budget": state_guard.budget,
+        "speculative": state_guard.speculative,
+        "used_ram_bytes": used_ram,
+        "used_vram_bytes": used_vram
     }))
 }
 
+/// 🆕 Havuz bellek bütçesini ayarla (GB cinsinden)
+#[tauri::command]
+pub async fn set_model_pool_budget(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    max_ram_gb: Option<f64>,
+    max_vram_gb: Option<f64>,
+    policy: Option<PoolPolicy>,
+) -> Result<PoolBudget, String> {
+    let mut guard = state.lock().unwrap();
+    if let Some(gb) = max_ram_gb {
+        guard.budget.max_ram_bytes = (gb * 1_073_741_824.0) as u64;
+    }
+    if let Some(gb) = max_vram_gb {
+        guard.budget.max_vram_bytes = (gb * 1_073_741_824.0) as u64;
+    }
+    if let Some(policy) = policy {
+        guard.budget.policy = policy;
+    }
+    info!("🧮 Model pool budget: {:?}", guard.budget);
+    Ok(guard.budget.clone())
+}
+
+/// 🆕 Bu donanım için n_gpu_layers ve n_ctx öner (model yüklemeden)
+#[tauri::command]
+pub async fn plan_model_load(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    desired_ctx: Option<u32>,
+    kv_cache_type: Option<KvCacheType>,
+) -> Result<LoadPlan, String> {
+    let model_path = resolve_split_gguf_path(&model_path);
+    if !Path::new(&model_path).exists() {
+        return Err(format!("Model dosyası bulunamadı: {}", model_path));
+    }
+    let arch = model_arch_for(&model_path);
+
+    // Havuzdaki diğer modellerin kullandığı bellek düşülür
+    let (budget, used_ram, used_vram) = {
+        let guard = state.lock().unwrap();
+        let (ram, vram) = guard.used_bytes();
+        (guard.budget.clone(), ram, vram)
+    };
+    use sysinfo::System;
+    let mut sys = System::new();
+    sys.refresh_memory();
+    let hw = HardwareBudget {
+        available_ram_bytes: budget.max_ram_bytes.saturating_sub(used_ram).min(sys.available_memory()),
+        available_vram_bytes: budget.max_vram_bytes.saturating_sub(used_vram),
+        gpu_backend: cfg!(feature = "cuda") || cfg!(feature = "vulkan"),
+    };
+
+    let plan = gguf_pool::plan_load(&arch, &hw, desired_ctx, kv_cache_type.unwrap_or_default());
+    info!("🧭 Load plan for {}: n_gpu_layers={}, n_ctx={}, fits={}", model_path, plan.n_gpu_layers, plan.n_ctx, plan.fits);
+    Ok(plan)
+}
+
+/// Metadata'dan mimari bilgisi; okunamazsa dosya boyutuyla varsayılan değerler
+fn model_arch_for(model_path: &str) -> ModelArch {
+    let options = GgufReadOptions { max_array_len: Some(0), read_tensors: true };
+    let first = gguf_reader::read_gguf_file(model_path, &options);
+    let mut meta = match &first {
+        Ok(file) => gguf_file_to_map(file),
+        Err(e) => {
+            warn!("⚠️ GGUF metadata okunamadı, varsayılan mimari kullanılıyor: {}", e);
+            serde_json::Map::new()
+        }
+    };
+
+    // Split modellerde ağırlıklar tüm parçalara dağılmıştır; tensor tablosu yoksa dosya boyutu
+    let weights: u64 = split_part_paths(model_path).iter()
+        .map(|p| match gguf_reader::read_gguf_file(p, &options) {
+            Ok(f) => f.summary().tensor_bytes,
+            Err(_) => std::fs::metadata(p).map(|m| m.len()).unwrap_or(0),
+        })
+        .sum();
+    if weights > 0 {
+        meta.insert("file_size_bytes".to_string(), weights.into());
+    }
+
+    let mut arch = ModelArch::from_metadata(&meta);
+    if let Ok(file) = &first {
+        if let Some(n_vocab) = file.array_len("tokenizer.ggml.tokens") {
+            arch.n_vocab = n_vocab;
+        }
+    }
+    arch
+}
+
 // 🆕 GPU Memory bilgisi al
 #[tauri::command]
 pub async fn get_gpu_memory_info(
@@ -542,12 +1131,6 @@ pub async fn get_gpu_memory_info(
         }));
     }
     
-    // Use the first model for estimation or sum them up
-    // For now, let's just use representative values
-    let representative_model = state_guard.models.values().next().unwrap();
-    let n_ctx = representative_model.n_ctx;
-    let n_gpu_layers = representative_model.n_gpu_layers;
-    
     // 🔥 Dinamik sistem VRAM bilgisi al
     // Eğer GPU yüklü değilse sistem RAM'ı kullan
     use sysinfo::System;
@@ -577,21 +1160,14 @@ pub async fn get_gpu_memory_info(
         available_memory_gb * 0.7
     };
     
-    // 🔥 Düzeltilmiş hesaplamalar
-    // Model size: Q4 quantization için ~0.5-0.6 GB per billion parameters
-    // 7B model Q4 = ~4.2 GB
-    let estimated_model_size = 4.2; // GB
-    
-    // KV Cache hesaplama (daha doğru):
-    // KV cache = 2 (K + V) * n_layers * n_ctx * hidden_size * bytes_per_element / 1e9
-    // Qwen 7B: 28 layers, 4096 hidden_size, fp16 = 2 bytes
-    let n_layers = n_gpu_layers.min(28) as f64; // Maksimum 28 layer
-    let hidden_size = 4096.0;
-    let bytes_per_element = 2.0; // fp16
+    // 🆕 Havuzdaki modellerin GGUF metadata'sından hesaplanan tahminleri topla
+    let gb = |bytes: u64| bytes as f64 / 1_073_741_824.0;
+    let gpu_mode = cfg!(feature = "cuda") || cfg!(feature = "vulkan");
+    let estimated_model_size: f64 = state_guard.models.values().map(|m| gb(m.footprint.weights_bytes)).sum();
+    let kv_cache_gb: f64 = state_guard.models.values().map(|m| gb(m.footprint.kv_cache_bytes)).sum();
+    let (used_ram, used_vram_bytes) = state_guard.used_bytes();
     
-    let kv_cache_gb = (2.0 * n_layers * n_ctx as f64 * hidden_size * bytes_per_element) / 1_000_000_000.0;
-    
-    let used_vram = estimated_model_size + kv_cache_gb;
+    let used_vram = if gpu_mode { gb(used_vram_bytes) } else { gb(used_ram) };
     let safe_used_vram = used_vram.min(total_vram_gb);
     let safe_free_vram = (total_vram_gb - safe_used_vram).max(0.0); // Negatif olmasın
     let usage_percent = ((safe_used_vram / total_vram_gb) * 100.0).min(100.0); // Max %100
@@ -611,7 +1187,7 @@ pub async fn get_gpu_memory_info(
 }
 
 /// GPU VRAM bilgisini algıla (platform-specific) (FIX-27)
-fn detect_gpu_vram() -> f64 {
+pub(crate) fn detect_gpu_vram() -> f64 {
     // 🎮 NVIDIA GPU - nvidia-smi ile kontrol et
     if let Ok(output) = std::process::Command::new("nvidia-smi")
         .args(&["--query-gpu=memory.total", "--format=csv,noheader,nounits"])
@@ -628,6 +1204,13 @@ fn detect_gpu_vram() -> f64 {
         }
     }
 
+    // 🔴 sysfs/DRM (amdgpu gibi sürücüler VRAM'i doğrudan raporlar, araç gerekmez)
+    if let Some(vram_bytes) = hardware::largest_vram_bytes(&hardware::probe_gpus()) {
+        let vram_gb = vram_bytes as f64 / 1_073_741_824.0;
+        info!("🎮 sysfs GPU VRAM algılandı: {:.1} GB", vram_gb);
+        return vram_gb;
+    }
+
     // 🍎 Apple Silicon - Mac'lerde genelde unified memory var
     #[cfg(target_os = "macos")]
     {
@@ -652,125 +1235,55 @@ fn detect_gpu_vram() -> f64 {
 // 🆕 GGUF Metadata Okuyucu
 // 🆕 GGUF Metadata Okuyucu - Gerçek Binary Okuma Entegrasyonu
 #[tauri::command]
-pub async fn read_gguf_metadata(path: String) -> Result<serde_json::Value, String> {
-    use std::io::Read;
-    use std::fs::File;
-
+pub async fn read_gguf_metadata(
+    path: String,
+    max_array_len: Option<usize>, // 🆕 Büyük vocab dizileri için kırpma (varsayılan 64)
+    include_tensors: Option<bool>,
+) -> Result<serde_json::Value, String> {
     info!("📖 Gerçek GGUF metadata okuma başlatıldı: {}", path);
 
-    let mut file = File::open(&path).map_err(|e| format!("Dosya açılamadı: {}", e))?;
-
-    // GGUF magic: 0x47 0x47 0x55 0x46 ("GGUF")
-    let mut magic = [0u8; 4];
-    file.read_exact(&mut magic).map_err(|e| format!("Magic bytes okunamadı: {}", e))?;
-
-    if &magic != b"GGUF" {
-        return Err("Geçersiz GGUF dosyası (magic bytes yanlış)".to_string());
-    }
-
-    // Version (uint32 LE)
-    let mut version_bytes = [0u8; 4];
-    file.read_exact(&mut version_bytes).map_err(|e| e.to_string())?;
-    let version = u32::from_le_bytes(version_bytes);
-
-    // tensor_count (uint64 LE)
-    let mut tc_bytes = [0u8; 8];
-    file.read_exact(&mut tc_bytes).map_err(|e| e.to_string())?;
-    let tensor_count = u64::from_le_bytes(tc_bytes);
-
-    // metadata_kv_count (uint64 LE)
-    let mut kvc_bytes = [0u8; 8];
-    file.read_exact(&mut kvc_bytes).map_err(|e| e.to_string())?;
-    let kv_count = u64::from_le_bytes(kvc_bytes);
-
-    let mut metadata = serde_json::Map::new();
-    metadata.insert("gguf_version".to_string(), version.into());
-    metadata.insert("tensor_count".to_string(), tensor_count.into());
-    metadata.insert("kv_count".to_string(), kv_count.into());
-
-    // KV çiftlerini oku (ilk 200 tanesi - güvenlik için)
-    for _ in 0..kv_count.min(200) {
-        let key = match read_gguf_string(&mut file) {
-            Ok(k) => k,
-            Err(_) => break,
-        };
-
-        let mut vtype_bytes = [0u8; 4];
-        if file.read_exact(&mut vtype_bytes).is_err() { break; }
-        let vtype = u32::from_le_bytes(vtype_bytes);
+    let include_tensors = include_tensors.unwrap_or(false);
+    let options = GgufReadOptions {
+        max_array_len: Some(max_array_len.unwrap_or(64)),
+        read_tensors: true,
+    };
+    let file = gguf_reader::read_gguf_file(&path, &options)?;
+    let summary = file.summary();
 
-        let value = match read_gguf_value(&mut file, vtype) {
-            Ok(v) => v,
-            Err(_) => serde_json::Value::Null,
-        };
-        
-        metadata.insert(key, value);
+    let mut metadata = gguf_file_to_map(&file);
+    metadata.insert("summary".to_string(), serde_json::to_value(&summary).map_err(|e| e.to_string())?);
+    metadata.insert("truncated_arrays".to_string(), serde_json::to_value(&file.truncated_arrays).map_err(|e| e.to_string())?);
+    if include_tensors {
+        metadata.insert("tensors".to_string(), serde_json::to_value(&file.tensors).map_err(|e| e.to_string())?);
     }
 
-    // Dosya boyutunu ekle
-    let file_size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
-    metadata.insert("file_size_gb".to_string(), (file_size as f64 / 1_073_741_824.0).into());
-
-    info!("✅ GGUF metadata başarıyla okundu");
+    info!("✅ GGUF metadata başarıyla okundu: {} key, {} tensor, {} parametre ({})",
+        file.kv_count, file.tensors.len(), summary.parameter_count,
+        summary.dominant_type.as_deref().unwrap_or("?"));
     Ok(serde_json::Value::Object(metadata))
 }
 
-fn read_gguf_string(file: &mut std::fs::File) -> Result<String, String> {
-    use std::io::Read;
-    let mut len_bytes = [0u8; 8];
-    file.read_exact(&mut len_bytes).map_err(|e| e.to_string())?;
-    let len = u64::from_le_bytes(len_bytes) as usize;
-    if len > 4096 { return Err("String çok uzun".to_string()); }
-    let mut buf = vec![0u8; len];
-    file.read_exact(&mut buf).map_err(|e| e.to_string())?;
-    String::from_utf8(buf).map_err(|e| e.to_string())
+/// GGUF header + KV çiftlerini oku (komut dışı kullanım için senkron, diziler kırpılır)
+pub fn read_gguf_metadata_map(path: &str) -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let options = GgufReadOptions { max_array_len: Some(0), read_tensors: false };
+    let file = gguf_reader::read_gguf_file(path, &options)?;
+    Ok(gguf_file_to_map(&file))
 }
 
-fn read_gguf_value(file: &mut std::fs::File, vtype: u32) -> Result<serde_json::Value, String> {
-    use std::io::Read;
-    match vtype {
-        0 => { // UINT8
-            let mut b = [0u8; 1]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok(b[0].into())
-        }
-        4 => { // UINT32
-            let mut b = [0u8; 4]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok(u32::from_le_bytes(b).into())
-        }
-        5 => { // INT32
-            let mut b = [0u8; 4]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok(i32::from_le_bytes(b).into())
-        }
-        6 => { // FLOAT32
-            let mut b = [0u8; 4]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok(f32::from_le_bytes(b).into())
-        }
-        7 => { // BOOL
-            let mut b = [0u8; 1]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok((b[0] != 0).into())
-        }
-        8 => { // STRING
-            Ok(read_gguf_string(file)?.into())
-        }
-        10 => { // UINT64
-            let mut b = [0u8; 8]; file.read_exact(&mut b).map_err(|e| e.to_string())?;
-            Ok(u64::from_le_bytes(b).into())
-        }
-        9 => { // ARRAY
-            let mut type_bytes = [0u8; 4]; 
-            file.read_exact(&mut type_bytes).map_err(|e| e.to_string())?;
-            let _subtype = u32::from_le_bytes(type_bytes);
-            let mut len_bytes = [0u8; 8];
-            file.read_exact(&mut len_bytes).map_err(|e| e.to_string())?;
-            let _len = u64::from_le_bytes(len_bytes);
-            // Dizileri şimdilik pas geçiyoruz (header okumak yetiyor)
-            Ok(serde_json::Value::String("[Array]".to_string()))
-        }
-        _ => Ok(serde_json::Value::Null),
+/// Eski düz key/value formatı (frontend bu anahtarları bekliyor)
+fn gguf_file_to_map(file: &GgufFile) -> serde_json::Map<String, serde_json::Value> {
+    let mut metadata = serde_json::Map::new();
+    metadata.insert("gguf_version".to_string(), file.version.into());
+    metadata.insert("tensor_count".to_string(), file.tensor_count.into());
+    metadata.insert("kv_count".to_string(), file.kv_count.into());
+    for (k, v) in &file.metadata {
+        metadata.insert(k.clone(), v.clone());
     }
+    metadata.insert("file_size_gb".to_string(), (file.file_size as f64 / 1_073_741_824.0).into());
+    metadata.insert("file_size_bytes".to_string(), file.file_size.into());
+    metadata
 }
 
-
 // 🆕 Vision AI Support - Chat with images
 #[tauri::command]
 pub async fn chat_with_gguf_vision(
@@ -780,68 +1293,49 @@ pub async fn chat_with_gguf_vision(
     images: Vec<String>, // Base64 encoded images
     max_tokens: u32,
     temperature: f32,
+    mmproj_path: Option<String>, // 🆕 None = model klasöründe *mmproj*.gguf ara
 ) -> Result<String, String> {
-    info!("📷 Starting vision inference...");
+    info!("📷 Starting vision inference with {} image(s)...", images.len());
     
     // 🆕 Get model and backend from pool with minimum lock time
-    let (_loaded_model, _backend) = {
-        let guard = match state.lock() {
+    let (loaded_model, backend) = {
+        let mut guard = match state.lock() {
             Ok(g) => g,
             Err(poisoned) => poisoned.into_inner(),
         };
-        
-        let model = guard.models.get(&model_path)
-            .cloned()
-            .ok_or_else(|| format!("Vision model pool'da bulunamadı: {}", model_path))?;
-            
-        let backend = guard.backend.as_ref()
-            .cloned()
-            .ok_or_else(|| "Backend not initialized".to_string())?;
-            
-        (model, backend)
+        guard.checkout(&model_path)
+            .map_err(|_| format!("Vision model pool'da bulunamadı: {}", model_path))?
     };
     
-    // Decode base64 images (validation only for now)
+    // Decode base64 images
     let mut decoded_images = Vec::new();
     for (idx, img_data) in images.iter().enumerate() {
-        // Remove data:image/...;base64, prefix if present
-        let base64_data = if img_data.contains("base64,") {
-            img_data.split("base64,").nth(1).unwrap_or(img_data)
-        } else {
-            img_data
-        };
-        
-        match general_purpose::STANDARD.decode(base64_data) {
-            Ok(bytes) => {
-                decoded_images.push(bytes);
-            }
-            Err(e) => {
-                error!("❌ Failed to decode image {}: {:?}", idx, e);
-                return Err(format!("Failed to decode image {}: {:?}", idx, e));
-            }
-        }
+        let bytes = gguf_vision::decode_image_payload(img_data).map_err(|e| {
+            error!("❌ Failed to decode image {}: {}", idx, e);
+            format!("Failed to decode image {}: {}", idx, e)
+        })?;
+        decoded_images.push(bytes);
     }
     
     info!("✅ All images decoded successfully");
-    
-    // TODO: Full vision implementation
-    // 1. Load mmproj file (vision encoder)
-    // 2. Convert images to embeddings using mmproj
-    // 3. Combine text prompt + image embeddings
-    // 4. Pass to model for inference
-    
-    // For now, fall back to text-only chat with a note about images
-    info!("⚠️ Vision support not fully implemented yet");
-    info!("📝 Falling back to text-only mode with image count note");
-    
-    let vision_prompt = format!(
-        "[System: User sent {} image(s) but vision processing is not yet implemented. Please acknowledge the images and respond based on the text prompt.]\n\n{}",
-        images.len(),
-        prompt
-    );
-    
-    // Use the existing text chat function
-    chat_with_gguf_model(state, model_path, vision_prompt, max_tokens, temperature).await
+
+    if decoded_images.is_empty() {
+        return chat_with_gguf_model(state, model_path, prompt, max_tokens, temperature, None).await;
+    }
+
+    crate::usage::check_budget(Some("gguf"), "vision", None)?;
+    let completion = gguf_vision::run_vision_completion(
+        &loaded_model,
+        &backend,
+        &prompt,
+        &decoded_images,
+        mmproj_path.as_deref(),
+        max_tokens,
+        temperature,
+        &CompletionOptions::default(),
+    )?;
+    crate::usage::record(UsageEvent::local("vision", &model_path, completion.prompt_tokens, completion.completion_tokens));
+    Ok(completion.text)
 }
 
 // Check if CUDA is available
@@ -868,6 +1362,7 @@ pub fn check_cuda_support() -> Result<serde_json::Value, String> {
         "cuda_available": cuda_available,
         "vulkan_available": vulkan_available,
         "recommended_gpu_layers": if cuda_available || vulkan_available { 28 } else { 0 },
+        "hardware": hardware::probe(),
         "cuda_download_url": "https://developer.nvidia.com/cuda-downloads",
         "message": match backend {
             "CUDA" => "CUDA enabled. Maximum performance on NVIDIA GPUs.",
@@ -877,10 +1372,24 @@ pub fn check_cuda_support() -> Result<serde_json::Value, String> {
     }))
 }
 
+/// Bir split GGUF'un tüm parça yolları (tek parça dosyada sadece kendisi)
+pub(crate) fn split_part_paths(path: &str) -> Vec<String> {
+    if let Ok(re) = regex::Regex::new(r"(-\d{5})-of-(\d{5})\.gguf$") {
+        if let Some(caps) = re.captures(path) {
+            let total = caps[2].to_string();
+            let total_num: u32 = total.parse().unwrap_or(1);
+            return (1..=total_num)
+                .map(|i| re.replace(path, format!("-{:05}-of-{}.gguf", i, total).as_str()).to_string())
+                .collect();
+        }
+    }
+    vec![path.to_string()]
+}
+
 /// Split GGUF dosyalarini tespit edip ilk parcaya yonlendirir.
 /// Ornek: "model-00003-of-00004.gguf" -> "model-00001-of-00004.gguf"
 /// Tek parca dosyalarda ayni yolu dondurur.
-fn resolve_split_gguf_path(path: &str) -> String {
+pub(crate) fn resolve_split_gguf_path(path: &str) -> String {
     let re = regex::Regex::new(r"(-\d{5})-of-(\d{5})\.gguf$").ok();
     if let Some(re) = re {
         if let Some(caps) = re.captures(path) {
@@ -910,6 +1419,75 @@ fn resolve_split_gguf_path(path: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_chatml_prompt() {
+        let messages = vec![
+            crate::commands::ChatMessage { role: "system".into(), content: "Be brief".into() },
+            crate::commands::ChatMessage { role: "user".into(), content: "Hi".into() },
+        ];
+        assert_eq!(
+            chatml_prompt(&messages),
+            "<|im_start|>system\nBe brief<|im_end|>\n<|im_start|>user\nHi<|im_end|>\n<|im_start|>assistant\n"
+        );
+    }
+
+    #[test]
+    fn test_admission_counts_in_flight_loads() {
+        let mut state = GgufState::default();
+        state.budget = PoolBudget { max_ram_bytes: 10 << 30, max_vram_bytes: 0, policy: PoolPolicy::Refuse };
+        let six = ModelFootprint { ram_bytes: 6 << 30, ..Default::default() };
+
+        state.admit("a.gguf", &six).unwrap();
+        assert!(state.admit("b.gguf", &six).is_err());
+        // A reload of the same path doesn't compete with its own reservation
+        assert!(state.plan_admission("a.gguf", &six).is_ok());
+
+        state.reservations.remove("a.gguf");
+        assert!(state.admit("b.gguf", &six).is_ok());
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "cuda", feature = "vulkan")))]
+    fn test_cpu_build_books_no_vram() {
+        // The frontend recommends 28 layers even when the build can't offload any
+        assert_eq!(offload_layers(28), 0);
+        let arch = ModelArch {
+            architecture: "llama".into(),
+            n_layers: 32,
+            n_embd: 4096,
+            n_head: 32,
+            n_head_kv: 8,
+            key_length: 128,
+            value_length: 128,
+            n_vocab: 32_000,
+            context_length: 32_768,
+            weights_bytes: 4 << 30,
+        };
+        let footprint = gguf_pool::estimate_footprint(&arch, 4096, offload_layers(28), KvCacheType::default());
+        assert_eq!(footprint.vram_bytes, 0);
+        assert!(footprint.ram_bytes > arch.weights_bytes);
+    }
+
+    #[test]
+    fn test_logprobs_follow_sampling_logits() {
+        let candidates: Vec<LlamaTokenData> = [(1, 3.0), (2, 2.0), (3, 0.0)].iter()
+            .map(|&(id, logit)| LlamaTokenData::new(LlamaToken(id), logit, 0.0))
+            .collect();
+        let as_ids = |l: Vec<(LlamaToken, f32)>| l.into_iter().map(|(id, l)| (id.0, l)).collect::<Vec<_>>();
+
+        // Token 1 was just generated: the penalty lowers its logprob below the raw value
+        let raw = as_ids(sampling_logits(&candidates, &[], 0.0));
+        let penalised = as_ids(sampling_logits(&candidates, &[LlamaToken(1)], 0.0));
+        assert!(compute_logprobs(&penalised, 1, 0).0 < compute_logprobs(&raw, 1, 0).0);
+
+        // A higher temperature flattens the distribution the token is drawn from
+        let hot = as_ids(sampling_logits(&candidates, &[], 2.0));
+        assert_eq!(hot[0], (1, 1.5));
+        assert!(compute_logprobs(&hot, 1, 0).0 < compute_logprobs(&raw, 1, 0).0);
+
+        assert_eq!(sample_next_token(&candidates, &[], 0.0), LlamaToken(1));
+    }
+
     #[test]
     fn test_resolve_split_gguf_path() {
         assert_eq!(resolve_split_gguf_path("test.gguf"), "test.gguf");
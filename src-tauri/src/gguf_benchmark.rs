@@ -0,0 +1,537 @@
+// src-tauri/src/gguf_benchmark.rs
+// Benchmark harness for local GGUF models: load time, prompt processing and
+// generation throughput, peak RSS. Runs are kept as JSON history per model file
+// and hardware fingerprint so regressions after an upgrade show up in a diff.
+
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::AddBos;
+use llama_cpp_2::token::LlamaToken;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::gguf::{self, GgufState, LoadedModel};
+use crate::gguf_reader::{self, GgufReadOptions};
+use crate::hardware;
+
+/// Relative change treated as a regression in diffs (5%)
+const REGRESSION_THRESHOLD: f64 = 0.05;
+/// Runs kept per history file
+const MAX_HISTORY: usize = 100;
+
+const FILLER_TEXT: &str = "The quick brown fox jumps over the lazy dog while the compiler checks \
+    every borrow, and the benchmark keeps feeding tokens into the model. ";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkConfig {
+    #[serde(default = "default_n_ctx")]
+    pub n_ctx: u32,
+    #[serde(default)]
+    pub n_gpu_layers: u32,
+    /// Prompt lengths (tokens) for the prompt-processing test
+    #[serde(default = "default_prompt_sizes")]
+    pub prompt_sizes: Vec<usize>,
+    /// Tokens generated after each prompt for the generation test
+    #[serde(default = "default_gen_tokens")]
+    pub gen_tokens: usize,
+    /// Runs per prompt size; the reported figure is the mean
+    #[serde(default = "default_repetitions")]
+    pub repetitions: usize,
+}
+
+fn default_n_ctx() -> u32 { 4096 }
+fn default_prompt_sizes() -> Vec<usize> { vec![128, 512, 2048] }
+fn default_gen_tokens() -> usize { 128 }
+fn default_repetitions() -> usize { 2 }
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        Self {
+            n_ctx: default_n_ctx(),
+            n_gpu_layers: 0,
+            prompt_sizes: default_prompt_sizes(),
+            gen_tokens: default_gen_tokens(),
+            repetitions: default_repetitions(),
+        }
+    }
+}
+
+/// What the numbers depend on besides the model file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HardwareFingerprint {
+    pub cpu_brand: String,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+    pub total_ram_gb: u64,
+    pub gpu_backend: String,
+    pub os: String,
+}
+
+impl HardwareFingerprint {
+    pub fn detect() -> Self {
+        let cpu = hardware::probe_cpu();
+        Self {
+            cpu_brand: cpu.model,
+            physical_cores: cpu.physical_cores,
+            logical_cores: cpu.logical_cores,
+            total_ram_gb: (hardware::probe_memory().total_bytes as f64 / 1_073_741_824.0).round() as u64,
+            gpu_backend: hardware::BuildInfo::current().backend,
+            os: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+        }
+    }
+
+    /// Short stable id used in history file names
+    pub fn id(&self) -> String {
+        let canonical = format!(
+            "{}|{}|{}|{}|{}|{}",
+            self.cpu_brand, self.physical_cores, self.logical_cores, self.total_ram_gb, self.gpu_backend, self.os
+        );
+        let digest = Sha256::digest(canonical.as_bytes());
+        digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptSizeResult {
+    pub prompt_tokens: usize,
+    pub pp_ms: f64,
+    pub pp_tokens_per_sec: f64,
+    pub gen_tokens: usize,
+    pub tg_ms: f64,
+    pub tg_tokens_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkRun {
+    pub id: String,
+    /// Unix seconds
+    pub timestamp: u64,
+    pub model_path: String,
+    pub model_size_bytes: u64,
+    pub quantization: Option<String>,
+    pub config: BenchmarkConfig,
+    pub hardware: HardwareFingerprint,
+    pub app_version: String,
+    pub load_ms: f64,
+    pub peak_rss_bytes: u64,
+    pub results: Vec<PromptSizeResult>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricDelta {
+    pub metric: String,
+    pub baseline: f64,
+    pub current: f64,
+    /// (current - baseline) / baseline, in percent
+    pub change_pct: f64,
+    pub higher_is_better: bool,
+    pub regression: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkDiff {
+    pub baseline_id: String,
+    pub current_id: String,
+    pub baseline_app_version: String,
+    pub current_app_version: String,
+    pub metrics: Vec<MetricDelta>,
+    pub regressions: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    pub run: BenchmarkRun,
+    /// Compared with the previous run on the same hardware, if any
+    pub diff_vs_previous: Option<BenchmarkDiff>,
+}
+
+fn delta(metric: String, baseline: f64, current: f64, higher_is_better: bool) -> MetricDelta {
+    let change = if baseline != 0.0 { (current - baseline) / baseline } else { 0.0 };
+    let regression = if higher_is_better { change < -REGRESSION_THRESHOLD } else { change > REGRESSION_THRESHOLD };
+    MetricDelta { metric, baseline, current, change_pct: change * 100.0, higher_is_better, regression }
+}
+
+/// Compare two runs metric by metric; prompt sizes are matched by token count
+pub fn diff_runs(baseline: &BenchmarkRun, current: &BenchmarkRun) -> BenchmarkDiff {
+    let mut metrics = vec![
+        delta("load_ms".to_string(), baseline.load_ms, current.load_ms, false),
+        delta("peak_rss_mb".to_string(), baseline.peak_rss_bytes as f64 / 1_048_576.0, current.peak_rss_bytes as f64 / 1_048_576.0, false),
+    ];
+    for cur in &current.results {
+        if let Some(base) = baseline.results.iter().find(|b| b.prompt_tokens == cur.prompt_tokens) {
+            metrics.push(delta(format!("pp{}_tokens_per_sec", cur.prompt_tokens), base.pp_tokens_per_sec, cur.pp_tokens_per_sec, true));
+            metrics.push(delta(format!("tg{}_tokens_per_sec", cur.prompt_tokens), base.tg_tokens_per_sec, cur.tg_tokens_per_sec, true));
+        }
+    }
+    let regressions = metrics.iter().filter(|m| m.regression).count();
+    BenchmarkDiff {
+        baseline_id: baseline.id.clone(),
+        current_id: current.id.clone(),
+        baseline_app_version: baseline.app_version.clone(),
+        current_app_version: current.app_version.clone(),
+        metrics,
+        regressions,
+    }
+}
+
+/// `<model file name>__<hardware id>.json`, filesystem-safe
+pub fn history_file_name(model_path: &str, hardware_id: &str) -> String {
+    let name = Path::new(model_path)
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| model_path.to_string());
+    let safe: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    format!("{}__{}.json", safe, hardware_id)
+}
+
+fn history_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("benchmarks");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Benchmark klasörü oluşturulamadı: {}", e))?;
+    Ok(dir)
+}
+
+pub fn load_history(path: &Path) -> Vec<BenchmarkRun> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+fn save_history(path: &Path, runs: &[BenchmarkRun]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(runs).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| format!("Benchmark geçmişi yazılamadı: {}", e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Benchmark geçmişi yazılamadı: {}", e))
+}
+
+/// Samples this process' RSS in the background and keeps the maximum
+struct RssSampler {
+    peak: Arc<AtomicU64>,
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl RssSampler {
+    fn start() -> Self {
+        let peak = Arc::new(AtomicU64::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let (peak_t, stop_t) = (peak.clone(), stop.clone());
+        let handle = std::thread::spawn(move || {
+            let pid = match sysinfo::get_current_pid() {
+                Ok(pid) => pid,
+                Err(_) => return,
+            };
+            let mut sys = sysinfo::System::new();
+            while !stop_t.load(Ordering::Relaxed) {
+                sys.refresh_process(pid);
+                if let Some(process) = sys.process(pid) {
+                    peak_t.fetch_max(process.memory(), Ordering::Relaxed);
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        });
+        Self { peak, stop, handle: Some(handle) }
+    }
+
+    fn finish(mut self) -> u64 {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.peak.load(Ordering::Relaxed)
+    }
+}
+
+/// Prompt of exactly `n` tokens built from filler text
+fn synthetic_prompt(loaded_model: &LoadedModel, n: usize) -> Result<Vec<LlamaToken>, String> {
+    let filler = loaded_model.model.str_to_token(FILLER_TEXT, AddBos::Never)
+        .map_err(|e| format!("Tokenization failed: {:?}", e))?;
+    if filler.is_empty() {
+        return Err("Filler metni tokenize edilemedi".to_string());
+    }
+    let mut tokens = loaded_model.model.str_to_token("", AddBos::Always).unwrap_or_default();
+    while tokens.len() < n {
+        tokens.extend_from_slice(&filler);
+    }
+    tokens.truncate(n);
+    Ok(tokens)
+}
+
+/// One prompt-processing + generation measurement
+fn measure(loaded_model: &LoadedModel, backend: &llama_cpp_2::llama_backend::LlamaBackend, prompt: &[LlamaToken], gen_tokens: usize) -> Result<(f64, f64, usize), String> {
+    let mut context = gguf::new_inference_context(loaded_model, backend)?;
+
+    let mut batch = LlamaBatch::new(prompt.len(), 1);
+    batch.add_sequence(prompt, 0, false).map_err(|e| format!("Batch add failed: {:?}", e))?;
+    let pp_start = Instant::now();
+    context.decode(&mut batch).map_err(|e| format!("Decode failed: {:?}", e))?;
+    let pp_ms = pp_start.elapsed().as_secs_f64() * 1000.0;
+
+    // Greedy generation; EOG is ignored so every run produces the same token count
+    let mut n_cur = prompt.len() as i32;
+    let tg_start = Instant::now();
+    for _ in 0..gen_tokens {
+        let token = context.candidates()
+            .max_by(|a, b| a.logit().partial_cmp(&b.logit()).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|c| c.id())
+            .ok_or("Aday token yok")?;
+        batch.clear();
+        batch.add(token, n_cur, &[0], true).map_err(|e| format!("Batch add failed: {:?}", e))?;
+        context.decode(&mut batch).map_err(|e| format!("Decode failed: {:?}", e))?;
+        n_cur += 1;
+    }
+    let tg_ms = tg_start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok((pp_ms, tg_ms, gen_tokens))
+}
+
+fn per_sec(tokens: usize, ms: f64) -> f64 {
+    if ms > 0.0 { tokens as f64 * 1000.0 / ms } else { 0.0 }
+}
+
+fn run_measurements(loaded_model: &LoadedModel, backend: &llama_cpp_2::llama_backend::LlamaBackend, config: &BenchmarkConfig) -> Result<Vec<PromptSizeResult>, String> {
+    let n_ctx = loaded_model.n_ctx as usize;
+    if config.gen_tokens >= n_ctx {
+        return Err(format!("gen_tokens ({}) context'ten ({}) küçük olmalı", config.gen_tokens, n_ctx));
+    }
+    let mut results = Vec::new();
+    for &size in &config.prompt_sizes {
+        // Prompt and generated tokens share the context window
+        let size = size.min(n_ctx - config.gen_tokens).max(1);
+        let prompt = synthetic_prompt(loaded_model, size)?;
+        let reps = config.repetitions.max(1);
+        let (mut pp_total, mut tg_total) = (0.0, 0.0);
+        for rep in 0..reps {
+            let (pp_ms, tg_ms, _) = measure(loaded_model, backend, &prompt, config.gen_tokens)?;
+            info!("⏱️ pp{} rep {}: {:.1} ms, tg{}: {:.1} ms", size, rep + 1, pp_ms, config.gen_tokens, tg_ms);
+            pp_total += pp_ms;
+            tg_total += tg_ms;
+        }
+        let (pp_ms, tg_ms) = (pp_total / reps as f64, tg_total / reps as f64);
+        results.push(PromptSizeResult {
+            prompt_tokens: size,
+            pp_ms,
+            pp_tokens_per_sec: per_sec(size, pp_ms),
+            gen_tokens: config.gen_tokens,
+            tg_ms,
+            tg_tokens_per_sec: per_sec(config.gen_tokens, tg_ms),
+        });
+    }
+    Ok(results)
+}
+
+/// Benchmark a model with the given settings and append the run to its history.
+/// The model is (re)loaded through the pool so load time reflects real usage; a
+/// model that was not pooled before is unloaded again afterwards.
+#[tauri::command]
+pub async fn benchmark_gguf_model(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    config: Option<BenchmarkConfig>,
+) -> Result<BenchmarkReport, String> {
+    let config = config.unwrap_or_default();
+    let model_key = gguf::resolve_split_gguf_path(&model_path);
+    let previous = {
+        let guard = state.lock().unwrap_or_else(|p| p.into_inner());
+        guard.models.get(&model_key).map(|m| (m.n_ctx, m.n_gpu_layers))
+    };
+
+    info!("🏁 Benchmark başlıyor: {} ({:?})", model_key, config);
+    let sampler = RssSampler::start();
+
+    let load_start = Instant::now();
+    let loaded = gguf::load_gguf_model(app.clone(), state.clone(), model_key.clone(), config.n_ctx, config.n_gpu_layers).await;
+    let load_ms = load_start.elapsed().as_secs_f64() * 1000.0;
+    if let Err(e) = loaded {
+        sampler.finish();
+        return Err(e);
+    }
+
+    let (loaded_model, backend) = {
+        let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+        guard.checkout(&model_key)?
+    };
+    let bench_config = config.clone();
+    let measured = tokio::task::spawn_blocking(move || run_measurements(&loaded_model, &backend, &bench_config))
+        .await
+        .map_err(|e| format!("Benchmark görevi başarısız: {}", e))?;
+    let peak_rss_bytes = sampler.finish();
+
+    // Pool'u benchmark öncesi haline döndür
+    match previous {
+        None => {
+            state.lock().unwrap_or_else(|p| p.into_inner()).remove_model(&model_key);
+        }
+        Some((n_ctx, n_gpu_layers)) if (n_ctx, n_gpu_layers) != (config.n_ctx, config.n_gpu_layers) => {
+            if let Err(e) = gguf::load_gguf_model(app.clone(), state.clone(), model_key.clone(), n_ctx, n_gpu_layers).await {
+                warn!("⚠️ Model önceki ayarlarla yeniden yüklenemedi: {}", e);
+            }
+        }
+        Some(_) => {}
+    }
+    let results = measured?;
+
+    let file_info = gguf_reader::read_gguf_file(&model_key, &GgufReadOptions { max_array_len: Some(0), read_tensors: true }).ok();
+    let hardware = HardwareFingerprint::detect();
+    let run = BenchmarkRun {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+        model_size_bytes: gguf::split_part_paths(&model_key).iter()
+            .filter_map(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum(),
+        quantization: file_info.and_then(|f| f.summary().dominant_type),
+        model_path: model_key.clone(),
+        config,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        load_ms,
+        peak_rss_bytes,
+        results,
+        hardware,
+    };
+
+    let history_path = history_dir(&app)?.join(history_file_name(&model_key, &run.hardware.id()));
+    let mut history = load_history(&history_path);
+    let diff_vs_previous = history.last().map(|prev| diff_runs(prev, &run));
+    history.push(run.clone());
+    if history.len() > MAX_HISTORY {
+        let excess = history.len() - MAX_HISTORY;
+        history.drain(..excess);
+    }
+    save_history(&history_path, &history)?;
+
+    if let Some(diff) = &diff_vs_previous {
+        if diff.regressions > 0 {
+            warn!("📉 {} metrikte gerileme tespit edildi ({})", diff.regressions, model_key);
+        }
+    }
+    info!("✅ Benchmark tamamlandı: load {:.0} ms, peak RSS {:.0} MB", run.load_ms, run.peak_rss_bytes as f64 / 1_048_576.0);
+    Ok(BenchmarkReport { run, diff_vs_previous })
+}
+
+/// Benchmark history of a model on this machine (oldest first)
+#[tauri::command]
+pub async fn get_benchmark_history(app: AppHandle, model_path: String) -> Result<Vec<BenchmarkRun>, String> {
+    let model_key = gguf::resolve_split_gguf_path(&model_path);
+    let hardware_id = HardwareFingerprint::detect().id();
+    Ok(load_history(&history_dir(&app)?.join(history_file_name(&model_key, &hardware_id))))
+}
+
+/// Diff two runs from a model's history; defaults to the last two
+#[tauri::command]
+pub async fn diff_benchmark_runs(
+    app: AppHandle,
+    model_path: String,
+    baseline_id: Option<String>,
+    current_id: Option<String>,
+) -> Result<BenchmarkDiff, String> {
+    let history = get_benchmark_history(app, model_path).await?;
+    let find = |id: &Option<String>, fallback: Option<&BenchmarkRun>| -> Result<BenchmarkRun, String> {
+        match id {
+            Some(id) => history.iter().find(|r| &r.id == id).cloned()
+                .ok_or_else(|| format!("Benchmark bulunamadı: {}", id)),
+            None => fallback.cloned().ok_or_else(|| "Karşılaştırma için en az iki benchmark gerekli".to_string()),
+        }
+    };
+    let len = history.len();
+    let baseline = find(&baseline_id, len.checked_sub(2).and_then(|i| history.get(i)))?;
+    let current = find(&current_id, history.last())?;
+    Ok(diff_runs(&baseline, &current))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hardware() -> HardwareFingerprint {
+        HardwareFingerprint {
+            cpu_brand: "AMD Ryzen 7 5800X".to_string(),
+            physical_cores: 8,
+            logical_cores: 16,
+            total_ram_gb: 32,
+            gpu_backend: "CPU".to_string(),
+            os: "linux-x86_64".to_string(),
+        }
+    }
+
+    fn run(id: &str, load_ms: f64, pp: f64, tg: f64) -> BenchmarkRun {
+        BenchmarkRun {
+            id: id.to_string(),
+            timestamp: 0,
+            model_path: "/models/qwen2.5-1.5b-q4_k_m.gguf".to_string(),
+            model_size_bytes: 1,
+            quantization: Some("Q4_K".to_string()),
+            config: BenchmarkConfig::default(),
+            hardware: hardware(),
+            app_version: "0.1.0".to_string(),
+            load_ms,
+            peak_rss_bytes: 1_048_576 * 1200,
+            results: vec![PromptSizeResult {
+                prompt_tokens: 512,
+                pp_ms: 0.0,
+                pp_tokens_per_sec: pp,
+                gen_tokens: 128,
+                tg_ms: 0.0,
+                tg_tokens_per_sec: tg,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_id_is_stable() {
+        let a = hardware();
+        assert_eq!(a.id(), hardware().id());
+        assert_eq!(a.id().len(), 12);
+        let mut b = hardware();
+        b.gpu_backend = "CUDA".to_string();
+        assert_ne!(a.id(), b.id());
+    }
+
+    #[test]
+    fn test_diff_flags_regressions() {
+        let baseline = run("a", 1000.0, 200.0, 20.0);
+        let current = run("b", 1020.0, 150.0, 22.0);
+        let diff = diff_runs(&baseline, &current);
+
+        let get = |name: &str| diff.metrics.iter().find(|m| m.metric == name).unwrap();
+        assert!(!get("load_ms").regression); // +2% is within the threshold
+        assert!(get("pp512_tokens_per_sec").regression);
+        assert!((get("pp512_tokens_per_sec").change_pct + 25.0).abs() < 1e-9);
+        assert!(!get("tg512_tokens_per_sec").regression);
+        assert_eq!(diff.regressions, 1);
+    }
+
+    #[test]
+    fn test_history_file_name() {
+        assert_eq!(
+            history_file_name("/models/Qwen 2.5 (1.5B).Q4_K_M.gguf", "abc123"),
+            "Qwen_2.5__1.5B_.Q4_K_M.gguf__abc123.json"
+        );
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("corex_bench_test_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("history.json");
+        assert!(load_history(&path).is_empty());
+
+        save_history(&path, &[run("a", 1.0, 2.0, 3.0), run("b", 1.0, 2.0, 3.0)]).unwrap();
+        let loaded = load_history(&path);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[1].id, "b");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,614 @@
+// src-tauri/src/gguf_completion.rs
+// Structured completion results for GGUF inference: finish reasons, token counts,
+// timings, logprobs and stop-sequence handling across token boundaries.
+
+use serde::{Deserialize, Serialize};
+
+use crate::response_cache::CacheInfo;
+
+/// Why generation stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    /// Model produced an end-of-generation token
+    Stop,
+    /// `max_tokens` reached
+    Length,
+    /// A user-supplied stop sequence matched
+    StopSequence,
+    /// The caller cancelled generation
+    Cancelled,
+}
+
+/// One alternative token with its log probability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopLogprob {
+    pub token: String,
+    pub token_id: i32,
+    pub logprob: f32,
+}
+
+/// Log probability of a generated token plus the top-N alternatives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    pub token: String,
+    pub token_id: i32,
+    pub logprob: f32,
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// Prompt evaluation and generation timings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletionTimings {
+    pub prompt_eval_ms: f64,
+    pub generation_ms: f64,
+    pub prompt_tokens_per_sec: f64,
+    pub tokens_per_sec: f64,
+}
+
+impl CompletionTimings {
+    pub fn new(prompt_tokens: usize, prompt_eval_ms: f64, completion_tokens: usize, generation_ms: f64) -> Self {
+        Self {
+            prompt_eval_ms,
+            generation_ms,
+            prompt_tokens_per_sec: per_sec(prompt_tokens, prompt_eval_ms),
+            tokens_per_sec: per_sec(completion_tokens, generation_ms),
+        }
+    }
+}
+
+/// Draft/verify statistics for speculative decoding
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeculativeStats {
+    pub draft_model: String,
+    pub drafted_tokens: usize,
+    pub accepted_tokens: usize,
+    /// accepted / drafted
+    pub acceptance_rate: f64,
+    /// Verification batches run on the target model
+    pub target_passes: usize,
+    pub tokens_per_target_pass: f64,
+    /// Measured single-token target decode time, the non-speculative baseline
+    pub baseline_ms_per_token: f64,
+    pub ms_per_token: f64,
+    /// baseline_ms_per_token / ms_per_token
+    pub speedup: f64,
+}
+
+impl SpeculativeStats {
+    /// Fill in the derived ratios from the raw counters
+    pub fn finish(&mut self, generated_tokens: usize, generation_ms: f64) {
+        self.acceptance_rate = ratio(self.accepted_tokens as f64, self.drafted_tokens as f64);
+        self.tokens_per_target_pass = ratio(generated_tokens as f64, self.target_passes as f64);
+        self.ms_per_token = ratio(generation_ms, generated_tokens as f64);
+        self.speedup = ratio(self.baseline_ms_per_token, self.ms_per_token);
+    }
+}
+
+/// KV-cache context shifts applied while a completion ran
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextShiftStats {
+    /// Kept at the start of the window (system prompt, BOS)
+    pub n_keep: usize,
+    /// Prompt tokens cut before evaluation because the prompt alone overflowed
+    pub prompt_tokens_discarded: usize,
+    /// Shifts during generation
+    pub shifts: usize,
+    /// Tokens evicted from the KV cache by those shifts
+    pub tokens_discarded: usize,
+}
+
+/// Tokens to evict when the window is full: half of what follows `n_keep` (llama.cpp's rule)
+pub fn context_shift_discard(n_past: usize, n_keep: usize) -> usize {
+    n_past.saturating_sub(n_keep) / 2
+}
+
+/// Plan one shift of a full window of `n_past` tokens and record it in `stats`.
+/// Returns the effective `n_keep` and how many tokens to evict after it.
+pub fn plan_context_shift(stats: &mut Option<ContextShiftStats>, n_past: usize, n_keep: usize) -> (usize, usize) {
+    let n_keep = n_keep.min(n_past / 2);
+    let n_discard = context_shift_discard(n_past, n_keep);
+    let stats = stats.get_or_insert_with(|| ContextShiftStats { n_keep, ..Default::default() });
+    stats.shifts += 1;
+    stats.tokens_discarded += n_discard;
+    (n_keep, n_discard)
+}
+
+/// Fit an over-long prompt into `n_ctx` by keeping the first `n_keep` tokens and
+/// dropping whole blocks of the oldest tokens after them, like llama.cpp's server does.
+/// Returns the shortened prompt and how many tokens were dropped.
+pub fn shift_prompt<T: Clone>(tokens: &[T], n_ctx: usize, n_keep: usize) -> (Vec<T>, usize) {
+    if tokens.len() <= n_ctx {
+        return (tokens.to_vec(), 0);
+    }
+    let n_keep = n_keep.min(n_ctx.saturating_sub(4));
+    let block = ((n_ctx - n_keep) / 2).max(1);
+    // What remains after n_keep is between one and two blocks long, so it always fits
+    let erased = (tokens.len() - n_keep - block) / block * block;
+    let mut out = tokens[..n_keep].to_vec();
+    out.extend_from_slice(&tokens[n_keep + erased..]);
+    (out, erased)
+}
+
+fn ratio(a: f64, b: f64) -> f64 {
+    if b > 0.0 { a / b } else { 0.0 }
+}
+
+fn per_sec(tokens: usize, ms: f64) -> f64 {
+    if ms > 0.0 { tokens as f64 * 1000.0 / ms } else { 0.0 }
+}
+
+/// Structured result of a GGUF completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufCompletion {
+    pub text: String,
+    pub finish_reason: FinishReason,
+    /// The stop sequence that ended generation, if any
+    pub stop_sequence: Option<String>,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+    pub timings: CompletionTimings,
+    pub logprobs: Option<Vec<TokenLogprob>>,
+    /// Present when the completion used a draft model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speculative: Option<SpeculativeStats>,
+    /// Present when `n_keep` was set and the window had to be shifted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_shift: Option<ContextShiftStats>,
+    /// Set when the caller asked for response caching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheInfo>,
+}
+
+/// Optional knobs for a completion request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CompletionOptions {
+    /// Stop strings; generation ends when any of them appears in the output
+    #[serde(default)]
+    pub stop: Vec<String>,
+    /// Number of alternatives per token to report (None = no logprobs)
+    pub top_logprobs: Option<u32>,
+    /// Context shift: on overflow keep this many leading tokens and evict the oldest
+    /// of the rest instead of failing (None = "Prompt too long" / stop at the window)
+    #[serde(default)]
+    pub n_keep: Option<usize>,
+    /// Constrain output to a single JSON object (greedy, grammar-checked per token).
+    /// Generation stops as soon as the object is closed.
+    #[serde(default)]
+    pub json: bool,
+}
+
+/// Incremental stop-sequence detector.
+///
+/// Text is pushed piece by piece as tokens are decoded. Anything that could
+/// still be the start of a stop sequence is held back, so a stop string split
+/// over several tokens never leaks into the emitted output.
+pub struct StopSequenceMatcher {
+    stops: Vec<String>,
+    pending: String,
+}
+
+/// Result of pushing a piece into the matcher
+#[derive(Debug, PartialEq, Eq)]
+pub enum StopMatch {
+    /// Text that is safe to emit (may be empty while a partial match is held)
+    Continue(String),
+    /// A stop sequence matched; carries the text before it and the stop itself
+    Matched { emit: String, stop: String },
+}
+
+impl StopSequenceMatcher {
+    pub fn new(stops: &[String]) -> Self {
+        Self {
+            stops: stops.iter().filter(|s| !s.is_empty()).cloned().collect(),
+            pending: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, piece: &str) -> StopMatch {
+        self.pending.push_str(piece);
+
+        // Earliest full match wins
+        let found = self.stops.iter()
+            .filter_map(|s| self.pending.find(s.as_str()).map(|pos| (pos, s)))
+            .min_by_key(|(pos, _)| *pos);
+        if let Some((pos, stop)) = found {
+            let stop = stop.clone();
+            let emit = self.pending[..pos].to_string();
+            self.pending.clear();
+            return StopMatch::Matched { emit, stop };
+        }
+
+        // Hold back the longest suffix that is a prefix of some stop string
+        let hold = self.longest_partial_suffix();
+        let split = self.pending.len() - hold;
+        let emit = self.pending[..split].to_string();
+        self.pending.drain(..split);
+        StopMatch::Continue(emit)
+    }
+
+    /// Release whatever is still held back (generation ended without a match)
+    pub fn flush(&mut self) -> String {
+        std::mem::take(&mut self.pending)
+    }
+
+    fn longest_partial_suffix(&self) -> usize {
+        let mut best = 0;
+        for stop in &self.stops {
+            let max = stop.len().saturating_sub(1).min(self.pending.len());
+            for len in (1..=max).rev() {
+                let start = self.pending.len() - len;
+                if !self.pending.is_char_boundary(start) || !stop.is_char_boundary(len) {
+                    continue;
+                }
+                if stop.starts_with(&self.pending[start..]) {
+                    best = best.max(len);
+                    break;
+                }
+            }
+        }
+        best
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumberState {
+    Minus,
+    Zero,
+    Int,
+    Dot,
+    Frac,
+    Exp,
+    ExpSign,
+    ExpInt,
+}
+
+impl NumberState {
+    fn next(self, c: char) -> Option<NumberState> {
+        use NumberState::*;
+        match (self, c) {
+            (Minus, '0') => Some(Zero),
+            (Minus, '1'..='9') => Some(Int),
+            (Int, '0'..='9') => Some(Int),
+            (Zero | Int, '.') => Some(Dot),
+            (Dot | Frac, '0'..='9') => Some(Frac),
+            (Zero | Int | Frac, 'e' | 'E') => Some(Exp),
+            (Exp, '+' | '-') => Some(ExpSign),
+            (Exp | ExpSign | ExpInt, '0'..='9') => Some(ExpInt),
+            _ => None,
+        }
+    }
+
+    fn can_end(self) -> bool {
+        matches!(self, NumberState::Zero | NumberState::Int | NumberState::Frac | NumberState::ExpInt)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JsonState {
+    /// Before the top-level '{'
+    Start,
+    /// After '{': key or '}'
+    ObjectOpen,
+    /// After ',' inside an object
+    Key,
+    Colon,
+    Value,
+    /// After '[': value or ']'
+    ArrayOpen,
+    /// `escape`: 0 = none, 1 = after a backslash, 2..=5 = \uXXXX hex digits still expected + 1
+    String { key: bool, escape: u8 },
+    Number(NumberState),
+    Literal(&'static str),
+    AfterValue,
+    Done,
+}
+
+/// Incremental JSON grammar: accepts text only while it can still become one
+/// JSON object. Used to constrain GGUF sampling for tool calls.
+#[derive(Debug, Clone)]
+pub struct JsonPrefix {
+    /// Open containers, true = object
+    stack: Vec<bool>,
+    state: JsonState,
+}
+
+impl Default for JsonPrefix {
+    fn default() -> Self {
+        Self { stack: Vec::new(), state: JsonState::Start }
+    }
+}
+
+impl JsonPrefix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The top-level object is closed
+    pub fn is_complete(&self) -> bool {
+        self.state == JsonState::Done
+    }
+
+    /// Would `piece` keep the text valid? Leaves `self` untouched.
+    pub fn accepts(&self, piece: &str) -> bool {
+        self.clone().push_str(piece)
+    }
+
+    /// Feed text; false (and a poisoned state) if it breaks the grammar
+    pub fn push_str(&mut self, piece: &str) -> bool {
+        piece.chars().all(|c| self.push(c))
+    }
+
+    fn open(&mut self, object: bool) {
+        self.stack.push(object);
+        self.state = if object { JsonState::ObjectOpen } else { JsonState::ArrayOpen };
+    }
+
+    fn close(&mut self, object: bool) -> bool {
+        if self.stack.pop() != Some(object) {
+            return false;
+        }
+        self.state = if self.stack.is_empty() { JsonState::Done } else { JsonState::AfterValue };
+        true
+    }
+
+    fn start_value(&mut self, c: char) -> bool {
+        self.state = match c {
+            '"' => JsonState::String { key: false, escape: 0 },
+            '{' => { self.open(true); return true; }
+            '[' => { self.open(false); return true; }
+            '-' => JsonState::Number(NumberState::Minus),
+            '0' => JsonState::Number(NumberState::Zero),
+            '1'..='9' => JsonState::Number(NumberState::Int),
+            't' => JsonState::Literal("rue"),
+            'f' => JsonState::Literal("alse"),
+            'n' => JsonState::Literal("ull"),
+            _ => return false,
+        };
+        true
+    }
+
+    fn push(&mut self, c: char) -> bool {
+        let ws = matches!(c, ' ' | '\t' | '\n' | '\r');
+        match self.state {
+            JsonState::Start => match c {
+                '{' => { self.open(true); true }
+                _ => ws,
+            },
+            JsonState::ObjectOpen | JsonState::Key => match c {
+                '"' => { self.state = JsonState::String { key: true, escape: 0 }; true }
+                '}' if self.state == JsonState::ObjectOpen => self.close(true),
+                _ => ws,
+            },
+            JsonState::Colon => match c {
+                ':' => { self.state = JsonState::Value; true }
+                _ => ws,
+            },
+            JsonState::Value | JsonState::ArrayOpen => {
+                if ws {
+                    true
+                } else if c == ']' && self.state == JsonState::ArrayOpen {
+                    self.close(false)
+                } else {
+                    self.start_value(c)
+                }
+            }
+            JsonState::String { key, escape } => {
+                let escape = match (escape, c) {
+                    (0, '"') => {
+                        self.state = if key { JsonState::Colon } else { JsonState::AfterValue };
+                        return true;
+                    }
+                    (0, '\\') => 1,
+                    (0, c) if (c as u32) < 0x20 => return false,
+                    (0, _) => 0,
+                    (1, '"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't') => 0,
+                    (1, 'u') => 5,
+                    (1, _) => return false,
+                    (n, c) if c.is_ascii_hexdigit() => if n == 2 { 0 } else { n - 1 },
+                    _ => return false,
+                };
+                self.state = JsonState::String { key, escape };
+                true
+            }
+            JsonState::Number(n) => match n.next(c) {
+                Some(next) => { self.state = JsonState::Number(next); true }
+                None if n.can_end() => { self.state = JsonState::AfterValue; self.push(c) }
+                None => false,
+            },
+            JsonState::Literal(rest) => {
+                if !rest.starts_with(c) {
+                    return false;
+                }
+                let rest = &rest[c.len_utf8()..];
+                self.state = if rest.is_empty() { JsonState::AfterValue } else { JsonState::Literal(rest) };
+                true
+            }
+            JsonState::AfterValue => match (c, self.stack.last()) {
+                (',', Some(true)) => { self.state = JsonState::Key; true }
+                (',', Some(false)) => { self.state = JsonState::Value; true }
+                ('}', Some(true)) => self.close(true),
+                (']', Some(false)) => self.close(false),
+                _ => ws,
+            },
+            JsonState::Done => ws,
+        }
+    }
+}
+
+/// Log-softmax over `logits` and return the chosen token's logprob and the top-N alternatives.
+/// `logits` is (token_id, logit). Pieces for the alternatives are resolved by the caller.
+pub fn compute_logprobs(logits: &[(i32, f32)], chosen: i32, top_n: usize) -> (f32, Vec<(i32, f32)>) {
+    if logits.is_empty() {
+        return (f32::NEG_INFINITY, Vec::new());
+    }
+
+    let max_logit = logits.iter().map(|(_, l)| *l).fold(f32::NEG_INFINITY, f32::max);
+    let log_sum = logits.iter().map(|(_, l)| (l - max_logit).exp()).sum::<f32>().ln() + max_logit;
+
+    let chosen_logprob = logits.iter()
+        .find(|(id, _)| *id == chosen)
+        .map(|(_, l)| l - log_sum)
+        .unwrap_or(f32::NEG_INFINITY);
+
+    // Partial selection: only the top_n entries of a 100k+ vocabulary get sorted
+    let by_logprob = |a: &(i32, f32), b: &(i32, f32)| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal);
+    let mut top: Vec<(i32, f32)> = logits.iter().map(|(id, l)| (*id, l - log_sum)).collect();
+    let top_n = top_n.min(top.len());
+    if top_n > 0 && top_n < top.len() {
+        top.select_nth_unstable_by(top_n - 1, by_logprob);
+    }
+    top.truncate(top_n);
+    top.sort_by(by_logprob);
+
+    (chosen_logprob, top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_prefix() {
+        let text = r#" {"tool_calls": [{"name": "read_file", "arguments": {"path": "a\\\"b.rs", "n": -1.5e+3, "ok": true, "x": null, "u": "\u00e9"}}], "e": []}"#;
+        // Every prefix is accepted, piece by piece
+        let mut json = JsonPrefix::new();
+        for c in text.chars() {
+            assert!(json.accepts(&c.to_string()), "rejected {:?}", c);
+            assert!(!json.is_complete());
+            json.push_str(&c.to_string());
+        }
+        assert!(json.is_complete());
+        assert!(json.accepts("\n"));
+        assert!(!json.accepts("x"));
+
+        let fresh = JsonPrefix::new();
+        assert!(!fresh.accepts("Sure! {"));
+        assert!(!fresh.accepts("[1]"));
+        assert!(!fresh.accepts("{\"a\" 1"));
+        assert!(!fresh.accepts("{\"a\": 01"));
+        assert!(!fresh.accepts("{\"a\": [1,]"));
+        assert!(!fresh.accepts("{\"a\": tru }"));
+        assert!(!fresh.accepts("{\"a\": \"\\q\"}"));
+        assert!(fresh.accepts("{\"a\": [1, {\"b\": \"c"));
+        assert!(fresh.accepts("{}"));
+    }
+
+    fn stops(s: &[&str]) -> Vec<String> {
+        s.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_stop_sequence_across_tokens() {
+        let mut m = StopSequenceMatcher::new(&stops(&["</end>"]));
+        assert_eq!(m.push("Hello "), StopMatch::Continue("Hello ".into()));
+        assert_eq!(m.push("world</"), StopMatch::Continue("world".into()));
+        assert_eq!(m.push("en"), StopMatch::Continue(String::new()));
+        assert_eq!(
+            m.push("d> trailing"),
+            StopMatch::Matched { emit: String::new(), stop: "</end>".into() }
+        );
+    }
+
+    #[test]
+    fn test_partial_match_released() {
+        let mut m = StopSequenceMatcher::new(&stops(&["###"]));
+        assert_eq!(m.push("a #"), StopMatch::Continue("a ".into()));
+        assert_eq!(m.push("b"), StopMatch::Continue("#b".into()));
+        assert_eq!(m.push("##"), StopMatch::Continue(String::new()));
+        assert_eq!(m.flush(), "##");
+    }
+
+    #[test]
+    fn test_earliest_stop_wins() {
+        let mut m = StopSequenceMatcher::new(&stops(&["world", "lo"]));
+        assert_eq!(
+            m.push("hello world"),
+            StopMatch::Matched { emit: "hel".into(), stop: "lo".into() }
+        );
+    }
+
+    #[test]
+    fn test_no_stops_passthrough() {
+        let mut m = StopSequenceMatcher::new(&[]);
+        assert_eq!(m.push("abc"), StopMatch::Continue("abc".into()));
+        assert_eq!(m.flush(), "");
+    }
+
+    #[test]
+    fn test_speculative_stats_finish() {
+        let mut stats = SpeculativeStats {
+            drafted_tokens: 40,
+            accepted_tokens: 30,
+            target_passes: 10,
+            baseline_ms_per_token: 100.0,
+            ..Default::default()
+        };
+        stats.finish(40, 2000.0);
+        assert!((stats.acceptance_rate - 0.75).abs() < 1e-9);
+        assert!((stats.tokens_per_target_pass - 4.0).abs() < 1e-9);
+        assert!((stats.ms_per_token - 50.0).abs() < 1e-9);
+        assert!((stats.speedup - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_shift_prompt() {
+        let tokens: Vec<u32> = (0..100).collect();
+        let (fits, erased) = shift_prompt(&tokens, 128, 4);
+        assert_eq!((fits.len(), erased), (100, 0));
+
+        // block = (64 - 4) / 2 = 30; (100 - 4 - 30) / 30 = 2 blocks = 60 dropped
+        let (shifted, erased) = shift_prompt(&tokens, 64, 4);
+        assert_eq!(erased, 60);
+        assert_eq!(&shifted[..4], &[0, 1, 2, 3]);
+        assert_eq!(shifted[4], 64);
+        assert_eq!(*shifted.last().unwrap(), 99);
+        assert!(shifted.len() <= 64);
+
+        assert_eq!(context_shift_discard(100, 10), 45);
+        assert_eq!(context_shift_discard(5, 10), 0);
+    }
+
+    #[test]
+    fn test_context_shift_during_generation() {
+        // Same bookkeeping as the generation loop: 64-token window, 20-token prompt, 200 new tokens
+        let (window, n_keep) = (64usize, 4usize);
+        let mut stats = None;
+        let mut n_cur = 20usize;
+        for _ in 0..200 {
+            if n_cur >= window {
+                let (keep, discard) = plan_context_shift(&mut stats, n_cur, n_keep);
+                assert_eq!((keep, discard), (4, 30));
+                n_cur -= discard;
+            }
+            assert!(n_cur < window);
+            n_cur += 1;
+        }
+        let stats = stats.expect("window overflowed, shift must have run");
+        assert_eq!(stats.n_keep, 4);
+        // 44 tokens fill the window, then every 30 tokens frees room again
+        assert_eq!(stats.shifts, 6);
+        assert_eq!(stats.tokens_discarded, 180);
+        assert_eq!(stats.prompt_tokens_discarded, 0);
+    }
+
+    #[test]
+    fn test_compute_logprobs() {
+        let logits = vec![(1, 2.0), (2, 1.0), (3, 0.0)];
+        let (lp, top) = compute_logprobs(&logits, 1, 2);
+        assert!(lp < 0.0 && lp > -1.0);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, 1);
+        let total: f32 = logits.iter()
+            .map(|(id, _)| compute_logprobs(&logits, *id, 0).0.exp())
+            .sum();
+        assert!((total - 1.0).abs() < 1e-5);
+
+        // Top-N from a larger vocabulary comes back sorted
+        let vocab: Vec<(i32, f32)> = (0..1000).map(|i| (i, ((i * 37) % 1000) as f32 / 100.0)).collect();
+        let (_, top) = compute_logprobs(&vocab, 0, 3);
+        let ids: Vec<i32> = top.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![27, 54, 81]);
+        assert!(top[0].1 >= top[1].1 && top[1].1 >= top[2].1);
+    }
+}
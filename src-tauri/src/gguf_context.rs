@@ -0,0 +1,359 @@
+// src-tauri/src/gguf_context.rs
+// Context-window overflow handling for long GGUF conversations.
+//
+// When a rendered conversation no longer fits the model's n_ctx, one of these
+// strategies makes it fit: drop the oldest turns (system prompt kept), replace
+// them with a summary written by the model itself, or keep the prompt and let
+// the KV cache shift (first n_keep tokens kept, oldest of the rest evicted).
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::commands::ChatMessage;
+use crate::gguf::GgufState;
+use crate::gguf_completion::{CompletionOptions, GgufCompletion};
+use crate::inference::{GenerateRequest, InferenceEngine};
+use crate::response_cache::{self, CacheOptions};
+use crate::usage::{self, UsageEvent};
+
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+const SUMMARY_INSTRUCTIONS: &str = "Summarize the conversation below in a few sentences. \
+Keep facts, decisions, file names, code identifiers and open questions. Reply with the summary only.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowStrategy {
+    /// Fail with "Prompt too long" (previous behaviour)
+    Error,
+    /// Drop the oldest non-system turns until the conversation fits
+    DropOldest,
+    /// Replace the oldest turns with a model-written summary
+    Summarize,
+    /// Keep the system prompt in the KV cache and shift out the oldest tokens.
+    /// The window is exactly n_ctx, so long replies shift during generation; a
+    /// prompt that alone overflows loses whole token blocks first, which may cut mid-turn.
+    ContextShift,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OverflowOptions {
+    pub strategy: OverflowStrategy,
+    /// Token budget for the summary (Summarize only)
+    #[serde(default = "default_summary_max_tokens")]
+    pub summary_max_tokens: u32,
+}
+
+fn default_summary_max_tokens() -> u32 { 256 }
+
+impl Default for OverflowOptions {
+    fn default() -> Self {
+        Self { strategy: OverflowStrategy::DropOldest, summary_max_tokens: default_summary_max_tokens() }
+    }
+}
+
+/// What an overflow strategy did to make the conversation fit
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContextOverflowReport {
+    pub strategy: Option<OverflowStrategy>,
+    pub n_ctx: usize,
+    pub prompt_tokens_before: usize,
+    pub prompt_tokens_after: usize,
+    pub tokens_reclaimed: usize,
+    pub messages_dropped: usize,
+    pub messages_summarized: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    /// KV-cache shifts during generation (ContextShift only)
+    pub context_shifts: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationCompletion {
+    pub completion: GgufCompletion,
+    /// None when the conversation fitted as is
+    pub overflow: Option<ContextOverflowReport>,
+}
+
+/// Oldest message that may be dropped: not a system message and not the latest turn
+pub fn oldest_droppable(messages: &[ChatMessage]) -> Option<usize> {
+    let last = messages.len().checked_sub(1)?;
+    messages.iter().position(|m| m.role != "system").filter(|&i| i < last)
+}
+
+/// Drop the oldest droppable messages until `count(messages) <= budget`.
+/// Each dropped message is counted on its own and subtracted; since that includes
+/// template overhead, a full count confirms the result before it is returned.
+/// Returns the kept messages and the dropped ones (oldest first).
+pub fn drop_oldest_until_fits(
+    messages: &[ChatMessage],
+    budget: usize,
+    count: &mut dyn FnMut(&[ChatMessage]) -> Result<usize, String>,
+) -> Result<(Vec<ChatMessage>, Vec<ChatMessage>), String> {
+    let mut kept = messages.to_vec();
+    let mut dropped = Vec::new();
+    let mut tokens = count(&kept)?;
+    let mut exact = true;
+    loop {
+        if tokens <= budget {
+            if exact {
+                return Ok((kept, dropped));
+            }
+            tokens = count(&kept)?;
+            exact = true;
+            continue;
+        }
+        match oldest_droppable(&kept) {
+            Some(idx) => {
+                let message = kept.remove(idx);
+                tokens = tokens.saturating_sub(count(std::slice::from_ref(&message))?);
+                exact = false;
+                dropped.push(message);
+            }
+            None if !exact => {
+                tokens = count(&kept)?;
+                exact = true;
+            }
+            None => return Err(format!(
+                "Sistem mesajı ve son mesaj tek başına context'e sığmıyor: {} tokens (max: {})",
+                tokens, budget
+            )),
+        }
+    }
+}
+
+/// Put the summary right after the leading system messages
+pub fn insert_summary(messages: Vec<ChatMessage>, summary: &str) -> Vec<ChatMessage> {
+    let split = messages.iter().position(|m| m.role != "system").unwrap_or(messages.len());
+    let mut out = messages;
+    out.insert(split, ChatMessage { role: "system".into(), content: format!("{}{}", SUMMARY_PREFIX, summary.trim()) });
+    out
+}
+
+/// Plain-text transcript used as the summariser's input
+pub fn transcript(messages: &[ChatMessage]) -> String {
+    messages.iter()
+        .map(|m| format!("{}: {}", m.role, m.content.trim()))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Render `messages`, applying `overflow` if they don't fit, and generate a reply
+pub fn complete_conversation(
+    engine: &InferenceEngine,
+    model_id: &str,
+    messages: &[ChatMessage],
+    request: GenerateRequest,
+    overflow: &OverflowOptions,
+) -> Result<ConversationCompletion, String> {
+    let n_ctx = engine.context_size(model_id)?;
+    let mut count = |m: &[ChatMessage]| -> Result<usize, String> {
+        engine.count_tokens(model_id, &engine.format_chat(model_id, m)?)
+    };
+    let before = count(messages)?;
+    // The reply is generated in the same window, so the prompt must leave room for it
+    let budget = n_ctx - (request.max_tokens as usize).min(n_ctx / 2);
+
+    if before <= budget || overflow.strategy == OverflowStrategy::Error {
+        let prompt = engine.format_chat(model_id, messages)?;
+        let completion = engine.generate(model_id, &GenerateRequest { prompt, ..request })?;
+        return Ok(ConversationCompletion { completion, overflow: None });
+    }
+
+    info!("📏 Conversation overflows the window: {} > {} tokens, strategy {:?}", before, budget, overflow.strategy);
+    let mut report = ContextOverflowReport {
+        strategy: Some(overflow.strategy),
+        n_ctx,
+        prompt_tokens_before: before,
+        ..Default::default()
+    };
+
+    let fitted = match overflow.strategy {
+        OverflowStrategy::Error => unreachable!(),
+        OverflowStrategy::DropOldest => {
+            let (kept, dropped) = drop_oldest_until_fits(messages, budget, &mut count)?;
+            report.messages_dropped = dropped.len();
+            kept
+        }
+        OverflowStrategy::Summarize => {
+            // Leave room for the summary message itself
+            let reserve = overflow.summary_max_tokens as usize + 16;
+            let (kept, dropped) = drop_oldest_until_fits(messages, budget.saturating_sub(reserve), &mut count)?;
+            let (summary, summarized) = summarize(engine, model_id, &dropped, n_ctx, overflow.summary_max_tokens)?;
+            if summarized == 0 || summary.trim().is_empty() {
+                warn!("⚠️ Özet boş, eski mesajlar yalnızca atıldı");
+                report.messages_dropped = dropped.len();
+                kept
+            } else {
+                report.messages_summarized = summarized;
+                report.messages_dropped = dropped.len() - summarized;
+                let with_summary = insert_summary(kept, &summary);
+                report.summary = Some(summary);
+                // The summary may still come out longer than planned
+                let (kept, extra) = drop_oldest_until_fits(&with_summary, budget, &mut count)?;
+                report.messages_dropped += extra.len();
+                kept
+            }
+        }
+        OverflowStrategy::ContextShift => {
+            let system: Vec<ChatMessage> = messages.iter().take_while(|m| m.role == "system").cloned().collect();
+            let n_keep = if system.is_empty() { 1 } else { count(&system)? };
+            let prompt = engine.format_chat(model_id, messages)?;
+            let options = CompletionOptions { n_keep: Some(n_keep), ..request.options.clone() };
+            let completion = engine.generate(model_id, &GenerateRequest { prompt, options, ..request })?;
+            if let Some(shift) = &completion.context_shift {
+                report.tokens_reclaimed = shift.prompt_tokens_discarded + shift.tokens_discarded;
+                report.context_shifts = shift.shifts;
+            }
+            report.prompt_tokens_after = completion.prompt_tokens;
+            info!("✂️ Context shift reclaimed {} tokens", report.tokens_reclaimed);
+            return Ok(ConversationCompletion { completion, overflow: Some(report) });
+        }
+    };
+
+    report.prompt_tokens_after = count(&fitted)?;
+    report.tokens_reclaimed = before.saturating_sub(report.prompt_tokens_after);
+    info!("✂️ {:?}: {} -> {} tokens ({} dropped, {} summarized)",
+        overflow.strategy, before, report.prompt_tokens_after, report.messages_dropped, report.messages_summarized);
+
+    let prompt = engine.format_chat(model_id, &fitted)?;
+    let completion = engine.generate(model_id, &GenerateRequest { prompt, ..request })?;
+    Ok(ConversationCompletion { completion, overflow: Some(report) })
+}
+
+/// Ask the model to summarise `messages`; if even the summariser prompt is too long,
+/// the oldest of them are left out. Returns the summary and how many messages it covers.
+fn summarize(
+    engine: &InferenceEngine,
+    model_id: &str,
+    messages: &[ChatMessage],
+    n_ctx: usize,
+    max_tokens: u32,
+) -> Result<(String, usize), String> {
+    let budget = n_ctx.saturating_sub(max_tokens as usize);
+    let mut start = 0;
+    while start < messages.len() {
+        let prompt = engine.format_chat(model_id, &[
+            ChatMessage { role: "system".into(), content: SUMMARY_INSTRUCTIONS.into() },
+            ChatMessage { role: "user".into(), content: transcript(&messages[start..]) },
+        ])?;
+        if engine.count_tokens(model_id, &prompt)? <= budget {
+            let request = GenerateRequest { prompt, max_tokens, temperature: 0.2, options: CompletionOptions::default() };
+            let summary = engine.generate(model_id, &request)?.text;
+            return Ok((summary, messages.len() - start));
+        }
+        start += 1;
+    }
+    warn!("⚠️ Özetlenecek mesajlar context'e sığmadı, özetsiz devam ediliyor");
+    Ok((String::new(), 0))
+}
+
+/// Chat over a message list, fitting it into the model's context window if needed
+#[tauri::command]
+pub async fn chat_with_gguf_conversation(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    messages: Vec<ChatMessage>,
+    max_tokens: u32,
+    temperature: f32,
+    options: Option<CompletionOptions>,
+    overflow: Option<OverflowOptions>,
+    cache: Option<CacheOptions>,
+) -> Result<ConversationCompletion, String> {
+    let engine = InferenceEngine::llama_cpp(state.inner().clone());
+    let request = GenerateRequest { prompt: String::new(), max_tokens, temperature, options: options.unwrap_or_default() };
+    let overflow = overflow.unwrap_or_default();
+    let variant = response_cache::gguf_variant(state.inner(), &model_path);
+    let input = json!({ "messages": response_cache::gguf_messages(&messages), "overflow": overflow });
+    let key = response_cache::gguf_key("chat", &model_path, &variant, input, max_tokens, temperature, &request.options);
+    if let Some((mut result, info)) = response_cache::lookup::<ConversationCompletion>(&cache, key.as_ref()) {
+        result.completion.cache = Some(info);
+        return Ok(result);
+    }
+
+    usage::check_budget(Some("gguf"), "chat", None)?;
+    let model_id = model_path.clone();
+    let mut result = tokio::task::spawn_blocking(move || complete_conversation(&engine, &model_id, &messages, request, &overflow))
+        .await
+        .map_err(|e| format!("Inference görevi başarısız: {}", e))??;
+    let completion = &result.completion;
+    usage::record(UsageEvent::local("chat", &model_path, completion.prompt_tokens, completion.completion_tokens));
+    result.completion.cache = response_cache::store(&cache, key.as_ref(), &result);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str, content: &str) -> ChatMessage {
+        ChatMessage { role: role.into(), content: content.into() }
+    }
+
+    /// One token per word
+    fn words(messages: &[ChatMessage]) -> Result<usize, String> {
+        Ok(messages.iter().map(|m| m.content.split_whitespace().count()).sum())
+    }
+
+    #[test]
+    fn test_oldest_droppable_skips_system_and_last() {
+        let conv = vec![msg("system", "be brief"), msg("user", "a"), msg("assistant", "b"), msg("user", "c")];
+        assert_eq!(oldest_droppable(&conv), Some(1));
+        assert_eq!(oldest_droppable(&[msg("system", "s"), msg("user", "only")]), None);
+        assert_eq!(oldest_droppable(&[]), None);
+    }
+
+    #[test]
+    fn test_drop_oldest_until_fits() {
+        let conv = vec![
+            msg("system", "you are helpful"),
+            msg("user", "one two three four"),
+            msg("assistant", "five six seven"),
+            msg("user", "eight nine"),
+        ];
+        let (kept, dropped) = drop_oldest_until_fits(&conv, 9, &mut words).unwrap();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(kept[0].role, "system");
+        assert_eq!(kept.last().unwrap().content, "eight nine");
+        assert_eq!(words(&kept).unwrap(), 8);
+
+        let (kept, dropped) = drop_oldest_until_fits(&conv, 100, &mut words).unwrap();
+        assert_eq!((kept.len(), dropped.len()), (4, 0));
+
+        assert!(drop_oldest_until_fits(&conv, 4, &mut words).is_err());
+    }
+
+    #[test]
+    fn test_drop_oldest_counts_each_message_once() {
+        let mut conv = vec![msg("system", "rules")];
+        conv.extend((0..200).map(|i| msg(if i % 2 == 0 { "user" } else { "assistant" }, "a b c d")));
+        // Two tokens of template overhead per render, like BOS plus the generation prompt
+        let mut calls = 0;
+        let mut counter = |m: &[ChatMessage]| -> Result<usize, String> {
+            calls += 1;
+            Ok(words(m)? + 2)
+        };
+        let (kept, dropped) = drop_oldest_until_fits(&conv, 43, &mut counter).unwrap();
+        assert!(words(&kept).unwrap() + 2 <= 43);
+        assert_eq!(kept.len() + dropped.len(), conv.len());
+        assert!(calls < 2 * conv.len(), "{} count calls", calls);
+    }
+
+    #[test]
+    fn test_insert_summary_after_system() {
+        let conv = vec![msg("system", "rules"), msg("user", "latest")];
+        let out = insert_summary(conv, " they discussed X ");
+        assert_eq!(out.len(), 3);
+        assert_eq!(out[1].role, "system");
+        assert_eq!(out[1].content, format!("{}they discussed X", SUMMARY_PREFIX));
+        assert_eq!(out[2].content, "latest");
+    }
+
+    #[test]
+    fn test_transcript() {
+        let t = transcript(&[msg("user", " hi "), msg("assistant", "hello")]);
+        assert_eq!(t, "user: hi\n\nassistant: hello");
+    }
+}
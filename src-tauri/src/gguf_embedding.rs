@@ -0,0 +1,278 @@
+// src-tauri/src/gguf_embedding.rs
+// Embedding mode for pooled GGUF models: pooled (mean / CLS / last-token)
+// sentence embeddings with L2 normalisation, many inputs per decode.
+
+use llama_cpp_2::context::params::{LlamaContextParams, LlamaPoolingType};
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::AddBos;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::gguf::{self, GgufState, LoadedModel};
+use crate::hardware;
+use crate::usage::{self, UsageEvent};
+
+/// How token embeddings are reduced to one vector per input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbeddingPooling {
+    Mean,
+    Cls,
+    Last,
+}
+
+impl EmbeddingPooling {
+    fn llama_type(self) -> LlamaPoolingType {
+        match self {
+            EmbeddingPooling::Mean => LlamaPoolingType::Mean,
+            EmbeddingPooling::Cls => LlamaPoolingType::Cls,
+            EmbeddingPooling::Last => LlamaPoolingType::Last,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingOptions {
+    /// None = the pooling declared in the GGUF (`{arch}.pooling_type`), last-token otherwise
+    #[serde(default)]
+    pub pooling: Option<EmbeddingPooling>,
+    /// L2-normalise the output vectors (cosine similarity becomes a dot product)
+    #[serde(default = "default_normalize")]
+    pub normalize: bool,
+    /// Inputs longer than this are truncated (capped by the model's training context)
+    #[serde(default = "default_max_input_tokens")]
+    pub max_input_tokens: usize,
+    /// Max inputs decoded together in one batch
+    #[serde(default = "default_max_batch_inputs")]
+    pub max_batch_inputs: usize,
+}
+
+fn default_normalize() -> bool { true }
+fn default_max_input_tokens() -> usize { 512 }
+fn default_max_batch_inputs() -> usize { 16 }
+
+impl Default for EmbeddingOptions {
+    fn default() -> Self {
+        Self {
+            pooling: None,
+            normalize: default_normalize(),
+            max_input_tokens: default_max_input_tokens(),
+            max_batch_inputs: default_max_batch_inputs(),
+        }
+    }
+}
+
+/// Result of an embedding call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufEmbeddings {
+    pub embeddings: Vec<Vec<f32>>,
+    pub dimensions: usize,
+    pub pooling: EmbeddingPooling,
+    pub prompt_tokens: usize,
+    /// Indexes of inputs that were cut to `max_input_tokens`
+    pub truncated: Vec<usize>,
+}
+
+/// Pick the pooling: explicit request, else the GGUF's `pooling_type`
+/// (1 = mean, 2 = cls, 3 = last), else last-token for decoder-style models.
+pub fn resolve_pooling(requested: Option<EmbeddingPooling>, gguf_pooling_type: Option<u64>) -> EmbeddingPooling {
+    requested.unwrap_or(match gguf_pooling_type {
+        Some(1) => EmbeddingPooling::Mean,
+        Some(2) => EmbeddingPooling::Cls,
+        _ => EmbeddingPooling::Last,
+    })
+}
+
+/// True when the GGUF declares a sentence-embedding pooling (bge, nomic, gte...)
+pub fn is_embedding_model(metadata: &serde_json::Map<String, serde_json::Value>) -> bool {
+    let arch = metadata.get("general.architecture").and_then(|v| v.as_str()).unwrap_or("");
+    metadata.get(&format!("{}.pooling_type", arch))
+        .and_then(|v| v.as_u64())
+        .map(|p| p > 0)
+        .unwrap_or(false)
+}
+
+pub fn normalize_l2(vector: &mut [f32]) {
+    let norm = vector.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// Group inputs (by token count) into batches of at most `max_inputs` sequences
+/// and `max_tokens` tokens, keeping the original order.
+pub fn plan_batches(token_counts: &[usize], max_tokens: usize, max_inputs: usize) -> Vec<Vec<usize>> {
+    let mut batches = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut current_tokens = 0;
+    for (idx, &count) in token_counts.iter().enumerate() {
+        if !current.is_empty() && (current.len() >= max_inputs || current_tokens + count > max_tokens) {
+            batches.push(std::mem::take(&mut current));
+            current_tokens = 0;
+        }
+        current.push(idx);
+        current_tokens += count;
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// Embed `texts` with a pooled model, several inputs per decode
+pub fn embed_texts(
+    loaded_model: &LoadedModel,
+    backend: &LlamaBackend,
+    texts: &[String],
+    options: &EmbeddingOptions,
+) -> Result<GgufEmbeddings, String> {
+    if texts.is_empty() {
+        return Err("Embed edilecek metin yok".to_string());
+    }
+
+    let metadata = gguf::read_gguf_metadata_map(&loaded_model.model_path).unwrap_or_default();
+    let arch = metadata.get("general.architecture").and_then(|v| v.as_str()).unwrap_or("").to_string();
+    let pooling = resolve_pooling(
+        options.pooling,
+        metadata.get(&format!("{}.pooling_type", arch)).and_then(|v| v.as_u64()),
+    );
+    let n_ctx_train = metadata.get(&format!("{}.context_length", arch))
+        .and_then(|v| v.as_u64())
+        .map(|v| v as usize)
+        .unwrap_or(usize::MAX);
+    let max_input_tokens = options.max_input_tokens.clamp(1, n_ctx_train);
+    let max_batch_inputs = options.max_batch_inputs.max(1);
+
+    let model = &loaded_model.model;
+    let mut tokenized = Vec::with_capacity(texts.len());
+    let mut truncated = Vec::new();
+    for (idx, text) in texts.iter().enumerate() {
+        let mut tokens = model.str_to_token(text, AddBos::Always)
+            .map_err(|e| format!("Tokenization failed: {:?}", e))?;
+        if tokens.is_empty() {
+            return Err(format!("Girdi {} boş, embed edilemez", idx));
+        }
+        if tokens.len() > max_input_tokens {
+            tokens.truncate(max_input_tokens);
+            truncated.push(idx);
+        }
+        tokenized.push(tokens);
+    }
+    if !truncated.is_empty() {
+        warn!("⚠️ {} girdi {} tokena kısaltıldı", truncated.len(), max_input_tokens);
+    }
+
+    // Each sequence gets its own slice of the context, so size it for the worst case
+    let batch_tokens = max_input_tokens * max_batch_inputs;
+    let ctx_params = LlamaContextParams::default()
+        .with_n_ctx(std::num::NonZeroU32::new(batch_tokens as u32))
+        .with_n_batch(batch_tokens as u32)
+        .with_n_ubatch(batch_tokens as u32)
+        .with_n_seq_max(max_batch_inputs as u32)
+        .with_embeddings(true)
+        .with_pooling_type(pooling.llama_type())
+        // Embedding is all prompt processing, so every logical core helps
+        .with_n_threads(hardware::thread_counts().n_threads_batch)
+        .with_n_threads_batch(hardware::thread_counts().n_threads_batch);
+    let mut context = model.new_context(backend, ctx_params)
+        .map_err(|e| format!("Embedding context creation failed: {:?}", e))?;
+
+    let counts: Vec<usize> = tokenized.iter().map(|t| t.len()).collect();
+    let batches = plan_batches(&counts, batch_tokens, max_batch_inputs);
+    info!("🧩 Embedding {} input(s) in {} batch(es), pooling {:?}", texts.len(), batches.len(), pooling);
+
+    let mut embeddings = vec![Vec::new(); texts.len()];
+    for indices in &batches {
+        let n_tokens: usize = indices.iter().map(|&i| counts[i]).sum();
+        let mut batch = LlamaBatch::new(n_tokens, indices.len() as i32);
+        for (seq_id, &idx) in indices.iter().enumerate() {
+            batch.add_sequence(&tokenized[idx], seq_id as i32, true)
+                .map_err(|e| format!("Batch add failed: {:?}", e))?;
+        }
+
+        context.clear_kv_cache();
+        context.decode(&mut batch)
+            .map_err(|e| format!("Embedding decode failed: {:?}", e))?;
+
+        for (seq_id, &idx) in indices.iter().enumerate() {
+            let mut vector = context.embeddings_seq_ith(seq_id as i32)
+                .map_err(|e| format!("Embedding okunamadı (girdi {}): {:?}", idx, e))?
+                .to_vec();
+            if options.normalize {
+                normalize_l2(&mut vector);
+            }
+            embeddings[idx] = vector;
+        }
+    }
+
+    Ok(GgufEmbeddings {
+        dimensions: embeddings.first().map(|e| e.len()).unwrap_or(0),
+        embeddings,
+        pooling,
+        prompt_tokens: counts.iter().sum(),
+        truncated,
+    })
+}
+
+/// Embed one or more texts with a pooled GGUF model
+#[tauri::command]
+pub async fn embed_with_gguf_model(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    texts: Vec<String>,
+    options: Option<EmbeddingOptions>,
+) -> Result<GgufEmbeddings, String> {
+    let (loaded_model, backend) = {
+        let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+        guard.checkout(&model_path)?
+    };
+    let options = options.unwrap_or_default();
+    let embeddings = tokio::task::spawn_blocking(move || embed_texts(&loaded_model, &backend, &texts, &options))
+        .await
+        .map_err(|e| format!("Embedding görevi başarısız: {}", e))??;
+    usage::record(UsageEvent::local("embedding", &model_path, embeddings.prompt_tokens, 0));
+    Ok(embeddings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_resolve_pooling() {
+        assert_eq!(resolve_pooling(None, Some(1)), EmbeddingPooling::Mean);
+        assert_eq!(resolve_pooling(None, Some(2)), EmbeddingPooling::Cls);
+        assert_eq!(resolve_pooling(None, None), EmbeddingPooling::Last);
+        assert_eq!(resolve_pooling(Some(EmbeddingPooling::Cls), Some(1)), EmbeddingPooling::Cls);
+    }
+
+    #[test]
+    fn test_normalize_l2() {
+        let mut v = vec![3.0, 4.0];
+        normalize_l2(&mut v);
+        assert!((v[0] - 0.6).abs() < 1e-6 && (v[1] - 0.8).abs() < 1e-6);
+
+        let mut zero = vec![0.0, 0.0];
+        normalize_l2(&mut zero);
+        assert_eq!(zero, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_plan_batches() {
+        assert_eq!(plan_batches(&[10, 10, 10, 10, 10], 1000, 2), vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert_eq!(plan_batches(&[300, 300, 300], 700, 16), vec![vec![0, 1], vec![2]]);
+        assert!(plan_batches(&[], 100, 4).is_empty());
+    }
+
+    #[test]
+    fn test_is_embedding_model() {
+        let bert = json!({ "general.architecture": "bert", "bert.pooling_type": 2 });
+        let llama = json!({ "general.architecture": "llama" });
+        assert!(is_embedding_model(bert.as_object().unwrap()));
+        assert!(!is_embedding_model(llama.as_object().unwrap()));
+    }
+}
@@ -0,0 +1,771 @@
+// src-tauri/src/gguf_fim.rs
+// Fill-in-the-middle code completion on pooled GGUF models.
+//
+// FIM special tokens are detected from the GGUF tokenizer metadata (explicit
+// `tokenizer.ggml.fim_*_token_id` keys, else well-known vocab entries for
+// StarCoder, CodeLlama, DeepSeek-Coder and Qwen-Coder). Prompts follow llama.cpp's
+// infill layout: optional repo-level context snippets, then PRE prefix SUF suffix MID.
+
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::token::data::LlamaTokenData;
+use llama_cpp_2::token::LlamaToken;
+use log::{info, warn};
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager, State};
+
+use crate::gguf::{self, GgufState, LoadedModel};
+use crate::gguf_reader::{self, GgufReadOptions};
+use crate::usage::{self, UsageEvent};
+
+const MAX_CANDIDATES: usize = 5;
+const RAG_TIMEOUT: Duration = Duration::from_millis(300);
+const RAG_SNIPPET_MAX_CHARS: usize = 1500;
+
+/// Which model family's FIM tokens were found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FimStyle {
+    StarCoder,
+    CodeLlama,
+    DeepSeekCoder,
+    QwenCoder,
+    /// Token ids from metadata whose texts match no known family
+    Generic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FimTokens {
+    pub style: FimStyle,
+    pub prefix: i32,
+    pub suffix: i32,
+    pub middle: i32,
+    /// End-of-infill / padding tokens that terminate a completion
+    pub stop: Vec<i32>,
+    /// Separates files in repo-level context (`<file_sep>`)
+    pub file_sep: Option<i32>,
+    /// Starts repo-level context (`<repo_name>`)
+    pub repo_name: Option<i32>,
+}
+
+struct KnownFim {
+    style: FimStyle,
+    prefix: &'static [&'static str],
+    suffix: &'static [&'static str],
+    middle: &'static [&'static str],
+    stop: &'static [&'static str],
+    file_sep: &'static [&'static str],
+    repo_name: &'static [&'static str],
+}
+
+const KNOWN_FIM: &[KnownFim] = &[
+    KnownFim {
+        style: FimStyle::QwenCoder,
+        prefix: &["<|fim_prefix|>"],
+        suffix: &["<|fim_suffix|>"],
+        middle: &["<|fim_middle|>"],
+        stop: &["<|fim_pad|>", "<|endoftext|>"],
+        file_sep: &["<|file_sep|>"],
+        repo_name: &["<|repo_name|>"],
+    },
+    KnownFim {
+        style: FimStyle::StarCoder,
+        prefix: &["<fim_prefix>", "<fim-prefix>"],
+        suffix: &["<fim_suffix>", "<fim-suffix>"],
+        middle: &["<fim_middle>", "<fim-middle>"],
+        stop: &["<fim_pad>", "<|endoftext|>"],
+        file_sep: &["<file_sep>", "<filename>"],
+        repo_name: &["<repo_name>", "<reponame>"],
+    },
+    KnownFim {
+        style: FimStyle::DeepSeekCoder,
+        prefix: &["<｜fim▁begin｜>"],
+        suffix: &["<｜fim▁hole｜>"],
+        middle: &["<｜fim▁end｜>"],
+        stop: &["<|EOT|>", "<｜end▁of▁sentence｜>"],
+        file_sep: &[],
+        repo_name: &[],
+    },
+    KnownFim {
+        style: FimStyle::CodeLlama,
+        prefix: &["▁<PRE>", "<PRE>"],
+        suffix: &["▁<SUF>", "<SUF>"],
+        middle: &["▁<MID>", "<MID>"],
+        stop: &["▁<EOT>", "<EOT>"],
+        file_sep: &[],
+        repo_name: &[],
+    },
+];
+
+/// Find the FIM tokens of a model from its tokenizer metadata
+pub fn detect_fim_tokens(metadata: &Map<String, Value>) -> Option<FimTokens> {
+    let vocab: Vec<&str> = metadata.get("tokenizer.ggml.tokens")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().map(|t| t.as_str().unwrap_or("")).collect())
+        .unwrap_or_default();
+    let index: HashMap<&str, i32> = vocab.iter().enumerate().map(|(i, t)| (*t, i as i32)).collect();
+    let find = |names: &[&str]| names.iter().find_map(|n| index.get(n).copied());
+    let id_key = |keys: &[&str]| keys.iter()
+        .find_map(|k| metadata.get(*k).and_then(|v| v.as_i64()))
+        .map(|id| id as i32);
+
+    // 1) Explicit ids (newer converters write fim_*, older ones prefix/suffix/middle)
+    let explicit = (
+        id_key(&["tokenizer.ggml.fim_pre_token_id", "tokenizer.ggml.prefix_token_id"]),
+        id_key(&["tokenizer.ggml.fim_suf_token_id", "tokenizer.ggml.suffix_token_id"]),
+        id_key(&["tokenizer.ggml.fim_mid_token_id", "tokenizer.ggml.middle_token_id"]),
+    );
+    if let (Some(prefix), Some(suffix), Some(middle)) = explicit {
+        let text = vocab.get(prefix as usize).copied().unwrap_or("");
+        let known = KNOWN_FIM.iter().find(|k| k.prefix.contains(&text));
+        let mut stop: Vec<i32> = ["tokenizer.ggml.fim_pad_token_id", "tokenizer.ggml.eot_token_id"].iter()
+            .filter_map(|k| id_key(&[k]))
+            .collect();
+        if let Some(k) = known {
+            stop.extend(k.stop.iter().filter_map(|s| index.get(s).copied()));
+        }
+        stop.sort_unstable();
+        stop.dedup();
+        return Some(FimTokens {
+            style: known.map(|k| k.style).unwrap_or(FimStyle::Generic),
+            prefix,
+            suffix,
+            middle,
+            stop,
+            file_sep: id_key(&["tokenizer.ggml.fim_sep_token_id"]).or_else(|| known.and_then(|k| find(k.file_sep))),
+            repo_name: id_key(&["tokenizer.ggml.fim_rep_token_id"]).or_else(|| known.and_then(|k| find(k.repo_name))),
+        });
+    }
+
+    // 2) Well-known vocab entries
+    KNOWN_FIM.iter().find_map(|k| {
+        Some(FimTokens {
+            style: k.style,
+            prefix: find(k.prefix)?,
+            suffix: find(k.suffix)?,
+            middle: find(k.middle)?,
+            stop: k.stop.iter().filter_map(|s| index.get(s).copied()).collect(),
+            file_sep: find(k.file_sep),
+            repo_name: find(k.repo_name),
+        })
+    })
+}
+
+/// Context snippet shown to the model before the current file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FimSnippet {
+    pub file_path: String,
+    pub content: String,
+}
+
+/// Build the token sequence:
+/// `[bos] [<repo_name>project\n (<file_sep>path\ncontent)* <file_sep>current\n] PRE prefix SUF suffix MID`.
+/// Snippets are only emitted when the model has a file separator token.
+pub fn assemble_fim_prompt(
+    fim: &FimTokens,
+    bos: Option<i32>,
+    repo_header: &[i32],
+    snippets: &[Vec<i32>],
+    file_header: &[i32],
+    prefix: &[i32],
+    suffix: &[i32],
+) -> Vec<i32> {
+    let mut out = Vec::with_capacity(prefix.len() + suffix.len() + 8);
+    out.extend(bos);
+    if let Some(sep) = fim.file_sep {
+        if !snippets.is_empty() {
+            if let Some(rep) = fim.repo_name {
+                out.push(rep);
+                out.extend_from_slice(repo_header);
+            }
+            for snippet in snippets {
+                out.push(sep);
+                out.extend_from_slice(snippet);
+            }
+            out.push(sep);
+            out.extend_from_slice(file_header);
+        }
+    }
+    out.push(fim.prefix);
+    out.extend_from_slice(prefix);
+    out.push(fim.suffix);
+    out.extend_from_slice(suffix);
+    out.push(fim.middle);
+    out
+}
+
+/// Drop the tail of a completion that merely re-types the start of the suffix
+pub fn trim_suffix_overlap(completion: &str, suffix: &str) -> String {
+    let suffix = suffix.trim_start();
+    if suffix.is_empty() {
+        return completion.to_string();
+    }
+    let trimmed = completion.trim_end();
+    for (idx, _) in trimmed.char_indices() {
+        let tail = &trimmed[idx..];
+        if tail.trim_start().len() >= 3 && suffix.starts_with(tail.trim_start()) {
+            return trimmed[..idx].trim_end().to_string();
+        }
+    }
+    completion.to_string()
+}
+
+/// Line comment syntax used to inline snippets for models without a file separator
+fn line_comment_for(file_path: &str) -> &'static str {
+    let ext = std::path::Path::new(file_path).extension().and_then(|e| e.to_str()).unwrap_or("");
+    match ext {
+        "py" | "rb" | "sh" | "bash" | "toml" | "yaml" | "yml" | "r" | "pl" => "#",
+        "sql" | "lua" | "hs" => "--",
+        _ => "//",
+    }
+}
+
+fn comment_snippets(snippets: &[FimSnippet], file_path: &str) -> String {
+    let marker = line_comment_for(file_path);
+    snippets.iter()
+        .map(|s| {
+            let body: String = s.content.lines().map(|l| format!("{} {}\n", marker, l)).collect();
+            format!("{} {}\n{}", marker, s.file_path, body)
+        })
+        .collect()
+}
+
+// --------------------
+// DEBOUNCE / CANCELLATION
+// --------------------
+
+/// Latest ticket per session; a request keeps running only while its ticket is current
+static FIM_SESSIONS: Lazy<Mutex<HashMap<String, u64>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static FIM_TICKETS: Lazy<Mutex<u64>> = Lazy::new(|| Mutex::new(0));
+
+fn begin_request(session: &str) -> u64 {
+    let ticket = {
+        let mut counter = FIM_TICKETS.lock().unwrap_or_else(|p| p.into_inner());
+        *counter += 1;
+        *counter
+    };
+    FIM_SESSIONS.lock().unwrap_or_else(|p| p.into_inner()).insert(session.to_string(), ticket);
+    ticket
+}
+
+fn is_current(session: &str, ticket: u64) -> bool {
+    FIM_SESSIONS.lock().unwrap_or_else(|p| p.into_inner()).get(session) == Some(&ticket)
+}
+
+fn cancel_session(session: &str) -> bool {
+    FIM_SESSIONS.lock().unwrap_or_else(|p| p.into_inner()).remove(session).is_some()
+}
+
+// --------------------
+// CACHE
+// --------------------
+
+static FIM_CACHE: Lazy<Mutex<LruCache<u64, Vec<FimCandidate>>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(NonZeroUsize::new(256).unwrap())));
+
+/// FIM token lookup reads the whole vocab, so it is done once per model file
+static FIM_TOKENS: Lazy<Mutex<HashMap<String, Option<FimTokens>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn fim_tokens_for(model_path: &str) -> Result<Option<FimTokens>, String> {
+    if let Some(cached) = FIM_TOKENS.lock().unwrap_or_else(|p| p.into_inner()).get(model_path) {
+        return Ok(cached.clone());
+    }
+    let options = GgufReadOptions { max_array_len: None, read_tensors: false };
+    let file = gguf_reader::read_gguf_file(model_path, &options)?;
+    let tokens = detect_fim_tokens(&file.metadata);
+    FIM_TOKENS.lock().unwrap_or_else(|p| p.into_inner()).insert(model_path.to_string(), tokens.clone());
+    Ok(tokens)
+}
+
+// --------------------
+// REQUEST / RESPONSE
+// --------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FimRequest {
+    /// Pooled model; None = first loaded model
+    pub model_path: Option<String>,
+    pub file_path: String,
+    pub prefix: String,
+    pub suffix: String,
+    #[serde(default = "default_candidates")]
+    pub n_candidates: usize,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    /// Stop after this many lines (None = until EOT / max_tokens)
+    pub max_lines: Option<usize>,
+    /// Temperature for candidates after the first (the first one is greedy)
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    #[serde(default = "default_true")]
+    pub use_rag: bool,
+    #[serde(default = "default_rag_top_k")]
+    pub rag_top_k: usize,
+    /// Wait this long and drop the request if a newer one arrived meanwhile
+    #[serde(default)]
+    pub debounce_ms: u64,
+    /// Requests with the same session supersede each other (default: file path)
+    pub session_id: Option<String>,
+    #[serde(default = "default_prefix_tokens")]
+    pub max_prefix_tokens: usize,
+    #[serde(default = "default_suffix_tokens")]
+    pub max_suffix_tokens: usize,
+}
+
+fn default_candidates() -> usize { 1 }
+fn default_max_tokens() -> u32 { 64 }
+fn default_temperature() -> f32 { 0.6 }
+fn default_true() -> bool { true }
+fn default_rag_top_k() -> usize { 3 }
+fn default_prefix_tokens() -> usize { 1024 }
+fn default_suffix_tokens() -> usize { 256 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FimCandidate {
+    pub text: String,
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FimResponse {
+    pub candidates: Vec<FimCandidate>,
+    pub cached: bool,
+    /// Superseded by a newer request in the same session
+    pub cancelled: bool,
+    pub style: Option<FimStyle>,
+    pub rag_snippets: usize,
+    pub prompt_tokens: usize,
+    pub elapsed_ms: f64,
+}
+
+impl FimResponse {
+    fn cancelled(started: Instant) -> Self {
+        Self {
+            candidates: Vec::new(),
+            cached: false,
+            cancelled: true,
+            style: None,
+            rag_snippets: 0,
+            prompt_tokens: 0,
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+pub fn cache_key(model_path: &str, req: &FimRequest) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    model_path.hash(&mut hasher);
+    req.file_path.hash(&mut hasher);
+    req.prefix.hash(&mut hasher);
+    req.suffix.hash(&mut hasher);
+    req.n_candidates.hash(&mut hasher);
+    req.max_tokens.hash(&mut hasher);
+    req.max_lines.hash(&mut hasher);
+    req.temperature.to_bits().hash(&mut hasher);
+    req.use_rag.hash(&mut hasher);
+    req.rag_top_k.hash(&mut hasher);
+    req.max_prefix_tokens.hash(&mut hasher);
+    req.max_suffix_tokens.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn tokenize(model: &LlamaModel, text: &str) -> Result<Vec<i32>, String> {
+    if text.is_empty() {
+        return Ok(Vec::new());
+    }
+    model.str_to_token(text, AddBos::Never)
+        .map(|t| t.into_iter().map(|t| t.0).collect())
+        .map_err(|e| format!("Tokenization failed: {:?}", e))
+}
+
+fn sample(candidates: &[LlamaTokenData], temperature: f32) -> LlamaToken {
+    let best = candidates.iter()
+        .max_by(|a, b| a.logit().partial_cmp(&b.logit()).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|c| c.id())
+        .unwrap_or(LlamaToken(0));
+    if temperature <= 0.0 {
+        return best;
+    }
+    let max_logit = candidates.iter().map(|c| c.logit()).fold(f32::NEG_INFINITY, f32::max);
+    let weights: Vec<f32> = candidates.iter().map(|c| ((c.logit() - max_logit) / temperature).exp()).collect();
+    let total: f32 = weights.iter().sum();
+    let mut target = rand::thread_rng().gen::<f32>() * total;
+    for (c, w) in candidates.iter().zip(&weights) {
+        target -= w;
+        if target <= 0.0 {
+            return c.id();
+        }
+    }
+    best
+}
+
+/// Generate `n` completions sharing one evaluated prompt
+#[allow(clippy::too_many_arguments)]
+fn generate_candidates(
+    loaded_model: &LoadedModel,
+    backend: &llama_cpp_2::llama_backend::LlamaBackend,
+    fim: &FimTokens,
+    prompt: &[i32],
+    req: &FimRequest,
+    session: &str,
+    ticket: u64,
+) -> Result<Option<Vec<FimCandidate>>, String> {
+    let model = &loaded_model.model;
+    let mut context = gguf::new_inference_context(loaded_model, backend)?;
+    // The prompt is shorter than n_ctx (checked by the caller); the rest is for the reply
+    let max_tokens = (req.max_tokens as usize).min(loaded_model.n_ctx as usize - prompt.len());
+
+    let mut batch = LlamaBatch::new(prompt.len().max(1), 1);
+    let tokens: Vec<LlamaToken> = prompt.iter().map(|t| LlamaToken(*t)).collect();
+    batch.add_sequence(&tokens, 0, false).map_err(|e| format!("Batch add failed: {:?}", e))?;
+    context.decode(&mut batch).map_err(|e| format!("Decode failed: {:?}", e))?;
+    let first_candidates: Vec<LlamaTokenData> = context.candidates().collect();
+
+    let n = req.n_candidates.clamp(1, MAX_CANDIDATES);
+    let mut results: Vec<FimCandidate> = Vec::with_capacity(n);
+    // Sampling can repeat an earlier candidate; allow a few extra attempts
+    for attempt in 0..n * 2 {
+        if results.len() == n {
+            break;
+        }
+        if attempt > 0 {
+            context.clear_kv_cache_seq(Some(0), Some(prompt.len() as u32), None)
+                .map_err(|e| format!("KV cache temizlenemedi: {:?}", e))?;
+        }
+        let temperature = if attempt == 0 { 0.0 } else { req.temperature };
+
+        let mut decoder = encoding_rs::UTF_8.new_decoder();
+        let mut text = String::new();
+        let mut generated = 0;
+        let mut n_cur = prompt.len() as i32;
+        let mut token = sample(&first_candidates, temperature);
+        loop {
+            if !is_current(session, ticket) {
+                return Ok(None);
+            }
+            if model.is_eog_token(token) || fim.stop.contains(&token.0) || token.0 == fim.file_sep.unwrap_or(-1) {
+                break;
+            }
+            text.push_str(&model.token_to_piece(token, &mut decoder, false, None).unwrap_or_default());
+            generated += 1;
+            if generated >= max_tokens {
+                break;
+            }
+            if let Some(max_lines) = req.max_lines {
+                if text.matches('\n').count() >= max_lines {
+                    break;
+                }
+            }
+            batch.clear();
+            batch.add(token, n_cur, &[0], true).map_err(|e| format!("Batch add failed: {:?}", e))?;
+            context.decode(&mut batch).map_err(|e| format!("Decode failed: {:?}", e))?;
+            n_cur += 1;
+            let candidates: Vec<LlamaTokenData> = context.candidates().collect();
+            token = sample(&candidates, temperature);
+        }
+
+        if let Some(max_lines) = req.max_lines {
+            text = text.split_inclusive('\n').take(max_lines).collect::<String>().trim_end_matches('\n').to_string();
+        }
+        let text = trim_suffix_overlap(&text, &req.suffix);
+        if !text.trim().is_empty() && !results.iter().any(|c| c.text == text) {
+            results.push(FimCandidate { text, tokens: generated });
+        }
+    }
+    Ok(Some(results))
+}
+
+async fn rag_snippets(app: &AppHandle, req: &FimRequest) -> Vec<FimSnippet> {
+    let Some(vector_db) = app.try_state::<crate::vector_db::VectorDB>() else {
+        return Vec::new();
+    };
+    // The last few lines before the cursor describe what is being written
+    let query: String = {
+        let lines: Vec<&str> = req.prefix.lines().collect();
+        lines[lines.len().saturating_sub(20)..].join("\n")
+    };
+    if query.trim().is_empty() {
+        return Vec::new();
+    }
+
+    let lookup = async {
+        let embedding = vector_db.generate_embedding(&query).await.map_err(|e| e.to_string())?;
+        vector_db.query(embedding, req.rag_top_k + 2, None).await.map_err(|e| e.to_string())
+    };
+    match tokio::time::timeout(RAG_TIMEOUT, lookup).await {
+        Ok(Ok(chunks)) => chunks.into_iter()
+            .filter(|c| c.file_path != req.file_path)
+            .take(req.rag_top_k)
+            .map(|c| FimSnippet {
+                file_path: c.file_path,
+                content: c.content.chars().take(RAG_SNIPPET_MAX_CHARS).collect(),
+            })
+            .collect(),
+        Ok(Err(e)) => {
+            warn!("⚠️ FIM RAG araması başarısız: {}", e);
+            Vec::new()
+        }
+        Err(_) => {
+            warn!("⚠️ FIM RAG araması zaman aşımına uğradı");
+            Vec::new()
+        }
+    }
+}
+
+/// Fill-in-the-middle completion at the cursor between `prefix` and `suffix`
+#[tauri::command]
+pub async fn complete_fim(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    request: FimRequest,
+) -> Result<FimResponse, String> {
+    let started = Instant::now();
+    let session = request.session_id.clone().unwrap_or_else(|| request.file_path.clone());
+    let ticket = begin_request(&session);
+
+    if request.debounce_ms > 0 {
+        tokio::time::sleep(Duration::from_millis(request.debounce_ms)).await;
+        if !is_current(&session, ticket) {
+            return Ok(FimResponse::cancelled(started));
+        }
+    }
+
+    usage::check_budget(Some("gguf"), "fim", None)?;
+    let (loaded_model, backend) = {
+        let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+        let model_path = match &request.model_path {
+            Some(path) => path.clone(),
+            None => guard.models.keys().next().cloned().ok_or("No models loaded")?,
+        };
+        guard.checkout(&model_path)?
+    };
+
+    let key = cache_key(&loaded_model.model_path, &request);
+    if let Some(candidates) = FIM_CACHE.lock().unwrap_or_else(|p| p.into_inner()).get(&key) {
+        return Ok(FimResponse {
+            candidates: candidates.clone(),
+            cached: true,
+            cancelled: false,
+            style: None,
+            rag_snippets: 0,
+            prompt_tokens: 0,
+            elapsed_ms: started.elapsed().as_secs_f64() * 1000.0,
+        });
+    }
+
+    // The first lookup reads the whole vocabulary from disk
+    let tokens_path = loaded_model.model_path.clone();
+    let fim = tokio::task::spawn_blocking(move || fim_tokens_for(&tokens_path))
+        .await
+        .map_err(|e| format!("FIM tokenları okunamadı: {}", e))??
+        .ok_or("Bu model FIM tokenlarına sahip değil (StarCoder, CodeLlama, DeepSeek-Coder veya Qwen-Coder gerekli)")?;
+
+    let snippets = if request.use_rag && request.rag_top_k > 0 {
+        rag_snippets(&app, &request).await
+    } else {
+        Vec::new()
+    };
+    if !is_current(&session, ticket) {
+        return Ok(FimResponse::cancelled(started));
+    }
+
+    let model = &loaded_model.model;
+    let inline_context = if fim.file_sep.is_none() && !snippets.is_empty() {
+        comment_snippets(&snippets, &request.file_path)
+    } else {
+        String::new()
+    };
+
+    // Keep the tokens closest to the cursor
+    let mut prefix = tokenize(model, &format!("{}{}", inline_context, request.prefix))?;
+    if prefix.len() > request.max_prefix_tokens {
+        prefix.drain(..prefix.len() - request.max_prefix_tokens);
+    }
+    let mut suffix = tokenize(model, &request.suffix)?;
+    suffix.truncate(request.max_suffix_tokens);
+
+    let snippet_tokens: Vec<Vec<i32>> = if fim.file_sep.is_some() {
+        snippets.iter()
+            .map(|s| tokenize(model, &format!("{}\n{}", s.file_path, s.content)))
+            .collect::<Result<_, _>>()?
+    } else {
+        Vec::new()
+    };
+    let repo_header = tokenize(model, "workspace\n")?;
+    let file_header = tokenize(model, &format!("{}\n", request.file_path))?;
+    let bos = model.str_to_token("", AddBos::Always).ok()
+        .and_then(|t| t.first().map(|t| t.0));
+
+    let prompt = assemble_fim_prompt(&fim, bos, &repo_header, &snippet_tokens, &file_header, &prefix, &suffix);
+    if prompt.len() >= loaded_model.n_ctx as usize {
+        return Err(format!("FIM prompt çok uzun: {} token (max {})", prompt.len(), loaded_model.n_ctx));
+    }
+
+    let prompt_tokens = prompt.len();
+    let loaded_model_path = loaded_model.model_path.clone();
+    let style = fim.style;
+    let rag_count = snippets.len();
+    let req = request.clone();
+    let session_for_task = session.clone();
+    let generated = tokio::task::spawn_blocking(move || {
+        generate_candidates(&loaded_model, &backend, &fim, &prompt, &req, &session_for_task, ticket)
+    })
+    .await
+    .map_err(|e| format!("FIM görevi başarısız: {}", e))??;
+
+    let Some(candidates) = generated else {
+        return Ok(FimResponse::cancelled(started));
+    };
+    FIM_CACHE.lock().unwrap_or_else(|p| p.into_inner()).put(key, candidates.clone());
+
+    let completion_tokens = candidates.iter().map(|c| c.tokens).sum();
+    usage::record(UsageEvent::local("fim", &loaded_model_path, prompt_tokens, completion_tokens));
+
+    let elapsed_ms = started.elapsed().as_secs_f64() * 1000.0;
+    info!("✍️ FIM ({:?}): {} candidate(s), {} prompt tokens, {:.0} ms", style, candidates.len(), prompt_tokens, elapsed_ms);
+    Ok(FimResponse {
+        candidates,
+        cached: false,
+        cancelled: false,
+        style: Some(style),
+        rag_snippets: rag_count,
+        prompt_tokens,
+        elapsed_ms,
+    })
+}
+
+/// Cancel the in-flight completion of a session (e.g. the editor moved the cursor)
+#[tauri::command]
+pub async fn cancel_fim_completion(session_id: String) -> Result<bool, String> {
+    Ok(cancel_session(&session_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn metadata(tokens: &[&str]) -> Map<String, Value> {
+        json!({ "tokenizer.ggml.tokens": tokens }).as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_detect_known_styles() {
+        let qwen = detect_fim_tokens(&metadata(&["a", "<|fim_prefix|>", "<|fim_middle|>", "<|fim_suffix|>", "<|fim_pad|>", "<|repo_name|>", "<|file_sep|>"])).unwrap();
+        assert_eq!(qwen.style, FimStyle::QwenCoder);
+        assert_eq!((qwen.prefix, qwen.suffix, qwen.middle), (1, 3, 2));
+        assert_eq!(qwen.stop, vec![4]);
+        assert_eq!((qwen.repo_name, qwen.file_sep), (Some(5), Some(6)));
+
+        let star = detect_fim_tokens(&metadata(&["<|endoftext|>", "<fim_prefix>", "<fim_middle>", "<fim_suffix>", "<fim_pad>"])).unwrap();
+        assert_eq!(star.style, FimStyle::StarCoder);
+        assert_eq!(star.stop, vec![4, 0]);
+
+        let deepseek = detect_fim_tokens(&metadata(&["x", "<｜fim▁hole｜>", "<｜fim▁begin｜>", "<｜fim▁end｜>"])).unwrap();
+        assert_eq!(deepseek.style, FimStyle::DeepSeekCoder);
+        assert_eq!((deepseek.prefix, deepseek.suffix, deepseek.middle), (2, 1, 3));
+
+        let codellama = detect_fim_tokens(&metadata(&["<unk>", "▁<PRE>", "▁<SUF>", "▁<MID>", "▁<EOT>"])).unwrap();
+        assert_eq!(codellama.style, FimStyle::CodeLlama);
+        assert_eq!(codellama.stop, vec![4]);
+
+        assert!(detect_fim_tokens(&metadata(&["<s>", "</s>", "hello"])).is_none());
+    }
+
+    #[test]
+    fn test_detect_explicit_ids() {
+        let mut meta = metadata(&["<s>", "<|fim_prefix|>", "<|fim_suffix|>", "<|fim_middle|>", "<|fim_pad|>"]);
+        meta.insert("tokenizer.ggml.fim_pre_token_id".into(), json!(1));
+        meta.insert("tokenizer.ggml.fim_suf_token_id".into(), json!(2));
+        meta.insert("tokenizer.ggml.fim_mid_token_id".into(), json!(3));
+        meta.insert("tokenizer.ggml.fim_pad_token_id".into(), json!(4));
+        let fim = detect_fim_tokens(&meta).unwrap();
+        assert_eq!(fim.style, FimStyle::QwenCoder);
+        assert_eq!((fim.prefix, fim.suffix, fim.middle), (1, 2, 3));
+        assert_eq!(fim.stop, vec![4]);
+    }
+
+    #[test]
+    fn test_assemble_fim_prompt() {
+        let fim = FimTokens {
+            style: FimStyle::QwenCoder,
+            prefix: 100,
+            suffix: 101,
+            middle: 102,
+            stop: vec![],
+            file_sep: Some(103),
+            repo_name: Some(104),
+        };
+        assert_eq!(
+            assemble_fim_prompt(&fim, Some(1), &[9], &[], &[8], &[10, 11], &[20]),
+            vec![1, 100, 10, 11, 101, 20, 102]
+        );
+        assert_eq!(
+            assemble_fim_prompt(&fim, None, &[9], &[vec![30, 31]], &[8], &[10], &[20]),
+            vec![104, 9, 103, 30, 31, 103, 8, 100, 10, 101, 20, 102]
+        );
+
+        let no_sep = FimTokens { file_sep: None, ..fim };
+        assert_eq!(
+            assemble_fim_prompt(&no_sep, None, &[9], &[vec![30]], &[8], &[10], &[20]),
+            vec![100, 10, 101, 20, 102]
+        );
+    }
+
+    #[test]
+    fn test_trim_suffix_overlap() {
+        assert_eq!(trim_suffix_overlap("a + b)\n}", ")\n}\n"), "a + b");
+        assert_eq!(trim_suffix_overlap("let x = 1;", "\nfn main() {}"), "let x = 1;");
+        assert_eq!(trim_suffix_overlap("value", ""), "value");
+    }
+
+    #[test]
+    fn test_sessions_supersede_and_cancel() {
+        let first = begin_request("test-session");
+        assert!(is_current("test-session", first));
+        let second = begin_request("test-session");
+        assert!(!is_current("test-session", first));
+        assert!(is_current("test-session", second));
+        assert!(cancel_session("test-session"));
+        assert!(!is_current("test-session", second));
+    }
+
+    #[test]
+    fn test_cache_key_covers_prefix_and_suffix() {
+        let req = FimRequest {
+            model_path: None,
+            file_path: "src/main.rs".into(),
+            prefix: "fn main() {".into(),
+            suffix: "}".into(),
+            n_candidates: 1,
+            max_tokens: 64,
+            max_lines: None,
+            temperature: 0.6,
+            use_rag: true,
+            rag_top_k: 3,
+            debounce_ms: 0,
+            session_id: None,
+            max_prefix_tokens: 1024,
+            max_suffix_tokens: 256,
+        };
+        let same = req.clone();
+        let other_suffix = FimRequest { suffix: "}\n".into(), ..req.clone() };
+        assert_eq!(cache_key("m", &req), cache_key("m", &same));
+        assert_ne!(cache_key("m", &req), cache_key("m", &other_suffix));
+        assert_ne!(cache_key("m", &req), cache_key("other", &req));
+        // Context windows and RAG depth change the prompt, so they change the key
+        assert_ne!(cache_key("m", &req), cache_key("m", &FimRequest { max_prefix_tokens: 512, ..req.clone() }));
+        assert_ne!(cache_key("m", &req), cache_key("m", &FimRequest { max_suffix_tokens: 128, ..req.clone() }));
+        assert_ne!(cache_key("m", &req), cache_key("m", &FimRequest { rag_top_k: 5, ..req.clone() }));
+    }
+}
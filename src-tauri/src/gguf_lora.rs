@@ -0,0 +1,386 @@
+// src-tauri/src/gguf_lora.rs
+// LoRA adapters (GGUF) on pooled models: attach, detach, rescale and list.
+//
+// Adapters are scoped per pooled model: they live on the `LoadedModel` and are
+// applied, with their scale, to every inference context created for that model,
+// so a change takes effect from the next request without reloading the base
+// weights. Contexts already running keep the adapter set they were created with.
+
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::model::LlamaLoraAdapter;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::gguf::{self, GgufState, LoadedModel};
+use crate::gguf_reader::{self, GgufReadOptions, GgufTensorInfo};
+
+/// Public view of an attached adapter
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoraAdapterInfo {
+    pub path: String,
+    pub name: String,
+    pub scale: f32,
+    pub architecture: String,
+    /// `adapter.lora.alpha` from the adapter metadata, if present
+    pub alpha: Option<f64>,
+    /// LoRA rank (inner dimension of the A/B matrices)
+    pub rank: Option<u64>,
+    /// Number of base tensors the adapter modifies
+    pub target_tensors: usize,
+}
+
+/// An adapter attached to a pooled model
+pub struct ActiveLora {
+    pub info: LoraAdapterInfo,
+    adapter: Arc<Mutex<AdapterHandle>>,
+}
+
+struct AdapterHandle(LlamaLoraAdapter);
+
+// SAFETY: `LlamaLoraAdapter` is a pointer to a llama.cpp adapter with no thread
+// affinity. It is freed only when the last `Arc` is dropped: the model's adapter list
+// holds one and every context it was applied to holds another (see `AdapterContext`),
+// so detaching an adapter never frees it under a context that still reads it. The
+// `&mut` access `lora_adapter_set` needs happens under the handle's mutex.
+unsafe impl Send for AdapterHandle {}
+
+/// An inference context plus the adapters applied to it.
+///
+/// Fields drop in declaration order, so the context is freed before its adapter
+/// references are released.
+pub struct AdapterContext<'a> {
+    context: LlamaContext<'a>,
+    _adapters: Vec<Arc<Mutex<AdapterHandle>>>,
+}
+
+impl<'a> Deref for AdapterContext<'a> {
+    type Target = LlamaContext<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.context
+    }
+}
+
+impl DerefMut for AdapterContext<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.context
+    }
+}
+
+/// Apply every attached adapter to a freshly created context
+pub(crate) fn apply_adapters<'a>(loaded_model: &LoadedModel, context: LlamaContext<'a>) -> Result<AdapterContext<'a>, String> {
+    let loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+    let mut adapters = Vec::new();
+    for lora in loras.iter().filter(|l| l.info.scale != 0.0) {
+        let mut handle = lora.adapter.lock().unwrap_or_else(|p| p.into_inner());
+        context.lora_adapter_set(&mut handle.0, lora.info.scale)
+            .map_err(|e| format!("LoRA uygulanamadı ({}): {:?}", lora.info.name, e))?;
+        drop(handle);
+        adapters.push(Arc::clone(&lora.adapter));
+    }
+    Ok(AdapterContext { context, _adapters: adapters })
+}
+
+/// Split a LoRA tensor name (`blk.0.attn_q.weight.lora_a`) into base name and side
+fn lora_target(name: &str) -> Option<(&str, char)> {
+    name.strip_suffix(".lora_a").map(|base| (base, 'a'))
+        .or_else(|| name.strip_suffix(".lora_b").map(|base| (base, 'b')))
+}
+
+/// Check that the adapter's tensors fit the base model.
+///
+/// For a base weight of shape `[n_in, n_out]` llama.cpp expects `lora_a` as
+/// `[n_in, r]` and `lora_b` as `[r, n_out]`. Returns (matched targets, rank).
+pub fn check_lora_tensors(adapter: &[GgufTensorInfo], base: &[GgufTensorInfo]) -> Result<(usize, Option<u64>), String> {
+    let base_shapes: HashMap<&str, &[u64]> = base.iter().map(|t| (t.name.as_str(), t.shape.as_slice())).collect();
+    let mut targets: HashMap<&str, (Option<&[u64]>, Option<&[u64]>)> = HashMap::new();
+    for tensor in adapter {
+        if let Some((base_name, side)) = lora_target(&tensor.name) {
+            let entry = targets.entry(base_name).or_default();
+            match side {
+                'a' => entry.0 = Some(tensor.shape.as_slice()),
+                _ => entry.1 = Some(tensor.shape.as_slice()),
+            }
+        }
+    }
+    if targets.is_empty() {
+        return Err("Adapter LoRA tensoru içermiyor (*.lora_a / *.lora_b)".to_string());
+    }
+
+    let mut rank = None;
+    for (name, (a, b)) in &targets {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (*a, *b),
+            _ => return Err(format!("{} için lora_a/lora_b çifti eksik", name)),
+        };
+        let base_shape = base_shapes.get(name)
+            .ok_or_else(|| format!("Base modelde {} tensoru yok", name))?;
+        if a.len() < 2 || b.len() < 2 || base_shape.len() < 2 {
+            return Err(format!("{} için beklenmeyen tensor boyutu", name));
+        }
+        if a[0] != base_shape[0] || b[1] != base_shape[1] || a[1] != b[0] {
+            return Err(format!(
+                "{} boyutları uyuşmuyor: base {:?}, lora_a {:?}, lora_b {:?}",
+                name, base_shape, a, b
+            ));
+        }
+        rank = Some(rank.map_or(a[1], |r: u64| r.max(a[1])));
+    }
+    Ok((targets.len(), rank))
+}
+
+/// Check adapter metadata against the base model's metadata.
+/// Returns the adapter architecture and `adapter.lora.alpha`.
+pub fn check_lora_metadata(adapter: &Map<String, Value>, base: &Map<String, Value>) -> Result<(String, Option<f64>), String> {
+    let general_type = adapter.get("general.type").and_then(|v| v.as_str());
+    if let Some(t) = general_type {
+        if t != "adapter" {
+            return Err(format!("Dosya bir adapter değil (general.type: {})", t));
+        }
+    }
+    if let Some(t) = adapter.get("adapter.type").and_then(|v| v.as_str()) {
+        if t != "lora" {
+            return Err(format!("Desteklenmeyen adapter türü: {}", t));
+        }
+    }
+
+    let adapter_arch = adapter.get("general.architecture").and_then(|v| v.as_str()).unwrap_or("");
+    let base_arch = base.get("general.architecture").and_then(|v| v.as_str()).unwrap_or("");
+    if adapter_arch.is_empty() {
+        return Err("Adapter metadata'sında general.architecture yok".to_string());
+    }
+    if adapter_arch != base_arch {
+        return Err(format!(
+            "Adapter mimarisi ({}) base model mimarisiyle ({}) uyuşmuyor",
+            adapter_arch, base_arch
+        ));
+    }
+
+    Ok((adapter_arch.to_string(), adapter.get("adapter.lora.alpha").and_then(|v| v.as_f64())))
+}
+
+/// Read and validate an adapter against a pooled model's GGUF file(s)
+fn inspect_adapter(model_path: &str, adapter_path: &str) -> Result<LoraAdapterInfo, String> {
+    let options = GgufReadOptions { max_array_len: Some(0), read_tensors: true };
+    let adapter = gguf_reader::read_gguf_file(adapter_path, &options)?;
+    let base = gguf_reader::read_gguf_file(model_path, &options)?;
+
+    let (architecture, alpha) = check_lora_metadata(&adapter.metadata, &base.metadata)?;
+
+    // Split modellerde tensorler tüm parçalara dağılmıştır
+    let mut base_tensors = base.tensors;
+    for part in gguf::split_part_paths(model_path).iter().filter(|p| p.as_str() != model_path) {
+        base_tensors.extend(gguf_reader::read_gguf_file(part, &options)?.tensors);
+    }
+    let (target_tensors, rank) = check_lora_tensors(&adapter.tensors, &base_tensors)?;
+
+    let name = Path::new(adapter_path)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| adapter_path.to_string());
+
+    Ok(LoraAdapterInfo {
+        path: adapter_path.to_string(),
+        name,
+        scale: 1.0,
+        architecture,
+        alpha,
+        rank,
+        target_tensors,
+    })
+}
+
+fn pooled_model(state: &State<'_, Arc<Mutex<GgufState>>>, model_path: &str) -> Result<Arc<LoadedModel>, String> {
+    let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+    guard.checkout(model_path).map(|(model, _)| model)
+}
+
+fn validate_scale(scale: f32) -> Result<f32, String> {
+    if !scale.is_finite() || !(-4.0..=4.0).contains(&scale) {
+        return Err(format!("Geçersiz LoRA ölçeği: {} (-4.0 ile 4.0 arası olmalı)", scale));
+    }
+    Ok(scale)
+}
+
+/// Attach a LoRA adapter to a pooled model, for every context created from it afterwards
+/// (re-attaching the same file updates its scale)
+#[tauri::command]
+pub async fn attach_lora_adapter(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    adapter_path: String,
+    scale: Option<f32>,
+) -> Result<LoraAdapterInfo, String> {
+    let scale = validate_scale(scale.unwrap_or(1.0))?;
+    let loaded_model = pooled_model(&state, &model_path)?;
+
+    {
+        let mut loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(existing) = loras.iter_mut().find(|l| l.info.path == adapter_path) {
+            existing.info.scale = scale;
+            info!("🔁 LoRA zaten ekli, ölçek güncellendi: {} ({})", existing.info.name, scale);
+            return Ok(existing.info.clone());
+        }
+    }
+
+    // Reading the GGUF header and loading the tensors are file I/O: keep them off the async runtime
+    let task_model = loaded_model.clone();
+    let task_path = adapter_path.clone();
+    let (mut info, adapter) = tokio::task::spawn_blocking(move || {
+        let info = inspect_adapter(&task_model.model_path, &task_path)?;
+        info!("🧩 Loading LoRA adapter: {} (rank {:?}, {} tensors)", task_path, info.rank, info.target_tensors);
+        let adapter = task_model.model.lora_adapter_init(&task_path)
+            .map_err(|e| format!("LoRA yüklenemedi: {:?}", e))?;
+        Ok::<_, String>((info, AdapterHandle(adapter)))
+    })
+    .await
+    .map_err(|e| format!("LoRA yüklenemedi: {}", e))??;
+    info.scale = scale;
+
+    {
+        // A concurrent attach of the same file may have finished meanwhile
+        let mut loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(existing) = loras.iter_mut().find(|l| l.info.path == adapter_path) {
+            existing.info.scale = scale;
+            info!("🔁 LoRA zaten ekli, ölçek güncellendi: {} ({})", existing.info.name, scale);
+            return Ok(existing.info.clone());
+        }
+        loras.push(ActiveLora {
+            info: info.clone(),
+            adapter: Arc::new(Mutex::new(adapter)),
+        });
+    }
+
+    info!("✅ LoRA attached to {}: {}", model_path, info.name);
+    Ok(info)
+}
+
+/// Detach an adapter from a pooled model; returns false if it was not attached.
+/// Contexts that are still running keep it until they finish.
+#[tauri::command]
+pub async fn detach_lora_adapter(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    adapter_path: String,
+) -> Result<bool, String> {
+    let loaded_model = pooled_model(&state, &model_path)?;
+    let mut loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+    let before = loras.len();
+    loras.retain(|l| l.info.path != adapter_path);
+    let removed = loras.len() != before;
+    if removed {
+        info!("🗑️ LoRA detached from {}: {}", model_path, adapter_path);
+    } else {
+        warn!("⚠️ LoRA ekli değil: {}", adapter_path);
+    }
+    Ok(removed)
+}
+
+/// Change an attached adapter's scale for the model's next contexts (0.0 disables it without unloading)
+#[tauri::command]
+pub async fn set_lora_adapter_scale(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+    adapter_path: String,
+    scale: f32,
+) -> Result<LoraAdapterInfo, String> {
+    let scale = validate_scale(scale)?;
+    let loaded_model = pooled_model(&state, &model_path)?;
+    let mut loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+    let lora = loras.iter_mut()
+        .find(|l| l.info.path == adapter_path)
+        .ok_or_else(|| format!("LoRA ekli değil: {}", adapter_path))?;
+    lora.info.scale = scale;
+    Ok(lora.info.clone())
+}
+
+/// List adapters attached to a pooled model
+#[tauri::command]
+pub async fn list_lora_adapters(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: String,
+) -> Result<Vec<LoraAdapterInfo>, String> {
+    let loaded_model = pooled_model(&state, &model_path)?;
+    let loras = loaded_model.loras.lock().unwrap_or_else(|p| p.into_inner());
+    Ok(loras.iter().map(|l| l.info.clone()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tensor(name: &str, shape: &[u64]) -> GgufTensorInfo {
+        GgufTensorInfo {
+            name: name.to_string(),
+            shape: shape.to_vec(),
+            ggml_type: 1,
+            type_name: "F16".to_string(),
+            offset: 0,
+            n_elements: shape.iter().product(),
+            n_bytes: shape.iter().product::<u64>() * 2,
+        }
+    }
+
+    fn map(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_lora_tensors_match_base() {
+        let base = vec![
+            tensor("blk.0.attn_q.weight", &[2048, 2048]),
+            tensor("blk.0.ffn_up.weight", &[2048, 5632]),
+        ];
+        let adapter = vec![
+            tensor("blk.0.attn_q.weight.lora_a", &[2048, 16]),
+            tensor("blk.0.attn_q.weight.lora_b", &[16, 2048]),
+            tensor("blk.0.ffn_up.weight.lora_a", &[2048, 16]),
+            tensor("blk.0.ffn_up.weight.lora_b", &[16, 5632]),
+        ];
+        assert_eq!(check_lora_tensors(&adapter, &base).unwrap(), (2, Some(16)));
+    }
+
+    #[test]
+    fn test_lora_tensors_reject_mismatch() {
+        let base = vec![tensor("blk.0.attn_q.weight", &[4096, 4096])];
+        let wrong_dim = vec![
+            tensor("blk.0.attn_q.weight.lora_a", &[2048, 8]),
+            tensor("blk.0.attn_q.weight.lora_b", &[8, 2048]),
+        ];
+        assert!(check_lora_tensors(&wrong_dim, &base).is_err());
+
+        let missing_b = vec![tensor("blk.0.attn_q.weight.lora_a", &[4096, 8])];
+        assert!(check_lora_tensors(&missing_b, &base).is_err());
+
+        let unknown_target = vec![
+            tensor("blk.40.attn_q.weight.lora_a", &[4096, 8]),
+            tensor("blk.40.attn_q.weight.lora_b", &[8, 4096]),
+        ];
+        assert!(check_lora_tensors(&unknown_target, &base).is_err());
+    }
+
+    #[test]
+    fn test_lora_metadata_architecture() {
+        let base = map(json!({ "general.architecture": "llama" }));
+        let adapter = map(json!({
+            "general.type": "adapter",
+            "adapter.type": "lora",
+            "general.architecture": "llama",
+            "adapter.lora.alpha": 32.0
+        }));
+        assert_eq!(check_lora_metadata(&adapter, &base).unwrap(), ("llama".to_string(), Some(32.0)));
+
+        let other_arch = map(json!({ "general.type": "adapter", "general.architecture": "qwen2" }));
+        assert!(check_lora_metadata(&other_arch, &base).is_err());
+
+        let not_adapter = map(json!({ "general.type": "model", "general.architecture": "llama" }));
+        assert!(check_lora_metadata(&not_adapter, &base).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// src-tauri/src/gguf_pool.rs
+// Memory accounting for the GGUF model pool: footprint estimation from GGUF metadata,
+// RAM/VRAM budgets, LRU eviction planning and load planning (n_gpu_layers / n_ctx).
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+/// Micro-batch size llama.cpp uses for compute buffers by default
+const N_UBATCH: u64 = 512;
+
+/// KV cache element type (llama.cpp default is F16)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KvCacheType {
+    F32,
+    F16,
+    Q8_0,
+    Q4_0,
+}
+
+impl Default for KvCacheType {
+    fn default() -> Self {
+        KvCacheType::F16
+    }
+}
+
+impl KvCacheType {
+    /// Bytes per element (quantized types include their block scale)
+    pub fn bytes_per_element(self) -> f64 {
+        match self {
+            KvCacheType::F32 => 4.0,
+            KvCacheType::F16 => 2.0,
+            KvCacheType::Q8_0 => 34.0 / 32.0,
+            KvCacheType::Q4_0 => 18.0 / 32.0,
+        }
+    }
+}
+
+/// Architecture numbers needed for memory estimation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelArch {
+    pub architecture: String,
+    pub n_layers: u64,
+    pub n_embd: u64,
+    pub n_head: u64,
+    pub n_head_kv: u64,
+    pub key_length: u64,
+    pub value_length: u64,
+    pub n_vocab: u64,
+    pub context_length: u64,
+    /// Total bytes of all tensors (model weights)
+    pub weights_bytes: u64,
+}
+
+impl ModelArch {
+    /// Build from the key/value map returned by `gguf::read_gguf_metadata_map`.
+    /// Missing keys fall back to typical 7B values so estimation never fails outright.
+    pub fn from_metadata(meta: &Map<String, Value>) -> Self {
+        let architecture = meta.get("general.architecture")
+            .and_then(|v| v.as_str())
+            .unwrap_or("llama")
+            .to_string();
+        let get = |suffix: &str| -> Option<u64> {
+            meta.get(&format!("{}.{}", architecture, suffix)).and_then(|v| v.as_u64())
+        };
+
+        let n_layers = get("block_count").unwrap_or(32);
+        let n_embd = get("embedding_length").unwrap_or(4096);
+        let n_head = get("attention.head_count").unwrap_or(32).max(1);
+        let n_head_kv = get("attention.head_count_kv").unwrap_or(n_head).max(1);
+        let head_dim = n_embd / n_head;
+        let key_length = get("attention.key_length").unwrap_or(head_dim);
+        let value_length = get("attention.value_length").unwrap_or(head_dim);
+        let n_vocab = get("vocab_size").unwrap_or(32_000);
+        let context_length = get("context_length").unwrap_or(4096);
+        let weights_bytes = meta.get("file_size_bytes").and_then(|v| v.as_u64()).unwrap_or(0);
+
+        Self {
+            architecture,
+            n_layers,
+            n_embd,
+            n_head,
+            n_head_kv,
+            key_length,
+            value_length,
+            n_vocab,
+            context_length,
+            weights_bytes,
+        }
+    }
+}
+
+/// Estimated memory use of one loaded model, split between host RAM and GPU VRAM
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelFootprint {
+    pub weights_bytes: u64,
+    pub kv_cache_bytes: u64,
+    pub compute_bytes: u64,
+    pub ram_bytes: u64,
+    pub vram_bytes: u64,
+}
+
+impl ModelFootprint {
+    pub fn total_bytes(&self) -> u64 {
+        self.ram_bytes + self.vram_bytes
+    }
+}
+
+/// Estimate weights + KV cache + compute buffers for a given context and offload
+pub fn estimate_footprint(arch: &ModelArch, n_ctx: u32, n_gpu_layers: u32, kv_type: KvCacheType) -> ModelFootprint {
+    let n_ctx = n_ctx as u64;
+    let kv_per_layer = (n_ctx * arch.n_head_kv * (arch.key_length + arch.value_length)) as f64
+        * kv_type.bytes_per_element();
+    let kv_cache_bytes = (kv_per_layer * arch.n_layers as f64) as u64;
+    let compute_bytes = N_UBATCH * 4 * (arch.n_embd * 8 + arch.n_vocab);
+
+    let gpu_fraction = if arch.n_layers == 0 {
+        0.0
+    } else {
+        (n_gpu_layers as u64).min(arch.n_layers) as f64 / arch.n_layers as f64
+    };
+
+    let weights_gpu = (arch.weights_bytes as f64 * gpu_fraction) as u64;
+    let kv_gpu = (kv_cache_bytes as f64 * gpu_fraction) as u64;
+    let compute_gpu = if gpu_fraction > 0.0 { compute_bytes } else { 0 };
+
+    ModelFootprint {
+        weights_bytes: arch.weights_bytes,
+        kv_cache_bytes,
+        compute_bytes,
+        ram_bytes: (arch.weights_bytes - weights_gpu) + (kv_cache_bytes - kv_gpu) + (compute_bytes - compute_gpu),
+        vram_bytes: weights_gpu + kv_gpu + compute_gpu,
+    }
+}
+
+/// What to do when a new model would exceed the budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolPolicy {
+    /// Refuse the load and leave the pool untouched
+    Refuse,
+    /// Unload least-recently-used models until the new one fits
+    EvictLru,
+}
+
+/// Memory limits for the model pool
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolBudget {
+    pub max_ram_bytes: u64,
+    pub max_vram_bytes: u64,
+    pub policy: PoolPolicy,
+}
+
+impl Default for PoolBudget {
+    fn default() -> Self {
+        use sysinfo::System;
+        let mut sys = System::new();
+        sys.refresh_memory();
+        Self {
+            // Leave room for the OS, the webview and the editor itself
+            max_ram_bytes: (sys.total_memory() as f64 * 0.8) as u64,
+            max_vram_bytes: if cfg!(feature = "cuda") || cfg!(feature = "vulkan") {
+                (crate::gguf::detect_gpu_vram() * GIB * 0.9) as u64
+            } else {
+                0
+            },
+            policy: PoolPolicy::EvictLru,
+        }
+    }
+}
+
+/// A pool entry as seen by the eviction planner
+#[derive(Debug, Clone)]
+pub struct PoolEntry {
+    pub key: String,
+    /// Monotonic last-use stamp; lower means older
+    pub last_used: u64,
+    pub footprint: ModelFootprint,
+}
+
+/// Decide which models must go so `incoming` fits the budget.
+/// Returns the keys to evict (oldest first), or an error when it cannot fit.
+pub fn plan_admission(entries: &[PoolEntry], incoming: &ModelFootprint, budget: &PoolBudget) -> Result<Vec<String>, String> {
+    if incoming.ram_bytes > budget.max_ram_bytes || incoming.vram_bytes > budget.max_vram_bytes {
+        return Err(format!(
+            "Model bütçeye sığmıyor: RAM {:.1}/{:.1} GB, VRAM {:.1}/{:.1} GB",
+            incoming.ram_bytes as f64 / GIB, budget.max_ram_bytes as f64 / GIB,
+            incoming.vram_bytes as f64 / GIB, budget.max_vram_bytes as f64 / GIB,
+        ));
+    }
+
+    let mut used_ram: u64 = entries.iter().map(|e| e.footprint.ram_bytes).sum();
+    let mut used_vram: u64 = entries.iter().map(|e| e.footprint.vram_bytes).sum();
+    let fits = |ram: u64, vram: u64| {
+        ram + incoming.ram_bytes <= budget.max_ram_bytes && vram + incoming.vram_bytes <= budget.max_vram_bytes
+    };
+
+    if fits(used_ram, used_vram) {
+        return Ok(Vec::new());
+    }
+    if budget.policy == PoolPolicy::Refuse {
+        return Err(format!(
+            "Bellek bütçesi aşılacak (RAM {:.1} GB, VRAM {:.1} GB kullanımda). Önce bir model kaldırın.",
+            used_ram as f64 / GIB, used_vram as f64 / GIB,
+        ));
+    }
+
+    let mut by_age: Vec<&PoolEntry> = entries.iter().collect();
+    by_age.sort_by_key(|e| e.last_used);
+
+    let mut evict = Vec::new();
+    for entry in by_age {
+        if fits(used_ram, used_vram) {
+            break;
+        }
+        used_ram -= entry.footprint.ram_bytes;
+        used_vram -= entry.footprint.vram_bytes;
+        evict.push(entry.key.clone());
+    }
+
+    if fits(used_ram, used_vram) {
+        Ok(evict)
+    } else {
+        Err("Tüm modeller kaldırılsa bile yeni model bütçeye sığmıyor".to_string())
+    }
+}
+
+/// Detected memory available for a load plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareBudget {
+    pub available_ram_bytes: u64,
+    pub available_vram_bytes: u64,
+    pub gpu_backend: bool,
+}
+
+/// Recommended load parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadPlan {
+    pub n_gpu_layers: u32,
+    pub n_ctx: u32,
+    pub kv_cache_type: KvCacheType,
+    pub footprint: ModelFootprint,
+    pub fits: bool,
+    pub notes: Vec<String>,
+}
+
+/// Pick the largest n_ctx (up to `desired_ctx` / the trained context) and then the most
+/// GPU layers that fit the hardware, preferring context over offload below 2048 tokens.
+pub fn plan_load(arch: &ModelArch, hw: &HardwareBudget, desired_ctx: Option<u32>, kv_type: KvCacheType) -> LoadPlan {
+    let mut notes = Vec::new();
+    let max_ctx = arch.context_length.min(u32::MAX as u64) as u32;
+    let mut n_ctx = desired_ctx.unwrap_or(max_ctx.min(8192)).min(max_ctx).max(512);
+    let layers = arch.n_layers.min(u32::MAX as u64) as u32;
+
+    let fits_ram = |ctx: u32, gpu_layers: u32| {
+        estimate_footprint(arch, ctx, gpu_layers, kv_type).ram_bytes <= hw.available_ram_bytes
+    };
+    let fits_vram = |ctx: u32, gpu_layers: u32| {
+        estimate_footprint(arch, ctx, gpu_layers, kv_type).vram_bytes <= hw.available_vram_bytes
+    };
+
+    // Shrink context until the CPU-only configuration (worst case for RAM) fits, down to 2048
+    while n_ctx > 2048 && !fits_ram(n_ctx, 0) && !(hw.gpu_backend && fits_ram(n_ctx, layers)) {
+        n_ctx /= 2;
+    }
+
+    let n_gpu_layers = if hw.gpu_backend {
+        // Highest layer count whose VRAM share fits
+        let best = (0..=layers).rev().find(|l| fits_vram(n_ctx, *l)).unwrap_or(0);
+        if best == 0 {
+            notes.push("GPU belleği hiçbir katman için yeterli değil, CPU kullanılacak".to_string());
+        } else {
+            notes.push(format!("{} / {} katman GPU'ya sığıyor", best, layers));
+        }
+        best
+    } else {
+        notes.push("GPU backend derlenmemiş, CPU-only plan".to_string());
+        0
+    };
+
+    // With partial offload the remaining RAM share may still not fit; shrink context further
+    while n_ctx > 512 && !fits_ram(n_ctx, n_gpu_layers) {
+        n_ctx /= 2;
+    }
+
+    if let Some(desired) = desired_ctx {
+        if n_ctx < desired {
+            notes.push(format!("İstenen context {} yerine {} önerildi", desired, n_ctx));
+        }
+    }
+
+    let footprint = estimate_footprint(arch, n_ctx, n_gpu_layers, kv_type);
+    let fits = footprint.ram_bytes <= hw.available_ram_bytes && footprint.vram_bytes <= hw.available_vram_bytes;
+    if !fits {
+        notes.push("Model bu donanımda en küçük ayarlarla bile sığmıyor".to_string());
+    }
+
+    LoadPlan { n_gpu_layers, n_ctx, kv_cache_type: kv_type, footprint, fits, notes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arch_7b() -> ModelArch {
+        ModelArch {
+            architecture: "llama".into(),
+            n_layers: 32,
+            n_embd: 4096,
+            n_head: 32,
+            n_head_kv: 8,
+            key_length: 128,
+            value_length: 128,
+            n_vocab: 32_000,
+            context_length: 32_768,
+            weights_bytes: 4 * 1024 * 1024 * 1024,
+        }
+    }
+
+    fn entry(key: &str, last_used: u64, ram_gb: u64) -> PoolEntry {
+        PoolEntry {
+            key: key.into(),
+            last_used,
+            footprint: ModelFootprint { ram_bytes: ram_gb << 30, ..Default::default() },
+        }
+    }
+
+    #[test]
+    fn test_kv_cache_estimate() {
+        // 32 layers * 4096 ctx * 8 heads * 256 dims * 2 bytes = 512 MiB
+        let f = estimate_footprint(&arch_7b(), 4096, 0, KvCacheType::F16);
+        assert_eq!(f.kv_cache_bytes, 512 * 1024 * 1024);
+        assert_eq!(f.vram_bytes, 0);
+
+        let half = estimate_footprint(&arch_7b(), 4096, 16, KvCacheType::F16);
+        assert_eq!(half.vram_bytes - half.compute_bytes, (f.weights_bytes + f.kv_cache_bytes) / 2);
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let meta: Map<String, Value> = serde_json::from_value(serde_json::json!({
+            "general.architecture": "qwen2",
+            "qwen2.block_count": 28,
+            "qwen2.embedding_length": 3584,
+            "qwen2.attention.head_count": 28,
+            "qwen2.attention.head_count_kv": 4,
+            "qwen2.context_length": 32768,
+            "file_size_bytes": 1000,
+        })).unwrap();
+        let arch = ModelArch::from_metadata(&meta);
+        assert_eq!(arch.n_layers, 28);
+        assert_eq!(arch.key_length, 128);
+        assert_eq!(arch.n_head_kv, 4);
+        assert_eq!(arch.weights_bytes, 1000);
+    }
+
+    #[test]
+    fn test_plan_admission_evicts_lru() {
+        let budget = PoolBudget { max_ram_bytes: 10 << 30, max_vram_bytes: 0, policy: PoolPolicy::EvictLru };
+        let entries = vec![entry("new", 5, 4), entry("old", 1, 4)];
+        let incoming = ModelFootprint { ram_bytes: 4 << 30, ..Default::default() };
+        assert_eq!(plan_admission(&entries, &incoming, &budget).unwrap(), vec!["old".to_string()]);
+
+        let small = ModelFootprint { ram_bytes: 1 << 30, ..Default::default() };
+        assert!(plan_admission(&entries, &small, &budget).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_admission_refuse() {
+        let budget = PoolBudget { max_ram_bytes: 10 << 30, max_vram_bytes: 0, policy: PoolPolicy::Refuse };
+        let entries = vec![entry("a", 1, 8)];
+        let incoming = ModelFootprint { ram_bytes: 4 << 30, ..Default::default() };
+        assert!(plan_admission(&entries, &incoming, &budget).is_err());
+        let huge = ModelFootprint { ram_bytes: 11 << 30, ..Default::default() };
+        assert!(plan_admission(&[], &huge, &budget).is_err());
+    }
+
+    #[test]
+    fn test_plan_load_cpu_and_gpu() {
+        let cpu = HardwareBudget { available_ram_bytes: 16 << 30, available_vram_bytes: 0, gpu_backend: false };
+        let plan = plan_load(&arch_7b(), &cpu, Some(8192), KvCacheType::F16);
+        assert_eq!(plan.n_gpu_layers, 0);
+        assert_eq!(plan.n_ctx, 8192);
+        assert!(plan.fits);
+
+        // 3 GiB VRAM holds roughly half of a 4 GiB model plus its KV share
+        let gpu = HardwareBudget { available_ram_bytes: 16 << 30, available_vram_bytes: 3 << 30, gpu_backend: true };
+        let plan = plan_load(&arch_7b(), &gpu, Some(4096), KvCacheType::F16);
+        assert!(plan.n_gpu_layers > 0 && plan.n_gpu_layers < 32);
+        assert!(plan.fits);
+    }
+}
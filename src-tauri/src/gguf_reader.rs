@@ -0,0 +1,773 @@
+// src-tauri/src/gguf_reader.rs
+// Bounds-checked GGUF v2/v3 reader: every metadata value type (including nested arrays),
+// the tensor-info table and a quantization / parameter-count summary.
+//
+// Every length read from the file is validated against the bytes that are actually left,
+// so truncated or corrupted files produce an error instead of a huge allocation or panic.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+const DEFAULT_ALIGNMENT: u64 = 32;
+/// Nested arrays deeper than this are rejected
+const MAX_ARRAY_DEPTH: u32 = 8;
+/// Tensors with more dimensions than ggml supports are rejected
+const MAX_TENSOR_DIMS: u32 = 8;
+/// Skips up to this size are read rather than seeked
+const READ_THROUGH_SKIP: u64 = 64 * 1024;
+
+/// GGUF metadata value types
+const T_UINT8: u32 = 0;
+const T_INT8: u32 = 1;
+const T_UINT16: u32 = 2;
+const T_INT16: u32 = 3;
+const T_UINT32: u32 = 4;
+const T_INT32: u32 = 5;
+const T_FLOAT32: u32 = 6;
+const T_BOOL: u32 = 7;
+const T_STRING: u32 = 8;
+const T_ARRAY: u32 = 9;
+const T_UINT64: u32 = 10;
+const T_INT64: u32 = 11;
+const T_FLOAT64: u32 = 12;
+
+/// Reader options
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufReadOptions {
+    /// Keep at most this many elements per array (vocabularies can have 150k+ entries).
+    /// The full length is still reported in `truncated_arrays`.
+    pub max_array_len: Option<usize>,
+    /// Parse the tensor-info table
+    pub read_tensors: bool,
+}
+
+impl Default for GgufReadOptions {
+    fn default() -> Self {
+        Self { max_array_len: None, read_tensors: true }
+    }
+}
+
+/// One entry of the tensor-info table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufTensorInfo {
+    pub name: String,
+    pub shape: Vec<u64>,
+    pub ggml_type: u32,
+    pub type_name: String,
+    /// Absolute byte offset of the tensor data in the file
+    pub offset: u64,
+    pub n_elements: u64,
+    pub n_bytes: u64,
+}
+
+/// Per-type share of the weights
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantShare {
+    pub type_name: String,
+    pub tensors: usize,
+    pub parameters: u64,
+    pub bytes: u64,
+    pub percent_of_params: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufSummary {
+    pub parameter_count: u64,
+    pub tensor_bytes: u64,
+    /// Type holding the largest share of parameters (ignoring F32 norms/biases when possible)
+    pub dominant_type: Option<String>,
+    pub quantization_mix: Vec<QuantShare>,
+    pub bits_per_weight: f64,
+}
+
+/// Parsed GGUF file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufFile {
+    pub version: u32,
+    pub tensor_count: u64,
+    pub kv_count: u64,
+    pub metadata: Map<String, Value>,
+    /// Key -> full length for arrays cut by `max_array_len`
+    pub truncated_arrays: BTreeMap<String, u64>,
+    pub tensors: Vec<GgufTensorInfo>,
+    pub alignment: u64,
+    /// Absolute offset of the tensor data section (0 when tensors were not read)
+    pub data_offset: u64,
+    pub file_size: u64,
+}
+
+impl GgufFile {
+    pub fn summary(&self) -> GgufSummary {
+        let mut by_type: BTreeMap<String, (usize, u64, u64)> = BTreeMap::new();
+        for t in &self.tensors {
+            let e = by_type.entry(t.type_name.clone()).or_default();
+            e.0 += 1;
+            e.1 += t.n_elements;
+            e.2 += t.n_bytes;
+        }
+
+        let parameter_count: u64 = self.tensors.iter().map(|t| t.n_elements).sum();
+        let tensor_bytes: u64 = self.tensors.iter().map(|t| t.n_bytes).sum();
+
+        let mut quantization_mix: Vec<QuantShare> = by_type.into_iter()
+            .map(|(type_name, (tensors, parameters, bytes))| QuantShare {
+                type_name,
+                tensors,
+                parameters,
+                bytes,
+                percent_of_params: if parameter_count > 0 {
+                    parameters as f64 * 100.0 / parameter_count as f64
+                } else {
+                    0.0
+                },
+            })
+            .collect();
+        quantization_mix.sort_by(|a, b| b.parameters.cmp(&a.parameters));
+
+        let dominant_type = quantization_mix.iter()
+            .find(|q| q.type_name != "F32")
+            .or_else(|| quantization_mix.first())
+            .map(|q| q.type_name.clone());
+
+        GgufSummary {
+            parameter_count,
+            tensor_bytes,
+            dominant_type,
+            quantization_mix,
+            bits_per_weight: if parameter_count > 0 {
+                tensor_bytes as f64 * 8.0 / parameter_count as f64
+            } else {
+                0.0
+            },
+        }
+    }
+
+    /// Full length of an array value, whether or not it was truncated
+    pub fn array_len(&self, key: &str) -> Option<u64> {
+        self.truncated_arrays.get(key).copied().or_else(|| {
+            self.metadata.get(key).and_then(|v| v.as_array()).map(|a| a.len() as u64)
+        })
+    }
+}
+
+/// ggml tensor type name, block size (elements) and block size (bytes)
+pub fn ggml_type_info(ggml_type: u32) -> Option<(&'static str, u64, u64)> {
+    Some(match ggml_type {
+        0 => ("F32", 1, 4),
+        1 => ("F16", 1, 2),
+        2 => ("Q4_0", 32, 18),
+        3 => ("Q4_1", 32, 20),
+        6 => ("Q5_0", 32, 22),
+        7 => ("Q5_1", 32, 24),
+        8 => ("Q8_0", 32, 34),
+        9 => ("Q8_1", 32, 36),
+        10 => ("Q2_K", 256, 84),
+        11 => ("Q3_K", 256, 110),
+        12 => ("Q4_K", 256, 144),
+        13 => ("Q5_K", 256, 176),
+        14 => ("Q6_K", 256, 210),
+        15 => ("Q8_K", 256, 292),
+        16 => ("IQ2_XXS", 256, 66),
+        17 => ("IQ2_XS", 256, 74),
+        18 => ("IQ3_XXS", 256, 98),
+        19 => ("IQ1_S", 256, 50),
+        20 => ("IQ4_NL", 32, 18),
+        21 => ("IQ3_S", 256, 110),
+        22 => ("IQ2_S", 256, 82),
+        23 => ("IQ4_XS", 256, 136),
+        24 => ("I8", 1, 1),
+        25 => ("I16", 1, 2),
+        26 => ("I32", 1, 4),
+        27 => ("I64", 1, 8),
+        28 => ("F64", 1, 8),
+        29 => ("IQ1_M", 256, 56),
+        30 => ("BF16", 1, 2),
+        34 => ("TQ1_0", 256, 54),
+        35 => ("TQ2_0", 256, 66),
+        39 => ("MXFP4", 32, 17),
+        _ => return None,
+    })
+}
+
+/// Open and parse a GGUF file from disk
+pub fn read_gguf_file(path: &str, options: &GgufReadOptions) -> Result<GgufFile, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Dosya açılamadı: {}", e))?;
+    let len = file.metadata().map_err(|e| e.to_string())?.len();
+    parse_gguf(std::io::BufReader::new(file), len, options)
+}
+
+/// Check that a file is a loadable GGUF: valid header and metadata, at least one tensor
+/// and every tensor inside the file (catches truncated downloads of any size)
+pub fn validate_gguf_file(path: &str) -> Result<GgufFile, String> {
+    let file = read_gguf_file(path, &GgufReadOptions { max_array_len: Some(0), read_tensors: true })?;
+    if file.tensors.is_empty() {
+        return Err("GGUF dosyasında tensör yok".to_string());
+    }
+    Ok(file)
+}
+
+/// Parse GGUF from any seekable reader of `len` bytes
+pub fn parse_gguf<R: Read + Seek>(reader: R, len: u64, options: &GgufReadOptions) -> Result<GgufFile, String> {
+    let mut r = BoundedReader { inner: reader, pos: 0, len };
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != GGUF_MAGIC {
+        return Err("Geçersiz GGUF dosyası (magic bytes yanlış)".to_string());
+    }
+
+    let version = r.u32()?;
+    if version != 2 && version != 3 {
+        return Err(format!("Desteklenmeyen GGUF sürümü: {} (v2/v3 destekleniyor)", version));
+    }
+
+    let tensor_count = r.u64()?;
+    let kv_count = r.u64()?;
+    // Each KV needs at least a key length, a type and one byte of value
+    if kv_count > r.remaining() / 13 {
+        return Err(format!("Geçersiz KV sayısı: {}", kv_count));
+    }
+    // Each tensor info needs at least name length + n_dims + type + offset
+    if tensor_count > r.remaining() / 24 {
+        return Err(format!("Geçersiz tensor sayısı: {}", tensor_count));
+    }
+
+    let mut metadata = Map::new();
+    let mut truncated_arrays = BTreeMap::new();
+    for _ in 0..kv_count {
+        let key = r.string()?;
+        let vtype = r.u32()?;
+        let value = r.value(vtype, options.max_array_len, 0, &key, &mut truncated_arrays)?;
+        metadata.insert(key, value);
+    }
+
+    let alignment = match metadata.get("general.alignment").and_then(|v| v.as_u64()) {
+        Some(a) if a > 0 && a.is_power_of_two() => a,
+        Some(a) => return Err(format!("Geçersiz general.alignment: {}", a)),
+        None => DEFAULT_ALIGNMENT,
+    };
+
+    let mut tensors = Vec::new();
+    let mut data_offset = 0;
+    if options.read_tensors {
+        let mut raw = Vec::with_capacity(tensor_count.min(65_536) as usize);
+        for _ in 0..tensor_count {
+            let name = r.string()?;
+            let n_dims = r.u32()?;
+            if n_dims == 0 || n_dims > MAX_TENSOR_DIMS {
+                return Err(format!("Tensor '{}' geçersiz boyut sayısı: {}", name, n_dims));
+            }
+            let mut shape = Vec::with_capacity(n_dims as usize);
+            for _ in 0..n_dims {
+                shape.push(r.u64()?);
+            }
+            let ggml_type = r.u32()?;
+            let offset = r.u64()?;
+            raw.push((name, shape, ggml_type, offset));
+        }
+
+        data_offset = align_up(r.pos, alignment).ok_or("Tensor veri ofseti taşması")?;
+
+        for (name, shape, ggml_type, rel_offset) in raw {
+            let (type_name, block_elems, block_bytes) = ggml_type_info(ggml_type)
+                .ok_or_else(|| format!("Tensor '{}' bilinmeyen ggml tipi: {}", name, ggml_type))?;
+
+            let n_elements = shape.iter()
+                .try_fold(1u64, |acc, d| acc.checked_mul(*d))
+                .ok_or_else(|| format!("Tensor '{}' eleman sayısı taşması", name))?;
+            if shape[0] % block_elems != 0 {
+                return Err(format!("Tensor '{}' boyutu {} blok boyutuna ({}) bölünmüyor", name, shape[0], block_elems));
+            }
+            let n_bytes = (n_elements / block_elems)
+                .checked_mul(block_bytes)
+                .ok_or_else(|| format!("Tensor '{}' bayt boyutu taşması", name))?;
+            if rel_offset % alignment != 0 {
+                return Err(format!("Tensor '{}' ofseti hizalı değil: {}", name, rel_offset));
+            }
+            let offset = data_offset.checked_add(rel_offset)
+                .ok_or_else(|| format!("Tensor '{}' ofset taşması", name))?;
+            let end = offset.checked_add(n_bytes)
+                .ok_or_else(|| format!("Tensor '{}' ofset taşması", name))?;
+            if end > len {
+                return Err(format!("Tensor '{}' dosya sınırlarının dışında ({} > {})", name, end, len));
+            }
+
+            tensors.push(GgufTensorInfo {
+                name,
+                shape,
+                ggml_type,
+                type_name: type_name.to_string(),
+                offset,
+                n_elements,
+                n_bytes,
+            });
+        }
+    }
+
+    Ok(GgufFile {
+        version,
+        tensor_count,
+        kv_count,
+        metadata,
+        truncated_arrays,
+        tensors,
+        alignment,
+        data_offset,
+        file_size: len,
+    })
+}
+
+fn align_up(pos: u64, alignment: u64) -> Option<u64> {
+    let rem = pos % alignment;
+    if rem == 0 { Some(pos) } else { pos.checked_add(alignment - rem) }
+}
+
+/// Reader that knows how many bytes are left and refuses lengths beyond that
+struct BoundedReader<R> {
+    inner: R,
+    pos: u64,
+    len: u64,
+}
+
+impl<R: Read + Seek> BoundedReader<R> {
+    fn remaining(&self) -> u64 {
+        self.len.saturating_sub(self.pos)
+    }
+
+    fn ensure(&self, n: u64) -> Result<(), String> {
+        if n > self.remaining() {
+            Err(format!("Beklenmeyen dosya sonu: ofset {}, {} bayt gerekli, {} kaldı", self.pos, n, self.remaining()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), String> {
+        self.ensure(buf.len() as u64)?;
+        self.inner.read_exact(buf).map_err(|e| format!("Okuma hatası (ofset {}): {}", self.pos, e))?;
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Small skips are read through, so a `BufReader` keeps its buffer instead of
+    /// dropping it on every seek (vocabularies skip 100k+ short strings)
+    fn skip(&mut self, n: u64) -> Result<(), String> {
+        self.ensure(n)?;
+        if n <= READ_THROUGH_SKIP {
+            let skipped = std::io::copy(&mut (&mut self.inner).take(n), &mut std::io::sink())
+                .map_err(|e| format!("Okuma hatası (ofset {}): {}", self.pos, e))?;
+            if skipped != n {
+                return Err(format!("Beklenmeyen dosya sonu: ofset {}", self.pos + skipped));
+            }
+        } else {
+            let delta = i64::try_from(n).map_err(|_| "Atlama boyutu çok büyük".to_string())?;
+            self.inner.seek(SeekFrom::Current(delta)).map_err(|e| e.to_string())?;
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut b = [0u8; N];
+        self.read_exact(&mut b)?;
+        Ok(b)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u64()?;
+        self.ensure(len)?;
+        let mut buf = vec![0u8; len as usize];
+        self.read_exact(&mut buf)?;
+        // Some converters emit invalid UTF-8 in token tables; keep what we can
+        Ok(String::from_utf8(buf).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned()))
+    }
+
+    fn scalar(&mut self, vtype: u32) -> Result<Value, String> {
+        Ok(match vtype {
+            T_UINT8 => u8::from_le_bytes(self.bytes()?).into(),
+            T_INT8 => i8::from_le_bytes(self.bytes()?).into(),
+            T_UINT16 => u16::from_le_bytes(self.bytes()?).into(),
+            T_INT16 => i16::from_le_bytes(self.bytes()?).into(),
+            T_UINT32 => u32::from_le_bytes(self.bytes()?).into(),
+            T_INT32 => i32::from_le_bytes(self.bytes()?).into(),
+            T_FLOAT32 => float_value(f32::from_le_bytes(self.bytes()?) as f64),
+            T_BOOL => match self.bytes::<1>()?[0] {
+                0 => false.into(),
+                1 => true.into(),
+                b => return Err(format!("Geçersiz bool değeri: {}", b)),
+            },
+            T_STRING => self.string()?.into(),
+            T_UINT64 => u64::from_le_bytes(self.bytes()?).into(),
+            T_INT64 => i64::from_le_bytes(self.bytes()?).into(),
+            T_FLOAT64 => float_value(f64::from_le_bytes(self.bytes()?)),
+            other => return Err(format!("Bilinmeyen GGUF değer tipi: {}", other)),
+        })
+    }
+
+    fn value(
+        &mut self,
+        vtype: u32,
+        max_array_len: Option<usize>,
+        depth: u32,
+        key: &str,
+        truncated: &mut BTreeMap<String, u64>,
+    ) -> Result<Value, String> {
+        if vtype != T_ARRAY {
+            return self.scalar(vtype);
+        }
+        if depth >= MAX_ARRAY_DEPTH {
+            return Err(format!("'{}' dizisi çok derin iç içe", key));
+        }
+
+        let elem_type = self.u32()?;
+        let len = self.u64()?;
+        let min_elem = min_encoded_size(elem_type).ok_or_else(|| format!("'{}' dizisinde bilinmeyen tip: {}", key, elem_type))?;
+        if len.checked_mul(min_elem).map_or(true, |n| n > self.remaining()) {
+            return Err(format!("'{}' dizi uzunluğu geçersiz: {}", key, len));
+        }
+
+        let keep = max_array_len.map_or(len, |m| (m as u64).min(len));
+        if keep < len {
+            truncated.insert(key.to_string(), len);
+        }
+
+        let mut values = Vec::with_capacity(keep.min(65_536) as usize);
+        for i in 0..len {
+            if i < keep {
+                values.push(self.value(elem_type, max_array_len, depth + 1, key, truncated)?);
+            } else {
+                self.skip_value(elem_type, depth + 1, key)?;
+            }
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn skip_value(&mut self, vtype: u32, depth: u32, key: &str) -> Result<(), String> {
+        match vtype {
+            T_STRING => {
+                let len = self.u64()?;
+                self.skip(len)
+            }
+            T_ARRAY => {
+                if depth >= MAX_ARRAY_DEPTH {
+                    return Err(format!("'{}' dizisi çok derin iç içe", key));
+                }
+                let elem_type = self.u32()?;
+                let len = self.u64()?;
+                match fixed_size(elem_type) {
+                    Some(size) => self.skip(len.checked_mul(size).ok_or("Dizi boyutu taşması")?),
+                    None => {
+                        let min_elem = min_encoded_size(elem_type).ok_or_else(|| format!("'{}' dizisinde bilinmeyen tip: {}", key, elem_type))?;
+                        if len.checked_mul(min_elem).map_or(true, |n| n > self.remaining()) {
+                            return Err(format!("'{}' dizi uzunluğu geçersiz: {}", key, len));
+                        }
+                        for _ in 0..len {
+                            self.skip_value(elem_type, depth + 1, key)?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+            other => {
+                let size = fixed_size(other).ok_or_else(|| format!("Bilinmeyen GGUF değer tipi: {}", other))?;
+                self.skip(size)
+            }
+        }
+    }
+}
+
+fn fixed_size(vtype: u32) -> Option<u64> {
+    match vtype {
+        T_UINT8 | T_INT8 | T_BOOL => Some(1),
+        T_UINT16 | T_INT16 => Some(2),
+        T_UINT32 | T_INT32 | T_FLOAT32 => Some(4),
+        T_UINT64 | T_INT64 | T_FLOAT64 => Some(8),
+        _ => None,
+    }
+}
+
+/// Smallest possible encoding of one element (used to reject impossible array lengths)
+fn min_encoded_size(vtype: u32) -> Option<u64> {
+    match vtype {
+        T_STRING => Some(8),
+        T_ARRAY => Some(12),
+        other => fixed_size(other),
+    }
+}
+
+fn float_value(f: f64) -> Value {
+    // NaN / inf are not representable in JSON
+    serde_json::Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null)
+}
+
+/// GGUF writer for tests here and in other modules (model library, loader)
+#[cfg(test)]
+pub(crate) mod test_gguf {
+    use super::*;
+
+    /// Minimal GGUF v3 writer for building test files
+    pub(crate) struct Builder {
+        kvs: Vec<u8>,
+        kv_count: u64,
+        pub(crate) tensors: Vec<(String, Vec<u64>, u32, u64)>,
+    }
+
+    impl Builder {
+        pub(crate) fn new() -> Self {
+            Self { kvs: Vec::new(), kv_count: 0, tensors: Vec::new() }
+        }
+
+        /// Start a key of type `vtype`; the caller appends the encoded value
+        pub(crate) fn key(&mut self, key: &str, vtype: u32) -> &mut Vec<u8> {
+            self.kv_count += 1;
+            put_str(&mut self.kvs, key);
+            self.kvs.extend_from_slice(&vtype.to_le_bytes());
+            &mut self.kvs
+        }
+
+        pub(crate) fn string(&mut self, key: &str, value: &str) -> &mut Self {
+            put_str(self.key(key, T_STRING), value);
+            self
+        }
+
+        pub(crate) fn u32(&mut self, key: &str, value: u32) -> &mut Self {
+            self.key(key, T_UINT32).extend_from_slice(&value.to_le_bytes());
+            self
+        }
+
+        pub(crate) fn build(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            out.extend_from_slice(b"GGUF");
+            out.extend_from_slice(&3u32.to_le_bytes());
+            out.extend_from_slice(&(self.tensors.len() as u64).to_le_bytes());
+            out.extend_from_slice(&self.kv_count.to_le_bytes());
+            out.extend_from_slice(&self.kvs);
+            let mut data_len = 0;
+            for (name, shape, ty, offset) in &self.tensors {
+                put_str(&mut out, name);
+                out.extend_from_slice(&(shape.len() as u32).to_le_bytes());
+                for d in shape {
+                    out.extend_from_slice(&d.to_le_bytes());
+                }
+                out.extend_from_slice(&ty.to_le_bytes());
+                out.extend_from_slice(&offset.to_le_bytes());
+                let (_, be, bb) = ggml_type_info(*ty).unwrap();
+                data_len = data_len.max(offset + shape.iter().product::<u64>() / be * bb);
+            }
+            while out.len() % 32 != 0 {
+                out.push(0);
+            }
+            out.extend(std::iter::repeat(0u8).take(data_len as usize));
+            out
+        }
+
+        pub(crate) fn write(&self, path: &std::path::Path) {
+            std::fs::write(path, self.build()).unwrap();
+        }
+    }
+
+    pub(crate) fn put_str(out: &mut Vec<u8>, s: &str) {
+        out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+        out.extend_from_slice(s.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_gguf::{put_str, Builder};
+    use super::*;
+    use std::io::Cursor;
+
+    fn sample_file() -> Vec<u8> {
+        let mut b = Builder::new();
+        put_str(b.key("general.architecture", T_STRING), "llama");
+        b.key("llama.block_count", T_UINT32).extend_from_slice(&2u32.to_le_bytes());
+        b.key("i8", T_INT8).push(0xFF);
+        b.key("u16", T_UINT16).extend_from_slice(&65535u16.to_le_bytes());
+        b.key("i16", T_INT16).extend_from_slice(&(-2i16).to_le_bytes());
+        b.key("i64", T_INT64).extend_from_slice(&(-5i64).to_le_bytes());
+        b.key("f64", T_FLOAT64).extend_from_slice(&1.5f64.to_le_bytes());
+        {
+            let v = b.key("tokenizer.ggml.tokens", T_ARRAY);
+            v.extend_from_slice(&T_STRING.to_le_bytes());
+            v.extend_from_slice(&5u64.to_le_bytes());
+            for t in ["<s>", "</s>", "a", "b", "c"] {
+                put_str(v, t);
+            }
+        }
+        {
+            // Nested: [[1, 2], [3]]
+            let v = b.key("nested", T_ARRAY);
+            v.extend_from_slice(&T_ARRAY.to_le_bytes());
+            v.extend_from_slice(&2u64.to_le_bytes());
+            for inner in [vec![1u32, 2], vec![3]] {
+                v.extend_from_slice(&T_UINT32.to_le_bytes());
+                v.extend_from_slice(&(inner.len() as u64).to_le_bytes());
+                for x in inner {
+                    v.extend_from_slice(&x.to_le_bytes());
+                }
+            }
+        }
+        b.tensors.push(("token_embd.weight".into(), vec![64, 4], 8, 0)); // Q8_0: 256 elems -> 272 bytes
+        b.tensors.push(("output_norm.weight".into(), vec![64], 0, 288)); // F32: 256 bytes
+        b.build()
+    }
+
+    fn parse(bytes: &[u8], opts: &GgufReadOptions) -> Result<GgufFile, String> {
+        parse_gguf(Cursor::new(bytes), bytes.len() as u64, opts)
+    }
+
+    #[test]
+    fn test_parse_all_types_and_tensors() {
+        let bytes = sample_file();
+        let f = parse(&bytes, &GgufReadOptions::default()).unwrap();
+        assert_eq!(f.version, 3);
+        assert_eq!(f.metadata["general.architecture"], "llama");
+        assert_eq!(f.metadata["i8"], -1);
+        assert_eq!(f.metadata["u16"], 65535);
+        assert_eq!(f.metadata["i16"], -2);
+        assert_eq!(f.metadata["i64"], -5);
+        assert_eq!(f.metadata["f64"], 1.5);
+        assert_eq!(f.metadata["tokenizer.ggml.tokens"][2], "a");
+        assert_eq!(f.metadata["nested"], serde_json::json!([[1, 2], [3]]));
+
+        assert_eq!(f.tensors.len(), 2);
+        assert_eq!(f.tensors[0].type_name, "Q8_0");
+        assert_eq!(f.tensors[0].n_bytes, 272);
+        assert_eq!(f.tensors[1].offset, f.data_offset + 288);
+
+        let s = f.summary();
+        assert_eq!(s.parameter_count, 320);
+        assert_eq!(s.dominant_type.as_deref(), Some("Q8_0"));
+        assert_eq!(s.quantization_mix.len(), 2);
+    }
+
+    #[test]
+    fn test_array_truncation() {
+        let bytes = sample_file();
+        let opts = GgufReadOptions { max_array_len: Some(2), read_tensors: true };
+        let f = parse(&bytes, &opts).unwrap();
+        assert_eq!(f.metadata["tokenizer.ggml.tokens"].as_array().unwrap().len(), 2);
+        assert_eq!(f.array_len("tokenizer.ggml.tokens"), Some(5));
+        // Keys after a truncated array must still parse correctly
+        assert_eq!(f.tensors.len(), 2);
+    }
+
+    /// Counts seeks on the wrapped reader
+    struct SeekCounter<R> {
+        inner: R,
+        seeks: usize,
+    }
+
+    impl<R: Read> Read for SeekCounter<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl<R: Seek> Seek for SeekCounter<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.seeks += 1;
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_small_skips_do_not_seek() {
+        let bytes = sample_file();
+        let mut counter = SeekCounter { inner: Cursor::new(&bytes), seeks: 0 };
+        let opts = GgufReadOptions { max_array_len: Some(0), read_tensors: true };
+        let f = parse_gguf(&mut counter, bytes.len() as u64, &opts).unwrap();
+        assert_eq!(f.array_len("tokenizer.ggml.tokens"), Some(5));
+        assert_eq!(counter.seeks, 0);
+    }
+
+    #[test]
+    fn test_rejects_bad_header() {
+        assert!(parse(b"GGML\x03\x00\x00\x00", &GgufReadOptions::default()).is_err());
+        let mut v1 = sample_file();
+        v1[4] = 1;
+        assert!(parse(&v1, &GgufReadOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_validate_small_and_truncated_files() {
+        let dir = std::env::temp_dir().join(format!("corex_gguf_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let bytes = sample_file();
+
+        // A valid file passes however small it is
+        let ok = dir.join("tiny.gguf");
+        std::fs::write(&ok, &bytes).unwrap();
+        assert!(validate_gguf_file(ok.to_str().unwrap()).is_ok());
+
+        // Cut inside the tensor data
+        let cut = dir.join("cut.gguf");
+        std::fs::write(&cut, &bytes[..bytes.len() - 8]).unwrap();
+        assert!(validate_gguf_file(cut.to_str().unwrap()).is_err());
+
+        // Not GGUF, and GGUF without tensors
+        let html = dir.join("error.gguf");
+        std::fs::write(&html, b"<html>404</html>").unwrap();
+        assert!(validate_gguf_file(html.to_str().unwrap()).is_err());
+        let empty = dir.join("empty.gguf");
+        std::fs::write(&empty, Builder::new().build()).unwrap();
+        assert!(validate_gguf_file(empty.to_str().unwrap()).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_fuzz_truncation_never_panics() {
+        let bytes = sample_file();
+        for cut in 0..bytes.len() {
+            let result = parse(&bytes[..cut], &GgufReadOptions::default());
+            // Cutting only trailing tensor data must be caught by the bounds check
+            assert!(result.is_err(), "truncated at {} parsed successfully", cut);
+        }
+    }
+
+    #[test]
+    fn test_fuzz_corruption_never_panics() {
+        let original = sample_file();
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..2000 {
+            let mut bytes = original.clone();
+            for _ in 0..(next() % 4 + 1) {
+                let idx = (next() as usize) % bytes.len();
+                bytes[idx] = next() as u8;
+            }
+            let _ = parse(&bytes, &GgufReadOptions { max_array_len: Some(3), read_tensors: true });
+        }
+    }
+
+    #[test]
+    fn test_huge_lengths_rejected_without_allocation() {
+        let mut b = Builder::new();
+        b.key("s", T_STRING).extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(parse(&b.build(), &GgufReadOptions::default()).is_err());
+
+        let mut b = Builder::new();
+        let v = b.key("a", T_ARRAY);
+        v.extend_from_slice(&T_UINT64.to_le_bytes());
+        v.extend_from_slice(&(u64::MAX / 2).to_le_bytes());
+        assert!(parse(&b.build(), &GgufReadOptions::default()).is_err());
+    }
+}
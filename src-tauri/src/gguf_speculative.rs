@@ -0,0 +1,453 @@
+// src-tauri/src/gguf_speculative.rs
+// Speculative decoding: a small draft model proposes N tokens, the pooled target
+// model verifies them in a single batch and keeps the longest agreeing prefix.
+//
+// Verification samples the target exactly as plain decoding would and accepts a
+// draft token only if it equals that sample, so output quality is unchanged; only
+// the number of target forward passes goes down.
+
+use llama_cpp_2::context::LlamaContext;
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::{AddBos, LlamaModel};
+use llama_cpp_2::token::data::LlamaTokenData;
+use llama_cpp_2::token::LlamaToken;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tauri::State;
+
+use crate::gguf::{self, GgufState, LoadedModel};
+use crate::gguf_completion::{
+    CompletionOptions, CompletionTimings, FinishReason, GgufCompletion, SpeculativeStats, StopMatch,
+    StopSequenceMatcher,
+};
+use crate::gguf_reader::{self, GgufReadOptions};
+
+/// Same limits llama.cpp's speculative example uses
+const MAX_VOCAB_SIZE_DIFFERENCE: usize = 128;
+const VOCAB_CHECK_START_TOKEN_ID: usize = 5;
+const DEFAULT_N_DRAFT: usize = 5;
+const MAX_N_DRAFT: usize = 16;
+const PROMPT_CHUNK: usize = 2048;
+
+/// Result of comparing the target and draft tokenizers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VocabCompat {
+    pub tokenizer_model: String,
+    pub target_vocab_size: usize,
+    pub draft_vocab_size: usize,
+    /// Token texts compared one by one
+    pub checked_tokens: usize,
+}
+
+/// A target model paired with its draft model
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeculativePairing {
+    pub target_model_path: String,
+    pub draft_model_path: String,
+    pub n_draft: usize,
+    pub vocab: VocabCompat,
+}
+
+/// Check from GGUF metadata that the draft model tokenizes exactly like the target
+pub fn check_vocab_compat(target: &Map<String, Value>, draft: &Map<String, Value>) -> Result<VocabCompat, String> {
+    let str_key = |m: &Map<String, Value>, k: &str| m.get(k).and_then(|v| v.as_str()).map(String::from);
+
+    let target_model = str_key(target, "tokenizer.ggml.model").unwrap_or_default();
+    let draft_model = str_key(draft, "tokenizer.ggml.model").unwrap_or_default();
+    if target_model != draft_model {
+        return Err(format!("Tokenizer türü farklı: target {:?}, draft {:?}", target_model, draft_model));
+    }
+
+    for key in ["tokenizer.ggml.bos_token_id", "tokenizer.ggml.eos_token_id", "tokenizer.ggml.add_bos_token"] {
+        if let (Some(t), Some(d)) = (target.get(key), draft.get(key)) {
+            if t != d {
+                return Err(format!("{} farklı: target {}, draft {}", key, t, d));
+            }
+        }
+    }
+
+    let tokens = |m: &Map<String, Value>| m.get("tokenizer.ggml.tokens").and_then(|v| v.as_array()).cloned();
+    let (target_tokens, draft_tokens) = match (tokens(target), tokens(draft)) {
+        (Some(t), Some(d)) => (t, d),
+        _ => return Err("tokenizer.ggml.tokens metadata'sı eksik".to_string()),
+    };
+
+    let diff = target_tokens.len().abs_diff(draft_tokens.len());
+    if diff > MAX_VOCAB_SIZE_DIFFERENCE {
+        return Err(format!(
+            "Vocab boyutları çok farklı: target {}, draft {} (fark {} > {})",
+            target_tokens.len(), draft_tokens.len(), diff, MAX_VOCAB_SIZE_DIFFERENCE
+        ));
+    }
+
+    let shared = target_tokens.len().min(draft_tokens.len());
+    for id in VOCAB_CHECK_START_TOKEN_ID..shared {
+        if target_tokens[id] != draft_tokens[id] {
+            return Err(format!(
+                "Token {} farklı: target {}, draft {}",
+                id, target_tokens[id], draft_tokens[id]
+            ));
+        }
+    }
+
+    Ok(VocabCompat {
+        tokenizer_model: target_model,
+        target_vocab_size: target_tokens.len(),
+        draft_vocab_size: draft_tokens.len(),
+        checked_tokens: shared.saturating_sub(VOCAB_CHECK_START_TOKEN_ID),
+    })
+}
+
+/// Read tokenizer metadata with full (untruncated) token arrays
+fn read_tokenizer_metadata(path: &str) -> Result<Map<String, Value>, String> {
+    let options = GgufReadOptions { max_array_len: None, read_tensors: false };
+    let file = gguf_reader::read_gguf_file(path, &options)?;
+    Ok(file.metadata.into_iter().filter(|(k, _)| k.starts_with("tokenizer.")).collect())
+}
+
+/// Validate and register a target/draft pairing (both must be in the pool)
+pub fn pair_models(state: &Mutex<GgufState>, target_model_path: &str, draft_model_path: &str, n_draft: Option<usize>) -> Result<SpeculativePairing, String> {
+    if target_model_path == draft_model_path {
+        return Err("Draft model target modelden farklı olmalı".to_string());
+    }
+    let n_draft = n_draft.unwrap_or(DEFAULT_N_DRAFT).clamp(1, MAX_N_DRAFT);
+
+    let (target_key, draft_key) = {
+        let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+        if let Some(existing) = guard.speculative.get_mut(target_model_path) {
+            if existing.draft_model_path == draft_model_path {
+                existing.n_draft = n_draft;
+                return Ok(existing.clone());
+            }
+        }
+        let (target, _) = guard.checkout(target_model_path)?;
+        let (draft, _) = guard.checkout(draft_model_path)
+            .map_err(|e| format!("Draft model: {}", e))?;
+        (target.model_path.clone(), draft.model_path.clone())
+    };
+
+    // Tokenizer arrays can be large; read them outside the pool lock
+    let vocab = check_vocab_compat(&read_tokenizer_metadata(&target_key)?, &read_tokenizer_metadata(&draft_key)?)?;
+    info!("🔗 Speculative pairing: {} ← {} (n_draft {}, vocab {})", target_model_path, draft_model_path, n_draft, vocab.target_vocab_size);
+
+    let pairing = SpeculativePairing {
+        target_model_path: target_model_path.to_string(),
+        draft_model_path: draft_model_path.to_string(),
+        n_draft,
+        vocab,
+    };
+    state.lock().unwrap_or_else(|p| p.into_inner())
+        .speculative.insert(target_model_path.to_string(), pairing.clone());
+    Ok(pairing)
+}
+
+/// Decode `tokens` starting at `n_past`, requesting logits only for the last one
+fn decode_tokens(context: &mut LlamaContext<'_>, tokens: &[LlamaToken], n_past: usize, logits_last: bool) -> Result<(), String> {
+    for (chunk_idx, chunk) in tokens.chunks(PROMPT_CHUNK).enumerate() {
+        let start = n_past + chunk_idx * PROMPT_CHUNK;
+        let mut batch = LlamaBatch::new(chunk.len(), 1);
+        for (i, token) in chunk.iter().enumerate() {
+            let pos = start + i;
+            let is_last = pos == n_past + tokens.len() - 1;
+            batch.add(*token, pos as i32, &[0], logits_last && is_last)
+                .map_err(|e| format!("Batch add failed: {:?}", e))?;
+        }
+        context.decode(&mut batch).map_err(|e| format!("Decode failed: {:?}", e))?;
+    }
+    Ok(())
+}
+
+/// Drop KV entries at positions >= `n_past`
+fn truncate_kv(context: &mut LlamaContext<'_>, n_past: usize) -> Result<(), String> {
+    context.clear_kv_cache_seq(Some(0), Some(n_past as u32), None)
+        .map(|_| ())
+        .map_err(|e| format!("KV cache temizlenemedi: {:?}", e))
+}
+
+/// Turns accepted tokens into text: EOG, max_tokens, stop sequences and callback cancellation
+struct TokenSink<'a> {
+    model: &'a LlamaModel,
+    decoder: encoding_rs::Decoder,
+    matcher: StopSequenceMatcher,
+    on_text: &'a mut dyn FnMut(&str) -> bool,
+    response: String,
+    generated: usize,
+    max_tokens: usize,
+    finish: Option<FinishReason>,
+    stop_sequence: Option<String>,
+}
+
+impl TokenSink<'_> {
+    /// Returns true once generation is finished
+    fn push(&mut self, token: LlamaToken) -> bool {
+        if self.model.is_eog_token(token) {
+            self.finish = Some(FinishReason::Stop);
+            return true;
+        }
+        self.generated += 1;
+
+        let piece = self.model.token_to_piece(token, &mut self.decoder, false, None).unwrap_or_default();
+        match self.matcher.push(&piece) {
+            StopMatch::Continue(text) => {
+                self.response.push_str(&text);
+                if !text.is_empty() && !(self.on_text)(&text) {
+                    self.finish = Some(FinishReason::Cancelled);
+                    return true;
+                }
+            }
+            StopMatch::Matched { emit, stop } => {
+                self.response.push_str(&emit);
+                if !emit.is_empty() {
+                    (self.on_text)(&emit);
+                }
+                self.finish = Some(FinishReason::StopSequence);
+                self.stop_sequence = Some(stop);
+                return true;
+            }
+        }
+
+        if self.generated >= self.max_tokens {
+            self.finish = Some(FinishReason::Length);
+            return true;
+        }
+        false
+    }
+
+    fn finish_reason(&mut self) -> FinishReason {
+        let reason = self.finish.unwrap_or(FinishReason::Length);
+        if matches!(reason, FinishReason::Stop | FinishReason::Length) {
+            let rest = self.matcher.flush();
+            if !rest.is_empty() {
+                (self.on_text)(&rest);
+            }
+            self.response.push_str(&rest);
+        }
+        reason
+    }
+}
+
+/// Run a completion on `target`, drafting `n_draft` tokens per step with `draft`
+#[allow(clippy::too_many_arguments)]
+pub fn run_speculative_completion(
+    target: &LoadedModel,
+    draft: &LoadedModel,
+    backend: &LlamaBackend,
+    prompt: &str,
+    max_tokens: u32,
+    temperature: f32,
+    n_draft: usize,
+    options: &CompletionOptions,
+    on_text: &mut dyn FnMut(&str) -> bool,
+) -> Result<GgufCompletion, String> {
+    let mut target_ctx = gguf::new_inference_context(target, backend)?;
+    let mut draft_ctx = gguf::new_inference_context(draft, backend)?;
+    let window = target.n_ctx.min(draft.n_ctx) as usize;
+
+    let prompt_tokens = target.model.str_to_token(prompt, AddBos::Always)
+        .map_err(|e| format!("Tokenization failed: {:?}", e))?;
+    let n_prompt = prompt_tokens.len();
+    if n_prompt == 0 {
+        return Err("Boş prompt".to_string());
+    }
+    if n_prompt >= window {
+        return Err(format!("Prompt too long: {} tokens (max: {})", n_prompt, window - 1));
+    }
+
+    let prompt_start = Instant::now();
+    decode_tokens(&mut target_ctx, &prompt_tokens, 0, true)?;
+    decode_tokens(&mut draft_ctx, &prompt_tokens, 0, false)?;
+    let prompt_eval_ms = prompt_start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut stats = SpeculativeStats { draft_model: draft.model_path.clone(), ..Default::default() };
+    let mut sink = TokenSink {
+        model: &target.model,
+        decoder: encoding_rs::UTF_8.new_decoder(),
+        matcher: StopSequenceMatcher::new(&options.stop),
+        on_text,
+        response: String::new(),
+        generated: 0,
+        // Generated tokens must fit in the window after the prompt
+        max_tokens: (max_tokens.max(1) as usize).min(window - n_prompt),
+        finish: None,
+        stop_sequence: None,
+    };
+
+    let generation_start = Instant::now();
+
+    // `all` = prompt + generated tokens. The target KV holds all but the last one
+    // (still to be verified); the draft KV holds the first `draft_n_past`.
+    let mut all = prompt_tokens.clone();
+    let first_candidates: Vec<LlamaTokenData> = target_ctx.candidates().collect();
+    let first = gguf::sample_next_token(&first_candidates, &gguf::recent_token_window(&all, &[]), temperature);
+    all.push(first);
+    let mut finished = sink.push(first);
+    let mut draft_n_past = n_prompt;
+
+    while !finished {
+        let target_n_past = all.len() - 1;
+        let remaining = sink.max_tokens - sink.generated;
+
+        // The first step runs without drafts to measure the plain per-token cost
+        let k = if stats.target_passes == 0 { 0 } else { n_draft.min(remaining.saturating_sub(1)) };
+
+        let mut drafts: Vec<LlamaToken> = Vec::with_capacity(k);
+        if k > 0 {
+            decode_tokens(&mut draft_ctx, &all[draft_n_past..], draft_n_past, true)?;
+            draft_n_past = all.len();
+            for j in 0..k {
+                // Greedy drafting: the draft only has to guess what the target will pick
+                let token = draft_ctx.candidates()
+                    .max_by(|a, b| a.logit().partial_cmp(&b.logit()).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|c| c.id())
+                    .ok_or("Draft model aday üretmedi")?;
+                if draft.model.is_eog_token(token) {
+                    break;
+                }
+                drafts.push(token);
+                if j + 1 == k {
+                    break;
+                }
+                decode_tokens(&mut draft_ctx, &[token], draft_n_past, true)?;
+                draft_n_past += 1;
+            }
+        }
+
+        // Verify: last accepted token + all drafts in one target batch
+        let mut batch = LlamaBatch::new(drafts.len() + 1, 1);
+        batch.add(all[target_n_past], target_n_past as i32, &[0], true)
+            .map_err(|e| format!("Batch add failed: {:?}", e))?;
+        for (i, token) in drafts.iter().enumerate() {
+            batch.add(*token, (target_n_past + 1 + i) as i32, &[0], true)
+                .map_err(|e| format!("Batch add failed: {:?}", e))?;
+        }
+        let pass_start = Instant::now();
+        target_ctx.decode(&mut batch).map_err(|e| format!("Verify decode failed: {:?}", e))?;
+        if stats.target_passes == 0 {
+            stats.baseline_ms_per_token = pass_start.elapsed().as_secs_f64() * 1000.0;
+        }
+        stats.target_passes += 1;
+        stats.drafted_tokens += drafts.len();
+
+        let mut accepted_tokens = Vec::with_capacity(drafts.len() + 1);
+        for i in 0..=drafts.len() {
+            let candidates: Vec<LlamaTokenData> = target_ctx.candidates_ith(i as i32).collect();
+            let recent = gguf::recent_token_window(&all, &accepted_tokens);
+            let token = gguf::sample_next_token(&candidates, &recent, temperature);
+            accepted_tokens.push(token);
+            if i == drafts.len() || token != drafts[i] {
+                break;
+            }
+        }
+        let accepted = accepted_tokens.len() - 1;
+        stats.accepted_tokens += accepted;
+
+        // Keep only verified positions in both caches
+        truncate_kv(&mut target_ctx, target_n_past + 1 + accepted)?;
+        draft_n_past = draft_n_past.min(all.len() + accepted);
+        truncate_kv(&mut draft_ctx, draft_n_past)?;
+
+        for token in accepted_tokens {
+            all.push(token);
+            if sink.push(token) {
+                finished = true;
+                break;
+            }
+        }
+    }
+
+    let finish_reason = sink.finish_reason();
+    let generation_ms = generation_start.elapsed().as_secs_f64() * 1000.0;
+    stats.finish(sink.generated, generation_ms);
+    info!(
+        "⚡ Speculative: {}/{} drafts accepted ({:.0}%), {:.2} tok/pass, ~{:.2}x",
+        stats.accepted_tokens, stats.drafted_tokens, stats.acceptance_rate * 100.0,
+        stats.tokens_per_target_pass, stats.speedup
+    );
+    if stats.drafted_tokens > 0 && stats.acceptance_rate < 0.3 {
+        warn!("⚠️ Düşük kabul oranı; draft model bu görev için uygun olmayabilir");
+    }
+
+    Ok(GgufCompletion {
+        text: gguf::clean_response(&sink.response),
+        finish_reason,
+        stop_sequence: sink.stop_sequence.take(),
+        prompt_tokens: n_prompt,
+        completion_tokens: sink.generated,
+        timings: CompletionTimings::new(n_prompt, prompt_eval_ms, sink.generated, generation_ms),
+        logprobs: None,
+        speculative: Some(stats),
+        context_shift: None,
+        cache: None,
+    })
+}
+
+/// Pair a pooled target model with a pooled draft model after a vocab check
+#[tauri::command]
+pub async fn enable_speculative_decoding(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    target_model_path: String,
+    draft_model_path: String,
+    n_draft: Option<usize>,
+) -> Result<SpeculativePairing, String> {
+    pair_models(&state, &target_model_path, &draft_model_path, n_draft)
+}
+
+/// Remove a pairing; returns false if none was set
+#[tauri::command]
+pub async fn disable_speculative_decoding(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    target_model_path: String,
+) -> Result<bool, String> {
+    let mut guard = state.lock().unwrap_or_else(|p| p.into_inner());
+    Ok(guard.speculative.remove(&target_model_path).is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tokenizer(model: &str, tokens: &[&str]) -> Map<String, Value> {
+        json!({
+            "tokenizer.ggml.model": model,
+            "tokenizer.ggml.bos_token_id": 1,
+            "tokenizer.ggml.eos_token_id": 2,
+            "tokenizer.ggml.tokens": tokens,
+        }).as_object().unwrap().clone()
+    }
+
+    const VOCAB: [&str; 8] = ["<unk>", "<s>", "</s>", "<pad>", "<mask>", "a", "b", "c"];
+
+    #[test]
+    fn test_vocab_compat_accepts_identical_tokenizers() {
+        let target = tokenizer("llama", &VOCAB);
+        let mut draft_tokens = VOCAB.to_vec();
+        draft_tokens.push("extra");
+        let compat = check_vocab_compat(&target, &tokenizer("llama", &draft_tokens)).unwrap();
+        assert_eq!(compat.target_vocab_size, 8);
+        assert_eq!(compat.draft_vocab_size, 9);
+        assert_eq!(compat.checked_tokens, 3);
+    }
+
+    #[test]
+    fn test_vocab_compat_rejects_mismatches() {
+        let target = tokenizer("llama", &VOCAB);
+        assert!(check_vocab_compat(&target, &tokenizer("gpt2", &VOCAB)).is_err());
+
+        let mut swapped = VOCAB.to_vec();
+        swapped[6] = "x";
+        assert!(check_vocab_compat(&target, &tokenizer("llama", &swapped)).unwrap_err().contains("Token 6"));
+
+        let mut other_eos = tokenizer("llama", &VOCAB);
+        other_eos.insert("tokenizer.ggml.eos_token_id".to_string(), json!(3));
+        assert!(check_vocab_compat(&target, &other_eos).is_err());
+
+        let big: Vec<String> = (0..200).map(|i| format!("t{}", i)).collect();
+        let big_refs: Vec<&str> = big.iter().map(|s| s.as_str()).collect();
+        assert!(check_vocab_compat(&target, &tokenizer("llama", &big_refs)).unwrap_err().contains("Vocab"));
+    }
+}
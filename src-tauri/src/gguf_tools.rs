@@ -0,0 +1,315 @@
+// src-tauri/src/gguf_tools.rs
+// Prompt-based tool calling for local GGUF models.
+//
+// Local models get the tool list in the system prompt and must answer with one
+// JSON object, enforced token by token by the JSON grammar in generation:
+//   {"tool_calls": [{"name": "...", "arguments": {...}}, ...]}   (parallel calls)
+//   {"content": "..."}                                           (plain answer)
+// The reply is parsed into the same ChatResponse / ToolCall types the remote
+// providers return, and tool results come back as Role::Tool messages.
+
+use log::{info, warn};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+
+use crate::commands::ChatMessage;
+use crate::gguf::GgufState;
+use crate::gguf_completion::{CompletionOptions, FinishReason as GgufFinishReason};
+use crate::inference::{GenerateRequest, InferenceEngine};
+use crate::providers::{
+    finalize_tool_calls, parse_arguments, ChatRequest, ChatResponse, FinishReason, Message, Role, ToolCall, ToolChoice,
+    ToolDefinition, Usage,
+};
+use crate::response_cache::{self, CacheKey, CacheOptions};
+use crate::usage::{self, UsageEvent};
+
+/// System prompt section describing the tools and the reply format
+pub fn tool_system_prompt(tools: &[ToolDefinition], choice: &ToolChoice) -> String {
+    let mut prompt = String::from(
+        "You can call tools. Reply with exactly one JSON object and nothing else.\n\
+         To call tools: {\"tool_calls\": [{\"name\": \"<tool name>\", \"arguments\": {<arguments>}}]}\n\
+         List several calls to run them in parallel.\n\
+         To answer without tools: {\"content\": \"<your answer>\"}\n",
+    );
+    match choice {
+        ToolChoice::Required => prompt.push_str("You must call at least one tool.\n"),
+        ToolChoice::Tool { name } => prompt.push_str(&format!("You must call the `{}` tool.\n", name)),
+        _ => {}
+    }
+    prompt.push_str("\nTools:\n");
+    for tool in tools {
+        prompt.push_str(&format!("- {}: {}\n  arguments schema: {}\n", tool.name, tool.description, tool.parameters));
+    }
+    prompt
+}
+
+/// Flatten tool turns into plain chat messages any chat template can render
+pub fn render_messages(request: &ChatRequest) -> Vec<ChatMessage> {
+    let mut system: Vec<String> = request.messages.iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.clone())
+        .collect();
+    if request.uses_tools() {
+        system.push(tool_system_prompt(&request.tools, &request.tool_choice));
+    }
+
+    let mut out = Vec::new();
+    if !system.is_empty() {
+        out.push(ChatMessage { role: "system".into(), content: system.join("\n\n") });
+    }
+    for m in request.messages.iter().filter(|m| m.role != Role::System) {
+        let (role, content) = match m.role {
+            Role::Assistant if !m.tool_calls.is_empty() => {
+                let calls: Vec<Value> = m.tool_calls.iter()
+                    .map(|c| json!({ "name": c.name, "arguments": c.arguments }))
+                    .collect();
+                ("assistant", json!({ "tool_calls": calls }).to_string())
+            }
+            Role::Assistant if request.uses_tools() => ("assistant", json!({ "content": m.content }).to_string()),
+            Role::Assistant => ("assistant", m.content.clone()),
+            Role::Tool => {
+                let name = request.messages.iter()
+                    .flat_map(|p| p.tool_calls.iter())
+                    .find(|c| Some(&c.id) == m.tool_call_id.as_ref())
+                    .map(|c| c.name.as_str())
+                    .unwrap_or("tool");
+                ("user", format!("Result of {} ({}):\n{}", name, m.tool_call_id.as_deref().unwrap_or("?"), m.content))
+            }
+            _ => ("user", m.content.clone()),
+        };
+        out.push(ChatMessage { role: role.into(), content });
+    }
+    out
+}
+
+/// Outermost {...} of a reply, tolerating stray text or code fences around it
+fn json_object(text: &str) -> Option<Value> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    (end > start).then(|| serde_json::from_str(&text[start..=end]).ok()).flatten()
+}
+
+/// Reply text -> (answer text, tool calls). Non-JSON replies are a plain answer.
+pub fn parse_tool_reply(text: &str, tools: &[ToolDefinition]) -> (String, Vec<ToolCall>) {
+    let Some(value) = json_object(text) else {
+        return (text.trim().to_string(), Vec::new());
+    };
+    let calls: Vec<ToolCall> = value["tool_calls"].as_array().into_iter().flatten()
+        .filter_map(|c| {
+            let name = c["name"].as_str()?.to_string();
+            if !tools.iter().any(|t| t.name == name) {
+                warn!("⚠️ Model bilinmeyen bir aracı çağırdı: {}", name);
+            }
+            let arguments = match &c["arguments"] {
+                Value::Null => json!({}),
+                Value::String(raw) => parse_arguments(raw),
+                other => other.clone(),
+            };
+            Some(ToolCall { id: String::new(), name, arguments })
+        })
+        .collect();
+    let content = value["content"].as_str().map(String::from)
+        .unwrap_or_else(|| if calls.is_empty() { text.trim().to_string() } else { String::new() });
+    (content, calls)
+}
+
+/// One tool-aware turn on a loaded GGUF model
+pub fn complete_with_tools(engine: &InferenceEngine, model_id: &str, request: &ChatRequest) -> Result<ChatResponse, String> {
+    let messages = render_messages(request);
+    let prompt = engine.format_chat(model_id, &messages)?;
+    let generate = GenerateRequest {
+        prompt,
+        max_tokens: request.max_tokens.unwrap_or(1024),
+        temperature: request.temperature.unwrap_or(0.2),
+        options: CompletionOptions {
+            stop: request.stop.clone(),
+            json: request.uses_tools(),
+            ..Default::default()
+        },
+    };
+    let completion = engine.generate(model_id, &generate)?;
+
+    let (text, tool_calls) = if request.uses_tools() {
+        parse_tool_reply(&completion.text, &request.tools)
+    } else {
+        (completion.text.clone(), Vec::new())
+    };
+    let mut response = ChatResponse {
+        text,
+        model: Some(model_id.to_string()),
+        finish_reason: Some(match completion.finish_reason {
+            GgufFinishReason::Length => FinishReason::Length,
+            GgufFinishReason::Cancelled => FinishReason::Cancelled,
+            _ => FinishReason::Stop,
+        }),
+        usage: Some(Usage::new(completion.prompt_tokens as u64, completion.completion_tokens as u64)),
+        provider: "gguf".to_string(),
+        tool_calls,
+        cache: None,
+    };
+    finalize_tool_calls(&mut response);
+    info!("🛠️ GGUF araç turu: {} çağrı, {} karakter", response.tool_calls.len(), response.text.len());
+    Ok(response)
+}
+
+/// Tool-calling chat on a local model; same request/response shape as `chat_with_provider`
+#[tauri::command]
+pub async fn chat_with_gguf_tools(
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_path: Option<String>,
+    request: ChatRequest,
+    cache: Option<CacheOptions>,
+) -> Result<ChatResponse, String> {
+    let engine = InferenceEngine::llama_cpp(state.inner().clone());
+    let model_id = engine.resolve_model(model_path.as_deref())?;
+    let key = cache_key(&model_id, &response_cache::gguf_variant(state.inner(), &model_id), &request);
+    if let Some((mut response, info)) = response_cache::lookup::<ChatResponse>(&cache, key.as_ref()) {
+        response.cache = Some(info);
+        return Ok(response);
+    }
+
+    usage::check_budget(Some("gguf"), "tools", None)?;
+    let id = model_id.clone();
+    let mut response = tokio::task::spawn_blocking(move || complete_with_tools(&engine, &id, &request))
+        .await
+        .map_err(|e| format!("Inference görevi başarısız: {}", e))??;
+    let counts = response.usage.clone().unwrap_or_default();
+    usage::record(UsageEvent::local("tools", &model_id, counts.prompt_tokens as usize, counts.completion_tokens as usize));
+    response.cache = response_cache::store(&cache, key.as_ref(), &response);
+    Ok(response)
+}
+
+/// Response-cache key for a tool turn, using the same sampling as `complete_with_tools`
+fn cache_key(model_id: &str, variant: &str, request: &ChatRequest) -> Option<CacheKey> {
+    let options = CompletionOptions { stop: request.stop.clone(), json: request.uses_tools(), ..Default::default() };
+    let input = json!({
+        "messages": response_cache::gguf_messages(&render_messages(request)),
+        "tools": if request.uses_tools() { json!(request.tools) } else { Value::Null },
+        "tool_choice": request.tool_choice,
+    });
+    response_cache::gguf_key(
+        "tools",
+        model_id,
+        variant,
+        input,
+        request.max_tokens.unwrap_or(1024),
+        request.temperature.unwrap_or(0.2),
+        &options,
+    )
+}
+
+/// Helper for callers building the next turn by hand
+pub fn tool_results_turn(calls: &[ToolCall], results: &[String]) -> Vec<Message> {
+    let mut turn = vec![Message::assistant_tool_calls("", calls.to_vec())];
+    turn.extend(calls.iter().zip(results).map(|(c, r)| Message::tool_result(c.id.clone(), r.clone())));
+    turn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf_completion::{CompletionTimings, GgufCompletion};
+    use crate::inference::{InferenceBackend, LoadRequest};
+
+    fn tools() -> Vec<ToolDefinition> {
+        vec![ToolDefinition {
+            name: "read_file".into(),
+            description: "Read a workspace file".into(),
+            parameters: json!({ "type": "object", "properties": { "path": { "type": "string" } } }),
+        }]
+    }
+
+    /// Returns a canned reply and records whether JSON mode was requested
+    struct ScriptedBackend {
+        reply: String,
+        saw_json: Mutex<Option<bool>>,
+    }
+
+    impl InferenceBackend for ScriptedBackend {
+        fn name(&self) -> &'static str { "scripted" }
+        fn load(&self, request: &LoadRequest) -> Result<String, String> { Ok(request.model_path.clone()) }
+        fn unload(&self, _model_id: &str) -> Result<bool, String> { Ok(true) }
+        fn unload_all(&self) {}
+        fn loaded_models(&self) -> Vec<String> { vec!["m".into()] }
+        fn format_chat(&self, _model_id: &str, messages: &[ChatMessage]) -> Result<String, String> {
+            Ok(messages.iter().map(|m| format!("{}: {}", m.role, m.content)).collect::<Vec<_>>().join("\n"))
+        }
+        fn count_tokens(&self, _model_id: &str, text: &str) -> Result<usize, String> { Ok(text.len()) }
+        fn context_size(&self, _model_id: &str) -> Result<usize, String> { Ok(4096) }
+        fn generate(&self, _model_id: &str, request: &GenerateRequest, _on_text: &mut dyn FnMut(&str) -> bool) -> Result<GgufCompletion, String> {
+            *self.saw_json.lock().unwrap() = Some(request.options.json);
+            Ok(GgufCompletion {
+                text: self.reply.clone(),
+                finish_reason: GgufFinishReason::Stop,
+                stop_sequence: None,
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                timings: CompletionTimings::default(),
+                logprobs: None,
+                speculative: None,
+                context_shift: None,
+                cache: None,
+            })
+        }
+    }
+
+    #[test]
+    fn test_parse_tool_reply() {
+        let (text, calls) = parse_tool_reply(
+            r#"{"tool_calls": [{"name": "read_file", "arguments": {"path": "a.rs"}}, {"name": "read_file", "arguments": "{\"path\": \"b.rs\"}"}]}"#,
+            &tools(),
+        );
+        assert!(text.is_empty());
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1].arguments, json!({ "path": "b.rs" }));
+
+        let (text, calls) = parse_tool_reply("```json\n{\"content\": \"done\"}\n```", &tools());
+        assert_eq!((text.as_str(), calls.len()), ("done", 0));
+        let (text, _) = parse_tool_reply("plain words", &tools());
+        assert_eq!(text, "plain words");
+    }
+
+    #[test]
+    fn test_render_messages_with_tool_results() {
+        let mut request = ChatRequest {
+            messages: vec![Message::new(Role::System, "Be careful."), Message::new(Role::User, "Open a.rs")],
+            tools: tools(),
+            tool_choice: ToolChoice::Required,
+            ..Default::default()
+        };
+        let call = ToolCall { id: "call_0".into(), name: "read_file".into(), arguments: json!({ "path": "a.rs" }) };
+        request.messages.extend(tool_results_turn(&[call], &["fn main() {}".to_string()]));
+
+        let rendered = render_messages(&request);
+        assert_eq!(rendered.len(), 4);
+        assert!(rendered[0].content.starts_with("Be careful."));
+        assert!(rendered[0].content.contains("- read_file: Read a workspace file"));
+        assert!(rendered[0].content.contains("must call at least one tool"));
+        assert_eq!(rendered[2].content, r#"{"tool_calls":[{"arguments":{"path":"a.rs"},"name":"read_file"}]}"#);
+        assert_eq!(rendered[3].role, "user");
+        assert!(rendered[3].content.starts_with("Result of read_file (call_0):"));
+    }
+
+    #[test]
+    fn test_complete_with_tools_uses_json_mode() {
+        let backend = Arc::new(ScriptedBackend {
+            reply: r#"{"tool_calls": [{"name": "read_file", "arguments": {"path": "a.rs"}}, {"name": "read_file", "arguments": {"path": "b.rs"}}]}"#.into(),
+            saw_json: Mutex::new(None),
+        });
+        let engine = InferenceEngine::new(backend.clone());
+        let request = ChatRequest { messages: vec![Message::new(Role::User, "Open both")], tools: tools(), ..Default::default() };
+
+        let response = complete_with_tools(&engine, "m", &request).unwrap();
+        assert_eq!(*backend.saw_json.lock().unwrap(), Some(true));
+        assert_eq!(response.finish_reason, Some(FinishReason::ToolCalls));
+        let ids: Vec<&str> = response.tool_calls.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["call_0", "call_1"]);
+        assert_eq!(response.usage, Some(Usage::new(10, 5)));
+
+        // Without tools it is a normal completion
+        let plain = ChatRequest { messages: vec![Message::new(Role::User, "hi")], ..Default::default() };
+        complete_with_tools(&engine, "m", &plain).unwrap();
+        assert_eq!(*backend.saw_json.lock().unwrap(), Some(false));
+    }
+}
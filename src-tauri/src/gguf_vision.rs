@@ -0,0 +1,275 @@
+// src-tauri/src/gguf_vision.rs
+// Multimodal (image + text) inference for GGUF vision models through llama.cpp's
+// mtmd library: companion mmproj/CLIP projector discovery, image decoding and
+// interleaving image embeddings with the text prompt.
+//
+// Image preprocessing (resize / patching / mean-std normalisation) is done by the
+// projector itself using the parameters stored in the mmproj GGUF, so every model
+// family (LLaVA, Qwen-VL, Gemma 3, SmolVLM...) gets its own expected layout.
+
+use base64::{Engine as _, engine::general_purpose};
+use llama_cpp_2::llama_backend::LlamaBackend;
+use llama_cpp_2::llama_batch::LlamaBatch;
+use llama_cpp_2::model::AddBos;
+use llama_cpp_2::mtmd::{mtmd_default_marker, MtmdBitmap, MtmdContext, MtmdContextParams, MtmdInputText};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::commands::ChatMessage;
+use crate::gguf::{self, LoadedModel};
+use crate::gguf_completion::{CompletionOptions, GgufCompletion};
+
+/// Max tokens handed to llama_decode per step while evaluating prompt chunks
+const VISION_N_BATCH: i32 = 2048;
+
+/// A loaded mmproj projector bound to one pooled language model
+pub struct VisionProjector {
+    pub mmproj_path: String,
+    ctx: MtmdContext,
+}
+
+/// Image containers the projector's decoder (stb_image) understands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+}
+
+/// Identify the image container from its magic bytes
+pub fn sniff_image_format(bytes: &[u8]) -> Result<ImageFormat, String> {
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G']) {
+        Ok(ImageFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Ok(ImageFormat::Jpeg)
+    } else if bytes.starts_with(b"GIF8") {
+        Ok(ImageFormat::Gif)
+    } else if bytes.starts_with(b"BM") {
+        Ok(ImageFormat::Bmp)
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Err("WebP desteklenmiyor, lütfen PNG veya JPEG gönderin".to_string())
+    } else {
+        Err("Tanınmayan görüntü formatı (PNG, JPEG, GIF veya BMP bekleniyor)".to_string())
+    }
+}
+
+/// Decode a base64 image, accepting an optional `data:image/...;base64,` prefix
+pub fn decode_image_payload(data: &str) -> Result<Vec<u8>, String> {
+    let base64_data = match data.find("base64,") {
+        Some(pos) => &data[pos + "base64,".len()..],
+        None => data,
+    };
+    let bytes = general_purpose::STANDARD
+        .decode(base64_data.trim())
+        .map_err(|e| format!("Base64 çözülemedi: {:?}", e))?;
+    sniff_image_format(&bytes)?;
+    Ok(bytes)
+}
+
+/// Find the companion projector next to a model: any `*.gguf` with "mmproj" in its
+/// name, preferring the one sharing the longest name prefix with the model file.
+pub fn find_mmproj(model_path: &Path) -> Option<PathBuf> {
+    let dir = model_path.parent()?;
+    let model_name = model_path.file_name()?.to_string_lossy().to_lowercase();
+
+    let mut candidates: Vec<(usize, String, PathBuf)> = std::fs::read_dir(dir).ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let lower = name.to_lowercase();
+            if !lower.ends_with(".gguf") || !lower.contains("mmproj") || lower == model_name {
+                return None;
+            }
+            let shared = lower.chars().zip(model_name.chars()).take_while(|(a, b)| a == b).count();
+            Some((shared, name, entry.path()))
+        })
+        .collect();
+
+    // Longest shared prefix first, then by name for a stable pick
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+    candidates.into_iter().next().map(|(_, _, path)| path)
+}
+
+/// Make sure the prompt carries exactly one media marker per image.
+/// Prompts without markers get them prepended, one line per image.
+pub fn insert_media_markers(prompt: &str, n_images: usize, marker: &str) -> Result<String, String> {
+    let present = prompt.matches(marker).count();
+    if present == n_images {
+        return Ok(prompt.to_string());
+    }
+    if present > 0 {
+        return Err(format!(
+            "Prompt {} görüntü işaretçisi içeriyor ama {} görüntü gönderildi",
+            present, n_images
+        ));
+    }
+    let mut marked = String::new();
+    for _ in 0..n_images {
+        marked.push_str(marker);
+        marked.push('\n');
+    }
+    marked.push_str(prompt);
+    Ok(marked)
+}
+
+fn load_projector(loaded_model: &LoadedModel, mmproj_path: &str) -> Result<VisionProjector, String> {
+    // Cheap sanity check before handing the file to clip
+    let metadata = gguf::read_gguf_metadata_map(mmproj_path)?;
+    let arch = metadata.get("general.architecture").and_then(|v| v.as_str()).unwrap_or("");
+    if arch != "clip" {
+        return Err(format!("{} bir mmproj/CLIP projektörü değil (architecture: {:?})", mmproj_path, arch));
+    }
+
+    let params = MtmdContextParams {
+        // Projector follows the language model: CPU-only when no layers were offloaded
+        use_gpu: loaded_model.n_gpu_layers > 0 && cfg!(any(feature = "cuda", feature = "vulkan")),
+        print_timings: false,
+        n_threads: crate::hardware::thread_counts().n_threads_batch,
+        ..MtmdContextParams::default()
+    };
+
+    info!("🖼️ Loading vision projector: {} (gpu: {})", mmproj_path, params.use_gpu);
+    let ctx = MtmdContext::init_from_file(mmproj_path, &loaded_model.model, &params)
+        .map_err(|e| format!("mmproj yüklenemedi: {:?}", e))?;
+    if !ctx.support_vision() {
+        return Err(format!("{} görüntü girişi desteklemiyor", mmproj_path));
+    }
+
+    Ok(VisionProjector { mmproj_path: mmproj_path.to_string(), ctx })
+}
+
+/// Run an image + text completion against a pooled model.
+///
+/// The projector is loaded on first use (or when `mmproj_path` changes) and cached
+/// on the pooled model, so it is released together with it.
+#[allow(clippy::too_many_arguments)]
+pub fn run_vision_completion(
+    loaded_model: &LoadedModel,
+    backend: &LlamaBackend,
+    prompt: &str,
+    images: &[Vec<u8>],
+    mmproj_path: Option<&str>,
+    max_tokens: u32,
+    temperature: f32,
+    options: &CompletionOptions,
+) -> Result<GgufCompletion, String> {
+    let mmproj_path = match mmproj_path {
+        Some(path) => path.to_string(),
+        None => find_mmproj(Path::new(&loaded_model.model_path))
+            .map(|p| p.to_string_lossy().to_string())
+            .ok_or_else(|| format!(
+                "Model için mmproj dosyası bulunamadı: {} (aynı klasöre *mmproj*.gguf koyun)",
+                loaded_model.model_path
+            ))?,
+    };
+
+    let marked = insert_media_markers(prompt, images.len(), mtmd_default_marker())?;
+    let text = gguf::format_chat_prompt(&loaded_model.model, &[ChatMessage {
+        role: "user".to_string(),
+        content: marked,
+    }]);
+
+    // One vision request per model at a time; the projector context is not re-entrant
+    let mut projector_guard = loaded_model.vision.lock().unwrap_or_else(|p| p.into_inner());
+    let cached = projector_guard.as_ref().map(|p| p.mmproj_path == mmproj_path).unwrap_or(false);
+    if !cached {
+        *projector_guard = Some(load_projector(loaded_model, &mmproj_path)?);
+    }
+    let projector = projector_guard.as_ref().ok_or("Vision projektörü yüklenemedi")?;
+
+    let mut bitmaps = Vec::with_capacity(images.len());
+    for (idx, bytes) in images.iter().enumerate() {
+        sniff_image_format(bytes).map_err(|e| format!("Görüntü {}: {}", idx, e))?;
+        let bitmap = MtmdBitmap::from_buffer(&projector.ctx, bytes)
+            .map_err(|e| format!("Görüntü {} çözülemedi: {:?}", idx, e))?;
+        bitmaps.push(bitmap);
+    }
+    let bitmap_refs: Vec<&MtmdBitmap> = bitmaps.iter().collect();
+
+    let prompt_start = Instant::now();
+    let chunks = projector.ctx
+        .tokenize(
+            MtmdInputText { text, add_special: true, parse_special: true },
+            &bitmap_refs,
+        )
+        .map_err(|e| format!("Multimodal tokenization failed: {:?}", e))?;
+
+    let mut context = gguf::new_inference_context(loaded_model, backend)?;
+
+    // Text chunks are decoded as tokens, image chunks are encoded by the projector
+    // and their embeddings decoded in place
+    let n_past = chunks
+        .eval_chunks(&projector.ctx, &context, 0, 0, VISION_N_BATCH, true)
+        .map_err(|e| format!("Multimodal prompt evaluation failed: {:?}", e))?;
+    let prompt_eval_ms = prompt_start.elapsed().as_secs_f64() * 1000.0;
+    info!("✅ Vision prompt evaluated: {} positions, {} image(s), {:.0} ms", n_past, images.len(), prompt_eval_ms);
+
+    if n_past as u32 >= loaded_model.n_ctx {
+        warn!("⚠️ Vision prompt fills the context window ({} / {})", n_past, loaded_model.n_ctx);
+    }
+
+    // Image embeddings have no token ids; only the text takes part in the repetition penalty
+    let history = loaded_model.model.str_to_token(prompt, AddBos::Never).unwrap_or_default();
+    let mut batch = LlamaBatch::new(1, 1);
+
+    gguf::generate_from_context(
+        loaded_model, &mut context, &mut batch, &history, n_past, n_past as usize,
+        prompt_eval_ms, max_tokens, temperature, options, &mut |_| true,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG_1X1: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8BQDwAEhQGAhKmMIQAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_decode_image_payload() {
+        let plain = decode_image_payload(PNG_1X1).unwrap();
+        let data_url = decode_image_payload(&format!("data:image/png;base64,{}", PNG_1X1)).unwrap();
+        assert_eq!(plain, data_url);
+        assert_eq!(sniff_image_format(&plain), Ok(ImageFormat::Png));
+
+        let webp = general_purpose::STANDARD.encode(b"RIFF\0\0\0\0WEBPVP8 ");
+        assert!(decode_image_payload(&webp).unwrap_err().contains("WebP"));
+        assert!(decode_image_payload("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_insert_media_markers() {
+        let marker = "<__media__>";
+        assert_eq!(
+            insert_media_markers("Bu hata ne?", 2, marker).unwrap(),
+            "<__media__>\n<__media__>\nBu hata ne?"
+        );
+        let explicit = "Önce <__media__> sonra <__media__>";
+        assert_eq!(insert_media_markers(explicit, 2, marker).unwrap(), explicit);
+        assert!(insert_media_markers(explicit, 1, marker).is_err());
+    }
+
+    #[test]
+    fn test_find_mmproj_prefers_matching_prefix() {
+        let dir = std::env::temp_dir().join(format!("corex_mmproj_test_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "Qwen2-VL-2B-Instruct-Q4_K_M.gguf",
+            "mmproj-SmolVLM-f16.gguf",
+            "Qwen2-VL-2B-Instruct-mmproj-f16.gguf",
+            "notes.txt",
+        ] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        let found = find_mmproj(&dir.join("Qwen2-VL-2B-Instruct-Q4_K_M.gguf")).unwrap();
+        assert_eq!(found.file_name().unwrap(), "Qwen2-VL-2B-Instruct-mmproj-f16.gguf");
+
+        std::fs::remove_file(dir.join("Qwen2-VL-2B-Instruct-mmproj-f16.gguf")).unwrap();
+        std::fs::remove_file(dir.join("mmproj-SmolVLM-f16.gguf")).unwrap();
+        assert!(find_mmproj(&dir.join("Qwen2-VL-2B-Instruct-Q4_K_M.gguf")).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
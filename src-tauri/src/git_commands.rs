@@ -1,8 +1,14 @@
+use std::collections::HashMap;
+use std::path::Path;
 use std::process::Command;
 use log::info;
 
+use crate::prompts::{PromptLibrary, RenderedPrompt};
+
+/// Commit-message prompt for the staged diff, from the `commit_message` template
+/// (or the repo's `.corex/prompts/` override)
 #[tauri::command]
-pub async fn generate_semantic_commit_message(repo_path: String) -> Result<String, String> {
+pub async fn generate_semantic_commit_message(repo_path: String) -> Result<RenderedPrompt, String> {
     info!("🤖 Generating semantic commit message for: {}", repo_path);
     
     // 1. Get staged diff
@@ -18,11 +24,10 @@ pub async fn generate_semantic_commit_message(repo_path: String) -> Result<Strin
         return Err("No staged changes found to commit.".to_string());
     }
 
-    // 2. We'll return the diff to the frontend and let the frontend call chat_with_dynamic_ai 
-    // to generate the message. This keeps the prompt logic in the frontend.
-    // Or we could do it here if we had access to the AI service.
-    // For now, let's keep it simple and just provide the diff.
-    Ok(diff)
+    // 2. Render the prompt; the frontend sends it to the selected provider
+    let root = Path::new(&repo_path);
+    let variables = HashMap::from([("diff".to_string(), diff)]);
+    PromptLibrary::load(Some(root)).render("commit_message", &variables, Some(root))
 }
 
 #[tauri::command]
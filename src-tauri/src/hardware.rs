@@ -0,0 +1,309 @@
+// src-tauri/src/hardware.rs
+// Hardware / capability probe that needs no vendor tools: CPU model and SIMD
+// extensions, core counts, RAM, GPU backends compiled into this binary and GPU
+// devices found through sysfs/DRM. Also picks llama.cpp thread counts.
+
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimdSupport {
+    pub sse4_2: bool,
+    pub avx: bool,
+    pub avx2: bool,
+    pub fma: bool,
+    pub f16c: bool,
+    pub avx512f: bool,
+    pub avx512bw: bool,
+    pub neon: bool,
+    pub dotprod: bool,
+}
+
+impl SimdSupport {
+    /// Runtime detection for the current CPU
+    pub fn detect() -> Self {
+        #[allow(unused_mut)]
+        let mut simd = Self::default();
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            simd.sse4_2 = std::arch::is_x86_feature_detected!("sse4.2");
+            simd.avx = std::arch::is_x86_feature_detected!("avx");
+            simd.avx2 = std::arch::is_x86_feature_detected!("avx2");
+            simd.fma = std::arch::is_x86_feature_detected!("fma");
+            simd.f16c = std::arch::is_x86_feature_detected!("f16c");
+            simd.avx512f = std::arch::is_x86_feature_detected!("avx512f");
+            simd.avx512bw = std::arch::is_x86_feature_detected!("avx512bw");
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            simd.neon = std::arch::is_aarch64_feature_detected!("neon");
+            simd.dotprod = std::arch::is_aarch64_feature_detected!("dotprod");
+        }
+        simd
+    }
+
+    /// Names of the supported extensions, e.g. ["AVX", "AVX2", "FMA"]
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.sse4_2, "SSE4.2"), (self.avx, "AVX"), (self.avx2, "AVX2"), (self.fma, "FMA"),
+            (self.f16c, "F16C"), (self.avx512f, "AVX-512F"), (self.avx512bw, "AVX-512BW"),
+            (self.neon, "NEON"), (self.dotprod, "DOTPROD"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, name)| *name)
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpuInfo {
+    pub model: String,
+    pub vendor: String,
+    pub arch: String,
+    pub physical_cores: usize,
+    pub logical_cores: usize,
+    pub simd: SimdSupport,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryInfo {
+    pub total_bytes: u64,
+    pub available_bytes: u64,
+}
+
+/// GPU backends compiled into this binary
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildInfo {
+    pub cuda: bool,
+    pub vulkan: bool,
+    /// "CUDA", "Vulkan" or "CPU"
+    pub backend: String,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        let cuda = cfg!(feature = "cuda");
+        let vulkan = cfg!(feature = "vulkan");
+        let backend = if cuda { "CUDA" } else if vulkan { "Vulkan" } else { "CPU" };
+        Self { cuda, vulkan, backend: backend.to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpuDevice {
+    /// DRM card name, e.g. "card0"
+    pub card: String,
+    /// "nvidia", "amd", "intel", "apple" or "unknown"
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    /// Kernel driver bound to the device (amdgpu, i915, xe, nvidia, nouveau...)
+    pub driver: Option<String>,
+    /// Dedicated VRAM, when the driver exposes it (amdgpu does, the NVIDIA blob doesn't)
+    pub vram_total_bytes: Option<u64>,
+    pub vram_used_bytes: Option<u64>,
+}
+
+/// Thread counts for `LlamaContextParams`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadCounts {
+    /// Token generation is memory-bound: one thread per physical core
+    pub n_threads: i32,
+    /// Prompt processing is compute-bound: use every logical core
+    pub n_threads_batch: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HardwareReport {
+    pub cpu: CpuInfo,
+    pub memory: MemoryInfo,
+    pub build: BuildInfo,
+    pub gpus: Vec<GpuDevice>,
+    pub threads: ThreadCounts,
+}
+
+pub fn recommend_threads(physical_cores: usize, logical_cores: usize) -> ThreadCounts {
+    let logical = logical_cores.max(1);
+    let physical = if physical_cores == 0 { logical } else { physical_cores.min(logical) };
+    ThreadCounts { n_threads: physical as i32, n_threads_batch: logical as i32 }
+}
+
+pub fn vendor_name(vendor_id: u32) -> &'static str {
+    match vendor_id {
+        0x10de => "nvidia",
+        0x1002 | 0x1022 => "amd",
+        0x8086 => "intel",
+        0x106b => "apple",
+        _ => "unknown",
+    }
+}
+
+/// Parse sysfs ids like "0x10de\n"
+pub fn parse_hex_id(raw: &str) -> Option<u32> {
+    u32::from_str_radix(raw.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// Read one `/sys/class/drm/cardN` entry
+pub fn read_drm_card(card_dir: &Path) -> Option<GpuDevice> {
+    let device = card_dir.join("device");
+    let read = |name: &str| std::fs::read_to_string(device.join(name)).ok();
+    let vendor_id = parse_hex_id(&read("vendor")?)?;
+    let device_id = read("device").and_then(|d| parse_hex_id(&d)).unwrap_or(0);
+    let driver = std::fs::read_link(device.join("driver")).ok()
+        .and_then(|p| p.file_name().map(|f| f.to_string_lossy().into_owned()));
+    let bytes = |name: &str| read(name).and_then(|v| v.trim().parse::<u64>().ok());
+
+    Some(GpuDevice {
+        card: card_dir.file_name()?.to_string_lossy().into_owned(),
+        vendor: vendor_name(vendor_id).to_string(),
+        vendor_id,
+        device_id,
+        driver,
+        vram_total_bytes: bytes("mem_info_vram_total"),
+        vram_used_bytes: bytes("mem_info_vram_used"),
+    })
+}
+
+/// GPUs listed under a DRM class directory (connectors such as card0-HDMI-A-1 are skipped)
+pub fn scan_drm(drm_dir: &Path) -> Vec<GpuDevice> {
+    let Ok(entries) = std::fs::read_dir(drm_dir) else {
+        return Vec::new();
+    };
+    let mut gpus: Vec<GpuDevice> = entries.flatten()
+        .filter(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            name.strip_prefix("card").map(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit())).unwrap_or(false)
+        })
+        .filter_map(|e| read_drm_card(&e.path()))
+        .collect();
+    gpus.sort_by(|a, b| a.card.cmp(&b.card));
+    gpus
+}
+
+pub fn probe_gpus() -> Vec<GpuDevice> {
+    if cfg!(target_os = "linux") {
+        scan_drm(Path::new("/sys/class/drm"))
+    } else {
+        Vec::new()
+    }
+}
+
+/// Largest dedicated VRAM reported by sysfs, if any
+pub fn largest_vram_bytes(gpus: &[GpuDevice]) -> Option<u64> {
+    gpus.iter().filter_map(|g| g.vram_total_bytes).max()
+}
+
+pub fn probe_cpu() -> CpuInfo {
+    use sysinfo::System;
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    let first = sys.cpus().first();
+    CpuInfo {
+        model: first.map(|c| c.brand().trim().to_string()).unwrap_or_default(),
+        vendor: first.map(|c| c.vendor_id().trim().to_string()).unwrap_or_default(),
+        arch: std::env::consts::ARCH.to_string(),
+        physical_cores: sys.physical_core_count().unwrap_or(0),
+        logical_cores: sys.cpus().len(),
+        simd: SimdSupport::detect(),
+    }
+}
+
+pub fn probe_memory() -> MemoryInfo {
+    use sysinfo::System;
+    let mut sys = System::new();
+    sys.refresh_memory();
+    MemoryInfo { total_bytes: sys.total_memory(), available_bytes: sys.available_memory() }
+}
+
+pub fn probe() -> HardwareReport {
+    let cpu = probe_cpu();
+    let threads = recommend_threads(cpu.physical_cores, cpu.logical_cores);
+    HardwareReport {
+        cpu,
+        memory: probe_memory(),
+        build: BuildInfo::current(),
+        gpus: probe_gpus(),
+        threads,
+    }
+}
+
+/// The CPU doesn't change while the app runs, so thread counts are probed once
+static THREADS: Lazy<ThreadCounts> = Lazy::new(|| {
+    let cpu = probe_cpu();
+    let threads = recommend_threads(cpu.physical_cores, cpu.logical_cores);
+    info!("🧵 {} ({}): {} physical / {} logical cores, SIMD [{}] -> n_threads {}, n_threads_batch {}",
+        cpu.model, cpu.arch, cpu.physical_cores, cpu.logical_cores, cpu.simd.names().join(", "),
+        threads.n_threads, threads.n_threads_batch);
+    threads
+});
+
+pub fn thread_counts() -> ThreadCounts {
+    *THREADS
+}
+
+/// Full hardware and capability report
+#[tauri::command]
+pub async fn get_hardware_report() -> Result<HardwareReport, String> {
+    tokio::task::spawn_blocking(probe)
+        .await
+        .map_err(|e| format!("Donanım taraması başarısız: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recommend_threads() {
+        assert_eq!(recommend_threads(8, 16), ThreadCounts { n_threads: 8, n_threads_batch: 16 });
+        // sysinfo can't always tell physical cores apart
+        assert_eq!(recommend_threads(0, 4), ThreadCounts { n_threads: 4, n_threads_batch: 4 });
+        assert_eq!(recommend_threads(0, 0), ThreadCounts { n_threads: 1, n_threads_batch: 1 });
+    }
+
+    #[test]
+    fn test_parse_ids() {
+        assert_eq!(parse_hex_id("0x10de\n"), Some(0x10de));
+        assert_eq!(parse_hex_id("1002"), Some(0x1002));
+        assert_eq!(parse_hex_id("zz"), None);
+        assert_eq!(vendor_name(0x8086), "intel");
+        assert_eq!(vendor_name(0x1234), "unknown");
+    }
+
+    #[test]
+    fn test_scan_drm() {
+        let root = std::env::temp_dir().join(format!("corex_drm_{}", uuid::Uuid::new_v4().simple()));
+        let amd = root.join("card1/device");
+        std::fs::create_dir_all(&amd).unwrap();
+        std::fs::write(amd.join("vendor"), "0x1002\n").unwrap();
+        std::fs::write(amd.join("device"), "0x744c\n").unwrap();
+        std::fs::write(amd.join("mem_info_vram_total"), "25753026560\n").unwrap();
+        std::fs::write(amd.join("mem_info_vram_used"), "1048576\n").unwrap();
+        let intel = root.join("card0/device");
+        std::fs::create_dir_all(&intel).unwrap();
+        std::fs::write(intel.join("vendor"), "0x8086\n").unwrap();
+        std::fs::create_dir_all(root.join("card1-DP-1")).unwrap();
+        std::fs::create_dir_all(root.join("renderD128")).unwrap();
+
+        let gpus = scan_drm(&root);
+        std::fs::remove_dir_all(&root).ok();
+
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].card, "card0");
+        assert_eq!(gpus[0].vendor, "intel");
+        assert_eq!(gpus[0].vram_total_bytes, None);
+        assert_eq!(gpus[1].vendor, "amd");
+        assert_eq!(gpus[1].device_id, 0x744c);
+        assert_eq!(gpus[1].vram_used_bytes, Some(1048576));
+        assert_eq!(largest_vram_bytes(&gpus), Some(25753026560));
+    }
+
+    #[test]
+    fn test_simd_names() {
+        let simd = SimdSupport { avx2: true, neon: false, avx512f: true, ..Default::default() };
+        assert_eq!(simd.names(), vec!["AVX2", "AVX-512F"]);
+    }
+}
@@ -0,0 +1,402 @@
+// src-tauri/src/http_client.rs
+// Shared HTTP plumbing for AI requests:
+// - one pooled client with a connect timeout (per-request timeouts on top)
+// - retries on 429/5xx and connection errors with exponential backoff,
+//   honouring Retry-After / retry-after-ms
+// - a circuit breaker per provider endpoint, so a dead server (e.g. LM Studio
+//   not running) fails fast and fallback chains can move on
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Whole non-streaming request; local models on CPU can take minutes
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Longest silence allowed between two streamed chunks
+pub const READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Consecutive failed requests before a circuit opens
+const FAILURE_THRESHOLD: u32 = 5;
+const OPEN_FOR: Duration = Duration::from_secs(30);
+
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .build()
+        .unwrap_or_else(|_| Client::new())
+});
+
+/// The app-wide client; cheap to clone, shares one connection pool
+pub fn client() -> Client {
+    CLIENT.clone()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    /// Upper bound for one wait; a longer Retry-After gives up instead
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 3, base_delay_ms: 500, max_delay_ms: 30_000 }
+    }
+}
+
+impl RetryPolicy {
+    /// base * 2^attempt, capped, plus up to 25% jitter
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self.base_delay_ms.saturating_mul(1u64 << attempt.min(20)).min(self.max_delay_ms);
+        let jitter = rand::thread_rng().gen_range(0..=delay / 4);
+        Duration::from_millis((delay + jitter).min(self.max_delay_ms))
+    }
+}
+
+/// Server-requested wait: `retry-after-ms` (OpenAI) or `Retry-After` in seconds
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::trim);
+    if let Some(ms) = value("retry-after-ms").and_then(|v| v.parse::<f64>().ok()) {
+        return Some(Duration::from_millis(ms.max(0.0) as u64));
+    }
+    value("retry-after")
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(|secs| Duration::from_millis((secs.max(0.0) * 1000.0) as u64))
+}
+
+/// 429, 5xx and Anthropic's 529 "overloaded"
+pub fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() || status.as_u16() == 529
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    /// The breaker is open; nothing was sent
+    CircuitOpen(String),
+    Network(String),
+    Status { status: StatusCode, body: String },
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HttpError::CircuitOpen(message) | HttpError::Network(message) => write!(f, "{}", message),
+            HttpError::Status { status, body } => write!(f, "HTTP {}: {}", status, body),
+        }
+    }
+}
+
+// --------------------
+// CIRCUIT BREAKER
+// --------------------
+
+#[derive(Default)]
+struct Breaker {
+    failures: u32,
+    open_until: Option<Instant>,
+    /// Half-open: one request is probing whether the endpoint recovered
+    probing: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CircuitStatus {
+    pub key: String,
+    /// "closed", "open" or "half_open"
+    pub state: String,
+    pub failures: u32,
+    pub retry_in_secs: u64,
+}
+
+static BREAKERS: Lazy<Mutex<HashMap<String, Breaker>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Breaker key for a request: provider id plus the endpoint's origin
+pub fn circuit_key(provider_id: &str, url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(u) => format!("{}@{}", provider_id, u.origin().ascii_serialization()),
+        Err(_) => provider_id.to_string(),
+    }
+}
+
+/// Ok(true) when this request is the half-open probe
+fn check_circuit(key: &str) -> Result<bool, HttpError> {
+    let mut breakers = BREAKERS.lock().unwrap_or_else(|p| p.into_inner());
+    let Some(breaker) = breakers.get_mut(key) else { return Ok(false) };
+    match breaker.open_until {
+        Some(until) if until > Instant::now() => Err(HttpError::CircuitOpen(format!(
+            "{} geçici olarak devre dışı ({} sn sonra tekrar denenecek)",
+            key,
+            (until - Instant::now()).as_secs() + 1
+        ))),
+        Some(_) if breaker.probing => Err(HttpError::CircuitOpen(format!("{} için deneme isteği sürüyor", key))),
+        Some(_) => {
+            breaker.probing = true;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Ends a half-open probe however the request finishes, including when the
+/// future is dropped mid-flight (e.g. a cancelled stream)
+struct ProbeGuard<'a>(Option<&'a str>);
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        let Some(key) = self.0 else { return };
+        if let Some(breaker) = BREAKERS.lock().unwrap_or_else(|p| p.into_inner()).get_mut(key) {
+            breaker.probing = false;
+        }
+    }
+}
+
+fn record_success(key: &str) {
+    BREAKERS.lock().unwrap_or_else(|p| p.into_inner()).remove(key);
+}
+
+fn record_failure(key: &str) {
+    let mut breakers = BREAKERS.lock().unwrap_or_else(|p| p.into_inner());
+    let breaker = breakers.entry(key.to_string()).or_default();
+    breaker.failures += 1;
+    breaker.probing = false;
+    if breaker.failures >= FAILURE_THRESHOLD {
+        if breaker.open_until.map_or(true, |until| until <= Instant::now()) {
+            warn!("🔌 Devre açıldı: {} ({} ardışık hata)", key, breaker.failures);
+        }
+        breaker.open_until = Some(Instant::now() + OPEN_FOR);
+    }
+}
+
+pub fn circuit_statuses() -> Vec<CircuitStatus> {
+    let breakers = BREAKERS.lock().unwrap_or_else(|p| p.into_inner());
+    let now = Instant::now();
+    let mut statuses: Vec<CircuitStatus> = breakers.iter()
+        .map(|(key, b)| {
+            let (state, retry_in_secs) = match b.open_until {
+                Some(until) if until > now => ("open", (until - now).as_secs() + 1),
+                Some(_) => ("half_open", 0),
+                None => ("closed", 0),
+            };
+            CircuitStatus { key: key.clone(), state: state.to_string(), failures: b.failures, retry_in_secs }
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.key.cmp(&b.key));
+    statuses
+}
+
+// --------------------
+// RETRYING SEND
+// --------------------
+
+/// Send the request built by `build` (called once per attempt) with retries and
+/// circuit breaking under `key`. Non-retryable statuses come back as
+/// `HttpError::Status` without retrying. Of the transport errors only failed
+/// connects are retried; a request that reached the server (e.g. a slow
+/// generation that timed out) would run and be billed again.
+pub async fn send_with_retry<F>(key: &str, policy: &RetryPolicy, build: F) -> Result<Response, HttpError>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let probing = check_circuit(key)?;
+    let _probe = ProbeGuard(probing.then_some(key));
+    let mut attempt = 0;
+    loop {
+        let (error, wait) = match build().send().await {
+            Ok(res) if res.status().is_success() => {
+                record_success(key);
+                return Ok(res);
+            }
+            Ok(res) => {
+                let status = res.status();
+                let wait = retry_after(res.headers());
+                let body = res.text().await.unwrap_or_default();
+                if !is_retryable(status) {
+                    // The endpoint answered; it's the request that is wrong
+                    record_success(key);
+                    return Err(HttpError::Status { status, body });
+                }
+                (HttpError::Status { status, body }, wait)
+            }
+            Err(e) if e.is_connect() => (HttpError::Network(e.to_string()), None),
+            // A slow answer says nothing about the endpoint's health
+            Err(e) if e.is_timeout() => return Err(HttpError::Network(e.to_string())),
+            Err(e) => {
+                record_failure(key);
+                return Err(HttpError::Network(e.to_string()));
+            }
+        };
+
+        let delay = wait.unwrap_or_else(|| policy.backoff(attempt));
+        if attempt >= policy.max_retries || delay > Duration::from_millis(policy.max_delay_ms) {
+            record_failure(key);
+            return Err(error);
+        }
+        attempt += 1;
+        info!("🔁 {} yeniden deneniyor ({}/{}, {} ms): {:?}", key, attempt, policy.max_retries, delay.as_millis(), error);
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Breaker state of every endpoint that failed recently
+#[tauri::command]
+pub fn get_provider_health() -> Vec<CircuitStatus> {
+    circuit_statuses()
+}
+
+/// Close one circuit (or all when `key` is None) after fixing the endpoint
+#[tauri::command]
+pub fn reset_provider_circuit(key: Option<String>) {
+    let mut breakers = BREAKERS.lock().unwrap_or_else(|p| p.into_inner());
+    match key {
+        Some(key) => {
+            breakers.remove(&key);
+        }
+        None => breakers.clear(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tiny_http::{Header, Response as HttpResponse, Server};
+
+    fn fast() -> RetryPolicy {
+        RetryPolicy { max_retries: 3, base_delay_ms: 1, max_delay_ms: 200 }
+    }
+
+    /// Answers the n-th request with `replies[n]` (status, Retry-After); counts requests
+    fn scripted(replies: Vec<(u16, Option<&'static str>)>) -> (String, Arc<AtomicUsize>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            for (request, (status, retry)) in server.incoming_requests().zip(replies) {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut response = HttpResponse::from_string(format!("status {}", status)).with_status_code(status);
+                if let Some(retry) = retry {
+                    response = response.with_header(Header::from_bytes("Retry-After", retry).unwrap());
+                }
+                let _ = request.respond(response);
+            }
+        });
+        (addr, hits)
+    }
+
+    #[test]
+    fn test_retry_after_and_backoff() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "2".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+        headers.insert("retry-after-ms", "150".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(150)));
+        assert_eq!(retry_after(&HeaderMap::new()), None);
+
+        let policy = RetryPolicy::default();
+        assert!(policy.backoff(0) >= Duration::from_millis(500) && policy.backoff(0) <= Duration::from_millis(625));
+        assert_eq!(policy.backoff(30), Duration::from_millis(30_000));
+        assert!(is_retryable(StatusCode::from_u16(529).unwrap()));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (addr, hits) = scripted(vec![(503, None), (429, Some("0")), (200, None)]);
+        let res = send_with_retry("test@retry", &fast(), || client().get(&addr)).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_on_long_retry_after_and_client_errors() {
+        let (addr, hits) = scripted(vec![(429, Some("60")), (400, None)]);
+        let err = send_with_retry("test@long", &fast(), || client().get(&addr)).await.unwrap_err();
+        assert!(matches!(err, HttpError::Status { status, .. } if status == 429));
+        let err = send_with_retry("test@long", &fast(), || client().get(&addr)).await.unwrap_err();
+        assert!(matches!(err, HttpError::Status { status, .. } if status == 400));
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_fails_fast() {
+        let key = "test@breaker";
+        for _ in 0..FAILURE_THRESHOLD {
+            record_failure(key);
+        }
+        let status = circuit_statuses().into_iter().find(|s| s.key == key).unwrap();
+        assert_eq!(status.state, "open");
+
+        // Nothing is sent while open
+        let (addr, hits) = scripted(vec![(200, None)]);
+        let err = send_with_retry(key, &fast(), || client().get(&addr)).await.unwrap_err();
+        assert!(matches!(err, HttpError::CircuitOpen(_)));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+
+        // Half-open lets one probe through; success closes the circuit
+        BREAKERS.lock().unwrap().get_mut(key).unwrap().open_until = Some(Instant::now());
+        send_with_retry(key, &fast(), || client().get(&addr)).await.unwrap();
+        assert!(circuit_statuses().iter().all(|s| s.key != key));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_probe_releases_half_open() {
+        let key = "test@cancelled-probe";
+        for _ in 0..FAILURE_THRESHOLD {
+            record_failure(key);
+        }
+        BREAKERS.lock().unwrap().get_mut(key).unwrap().open_until = Some(Instant::now());
+
+        // Accepts the connection but never answers
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let probe = send_with_retry(key, &fast(), || client().get(&addr));
+        assert!(tokio::time::timeout(Duration::from_millis(100), probe).await.is_err());
+
+        assert!(!BREAKERS.lock().unwrap()[key].probing);
+        let (addr, _) = scripted(vec![(200, None)]);
+        send_with_retry(key, &fast(), || client().get(&addr)).await.unwrap();
+        drop(listener);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_post_is_sent_once() {
+        // Reads the request but never answers
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        std::thread::spawn(move || {
+            let mut pending = Vec::new();
+            for request in server.incoming_requests() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                pending.push(request);
+            }
+        });
+
+        let key = "test@timeout";
+        let err = send_with_retry(key, &fast(), || {
+            client().post(&addr).body("{}").timeout(Duration::from_millis(100))
+        }).await.unwrap_err();
+        assert!(matches!(err, HttpError::Network(_)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        assert!(circuit_statuses().iter().all(|s| s.key != key));
+    }
+
+    #[test]
+    fn test_circuit_key() {
+        assert_eq!(circuit_key("openai", "http://127.0.0.1:1234/v1/chat/completions"), "openai@http://127.0.0.1:1234");
+        assert_eq!(circuit_key("gemini", "not a url"), "gemini");
+    }
+}
@@ -0,0 +1,370 @@
+// src-tauri/src/inference.rs
+// One inference API for every local entry point: the Tauri commands, the
+// streaming path and the OpenAI-compatible server all go through
+// `InferenceEngine`, which delegates to an `InferenceBackend` (the llama.cpp
+// model pool in the app, a scripted fake in tests).
+
+use llama_cpp_2::model::AddBos;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::commands::ChatMessage;
+use crate::gguf::{self, GgufState};
+use crate::gguf_completion::{CompletionOptions, GgufCompletion};
+use crate::gguf_speculative;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadRequest {
+    pub model_path: String,
+    pub n_ctx: u32,
+    pub n_gpu_layers: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerateRequest {
+    pub prompt: String,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    #[serde(default)]
+    pub options: CompletionOptions,
+}
+
+/// A runtime that can hold models and generate text
+pub trait InferenceBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Load (or reload) a model; returns the id later calls use
+    fn load(&self, request: &LoadRequest) -> Result<String, String>;
+
+    /// Unload one model; Ok(false) if it was not loaded
+    fn unload(&self, model_id: &str) -> Result<bool, String>;
+
+    /// Unload everything and release the runtime
+    fn unload_all(&self);
+
+    /// Loaded model ids, most recently used first
+    fn loaded_models(&self) -> Vec<String>;
+
+    /// Render chat messages with the model's own chat template
+    fn format_chat(&self, model_id: &str, messages: &[ChatMessage]) -> Result<String, String>;
+
+    /// Prompt length in tokens (including BOS)
+    fn count_tokens(&self, model_id: &str, text: &str) -> Result<usize, String>;
+
+    /// Max prompt tokens the model was loaded with
+    fn context_size(&self, model_id: &str) -> Result<usize, String>;
+
+    /// Generate a completion, handing each text piece to `on_text`.
+    /// Returning `false` from the callback cancels generation.
+    fn generate(
+        &self,
+        model_id: &str,
+        request: &GenerateRequest,
+        on_text: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<GgufCompletion, String>;
+}
+
+/// Front door for local inference; cheap to clone
+#[derive(Clone)]
+pub struct InferenceEngine {
+    backend: Arc<dyn InferenceBackend>,
+}
+
+impl InferenceEngine {
+    pub fn new(backend: Arc<dyn InferenceBackend>) -> Self {
+        Self { backend }
+    }
+
+    /// Engine over the app's shared llama.cpp model pool
+    pub fn llama_cpp(pool: Arc<Mutex<GgufState>>) -> Self {
+        Self::new(Arc::new(LlamaCppBackend::new(pool)))
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        self.backend.name()
+    }
+
+    pub fn load(&self, request: &LoadRequest) -> Result<String, String> {
+        self.backend.load(request)
+    }
+
+    /// Unload one model, or all of them when `model_id` is None
+    pub fn unload(&self, model_id: Option<&str>) -> Result<(), String> {
+        match model_id {
+            Some(id) => {
+                if self.backend.unload(id)? {
+                    Ok(())
+                } else {
+                    Err(format!("Model havuzda bulunamadı: {}", id))
+                }
+            }
+            None => {
+                self.backend.unload_all();
+                Ok(())
+            }
+        }
+    }
+
+    pub fn loaded_models(&self) -> Vec<String> {
+        self.backend.loaded_models()
+    }
+
+    /// The requested model if it is loaded, else the most recently used one
+    pub fn resolve_model(&self, requested: Option<&str>) -> Result<String, String> {
+        let loaded = self.backend.loaded_models();
+        match requested {
+            Some(id) if loaded.iter().any(|m| m == id) => Ok(id.to_string()),
+            Some(id) => Err(format!("Model havuzda bulunamadı: {}", id)),
+            None => loaded.into_iter().next().ok_or_else(|| "No models loaded".to_string()),
+        }
+    }
+
+    pub fn format_chat(&self, model_id: &str, messages: &[ChatMessage]) -> Result<String, String> {
+        self.backend.format_chat(model_id, messages)
+    }
+
+    pub fn count_tokens(&self, model_id: &str, text: &str) -> Result<usize, String> {
+        self.backend.count_tokens(model_id, text)
+    }
+
+    pub fn context_size(&self, model_id: &str) -> Result<usize, String> {
+        self.backend.context_size(model_id)
+    }
+
+    pub fn generate(&self, model_id: &str, request: &GenerateRequest) -> Result<GgufCompletion, String> {
+        self.stream(model_id, request, &mut |_| true)
+    }
+
+    pub fn stream(
+        &self,
+        model_id: &str,
+        request: &GenerateRequest,
+        on_text: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<GgufCompletion, String> {
+        if request.max_tokens == 0 {
+            return Err("max_tokens 0 olamaz".to_string());
+        }
+        info!("🧠 [{}] {} — prompt {} chars, max_tokens {}, temperature {}",
+            self.backend.name(), model_id, request.prompt.len(), request.max_tokens, request.temperature);
+        self.backend.generate(model_id, request, on_text)
+    }
+}
+
+/// llama.cpp backend over the shared `GgufState` pool
+pub struct LlamaCppBackend {
+    pool: Arc<Mutex<GgufState>>,
+}
+
+impl LlamaCppBackend {
+    pub fn new(pool: Arc<Mutex<GgufState>>) -> Self {
+        Self { pool }
+    }
+}
+
+impl InferenceBackend for LlamaCppBackend {
+    fn name(&self) -> &'static str {
+        "llama.cpp"
+    }
+
+    fn load(&self, request: &LoadRequest) -> Result<String, String> {
+        gguf::load_into_pool(&self.pool, &request.model_path, request.n_ctx, request.n_gpu_layers)
+    }
+
+    fn unload(&self, model_id: &str) -> Result<bool, String> {
+        Ok(self.pool.lock().unwrap_or_else(|p| p.into_inner()).remove_model(model_id))
+    }
+
+    fn unload_all(&self) {
+        self.pool.lock().unwrap_or_else(|p| p.into_inner()).unload_all();
+    }
+
+    fn loaded_models(&self) -> Vec<String> {
+        self.pool.lock().unwrap_or_else(|p| p.into_inner()).models_by_recency()
+    }
+
+    fn format_chat(&self, model_id: &str, messages: &[ChatMessage]) -> Result<String, String> {
+        let (loaded, _) = self.pool.lock().unwrap_or_else(|p| p.into_inner()).checkout(model_id)?;
+        Ok(gguf::format_chat_prompt(&loaded.model, messages))
+    }
+
+    fn count_tokens(&self, model_id: &str, text: &str) -> Result<usize, String> {
+        let (loaded, _) = self.pool.lock().unwrap_or_else(|p| p.into_inner()).checkout(model_id)?;
+        loaded.model.str_to_token(text, AddBos::Always)
+            .map(|t| t.len())
+            .map_err(|e| format!("Tokenization failed: {:?}", e))
+    }
+
+    fn context_size(&self, model_id: &str) -> Result<usize, String> {
+        let (loaded, _) = self.pool.lock().unwrap_or_else(|p| p.into_inner()).checkout(model_id)?;
+        Ok(loaded.n_ctx as usize)
+    }
+
+    fn generate(
+        &self,
+        model_id: &str,
+        request: &GenerateRequest,
+        on_text: &mut dyn FnMut(&str) -> bool,
+    ) -> Result<GgufCompletion, String> {
+        let (loaded, backend, draft) = {
+            let mut guard = self.pool.lock().unwrap_or_else(|p| p.into_inner());
+            let (loaded, backend) = guard.checkout(model_id)?;
+            // Speculative decoding has no logprobs, context shift or JSON mode, so those requests stay on the target
+            let pairing = guard.speculative.get(model_id).cloned()
+                .filter(|_| request.options.top_logprobs.is_none() && request.options.n_keep.is_none() && !request.options.json);
+            let draft = match pairing {
+                Some(p) => match guard.checkout(&p.draft_model_path) {
+                    Ok((draft, _)) => Some((draft, p.n_draft)),
+                    Err(e) => {
+                        warn!("⚠️ Draft model kullanılamıyor, normal decoding: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            (loaded, backend, draft)
+        };
+
+        match draft {
+            Some((draft, n_draft)) => gguf_speculative::run_speculative_completion(
+                &loaded, &draft, &backend, &request.prompt, request.max_tokens, request.temperature,
+                n_draft, &request.options, on_text,
+            ),
+            None => gguf::run_completion_with_callback(
+                &loaded, &backend, &request.prompt, request.max_tokens, request.temperature,
+                &request.options, on_text,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf_completion::{CompletionTimings, FinishReason};
+
+    /// Emits the prompt back word by word
+    #[derive(Default)]
+    struct EchoBackend {
+        models: Mutex<Vec<String>>,
+    }
+
+    impl InferenceBackend for EchoBackend {
+        fn name(&self) -> &'static str { "echo" }
+
+        fn load(&self, request: &LoadRequest) -> Result<String, String> {
+            self.models.lock().unwrap().insert(0, request.model_path.clone());
+            Ok(request.model_path.clone())
+        }
+
+        fn unload(&self, model_id: &str) -> Result<bool, String> {
+            let mut models = self.models.lock().unwrap();
+            let before = models.len();
+            models.retain(|m| m != model_id);
+            Ok(models.len() != before)
+        }
+
+        fn unload_all(&self) {
+            self.models.lock().unwrap().clear();
+        }
+
+        fn loaded_models(&self) -> Vec<String> {
+            self.models.lock().unwrap().clone()
+        }
+
+        fn format_chat(&self, _model_id: &str, messages: &[ChatMessage]) -> Result<String, String> {
+            Ok(messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>().join(" "))
+        }
+
+        fn count_tokens(&self, _model_id: &str, text: &str) -> Result<usize, String> {
+            Ok(text.split_whitespace().count())
+        }
+
+        fn context_size(&self, _model_id: &str) -> Result<usize, String> {
+            Ok(512)
+        }
+
+        fn generate(&self, _model_id: &str, request: &GenerateRequest, on_text: &mut dyn FnMut(&str) -> bool) -> Result<GgufCompletion, String> {
+            let mut text = String::new();
+            let mut finish_reason = FinishReason::Stop;
+            for (i, word) in request.prompt.split_whitespace().enumerate() {
+                if i as u32 == request.max_tokens {
+                    finish_reason = FinishReason::Length;
+                    break;
+                }
+                let piece = format!("{} ", word);
+                text.push_str(&piece);
+                if !on_text(&piece) {
+                    finish_reason = FinishReason::Cancelled;
+                    break;
+                }
+            }
+            Ok(GgufCompletion {
+                completion_tokens: text.split_whitespace().count(),
+                text,
+                finish_reason,
+                stop_sequence: None,
+                prompt_tokens: request.prompt.split_whitespace().count(),
+                timings: CompletionTimings::default(),
+                logprobs: None,
+                speculative: None,
+                context_shift: None,
+                cache: None,
+            })
+        }
+    }
+
+    fn request(prompt: &str, max_tokens: u32) -> GenerateRequest {
+        GenerateRequest { prompt: prompt.to_string(), max_tokens, temperature: 0.0, options: CompletionOptions::default() }
+    }
+
+    fn engine_with(models: &[&str]) -> InferenceEngine {
+        let engine = InferenceEngine::new(Arc::new(EchoBackend::default()));
+        for m in models {
+            engine.load(&LoadRequest { model_path: m.to_string(), n_ctx: 512, n_gpu_layers: 0 }).unwrap();
+        }
+        engine
+    }
+
+    #[test]
+    fn test_resolve_model() {
+        let engine = engine_with(&["a.gguf", "b.gguf"]);
+        assert_eq!(engine.resolve_model(None).unwrap(), "b.gguf");
+        assert_eq!(engine.resolve_model(Some("a.gguf")).unwrap(), "a.gguf");
+        assert!(engine.resolve_model(Some("c.gguf")).is_err());
+        assert!(engine_with(&[]).resolve_model(None).is_err());
+    }
+
+    #[test]
+    fn test_stream_and_cancel() {
+        let engine = engine_with(&["m.gguf"]);
+        let mut pieces = Vec::new();
+        let completion = engine.stream("m.gguf", &request("one two three", 10), &mut |t| {
+            pieces.push(t.to_string());
+            true
+        }).unwrap();
+        assert_eq!(pieces, vec!["one ", "two ", "three "]);
+        assert_eq!(completion.finish_reason, FinishReason::Stop);
+
+        let mut seen = 0;
+        let cancelled = engine.stream("m.gguf", &request("one two three", 10), &mut |_| {
+            seen += 1;
+            seen < 2
+        }).unwrap();
+        assert_eq!(cancelled.finish_reason, FinishReason::Cancelled);
+        assert_eq!(cancelled.text, "one two ");
+
+        assert_eq!(engine.generate("m.gguf", &request("one two three", 2)).unwrap().finish_reason, FinishReason::Length);
+        assert!(engine.generate("m.gguf", &request("one", 0)).is_err());
+    }
+
+    #[test]
+    fn test_unload() {
+        let engine = engine_with(&["a.gguf", "b.gguf"]);
+        engine.unload(Some("a.gguf")).unwrap();
+        assert_eq!(engine.loaded_models(), vec!["b.gguf".to_string()]);
+        assert!(engine.unload(Some("a.gguf")).is_err());
+        engine.unload(None).unwrap();
+        assert!(engine.loaded_models().is_empty());
+    }
+}
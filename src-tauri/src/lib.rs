@@ -3,20 +3,46 @@
 // This is the library entry point for Tauri 2.x
 // The main.rs file will call run() from here
 
+pub mod agent;
 pub mod collab;
 pub mod commands;
+pub mod conversations;
+pub mod credentials;
 pub mod docker;
 pub mod gguf;
+pub mod gguf_benchmark;
+pub mod gguf_completion;
+pub mod gguf_context;
+pub mod gguf_embedding;
+pub mod gguf_fim;
+pub mod gguf_lora;
+pub mod gguf_pool;
+pub mod gguf_reader;
+pub mod gguf_speculative;
+pub mod gguf_tools;
+pub mod gguf_vision;
 pub mod git_commands;
+pub mod hardware;
+pub mod http_client;
+pub mod inference;
 pub mod mcp;
+pub mod model_download;
+pub mod model_library;
+pub mod model_router;
 pub mod oauth;
 pub mod oauth_backend;
+pub mod openai_server;
 pub mod p2p;
 pub mod process_monitor;
+pub mod prompts;
+pub mod providers;
 pub mod rag_pipeline;
 pub mod remote;
+pub mod response_cache;
+pub mod sse;
 pub mod streaming;
 pub mod tree_sitter_parser;
+pub mod usage;
 pub mod vector_db;
 pub mod window_manager;
 
@@ -5,20 +5,22 @@
 
 // Use modules from lib
 use corex_lib::{
-    collab, commands, docker, gguf, git_commands, mcp, oauth, oauth_backend, 
-    remote, streaming, window_manager, p2p
+    agent, collab, commands, conversations, credentials, docker, gguf, gguf_benchmark, gguf_context, gguf_embedding, gguf_fim, gguf_lora, gguf_speculative, gguf_tools, git_commands, hardware, http_client, mcp, model_download, model_library, model_router, oauth, oauth_backend, 
+    openai_server, prompts, providers, remote, response_cache, streaming, usage, window_manager, p2p
 };
 use corex_lib::process_monitor::{ProcessMonitor, MonitorState};
 use corex_lib::gguf::GgufState;
 use corex_lib::collab::CollabState;
 use corex_lib::mcp::McpState;
+use corex_lib::openai_server::OpenAiServerState;
 
 use tauri::Manager;
 use std::sync::{Arc, Mutex};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    env_logger::init();
+    // Log lines pass through the credential redaction layer
+    credentials::init_logging();
 
     // 🎯 CUDA Kernel Cache - Prevent recompilation
     #[cfg(feature = "cuda")]
@@ -49,6 +51,7 @@ pub fn run() {
         .manage(collab_state)
         .manage(p2p_state)
         .manage(monitor_state.clone())
+        .manage(OpenAiServerState::default())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::new().build())
@@ -69,6 +72,13 @@ pub fn run() {
                 }
             });
 
+            // Token/cost ledger for every AI call
+            usage::init_for_app(app.handle());
+            // Opt-in response cache for deterministic requests
+            response_cache::init_for_app(app.handle());
+            // Credential vault (device-key vaults open here) and OAuth refresh task
+            credentials::init_for_app(app.handle());
+
             // Initialize ProcessMonitor
             let mut monitor = monitor_state.0.lock().unwrap();
             *monitor = Some(ProcessMonitor::new(app.handle().clone()));
@@ -83,6 +93,32 @@ pub fn run() {
             commands::chat_with_ai,
             commands::chat_with_specific_ai,
             commands::chat_with_dynamic_ai,
+            conversations::create_conversation,
+            conversations::append_conversation_message,
+            conversations::get_conversation,
+            conversations::list_conversations,
+            conversations::rename_conversation,
+            conversations::delete_conversation,
+            conversations::fork_conversation,
+            conversations::search_conversations,
+            conversations::export_conversation,
+            providers::list_chat_providers,
+            providers::chat_with_provider,
+            http_client::get_provider_health,
+            http_client::reset_provider_circuit,
+            model_router::get_fallback_chains,
+            model_router::set_fallback_chain,
+            model_router::chat_with_fallback,
+            usage::set_usage_project,
+            usage::get_usage_totals,
+            usage::list_usage_records,
+            usage::get_usage_settings,
+            usage::get_default_model_prices,
+            usage::set_usage_settings,
+            usage::get_budget_status,
+            response_cache::get_response_cache_stats,
+            response_cache::set_response_cache_settings,
+            response_cache::clear_response_cache,
             commands::create_embedding_bge,
             commands::test_project,
             commands::open_terminal,
@@ -107,30 +143,87 @@ pub fn run() {
             commands::git_log_project,
             commands::git_blame,
             git_commands::generate_semantic_commit_message,
+            prompts::list_prompt_templates,
+            prompts::render_prompt,
             git_commands::git_create_branch,
             git_commands::git_smart_commit,
             commands::execute_command,
             commands::test_provider_connection,
             gguf::load_gguf_model,
             gguf::chat_with_gguf_model,
+            gguf::complete_with_gguf_model,
             gguf::chat_with_gguf_vision,
+            gguf_context::chat_with_gguf_conversation,
+            gguf_tools::chat_with_gguf_tools,
             gguf::unload_gguf_model,
             gguf::get_gguf_model_status,
+            gguf::set_model_pool_budget,
+            gguf::plan_model_load,
             gguf::get_gpu_memory_info,
             gguf::read_gguf_metadata,
             gguf::check_cuda_support,
-            commands::download_gguf_model,
+            hardware::get_hardware_report,
+            // Fill-in-the-middle code completion
+            gguf_fim::complete_fim,
+            gguf_fim::cancel_fim_completion,
+            // GGUF embeddings
+            gguf_embedding::embed_with_gguf_model,
+            // Benchmarks
+            gguf_benchmark::benchmark_gguf_model,
+            gguf_benchmark::get_benchmark_history,
+            gguf_benchmark::diff_benchmark_runs,
+            // Speculative decoding
+            gguf_speculative::enable_speculative_decoding,
+            gguf_speculative::disable_speculative_decoding,
+            // LoRA adapters on pooled models
+            gguf_lora::attach_lora_adapter,
+            gguf_lora::detach_lora_adapter,
+            gguf_lora::set_lora_adapter_scale,
+            gguf_lora::list_lora_adapters,
+            // OpenAI-compatible local server
+            openai_server::start_openai_server,
+            openai_server::stop_openai_server,
+            openai_server::get_openai_server_status,
+            model_download::download_gguf_model,
+            model_download::download_hf_model,
+            model_download::list_hf_gguf_files,
+            model_download::cancel_model_download,
+            // Local model library
+            model_library::get_model_library_dir,
+            model_library::list_library_models,
+            model_library::scan_model_library,
+            model_library::tag_library_model,
+            model_library::delete_library_model,
+            model_library::dedupe_model_library,
             commands::get_all_files,
             commands::read_file_content,
             oauth::oauth_authenticate,
             oauth_backend::exchange_oauth_token,
             oauth_backend::refresh_oauth_token,
+            oauth_backend::exchange_oauth_credential,
+            credentials::get_credential_vault_status,
+            credentials::unlock_credential_vault,
+            credentials::lock_credential_vault,
+            credentials::list_credentials,
+            credentials::save_api_key_credential,
+            credentials::delete_credential,
+            credentials::refresh_credential,
             streaming::chat_with_streaming,
             streaming::chat_with_http_streaming,
+            streaming::stream_chat_with_provider,
+            streaming::chat_with_dynamic_ai_streaming,
+            streaming::cancel_chat_stream,
+            agent::start_agent_run,
+            agent::decide_agent_action,
+            agent::resume_agent_run,
+            agent::get_agent_run,
+            agent::list_agent_runs,
+            agent::cancel_agent_run,
             // Vector DB commands
             commands::init_vector_db,
             commands::vector_search,
             commands::semantic_search,
+            commands::set_vector_embedding_model,
             commands::index_file_vector,
             commands::vector_index_file,
             commands::index_manual_vector,
@@ -182,9 +275,7 @@ pub fn run() {
                 if let Ok(mut state) = gguf_state.lock() {
                     if !state.models.is_empty() {
                         log::info!("🧹 Unloading GGUF models from pool...");
-                        state.models.clear();
-                        state.backend = None;
-                        state.backend_initialized = false;
+                        state.unload_all();
                         log::info!("✅ All GGUF models unloaded");
                     }
                 }
@@ -0,0 +1,761 @@
+// src-tauri/src/model_download.rs
+// Resumable, verified GGUF downloads: HTTP Range resume into `<dest>.part`, SHA-256
+// verification, atomic rename, cancellation, a concurrency limit and Hugging Face
+// `org/repo:quant` resolution (including split `-0000N-of-0000M.gguf` parts).
+
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use once_cell::sync::Lazy;
+use reqwest::header::{HeaderMap, LINK};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+use tokio::sync::Semaphore;
+
+/// At most this many files download at the same time
+const MAX_CONCURRENT_DOWNLOADS: usize = 2;
+const DEFAULT_HF_ENDPOINT: &str = "https://huggingface.co";
+
+struct DownloadRegistry {
+    slots: Arc<Semaphore>,
+    cancel_flags: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+static DOWNLOADS: Lazy<DownloadRegistry> = Lazy::new(|| DownloadRegistry {
+    slots: Arc::new(Semaphore::new(MAX_CONCURRENT_DOWNLOADS)),
+    cancel_flags: Mutex::new(HashMap::new()),
+});
+
+/// A file to fetch, with optional expected SHA-256 (hex)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadTarget {
+    pub url: String,
+    pub destination: PathBuf,
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+/// A `.gguf` entry in a Hugging Face repo listing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HfFile {
+    pub path: String,
+    pub size: u64,
+    pub sha256: Option<String>,
+}
+
+/// Parsed `org/repo:quant[@revision]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HfSpec {
+    pub repo: String,
+    pub quant: Option<String>,
+    pub revision: String,
+}
+
+pub fn parse_hf_spec(spec: &str) -> Result<HfSpec, String> {
+    let (rest, revision) = match spec.split_once('@') {
+        Some((r, rev)) if !rev.is_empty() => (r, rev.to_string()),
+        _ => (spec, "main".to_string()),
+    };
+    let (repo, quant) = match rest.split_once(':') {
+        Some((repo, q)) if !q.is_empty() => (repo, Some(q.to_string())),
+        _ => (rest, None),
+    };
+    let valid = repo.split('/').count() == 2 && repo.split('/').all(|p| !p.is_empty() && p != "." && p != "..");
+    if !valid {
+        return Err(format!("Geçersiz Hugging Face spec: '{}' (beklenen: org/repo:quant)", spec));
+    }
+    Ok(HfSpec { repo: repo.to_string(), quant, revision })
+}
+
+/// Pick the GGUF file (or every split part of it) matching `quant` from a repo listing.
+/// Parts are returned in order, named like `resolve_split_gguf_path` expects.
+pub fn select_hf_files(files: &[HfFile], quant: Option<&str>) -> Result<Vec<HfFile>, String> {
+    let split_re = regex::Regex::new(r"-(\d{5})-of-(\d{5})\.gguf$").unwrap();
+    let candidates: Vec<&HfFile> = files.iter()
+        .filter(|f| f.path.to_lowercase().ends_with(".gguf"))
+        .filter(|f| !f.path.to_lowercase().contains("mmproj"))
+        .filter(|f| match quant {
+            Some(q) => quant_matches(&f.path, q),
+            None => true,
+        })
+        .collect();
+
+    let first = match candidates.iter().min_by_key(|f| (f.path.matches('/').count(), f.path.clone())) {
+        Some(f) => *f,
+        None => {
+            let available: Vec<&str> = files.iter().filter(|f| f.path.ends_with(".gguf")).map(|f| f.path.as_str()).collect();
+            return Err(format!("Eşleşen GGUF bulunamadı ({:?}). Mevcut dosyalar: {:?}", quant, available));
+        }
+    };
+
+    let caps = match split_re.captures(&first.path) {
+        Some(c) => c,
+        None => return Ok(vec![first.clone()]),
+    };
+    let total: u32 = caps[2].parse().unwrap_or(1);
+    let mut parts = Vec::with_capacity(total as usize);
+    for i in 1..=total {
+        let part_path = split_re.replace(&first.path, format!("-{:05}-of-{}.gguf", i, &caps[2]).as_str()).to_string();
+        let part = files.iter()
+            .find(|f| f.path == part_path)
+            .ok_or_else(|| format!("Split parça eksik: {}", part_path))?;
+        parts.push(part.clone());
+    }
+    Ok(parts)
+}
+
+/// `Q4_K_M` must match `model-Q4_K_M.gguf` / `model.q4_k_m-00001-of-00002.gguf`, not `Q4_K_M_L`
+fn quant_matches(path: &str, quant: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+    let quant = quant.to_lowercase();
+    let mut start = 0;
+    while let Some(pos) = name[start..].find(&quant) {
+        let begin = start + pos;
+        let end = begin + quant.len();
+        let before_ok = begin == 0 || !name.as_bytes()[begin - 1].is_ascii_alphanumeric();
+        let after_ok = end >= name.len() || matches!(name.as_bytes()[end], b'.' | b'-');
+        if before_ok && after_ok {
+            return true;
+        }
+        start = end;
+    }
+    false
+}
+
+fn hf_endpoint() -> String {
+    std::env::var("HF_ENDPOINT").unwrap_or_else(|_| DEFAULT_HF_ENDPOINT.to_string())
+}
+
+/// `rel="next"` URL of a `Link` header; Hub tree listings are paginated this way
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers.get_all(LINK).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .find(|link| link.split(';').skip(1).any(|param| matches!(param.trim(), "rel=\"next\"" | "rel=next")))
+        .and_then(|link| link.split(';').next())
+        .map(|url| url.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+}
+
+/// List a repo's files via the Hub API, following every page
+pub async fn list_hf_files(client: &Client, endpoint: &str, spec: &HfSpec, token: Option<&str>) -> Result<Vec<HfFile>, String> {
+    let mut url = format!("{}/api/models/{}/tree/{}?recursive=true", endpoint.trim_end_matches('/'), spec.repo, spec.revision);
+    let mut entries: Vec<serde_json::Value> = Vec::new();
+    loop {
+        let mut req = client.get(&url);
+        if let Some(t) = token.filter(|t| !t.is_empty()) {
+            req = req.bearer_auth(t);
+        }
+        let res = req.send().await.map_err(|e| format!("Hugging Face isteği başarısız: {}", e))?;
+        if !res.status().is_success() {
+            return Err(format!("Hugging Face API hatası ({}): {}", res.status(), url));
+        }
+        let next = next_link(res.headers()).filter(|next| *next != url);
+        let page: Vec<serde_json::Value> = res.json().await.map_err(|e| format!("Hugging Face yanıtı okunamadı: {}", e))?;
+        entries.extend(page);
+        match next {
+            Some(next) => url = next,
+            None => break,
+        }
+    }
+
+    Ok(entries.iter()
+        .filter(|e| e["type"].as_str() == Some("file"))
+        .filter_map(|e| {
+            Some(HfFile {
+                path: e["path"].as_str()?.to_string(),
+                size: e["lfs"]["size"].as_u64().or_else(|| e["size"].as_u64()).unwrap_or(0),
+                sha256: e["lfs"]["oid"].as_str().map(|s| s.to_string()),
+            })
+        })
+        .collect())
+}
+
+fn part_path(destination: &Path) -> PathBuf {
+    let mut name = destination.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
+
+/// SHA-256 of what is already in the part file, so a resumed download is still fully verified
+fn hash_existing(path: &Path, hasher: &mut Sha256) -> Result<u64, String> {
+    let mut file = std::fs::File::open(path).map_err(|e| format!("Part dosyası okunamadı: {}", e))?;
+    let mut buf = vec![0u8; 1024 * 1024];
+    let mut total = 0u64;
+    loop {
+        let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+    Ok(total)
+}
+
+/// Hash the part file on the blocking pool; returns the hasher and the bytes already there
+async fn hash_part(path: &Path) -> Result<(Sha256, u64), String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut hasher = Sha256::new();
+        hash_existing(&path, &mut hasher).map(|len| (hasher, len))
+    })
+    .await
+    .map_err(|e| format!("Part dosyası okunamadı: {}", e))?
+}
+
+/// First byte of a `Content-Range: bytes START-END/TOTAL` header
+fn content_range_start(headers: &HeaderMap) -> Option<u64> {
+    headers.get(reqwest::header::CONTENT_RANGE)?
+        .to_str().ok()?
+        .strip_prefix("bytes ")?
+        .split('-').next()?
+        .trim().parse().ok()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// An existing destination only counts as downloaded if its size and SHA-256 match
+fn verify_existing(target: &DownloadTarget) -> Result<bool, String> {
+    let len = std::fs::metadata(&target.destination).map_err(|e| e.to_string())?.len();
+    if target.size.map_or(false, |size| size > 0 && size != len) {
+        return Ok(false);
+    }
+    if let Some(expected) = &target.sha256 {
+        let mut hasher = Sha256::new();
+        hash_existing(&target.destination, &mut hasher)?;
+        return Ok(expected.eq_ignore_ascii_case(&to_hex(&hasher.finalize())));
+    }
+    Ok(true)
+}
+
+/// Download one file with Range resume, optional SHA-256 check and atomic rename.
+/// `progress(downloaded, total)` is called for every chunk. On cancellation the
+/// `.part` file is kept so the next call resumes.
+pub async fn download_file(
+    client: &Client,
+    target: &DownloadTarget,
+    token: Option<&str>,
+    cancel: &AtomicBool,
+    mut progress: impl FnMut(u64, u64),
+) -> Result<PathBuf, String> {
+    let destination = target.destination.clone();
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Klasör oluşturulamadı: {}", e))?;
+    }
+    let part = part_path(&destination);
+
+    let (mut hasher, mut downloaded) = if part.exists() { hash_part(&part).await? } else { (Sha256::new(), 0) };
+
+    let send = |from: u64| {
+        let mut req = client.get(&target.url);
+        if let Some(t) = token.filter(|t| !t.is_empty()) {
+            req = req.bearer_auth(t);
+        }
+        if from > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={}-", from));
+        }
+        req.send()
+    };
+    if downloaded > 0 {
+        info!("⏯️ Kaldığı yerden devam: {} bytes ({})", downloaded, part.display());
+    }
+    let mut response = send(downloaded).await.map_err(|e| format!("İndirme başlatılamadı: {}", e))?;
+
+    // A 206 that doesn't start where the part ends would corrupt the file: start over
+    if response.status() == StatusCode::PARTIAL_CONTENT && downloaded > 0 {
+        let start = content_range_start(response.headers());
+        if start != Some(downloaded) {
+            warn!("⚠️ Content-Range uyuşmuyor ({:?} != {}), baştan indiriliyor", start, downloaded);
+            hasher = Sha256::new();
+            downloaded = 0;
+            response = send(0).await.map_err(|e| format!("İndirme başlatılamadı: {}", e))?;
+        }
+    }
+    let status = response.status();
+
+    let mut file = match status {
+        StatusCode::PARTIAL_CONTENT if downloaded > 0 => std::fs::OpenOptions::new()
+            .append(true)
+            .open(&part)
+            .map_err(|e| format!("Part dosyası açılamadı: {}", e))?,
+        StatusCode::RANGE_NOT_SATISFIABLE if downloaded > 0 => {
+            // Part already holds the whole file
+            info!("✅ Part dosyası zaten tamamlanmış");
+            return finish(&part, &destination, hasher, target.sha256.as_deref());
+        }
+        s if s.is_success() => {
+            if downloaded > 0 {
+                warn!("⚠️ Sunucu Range desteklemiyor, baştan indiriliyor");
+                hasher = Sha256::new();
+                downloaded = 0;
+            }
+            std::fs::File::create(&part).map_err(|e| format!("Dosya oluşturulamadı: {}", e))?
+        }
+        s => return Err(format!("HTTP hatası: {}", s)),
+    };
+
+    let total = response.content_length().map(|len| len + downloaded).or(target.size).unwrap_or(0);
+    let mut stream = response.bytes_stream();
+    progress(downloaded, total);
+
+    while let Some(chunk) = stream.next().await {
+        if cancel.load(Ordering::Relaxed) {
+            file.flush().ok();
+            info!("⏹️ İndirme iptal edildi, part dosyası korunuyor: {}", part.display());
+            return Err("İndirme iptal edildi".to_string());
+        }
+        let chunk = chunk.map_err(|e| format!("İndirme hatası: {}", e))?;
+        file.write_all(&chunk).map_err(|e| format!("Yazma hatası: {}", e))?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        progress(downloaded, total);
+    }
+    file.flush().map_err(|e| format!("Yazma hatası: {}", e))?;
+    drop(file);
+
+    if total > 0 && downloaded != total {
+        return Err(format!("Eksik indirme: {} / {} bytes (tekrar denenince devam edecek)", downloaded, total));
+    }
+
+    finish(&part, &destination, hasher, target.sha256.as_deref())
+}
+
+fn finish(part: &Path, destination: &Path, hasher: Sha256, expected: Option<&str>) -> Result<PathBuf, String> {
+    let actual = to_hex(&hasher.finalize());
+    if let Some(expected) = expected {
+        if !expected.eq_ignore_ascii_case(&actual) {
+            let _ = std::fs::remove_file(part);
+            error!("❌ SHA-256 uyuşmazlığı: beklenen {}, gelen {}", expected, actual);
+            return Err(format!("SHA-256 doğrulaması başarısız (beklenen {}, gelen {})", expected, actual));
+        }
+        info!("🔒 SHA-256 doğrulandı: {}", actual);
+    }
+    std::fs::rename(part, destination).map_err(|e| format!("Dosya taşınamadı: {}", e))?;
+    Ok(destination.to_path_buf())
+}
+
+/// A download's cancel flag, registered under its id until dropped
+struct Registration {
+    id: String,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Registration {
+    /// Ids are generated unless given; a given id must not belong to a running download
+    fn new(download_id: Option<String>) -> Result<Self, String> {
+        let id = download_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        let mut flags = DOWNLOADS.cancel_flags.lock().unwrap();
+        if flags.contains_key(&id) {
+            return Err(format!("Bu indirme kimliği zaten kullanımda: {}", id));
+        }
+        let cancel = Arc::new(AtomicBool::new(false));
+        flags.insert(id.clone(), cancel.clone());
+        Ok(Self { id, cancel })
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        DOWNLOADS.cancel_flags.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Run a download under the concurrency limit
+async fn run_tracked(
+    app: &AppHandle,
+    client: &Client,
+    registration: &Registration,
+    target: &DownloadTarget,
+    token: Option<&str>,
+) -> Result<PathBuf, String> {
+    let download_id = registration.id.as_str();
+    let cancel = &registration.cancel;
+    let _permit = DOWNLOADS.slots.clone().acquire_owned().await.map_err(|e| e.to_string())?;
+    info!("🔵 GGUF model indiriliyor: {}", target.url);
+    info!("📁 Hedef: {}", target.destination.display());
+
+    let mut last_emit = 0u64;
+    let url = target.url.clone();
+    download_file(client, target, token, cancel, |downloaded, total| {
+        // Her 1MB'de bir progress gönder
+        if downloaded == 0 || downloaded - last_emit >= 1024 * 1024 || downloaded == total {
+            last_emit = downloaded;
+            let progress = if total > 0 { downloaded as f64 / total as f64 * 100.0 } else { 0.0 };
+            if let Err(e) = app.emit("download-progress", json!({
+                "download_id": download_id,
+                "url": url,
+                "downloaded": downloaded,
+                "total": total,
+                "progress": progress
+            })) {
+                error!("❌ Event emit hatası: {}", e);
+            }
+        }
+    }).await
+}
+
+/// Result of a download command; `download_id` is what `cancel_model_download` takes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadResult {
+    pub download_id: String,
+    /// Local paths in part order (the first one is what `load_gguf_model` needs)
+    pub paths: Vec<String>,
+}
+
+fn emit_started(app: &AppHandle, download_id: &str, source: &str) {
+    if let Err(e) = app.emit("download-started", json!({ "download_id": download_id, "source": source })) {
+        error!("❌ Event emit hatası: {}", e);
+    }
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+/// Download one file. Without `download_id` a unique id is generated; it is sent in
+/// the `download-started` event and returned.
+#[tauri::command]
+pub async fn download_gguf_model(
+    url: String,
+    destination: String,
+    app: AppHandle,
+    expected_sha256: Option<String>,
+    download_id: Option<String>,
+) -> Result<DownloadResult, String> {
+    let client = Client::new();
+    let registration = Registration::new(download_id)?;
+    emit_started(&app, &registration.id, &url);
+    let target = DownloadTarget {
+        url: url.clone(),
+        destination: PathBuf::from(&destination),
+        sha256: expected_sha256,
+        size: None,
+    };
+
+    let path = run_tracked(&app, &client, &registration, &target, None).await?;
+    info!("✅ İndirme tamamlandı: {}", path.display());
+    Ok(DownloadResult { download_id: registration.id.clone(), paths: vec![path.to_string_lossy().to_string()] })
+}
+
+/// `org/repo:quant[@revision]` -> download every part into `dest_dir`, verified against HF LFS hashes.
+/// Files already present are kept only if their size and hash match.
+#[tauri::command]
+pub async fn download_hf_model(
+    spec: String,
+    dest_dir: String,
+    app: AppHandle,
+    hf_token: Option<String>,
+    download_id: Option<String>,
+) -> Result<DownloadResult, String> {
+    let registration = Registration::new(download_id)?;
+    emit_started(&app, &registration.id, &spec);
+    let parsed = parse_hf_spec(&spec)?;
+    let client = Client::new();
+    let files = list_hf_files(&client, &hf_endpoint(), &parsed, hf_token.as_deref()).await?;
+    let selected = select_hf_files(&files, parsed.quant.as_deref())?;
+    info!("📦 {} -> {} dosya", spec, selected.len());
+
+    let mut paths = Vec::with_capacity(selected.len());
+    for file in selected {
+        let file_name = file.path.rsplit('/').next().unwrap_or(&file.path).to_string();
+        let target = DownloadTarget {
+            url: format!("{}/{}/resolve/{}/{}", hf_endpoint().trim_end_matches('/'), parsed.repo, parsed.revision, file.path),
+            destination: Path::new(&dest_dir).join(&file_name),
+            sha256: file.sha256.clone(),
+            size: Some(file.size),
+        };
+        if registration.cancel.load(Ordering::Relaxed) {
+            return Err("İndirme iptal edildi".to_string());
+        }
+        if target.destination.exists() {
+            let check = target.clone();
+            let verified = tokio::task::spawn_blocking(move || verify_existing(&check))
+                .await
+                .map_err(|e| format!("Doğrulama başarısız: {}", e))??;
+            if verified {
+                info!("⏭️ Zaten mevcut ve doğrulandı: {}", target.destination.display());
+                paths.push(target.destination.to_string_lossy().to_string());
+                continue;
+            }
+            warn!("⚠️ Mevcut dosya boyut/SHA-256 uyuşmuyor, yeniden indiriliyor: {}", target.destination.display());
+            std::fs::remove_file(&target.destination).map_err(|e| format!("Bozuk dosya silinemedi: {}", e))?;
+        }
+        let path = run_tracked(&app, &client, &registration, &target, hf_token.as_deref()).await?;
+        paths.push(path.to_string_lossy().to_string());
+    }
+
+    Ok(DownloadResult { download_id: registration.id.clone(), paths })
+}
+
+/// List GGUF files of a Hugging Face repo (for a quant picker)
+#[tauri::command]
+pub async fn list_hf_gguf_files(spec: String, hf_token: Option<String>) -> Result<Vec<HfFile>, String> {
+    let parsed = parse_hf_spec(&spec)?;
+    let files = list_hf_files(&Client::new(), &hf_endpoint(), &parsed, hf_token.as_deref()).await?;
+    Ok(files.into_iter().filter(|f| f.path.to_lowercase().ends_with(".gguf")).collect())
+}
+
+#[tauri::command]
+pub async fn cancel_model_download(download_id: String) -> Result<bool, String> {
+    let flags = DOWNLOADS.cancel_flags.lock().unwrap();
+    match flags.get(&download_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tiny_http::{Header, Response, Server};
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum Ranges {
+        Honour,
+        Ignore,
+        /// Answer a Range request with a 206 that starts 1000 bytes early, then serve normally
+        Misaligned,
+    }
+
+    /// Local HTTP stand-in that serves `body` with Range support
+    fn serve(body: Vec<u8>, ranges: Ranges) -> (String, std::thread::JoinHandle<()>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let requests = if ranges == Ranges::Misaligned { 2 } else { 1 };
+        let honour_range = ranges != Ranges::Ignore;
+        let handle = std::thread::spawn(move || {
+            for (i, request) in server.incoming_requests().take(requests).enumerate() {
+                let range_start = request.headers().iter()
+                    .find(|h| h.field.equiv("Range"))
+                    .and_then(|h| h.value.as_str().strip_prefix("bytes=").map(|s| s.trim_end_matches('-').to_string()))
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .map(|start| if ranges == Ranges::Misaligned && i == 0 { start.saturating_sub(1000) } else { start });
+                let response = match range_start {
+                    Some(start) if honour_range && start >= body.len() => Response::from_data(Vec::new()).with_status_code(416),
+                    Some(start) if honour_range => Response::from_data(body[start..].to_vec())
+                        .with_status_code(206)
+                        .with_header(Header::from_bytes("Content-Range", format!("bytes {}-{}/{}", start, body.len() - 1, body.len())).unwrap()),
+                    _ => Response::from_data(body.clone()),
+                };
+                let _ = request.respond(response);
+            }
+        });
+        (addr, handle)
+    }
+
+    fn body() -> Vec<u8> {
+        (0..200_000u32).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn sha(data: &[u8]) -> String {
+        to_hex(&Sha256::digest(data))
+    }
+
+    fn temp_dest(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corex_dl_test_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(name)
+    }
+
+    #[tokio::test]
+    async fn test_full_download_verified() {
+        let data = body();
+        let (addr, handle) = serve(data.clone(), Ranges::Honour);
+        let dest = temp_dest("model.gguf");
+        let target = DownloadTarget { url: format!("{}/model.gguf", addr), destination: dest.clone(), sha256: Some(sha(&data)), size: None };
+
+        let path = download_file(&Client::new(), &target, None, &AtomicBool::new(false), |_, _| {}).await.unwrap();
+        handle.join().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_part() {
+        let data = body();
+        let dest = temp_dest("model.gguf");
+        std::fs::write(part_path(&dest), &data[..70_000]).unwrap();
+
+        let (addr, handle) = serve(data.clone(), Ranges::Honour);
+        let target = DownloadTarget { url: format!("{}/model.gguf", addr), destination: dest.clone(), sha256: Some(sha(&data)), size: None };
+        let mut first_progress = None;
+        download_file(&Client::new(), &target, None, &AtomicBool::new(false), |d, _| {
+            first_progress.get_or_insert(d);
+        }).await.unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(first_progress, Some(70_000));
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_restart_when_range_ignored() {
+        let data = body();
+        let dest = temp_dest("model.gguf");
+        std::fs::write(part_path(&dest), b"garbage").unwrap();
+
+        let (addr, handle) = serve(data.clone(), Ranges::Ignore);
+        let target = DownloadTarget { url: format!("{}/m.gguf", addr), destination: dest.clone(), sha256: Some(sha(&data)), size: None };
+        download_file(&Client::new(), &target, None, &AtomicBool::new(false), |_, _| {}).await.unwrap();
+        handle.join().unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_restart_when_range_misaligned() {
+        let data = body();
+        let dest = temp_dest("model.gguf");
+        std::fs::write(part_path(&dest), &data[..70_000]).unwrap();
+
+        let (addr, handle) = serve(data.clone(), Ranges::Misaligned);
+        let target = DownloadTarget { url: format!("{}/m.gguf", addr), destination: dest.clone(), sha256: Some(sha(&data)), size: None };
+        let mut first_progress = None;
+        download_file(&Client::new(), &target, None, &AtomicBool::new(false), |d, _| {
+            first_progress.get_or_insert(d);
+        }).await.unwrap();
+        handle.join().unwrap();
+
+        assert_eq!(first_progress, Some(0));
+        assert_eq!(std::fs::read(&dest).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_checksum_mismatch_discards_part() {
+        let (addr, handle) = serve(body(), Ranges::Honour);
+        let dest = temp_dest("model.gguf");
+        let target = DownloadTarget { url: format!("{}/m.gguf", addr), destination: dest.clone(), sha256: Some("00".repeat(32)), size: None };
+        let result = download_file(&Client::new(), &target, None, &AtomicBool::new(false), |_, _| {}).await;
+        handle.join().unwrap();
+        assert!(result.unwrap_err().contains("SHA-256"));
+        assert!(!dest.exists());
+        assert!(!part_path(&dest).exists());
+    }
+
+    #[tokio::test]
+    async fn test_cancel_keeps_part() {
+        let (addr, handle) = serve(body(), Ranges::Honour);
+        let dest = temp_dest("model.gguf");
+        let target = DownloadTarget { url: format!("{}/m.gguf", addr), destination: dest.clone(), sha256: None, size: None };
+        let result = download_file(&Client::new(), &target, None, &AtomicBool::new(true), |_, _| {}).await;
+        handle.join().unwrap();
+        assert!(result.is_err());
+        assert!(part_path(&dest).exists());
+        assert!(!dest.exists());
+    }
+
+    fn hf(path: &str) -> HfFile {
+        HfFile { path: path.into(), size: 1, sha256: None }
+    }
+
+    #[test]
+    fn test_parse_hf_spec() {
+        assert_eq!(parse_hf_spec("Qwen/Qwen2.5-7B-GGUF:Q4_K_M").unwrap(), HfSpec {
+            repo: "Qwen/Qwen2.5-7B-GGUF".into(),
+            quant: Some("Q4_K_M".into()),
+            revision: "main".into(),
+        });
+        assert_eq!(parse_hf_spec("a/b@dev").unwrap().revision, "dev");
+        assert!(parse_hf_spec("no-org").is_err());
+        assert!(parse_hf_spec("../x:Q4").is_err());
+    }
+
+    #[test]
+    fn test_select_hf_files() {
+        let files = vec![
+            hf("model-Q4_K_M.gguf"),
+            hf("model-Q4_K_M_L.gguf"),
+            hf("mmproj-Q4_K_M.gguf"),
+            hf("big/model-q8_0-00002-of-00002.gguf"),
+            hf("big/model-q8_0-00001-of-00002.gguf"),
+            hf("README.md"),
+        ];
+        let single = select_hf_files(&files, Some("Q4_K_M")).unwrap();
+        assert_eq!(single.len(), 1);
+        assert_eq!(single[0].path, "model-Q4_K_M.gguf");
+
+        let split = select_hf_files(&files, Some("Q8_0")).unwrap();
+        assert_eq!(split.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec![
+            "big/model-q8_0-00001-of-00002.gguf",
+            "big/model-q8_0-00002-of-00002.gguf",
+        ]);
+
+        assert!(select_hf_files(&files, Some("Q2_K")).is_err());
+        assert!(select_hf_files(&files[3..4], Some("Q8_0")).is_err()); // missing part 1
+    }
+
+    #[test]
+    fn test_verify_existing() {
+        let data = body();
+        let dest = temp_dest("m.gguf");
+        std::fs::write(&dest, &data).unwrap();
+        let target = |size: Option<u64>, sha256: Option<String>| DownloadTarget { url: String::new(), destination: dest.clone(), sha256, size };
+        assert!(verify_existing(&target(Some(data.len() as u64), Some(sha(&data)))).unwrap());
+        assert!(!verify_existing(&target(Some(data.len() as u64 + 1), None)).unwrap());
+        assert!(!verify_existing(&target(None, Some("00".repeat(32)))).unwrap());
+        std::fs::write(&dest, &data[..1000]).unwrap();
+        assert!(!verify_existing(&target(Some(data.len() as u64), Some(sha(&data)))).unwrap());
+        std::fs::remove_dir_all(dest.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_registration_ids_are_unique() {
+        let a = Registration::new(None).unwrap();
+        let b = Registration::new(None).unwrap();
+        assert_ne!(a.id, b.id);
+        assert!(Registration::new(Some(a.id.clone())).is_err());
+        let id = a.id.clone();
+        drop(a);
+        assert!(Registration::new(Some(id)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_hf_files_follows_pages() {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let next = format!("{}/api/models/org/repo/tree/main?recursive=true&cursor=abc", addr);
+        let handle = std::thread::spawn(move || {
+            for (i, request) in server.incoming_requests().take(2).enumerate() {
+                let response = if i == 0 {
+                    Response::from_string(r#"[{ "type": "file", "path": "a.gguf", "size": 1 }]"#)
+                        .with_header(Header::from_bytes("Link", format!("<{}>; rel=\"next\"", next)).unwrap())
+                } else {
+                    assert!(request.url().contains("cursor=abc"));
+                    Response::from_string(r#"[{ "type": "file", "path": "b.gguf", "size": 2 }]"#)
+                };
+                let _ = request.respond(response);
+            }
+        });
+        let spec = parse_hf_spec("org/repo").unwrap();
+        let files = list_hf_files(&Client::new(), &addr, &spec, None).await.unwrap();
+        handle.join().unwrap();
+        assert_eq!(files.iter().map(|f| f.path.as_str()).collect::<Vec<_>>(), vec!["a.gguf", "b.gguf"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_hf_files_from_stand_in() {
+        let listing = serde_json::json!([
+            { "type": "file", "path": "m-Q4_0.gguf", "size": 134, "lfs": { "oid": "abc", "size": 4000 } },
+            { "type": "directory", "path": "sub" },
+            { "type": "file", "path": "README.md", "size": 10 }
+        ]);
+        let (addr, handle) = serve(listing.to_string().into_bytes(), Ranges::Ignore);
+        let spec = parse_hf_spec("org/repo:Q4_0").unwrap();
+        let files = list_hf_files(&Client::new(), &addr, &spec, None).await.unwrap();
+        handle.join().unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].size, 4000);
+        assert_eq!(files[0].sha256.as_deref(), Some("abc"));
+    }
+}
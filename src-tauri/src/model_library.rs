@@ -0,0 +1,704 @@
+// src-tauri/src/model_library.rs
+// Managed local model library: an index of every GGUF file under the library
+// directory (plus any extra folders the user scanned) with parsed metadata,
+// tags and last-used time. Split GGUFs are indexed as one logical model.
+//
+// Index: <app_data_dir>/model_library.json, library dir: <app_data_dir>/models
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager, State};
+
+use crate::gguf::{self, GgufState};
+use crate::gguf_reader::{self, GgufReadOptions};
+
+const INDEX_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModelKind {
+    Model,
+    /// Multimodal projector (mmproj)
+    Projector,
+    /// LoRA adapter
+    Adapter,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryModel {
+    /// Stable id derived from the first part's path
+    pub id: String,
+    pub name: String,
+    /// First part; what `load_gguf_model` takes
+    pub path: String,
+    /// All parts in order (just `path` for single-file models)
+    pub parts: Vec<String>,
+    /// Some parts of a split model are missing
+    pub incomplete: bool,
+    pub kind: ModelKind,
+    pub size_bytes: u64,
+    pub architecture: Option<String>,
+    pub quantization: Option<String>,
+    pub context_length: Option<u64>,
+    pub parameter_count: Option<u64>,
+    pub license: Option<String>,
+    pub has_chat_template: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// SHA-256 over all parts, computed lazily by dedupe
+    pub sha256: Option<String>,
+    /// Newest mtime across parts; entries are re-read when it changes
+    pub modified: u64,
+    pub added_at: u64,
+    pub last_used: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelIndex {
+    pub version: u32,
+    /// Extra folders included in every scan
+    #[serde(default)]
+    pub roots: Vec<String>,
+    #[serde(default)]
+    pub models: Vec<LibraryModel>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroup {
+    pub sha256: String,
+    pub size_bytes: u64,
+    /// Copy that stays
+    pub keep: String,
+    /// Ids of identical copies
+    pub duplicates: Vec<String>,
+    pub reclaimable_bytes: u64,
+    /// Per-duplicate result when deletion was requested
+    #[serde(default)]
+    pub outcomes: Vec<DuplicateOutcome>,
+    /// True when every duplicate was deleted
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateOutcome {
+    pub id: String,
+    pub deleted: bool,
+    /// Why the copy was kept (loaded in the pool, delete failed)
+    pub error: Option<String>,
+}
+
+/// llama.cpp `general.file_type` (LLAMA_FTYPE_*) names
+pub fn file_type_name(file_type: u64) -> Option<&'static str> {
+    Some(match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        _ => return None,
+    })
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn model_id(first_part: &str) -> String {
+    let digest = Sha256::digest(first_part.as_bytes());
+    digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Every .gguf file under `dir` (recursive, hidden folders skipped)
+pub fn find_gguf_files(dir: &Path) -> Vec<PathBuf> {
+    let mut out = Vec::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        let Ok(entries) = std::fs::read_dir(&current) else { continue };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if path.is_dir() && !hidden {
+                stack.push(path);
+            } else if path.extension().map(|e| e.eq_ignore_ascii_case("gguf")).unwrap_or(false) {
+                out.push(path);
+            }
+        }
+    }
+    out.sort();
+    out
+}
+
+/// Group files into logical models: split parts collapse onto their first part.
+/// Returns (first part, all expected parts) pairs.
+pub fn group_split_files(files: &[PathBuf]) -> Vec<(String, Vec<String>)> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for file in files {
+        let path = file.to_string_lossy().to_string();
+        let first = gguf::resolve_split_gguf_path(&path);
+        groups.entry(first.clone()).or_insert_with(|| gguf::split_part_paths(&first));
+    }
+    groups.into_iter().collect()
+}
+
+fn model_kind(meta: &Map<String, Value>, path: &str) -> ModelKind {
+    let general_type = meta.get("general.type").and_then(|v| v.as_str()).unwrap_or("");
+    let file_name = Path::new(path).file_name().map(|f| f.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if general_type == "mmproj" || meta.contains_key("clip.has_vision_encoder") || file_name.contains("mmproj") {
+        ModelKind::Projector
+    } else if general_type == "adapter" || meta.contains_key("adapter.type") {
+        ModelKind::Adapter
+    } else {
+        ModelKind::Model
+    }
+}
+
+/// Parts that exist on disk, their total size and newest mtime (seconds)
+fn stat_parts(parts: &[String]) -> (Vec<&String>, u64, u64) {
+    let present: Vec<&String> = parts.iter().filter(|p| Path::new(p.as_str()).exists()).collect();
+    let mut size_bytes = 0;
+    let mut modified = 0;
+    for part in &present {
+        if let Ok(m) = std::fs::metadata(part) {
+            size_bytes += m.len();
+            modified = modified.max(m.modified().ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0));
+        }
+    }
+    (present, size_bytes, modified)
+}
+
+/// Read metadata for one logical model (all parts for the parameter count)
+pub fn describe_model(first_part: &str, parts: &[String]) -> Result<LibraryModel, String> {
+    let options = GgufReadOptions { max_array_len: Some(0), read_tensors: true };
+    let first = gguf_reader::read_gguf_file(first_part, &options)?;
+    let meta = &first.metadata;
+    let arch = meta.get("general.architecture").and_then(|v| v.as_str()).map(String::from);
+    let str_key = |k: &str| meta.get(k).and_then(|v| v.as_str()).map(String::from);
+
+    let (present, size_bytes, modified) = stat_parts(parts);
+    let mut parameter_count: u64 = first.tensors.iter().map(|t| t.n_elements).sum();
+    for part in &present {
+        if part.as_str() != first_part {
+            if let Ok(file) = gguf_reader::read_gguf_file(part, &options) {
+                parameter_count += file.tensors.iter().map(|t| t.n_elements).sum::<u64>();
+            }
+        }
+    }
+
+    let quantization = meta.get("general.file_type")
+        .and_then(|v| v.as_u64())
+        .and_then(file_type_name)
+        .map(String::from)
+        .or_else(|| first.summary().dominant_type);
+    let name = str_key("general.name").unwrap_or_else(|| {
+        Path::new(first_part).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+    });
+
+    Ok(LibraryModel {
+        id: model_id(first_part),
+        name,
+        path: first_part.to_string(),
+        parts: parts.to_vec(),
+        incomplete: present.len() < parts.len(),
+        kind: model_kind(meta, first_part),
+        size_bytes,
+        context_length: arch.as_ref().and_then(|a| meta.get(&format!("{}.context_length", a))).and_then(|v| v.as_u64()),
+        architecture: arch,
+        quantization,
+        parameter_count: (parameter_count > 0).then_some(parameter_count),
+        license: str_key("general.license"),
+        has_chat_template: meta.contains_key("tokenizer.chat_template"),
+        tags: Vec::new(),
+        sha256: None,
+        modified,
+        added_at: now_secs(),
+        last_used: None,
+    })
+}
+
+/// Rebuild the index from disk. Entries whose parts kept their size and mtime are reused
+/// as they are; changed files are re-read but keep their tags and usage.
+pub fn rescan(index: &mut ModelIndex, dirs: &[PathBuf]) {
+    let files: Vec<PathBuf> = dirs.iter().flat_map(|d| find_gguf_files(d)).collect();
+    let previous: HashMap<String, LibraryModel> = index.models.drain(..).map(|m| (m.path.clone(), m)).collect();
+
+    for (first, parts) in group_split_files(&files) {
+        if let Some(old) = previous.get(&first) {
+            let (present, size_bytes, modified) = stat_parts(&parts);
+            let incomplete = present.len() < parts.len();
+            if old.parts == parts && old.incomplete == incomplete && old.size_bytes == size_bytes && old.modified == modified {
+                index.models.push(old.clone());
+                continue;
+            }
+        }
+        match describe_model(&first, &parts) {
+            Ok(mut model) => {
+                if let Some(old) = previous.get(&first) {
+                    model.tags = old.tags.clone();
+                    model.added_at = old.added_at;
+                    model.last_used = old.last_used;
+                    if old.modified == model.modified && old.size_bytes == model.size_bytes {
+                        model.sha256 = old.sha256.clone();
+                    }
+                }
+                index.models.push(model);
+            }
+            Err(e) => warn!("⚠️ GGUF okunamadı, atlanıyor: {} ({})", first, e),
+        }
+    }
+    index.models.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+}
+
+/// SHA-256 over all parts in order
+pub fn hash_parts(parts: &[String]) -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    for part in parts {
+        let mut file = std::fs::File::open(part).map_err(|e| format!("Dosya açılamadı: {} ({})", part, e))?;
+        loop {
+            let n = file.read(&mut buf).map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Same-size models without a cached hash: the only ones dedupe needs to hash
+pub fn pending_hashes(index: &ModelIndex) -> Vec<LibraryModel> {
+    let mut by_size: HashMap<u64, Vec<&LibraryModel>> = HashMap::new();
+    for m in index.models.iter().filter(|m| !m.incomplete) {
+        by_size.entry(m.size_bytes).or_default().push(m);
+    }
+    by_size.into_values()
+        .filter(|v| v.len() > 1)
+        .flatten()
+        .filter(|m| m.sha256.is_none())
+        .cloned()
+        .collect()
+}
+
+/// Hash a snapshot of models without holding the index; failures are logged and skipped
+pub fn compute_hashes(models: Vec<LibraryModel>) -> Vec<(LibraryModel, String)> {
+    models.into_iter()
+        .filter_map(|m| {
+            info!("🔐 Hashing {}", m.path);
+            match hash_parts(&m.parts) {
+                Ok(hash) => Some((m, hash)),
+                Err(e) => {
+                    warn!("⚠️ Hash hesaplanamadı, atlanıyor: {} ({})", m.path, e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Cache hashes for entries that still describe the same files (size and mtime unchanged)
+pub fn apply_hashes(index: &mut ModelIndex, hashed: Vec<(LibraryModel, String)>) {
+    for (snapshot, hash) in hashed {
+        if let Some(m) = index.models.iter_mut().find(|m| {
+            m.id == snapshot.id && m.modified == snapshot.modified && m.size_bytes == snapshot.size_bytes
+        }) {
+            m.sha256 = Some(hash);
+        }
+    }
+}
+
+/// Group identical models by their cached hashes (see `pending_hashes`)
+pub fn find_duplicates(index: &ModelIndex) -> Vec<DuplicateGroup> {
+    let mut by_hash: BTreeMap<String, Vec<&LibraryModel>> = BTreeMap::new();
+    for m in &index.models {
+        if let Some(hash) = &m.sha256 {
+            by_hash.entry(hash.clone()).or_default().push(m);
+        }
+    }
+    by_hash.into_iter()
+        .filter(|(_, models)| models.len() > 1)
+        .map(|(sha256, mut models)| {
+            // Keep the most recently used copy, then the oldest entry
+            models.sort_by_key(|m| (std::cmp::Reverse(m.last_used.unwrap_or(0)), m.added_at, m.path.clone()));
+            let size_bytes = models[0].size_bytes;
+            DuplicateGroup {
+                sha256,
+                size_bytes,
+                keep: models[0].id.clone(),
+                duplicates: models[1..].iter().map(|m| m.id.clone()).collect(),
+                reclaimable_bytes: size_bytes * (models.len() as u64 - 1),
+                outcomes: Vec::new(),
+                deleted: false,
+            }
+        })
+        .collect()
+}
+
+/// Delete a group's duplicates, recording each result; copies loaded in `pool` stay
+pub fn delete_duplicates(index: &mut ModelIndex, group: &mut DuplicateGroup, pool: &Arc<Mutex<GgufState>>) {
+    for id in &group.duplicates {
+        let result = match index.models.iter().find(|m| &m.id == id) {
+            Some(model) => ensure_not_loaded(pool, model).and_then(|_| delete_model(index, id)),
+            None => Err(format!("Model kütüphanede yok: {}", id)),
+        };
+        if let Err(e) = &result {
+            warn!("⚠️ {}", e);
+        }
+        group.outcomes.push(DuplicateOutcome { id: id.clone(), deleted: result.is_ok(), error: result.err() });
+    }
+    group.deleted = group.outcomes.iter().all(|o| o.deleted);
+}
+
+/// Remove a model's files and its index entry
+pub fn delete_model(index: &mut ModelIndex, id: &str) -> Result<Vec<String>, String> {
+    let pos = index.models.iter().position(|m| m.id == id)
+        .ok_or_else(|| format!("Model kütüphanede yok: {}", id))?;
+    let model = index.models.remove(pos);
+    let mut deleted = Vec::new();
+    for part in &model.parts {
+        match std::fs::remove_file(part) {
+            Ok(()) => deleted.push(part.clone()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("Silinemedi: {} ({})", part, e)),
+        }
+    }
+    Ok(deleted)
+}
+
+// --------------------
+// PERSISTENCE
+// --------------------
+
+/// Serialises index read-modify-write cycles between commands
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+fn index_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("model_library.json"))
+}
+
+pub fn library_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?.join("models");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Model klasörü oluşturulamadı: {}", e))?;
+    Ok(dir)
+}
+
+pub fn load_index(path: &Path) -> ModelIndex {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or(ModelIndex { version: INDEX_VERSION, ..Default::default() })
+}
+
+pub fn save_index(path: &Path, index: &ModelIndex) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(index).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Model index yazılamadı: {}", e))
+}
+
+fn read_index(app: &AppHandle) -> Result<ModelIndex, String> {
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|p| p.into_inner());
+    Ok(load_index(&index_path(app)?))
+}
+
+/// Load the index, apply `f`, save it back
+fn with_index<T>(app: &AppHandle, f: impl FnOnce(&mut ModelIndex) -> Result<T, String>) -> Result<T, String> {
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|p| p.into_inner());
+    let path = index_path(app)?;
+    let mut index = load_index(&path);
+    let result = f(&mut index)?;
+    save_index(&path, &index)?;
+    Ok(result)
+}
+
+/// Record that a model was loaded (called after `load_gguf_model` succeeds).
+/// Runs off-thread so a long scan holding the index never delays the load.
+pub fn mark_used(app: &AppHandle, model_path: &str) {
+    let (app, model_path) = (app.clone(), model_path.to_string());
+    tokio::task::spawn_blocking(move || {
+        let used = now_secs();
+        let result = with_index(&app, |index| {
+            if let Some(m) = index.models.iter_mut().find(|m| m.path == model_path) {
+                m.last_used = Some(used);
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("⚠️ Son kullanım kaydedilemedi: {}", e);
+        }
+    });
+}
+
+fn ensure_not_loaded(state: &Mutex<GgufState>, model: &LibraryModel) -> Result<(), String> {
+    if state.lock().unwrap_or_else(|p| p.into_inner()).models.contains_key(&model.path) {
+        return Err(format!("Model şu an yüklü, önce havuzdan kaldırın: {}", model.name));
+    }
+    Ok(())
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub async fn get_model_library_dir(app: AppHandle) -> Result<String, String> {
+    Ok(library_dir(&app)?.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+pub async fn list_library_models(app: AppHandle) -> Result<Vec<LibraryModel>, String> {
+    Ok(read_index(&app)?.models)
+}
+
+/// Scan the library dir, remembered roots and `extra_dirs` (which are remembered too)
+#[tauri::command]
+pub async fn scan_model_library(app: AppHandle, extra_dirs: Option<Vec<String>>) -> Result<Vec<LibraryModel>, String> {
+    let library = library_dir(&app)?;
+    let task_app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        with_index(&task_app, |index| {
+            for dir in extra_dirs.unwrap_or_default() {
+                if !index.roots.contains(&dir) {
+                    index.roots.push(dir);
+                }
+            }
+            let mut dirs = vec![library];
+            dirs.extend(index.roots.iter().map(PathBuf::from));
+            rescan(index, &dirs);
+            info!("📚 Model kütüphanesi: {} model ({} klasör)", index.models.len(), dirs.len());
+            Ok(index.models.clone())
+        })
+    })
+    .await
+    .map_err(|e| format!("Tarama başarısız: {}", e))?
+}
+
+#[tauri::command]
+pub async fn tag_library_model(
+    app: AppHandle,
+    model_id: String,
+    add: Option<Vec<String>>,
+    remove: Option<Vec<String>>,
+) -> Result<LibraryModel, String> {
+    with_index(&app, |index| {
+        let model = index.models.iter_mut().find(|m| m.id == model_id)
+            .ok_or_else(|| format!("Model kütüphanede yok: {}", model_id))?;
+        let remove = remove.unwrap_or_default();
+        model.tags.retain(|t| !remove.contains(t));
+        for tag in add.unwrap_or_default() {
+            let tag = tag.trim().to_string();
+            if !tag.is_empty() && !model.tags.contains(&tag) {
+                model.tags.push(tag);
+            }
+        }
+        Ok(model.clone())
+    })
+}
+
+/// Delete a model's files (all parts) from disk
+#[tauri::command]
+pub async fn delete_library_model(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    model_id: String,
+) -> Result<Vec<String>, String> {
+    with_index(&app, |index| {
+        if let Some(model) = index.models.iter().find(|m| m.id == model_id) {
+            ensure_not_loaded(&state, model)?;
+        }
+        let deleted = delete_model(index, &model_id)?;
+        info!("🗑️ Model silindi: {:?}", deleted);
+        Ok(deleted)
+    })
+}
+
+/// Find identical models by content hash; with `delete` the extra copies are removed.
+/// Hashing runs on a snapshot without the index lock, so other library commands stay responsive.
+/// The index is saved whatever the individual deletions do.
+#[tauri::command]
+pub async fn dedupe_model_library(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    delete: Option<bool>,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let pool = state.inner().clone();
+    let task_app = app.clone();
+    tokio::task::spawn_blocking(move || {
+        let hashed = compute_hashes(pending_hashes(&read_index(&task_app)?));
+        with_index(&task_app, |index| {
+            apply_hashes(index, hashed);
+            let mut groups = find_duplicates(index);
+            if delete.unwrap_or(false) {
+                for group in &mut groups {
+                    delete_duplicates(index, group, &pool);
+                }
+            }
+            Ok(groups)
+        })
+    })
+    .await
+    .map_err(|e| format!("Dedupe başarısız: {}", e))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gguf_reader::test_gguf::Builder;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("corex_library_{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_file_type_name() {
+        assert_eq!(file_type_name(15), Some("Q4_K_M"));
+        assert_eq!(file_type_name(1), Some("F16"));
+        assert_eq!(file_type_name(99), None);
+    }
+
+    #[test]
+    fn test_group_split_files() {
+        let files: Vec<PathBuf> = [
+            "/m/big-00002-of-00003.gguf",
+            "/m/big-00001-of-00003.gguf",
+            "/m/small.gguf",
+        ].iter().map(PathBuf::from).collect();
+        let groups = group_split_files(&files);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, "/m/big-00001-of-00003.gguf");
+        assert_eq!(groups[0].1.len(), 3);
+        assert_eq!(groups[1], ("/m/small.gguf".to_string(), vec!["/m/small.gguf".to_string()]));
+    }
+
+    #[test]
+    fn test_scan_describe_and_dedupe() {
+        let dir = temp_dir();
+        Builder::new()
+            .string("general.architecture", "qwen2")
+            .string("general.name", "Qwen Tiny")
+            .string("general.license", "apache-2.0")
+            .string("tokenizer.chat_template", "{{ messages }}")
+            .u32("qwen2.context_length", 32768)
+            .u32("general.file_type", 15)
+            .write(&dir.join("qwen.gguf"));
+        std::fs::create_dir_all(dir.join("copies")).unwrap();
+        std::fs::copy(dir.join("qwen.gguf"), dir.join("copies/qwen-copy.gguf")).unwrap();
+        Builder::new().string("general.architecture", "clip").write(&dir.join("mmproj-qwen.gguf"));
+        Builder::new().string("general.architecture", "llama").write(&dir.join("split-00001-of-00002.gguf"));
+
+        let mut index = ModelIndex::default();
+        rescan(&mut index, &[dir.clone()]);
+        assert_eq!(index.models.len(), 4);
+
+        let qwen = index.models.iter().find(|m| m.path.ends_with("/qwen.gguf")).unwrap();
+        assert_eq!(qwen.name, "Qwen Tiny");
+        assert_eq!(qwen.architecture.as_deref(), Some("qwen2"));
+        assert_eq!(qwen.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(qwen.context_length, Some(32768));
+        assert_eq!(qwen.license.as_deref(), Some("apache-2.0"));
+        assert!(qwen.has_chat_template);
+        assert_eq!(qwen.kind, ModelKind::Model);
+
+        let projector = index.models.iter().find(|m| m.path.ends_with("mmproj-qwen.gguf")).unwrap();
+        assert_eq!(projector.kind, ModelKind::Projector);
+        let split = index.models.iter().find(|m| m.path.contains("split-")).unwrap();
+        assert!(split.incomplete);
+        assert_eq!(split.parts.len(), 2);
+
+        // Tags survive a rescan
+        let qwen_id = qwen.id.clone();
+        index.models.iter_mut().find(|m| m.id == qwen_id).unwrap().tags.push("chat".into());
+        index.models.iter_mut().find(|m| m.id == qwen_id).unwrap().last_used = Some(10);
+        index.models.iter_mut().find(|m| m.id == qwen_id).unwrap().name = "Cached".into();
+        rescan(&mut index, &[dir.clone()]);
+        let cached = index.models.iter().find(|m| m.id == qwen_id).unwrap();
+        assert_eq!(cached.tags, vec!["chat".to_string()]);
+        assert_eq!(cached.name, "Cached", "unchanged files are not re-read");
+
+        // A file that grew is re-read; tags and usage stay
+        let mut grown = std::fs::read(dir.join("qwen.gguf")).unwrap();
+        grown.extend_from_slice(&[0; 32]);
+        std::fs::write(dir.join("qwen.gguf"), &grown).unwrap();
+        std::fs::write(dir.join("copies/qwen-copy.gguf"), &grown).unwrap();
+        rescan(&mut index, &[dir.clone()]);
+        let reread = index.models.iter().find(|m| m.id == qwen_id).unwrap();
+        assert_eq!(reread.name, "Qwen Tiny");
+        assert_eq!(reread.tags, vec!["chat".to_string()]);
+        assert_eq!(reread.last_used, Some(10));
+
+        // A hash computed for a file that changed meanwhile is not cached
+        let pending = pending_hashes(&index);
+        assert_eq!(pending.len(), 2);
+        let mut stale = compute_hashes(pending.clone());
+        stale.iter_mut().for_each(|(m, _)| m.modified += 1);
+        apply_hashes(&mut index, stale);
+        assert!(index.models.iter().all(|m| m.sha256.is_none()));
+
+        apply_hashes(&mut index, compute_hashes(pending));
+        assert!(pending_hashes(&index).is_empty());
+        let mut groups = find_duplicates(&index);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep, qwen_id);
+        assert_eq!(groups[0].duplicates.len(), 1);
+
+        // An id that vanished from the index is reported, not fatal
+        let copy_id = groups[0].duplicates[0].clone();
+        groups[0].duplicates.insert(0, "missing".into());
+        let pool = Arc::new(Mutex::new(GgufState::default()));
+        delete_duplicates(&mut index, &mut groups[0], &pool);
+        assert!(!groups[0].deleted);
+        assert!(!groups[0].outcomes[0].deleted && groups[0].outcomes[0].error.is_some());
+        assert_eq!(groups[0].outcomes[1].id, copy_id);
+        assert!(groups[0].outcomes[1].deleted);
+        assert!(!dir.join("copies/qwen-copy.gguf").exists());
+        assert_eq!(index.models.len(), 3);
+
+        // Unreadable files are skipped without losing cached hashes
+        std::fs::remove_file(dir.join("mmproj-qwen.gguf")).unwrap();
+        index.models.iter_mut().for_each(|m| m.size_bytes = 1);
+        apply_hashes(&mut index, compute_hashes(pending_hashes(&index)));
+        assert!(find_duplicates(&index).is_empty());
+        assert!(index.models.iter().find(|m| m.id == qwen_id).unwrap().sha256.is_some());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
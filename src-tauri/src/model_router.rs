@@ -0,0 +1,360 @@
+// src-tauri/src/model_router.rs
+// Model targets and per-task fallback chains.
+//
+// A ModelTarget is one way to answer a ChatRequest: a remote provider or a local
+// GGUF model. Chains list targets in order per task type ("chat", "agent",
+// "completion", ...); when a target fails (after http_client's retries, or with
+// its circuit open) the request moves to the next one, typically ending on a
+// local GGUF model. Chains live in {app_data}/fallback_chains.json.
+
+use log::{info, warn};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager, State};
+
+use crate::gguf::GgufState;
+use crate::gguf_tools;
+use crate::http_client;
+use crate::inference::InferenceEngine;
+use crate::providers::{self, ChatProvider, ChatRequest, ChatResponse, ProviderSettings};
+use crate::usage::{self, UsageEvent};
+
+/// Chain used for task types without their own entry
+pub const DEFAULT_TASK: &str = "default";
+
+pub type ModelFuture<'a> = Pin<Box<dyn Future<Output = Result<ChatResponse, String>> + Send + 'a>>;
+
+/// One (tool-aware) model turn
+pub trait ChatModel: Send + Sync {
+    /// Short name for logs and error messages
+    fn label(&self) -> String;
+    fn complete<'a>(&'a self, request: &'a ChatRequest) -> ModelFuture<'a>;
+}
+
+pub struct ProviderModel {
+    client: Client,
+    provider: Arc<dyn ChatProvider>,
+    settings: ProviderSettings,
+}
+
+impl ChatModel for ProviderModel {
+    fn label(&self) -> String {
+        format!("{}:{}", self.provider.id(), self.settings.model)
+    }
+
+    fn complete<'a>(&'a self, request: &'a ChatRequest) -> ModelFuture<'a> {
+        Box::pin(providers::send(&self.client, self.provider.as_ref(), &self.settings, request))
+    }
+}
+
+pub struct GgufModel {
+    engine: InferenceEngine,
+    model_id: String,
+}
+
+impl ChatModel for GgufModel {
+    fn label(&self) -> String {
+        format!("gguf:{}", self.model_id)
+    }
+
+    fn complete<'a>(&'a self, request: &'a ChatRequest) -> ModelFuture<'a> {
+        let (engine, model_id, request) = (self.engine.clone(), self.model_id.clone(), request.clone());
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || gguf_tools::complete_with_tools(&engine, &model_id, &request))
+                .await
+                .map_err(|e| format!("Inference görevi başarısız: {}", e))?
+        })
+    }
+}
+
+/// Tries each model in order and returns the first answer
+pub struct FallbackChain {
+    task: String,
+    models: Vec<Box<dyn ChatModel>>,
+}
+
+impl FallbackChain {
+    pub fn new(task: impl Into<String>, models: Vec<Box<dyn ChatModel>>) -> Self {
+        Self { task: task.into(), models }
+    }
+}
+
+impl ChatModel for FallbackChain {
+    fn label(&self) -> String {
+        format!("chain:{}", self.task)
+    }
+
+    fn complete<'a>(&'a self, request: &'a ChatRequest) -> ModelFuture<'a> {
+        Box::pin(async move {
+            let mut errors = Vec::new();
+            for (i, model) in self.models.iter().enumerate() {
+                match model.complete(request).await {
+                    Ok(response) => {
+                        if i > 0 {
+                            info!("↪️ {} yedek modelle yanıtlandı: {}", self.task, model.label());
+                        }
+                        return Ok(response);
+                    }
+                    Err(e) => {
+                        warn!("⚠️ {} başarısız, sıradaki deneniyor: {}", model.label(), e);
+                        errors.push(format!("{}: {}", model.label(), e));
+                    }
+                }
+            }
+            Err(format!("'{}' için tüm modeller başarısız oldu ({})", self.task, errors.join("; ")))
+        })
+    }
+}
+
+/// Where a request goes. Passed per call, so API keys need not be stored with runs;
+/// targets saved in fallback chains reference vault credentials instead (see `validate_chain`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ModelTarget {
+    Provider { provider_id: String, settings: ProviderSettings },
+    Gguf { model_path: Option<String> },
+    /// The fallback chain configured for a task type
+    Chain { task: String },
+}
+
+impl ModelTarget {
+    pub fn build(&self, gguf_state: &Arc<Mutex<GgufState>>, config: &FallbackConfig) -> Result<Box<dyn ChatModel>, String> {
+        match self {
+            ModelTarget::Provider { provider_id, settings } => Ok(Box::new(ProviderModel {
+                client: http_client::client(),
+                provider: providers::provider(provider_id)?,
+                settings: settings.clone(),
+            })),
+            ModelTarget::Gguf { model_path } => {
+                let engine = InferenceEngine::llama_cpp(gguf_state.clone());
+                let model_id = engine.resolve_model(model_path.as_deref())?;
+                Ok(Box::new(GgufModel { engine, model_id }))
+            }
+            ModelTarget::Chain { task } => {
+                let mut models = Vec::new();
+                for target in config.chain(task)? {
+                    if matches!(target, ModelTarget::Chain { .. }) {
+                        return Err(format!("'{}' zinciri başka bir zincire başvuramaz", task));
+                    }
+                    // A target that can't even be built (e.g. no GGUF loaded) is skipped
+                    match target.build(gguf_state, config) {
+                        Ok(model) => models.push(model),
+                        Err(e) => warn!("⚠️ '{}' zincirinde hedef atlandı: {}", task, e),
+                    }
+                }
+                if models.is_empty() {
+                    return Err(format!("'{}' zincirinde kullanılabilir model yok", task));
+                }
+                Ok(Box::new(FallbackChain::new(task.clone(), models)))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FallbackConfig {
+    /// Task type -> targets in priority order
+    pub chains: BTreeMap<String, Vec<ModelTarget>>,
+}
+
+impl FallbackConfig {
+    /// Chain for `task`, falling back to the "default" chain
+    pub fn chain(&self, task: &str) -> Result<&[ModelTarget], String> {
+        self.chains.get(task)
+            .or_else(|| self.chains.get(DEFAULT_TASK))
+            .map(Vec::as_slice)
+            .filter(|targets| !targets.is_empty())
+            .ok_or_else(|| format!("'{}' görevi için fallback zinciri tanımlı değil", task))
+    }
+}
+
+fn config_path(app: &AppHandle) -> Result<PathBuf, String> {
+    Ok(app.path().app_data_dir().map_err(|e| e.to_string())?.join("fallback_chains.json"))
+}
+
+pub fn load_config(path: &Path) -> FallbackConfig {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Saved chains hold no secrets: provider targets use `credential_id`, never `api_key`
+pub fn validate_chain(targets: &[ModelTarget]) -> Result<(), String> {
+    for target in targets {
+        match target {
+            ModelTarget::Chain { .. } => return Err("Zincir hedefleri başka bir zincire başvuramaz".to_string()),
+            ModelTarget::Provider { provider_id, settings } => {
+                if settings.api_key.as_deref().map_or(false, |k| !k.is_empty()) {
+                    return Err(format!(
+                        "{}: fallback zincirleri API anahtarını düz metin saklayamaz; anahtarı kasaya kaydedip credential_id kullanın",
+                        provider_id
+                    ));
+                }
+                if settings.credential_id.is_none() && providers::provider(provider_id)?.info().requires_api_key {
+                    return Err(format!("{} için credential_id gerekli", provider_id));
+                }
+            }
+            ModelTarget::Gguf { .. } => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn save_config(path: &Path, config: &FallbackConfig) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    // Never write keys to disk, even from chains saved before validation existed
+    let mut config = config.clone();
+    for target in config.chains.values_mut().flatten() {
+        if let ModelTarget::Provider { settings, .. } = target {
+            settings.api_key = None;
+        }
+    }
+    let json = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| format!("Fallback ayarları yazılamadı: {}", e))
+}
+
+/// Build `target` against the app's saved chains and model pool
+pub fn build_target(app: &AppHandle, target: &ModelTarget) -> Result<Box<dyn ChatModel>, String> {
+    let gguf_state = app.state::<Arc<Mutex<GgufState>>>();
+    target.build(gguf_state.inner(), &load_config(&config_path(app)?))
+}
+
+#[tauri::command]
+pub fn get_fallback_chains(app: AppHandle) -> Result<FallbackConfig, String> {
+    Ok(load_config(&config_path(&app)?))
+}
+
+/// Replace the chain for `task`; an empty list removes it
+#[tauri::command]
+pub fn set_fallback_chain(app: AppHandle, task: String, targets: Vec<ModelTarget>) -> Result<FallbackConfig, String> {
+    validate_chain(&targets)?;
+    let path = config_path(&app)?;
+    let mut config = load_config(&path);
+    if targets.is_empty() {
+        config.chains.remove(&task);
+    } else {
+        info!("🔗 Fallback zinciri güncellendi: {} ({} hedef)", task, targets.len());
+        config.chains.insert(task, targets);
+    }
+    save_config(&path, &config)?;
+    Ok(config)
+}
+
+/// Answer `request` with the first working model of the chain for `task`
+#[tauri::command]
+pub async fn chat_with_fallback(
+    app: AppHandle,
+    state: State<'_, Arc<Mutex<GgufState>>>,
+    task: String,
+    request: ChatRequest,
+) -> Result<ChatResponse, String> {
+    usage::check_budget(None, &task, None)?;
+    let model = ModelTarget::Chain { task: task.clone() }.build(state.inner(), &load_config(&config_path(&app)?))?;
+    let response = model.complete(&request).await?;
+    usage::record(UsageEvent::from_response(&task, &model.label(), &request, &response));
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails or answers with its own name, counting calls
+    struct FixedModel {
+        name: &'static str,
+        fail: bool,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl ChatModel for FixedModel {
+        fn label(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn complete<'a>(&'a self, _request: &'a ChatRequest) -> ModelFuture<'a> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let result = if self.fail {
+                Err("Bağlantı hatası: connection refused".to_string())
+            } else {
+                Ok(ChatResponse { text: self.name.to_string(), ..ChatResponse::empty(self.name) })
+            };
+            Box::pin(async move { result })
+        }
+    }
+
+    fn fixed(name: &'static str, fail: bool, calls: &Arc<AtomicUsize>) -> Box<dyn ChatModel> {
+        Box::new(FixedModel { name, fail, calls: calls.clone() })
+    }
+
+    #[tokio::test]
+    async fn test_chain_falls_through_to_first_working_model() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let chain = FallbackChain::new("chat", vec![
+            fixed("lmstudio", true, &calls),
+            fixed("gguf", false, &calls),
+            fixed("unused", false, &calls),
+        ]);
+        let response = chain.complete(&ChatRequest::default()).await.unwrap();
+        assert_eq!(response.text, "gguf");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let dead = FallbackChain::new("chat", vec![fixed("a", true, &calls), fixed("b", true, &calls)]);
+        let err = dead.complete(&ChatRequest::default()).await.unwrap_err();
+        assert!(err.contains("a: Bağlantı hatası") && err.contains("b: Bağlantı hatası"));
+    }
+
+    #[test]
+    fn test_config_lookup_and_nesting() {
+        let local = ModelTarget::Gguf { model_path: None };
+        let config: FallbackConfig = serde_json::from_value(serde_json::json!({
+            "chains": {
+                "default": [{ "kind": "gguf", "model_path": null }],
+                "agent": [
+                    { "kind": "provider", "provider_id": "openai", "settings": { "base_url": "http://127.0.0.1:1234/v1", "model": "qwen" } },
+                    { "kind": "chain", "task": "default" }
+                ]
+            }
+        })).unwrap();
+        assert!(matches!(config.chain("commit_message").unwrap(), [ModelTarget::Gguf { .. }]));
+        assert_eq!(config.chain("agent").unwrap().len(), 2);
+        assert!(FallbackConfig::default().chain("chat").is_err());
+
+        let state = Arc::new(Mutex::new(GgufState::default()));
+        let err = ModelTarget::Chain { task: "agent".into() }.build(&state, &config).err().unwrap();
+        assert!(err.contains("başka bir zincire"));
+        let err = local.build(&state, &config).err().unwrap();
+        assert!(!err.is_empty());
+    }
+
+    #[test]
+    fn test_saved_chains_hold_no_keys() {
+        let provider = |id: &str, api_key: Option<&str>, credential_id: Option<&str>| ModelTarget::Provider {
+            provider_id: id.into(),
+            settings: ProviderSettings {
+                base_url: String::new(),
+                api_key: api_key.map(String::from),
+                model: "m".into(),
+                credential_id: credential_id.map(String::from),
+            },
+        };
+        assert!(validate_chain(&[provider("openai", Some("sk-secret"), None)]).is_err());
+        assert!(validate_chain(&[provider("anthropic", None, None)]).is_err());
+        assert!(validate_chain(&[provider("anthropic", None, Some("work")), provider("openai", None, None)]).is_ok());
+
+        let path = std::env::temp_dir().join(format!("corex_chains_{}.json", uuid::Uuid::new_v4().simple()));
+        let mut config = FallbackConfig::default();
+        config.chains.insert("chat".into(), vec![provider("openai", Some("sk-secret"), None)]);
+        save_config(&path, &config).unwrap();
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("sk-secret"));
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -1,9 +1,13 @@
 // OAuth Backend - Secure token exchange
 // Client secrets are stored server-side, never exposed to frontend
 
+use log::info;
 use serde::{Deserialize, Serialize};
 use std::env;
 
+use crate::credentials::{self, CredentialInfo};
+use crate::http_client;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenRequest {
     pub code: String,
@@ -19,64 +23,87 @@ pub struct TokenResponse {
     pub token_type: String,
 }
 
-#[tauri::command]
-pub async fn exchange_oauth_token(
-    code: String,
-    provider: String,
-    redirect_uri: String,
-) -> Result<TokenResponse, String> {
-    println!("🔐 Exchanging OAuth token for provider: {}", provider);
-    
-    // Get client credentials from environment (NEVER from frontend!)
-    let (client_id, client_secret, token_url) = match provider.as_str() {
-        "github" => (
+/// Client id, client secret and token URL for `provider`
+fn client_credentials(provider: &str) -> Result<(String, String, String), String> {
+    match provider {
+        "github" => Ok((
             env::var("GITHUB_CLIENT_ID")
                 .map_err(|_| "GITHUB_CLIENT_ID not set".to_string())?,
             env::var("GITHUB_CLIENT_SECRET")
                 .map_err(|_| "GITHUB_CLIENT_SECRET not set".to_string())?,
             "https://github.com/login/oauth/access_token".to_string(),
-        ),
-        "microsoft" => (
+        )),
+        "microsoft" => Ok((
             env::var("MICROSOFT_CLIENT_ID")
                 .map_err(|_| "MICROSOFT_CLIENT_ID not set".to_string())?,
             env::var("MICROSOFT_CLIENT_SECRET")
                 .map_err(|_| "MICROSOFT_CLIENT_SECRET not set".to_string())?,
             "https://login.microsoftonline.com/common/oauth2/v2.0/token".to_string(),
-        ),
-        _ => return Err(format!("Unknown provider: {}", provider)),
-    };
-    
-    // Exchange code for token
-    let client = reqwest::Client::new();
-    
-    let params = [
+        )),
+        _ => Err(format!("Unknown provider: {}", provider)),
+    }
+}
+
+/// POST a token grant to `provider`'s token endpoint
+async fn request_token(provider: &str, grant: &[(&str, &str)], action: &str) -> Result<TokenResponse, String> {
+    let (client_id, client_secret, token_url) = client_credentials(provider)?;
+
+    let mut params = vec![
         ("client_id", client_id.as_str()),
         ("client_secret", client_secret.as_str()),
-        ("code", code.as_str()),
-        ("redirect_uri", redirect_uri.as_str()),
-        ("grant_type", "authorization_code"),
     ];
-    
-    let response = client
+    params.extend_from_slice(grant);
+
+    let response = http_client::client()
         .post(&token_url)
         .header("Accept", "application/json")
         .form(&params)
         .send()
         .await
-        .map_err(|e| format!("Token exchange request failed: {}", e))?;
-    
+        .map_err(|e| format!("Token {} request failed: {}", action, e))?;
+
     if !response.status().is_success() {
         let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Token exchange failed: {}", error_text));
+        return Err(format!("Token {} failed: {}", action, credentials::redact(&error_text)));
     }
-    
-    let token_data: TokenResponse = response
+
+    response
         .json()
         .await
-        .map_err(|e| format!("Failed to parse token response: {}", e))?;
-    
-    println!("✅ Token exchange successful");
-    
+        .map_err(|e| format!("Failed to parse token response: {}", e))
+}
+
+/// Exchange an authorization code for tokens
+pub async fn exchange_code(code: &str, provider: &str, redirect_uri: &str) -> Result<TokenResponse, String> {
+    request_token(provider, &[
+        ("code", code),
+        ("redirect_uri", redirect_uri),
+        ("grant_type", "authorization_code"),
+    ], "exchange").await
+}
+
+/// Trade a refresh token for a new access token
+pub async fn refresh_token(refresh_token: &str, provider: &str) -> Result<TokenResponse, String> {
+    if provider == "github" {
+        // GitHub doesn't support refresh tokens
+        return Err("GitHub does not support token refresh".to_string());
+    }
+    request_token(provider, &[
+        ("refresh_token", refresh_token),
+        ("grant_type", "refresh_token"),
+    ], "refresh").await
+}
+
+/// Returns the raw tokens to the webview; prefer `exchange_oauth_credential`
+#[tauri::command]
+pub async fn exchange_oauth_token(
+    code: String,
+    provider: String,
+    redirect_uri: String,
+) -> Result<TokenResponse, String> {
+    info!("🔐 Exchanging OAuth token for provider: {}", provider);
+    let token_data = exchange_code(&code, &provider, &redirect_uri).await?;
+    info!("✅ Token exchange successful");
     Ok(token_data)
 }
 
@@ -85,51 +112,24 @@ pub async fn refresh_oauth_token(
     refresh_token: String,
     provider: String,
 ) -> Result<TokenResponse, String> {
-    println!("🔄 Refreshing OAuth token for provider: {}", provider);
-    
-    let (client_id, client_secret, token_url) = match provider.as_str() {
-        "github" => {
-            // GitHub doesn't support refresh tokens
-            return Err("GitHub does not support token refresh".to_string());
-        }
-        "microsoft" => (
-            env::var("MICROSOFT_CLIENT_ID")
-                .map_err(|_| "MICROSOFT_CLIENT_ID not set".to_string())?,
-            env::var("MICROSOFT_CLIENT_SECRET")
-                .map_err(|_| "MICROSOFT_CLIENT_SECRET not set".to_string())?,
-            "https://login.microsoftonline.com/common/oauth2/v2.0/token".to_string(),
-        ),
-        _ => return Err(format!("Unknown provider: {}", provider)),
-    };
-    
-    let client = reqwest::Client::new();
-    
-    let params = [
-        ("client_id", client_id.as_str()),
-        ("client_secret", client_secret.as_str()),
-        ("refresh_token", refresh_token.as_str()),
-        ("grant_type", "refresh_token"),
-    ];
-    
-    let response = client
-        .post(&token_url)
-        .header("Accept", "application/json")
-        .form(&params)
-        .send()
-        .await
-        .map_err(|e| format!("Token refresh request failed: {}", e))?;
-    
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("Token refresh failed: {}", error_text));
-    }
-    
-    let token_data: TokenResponse = response
-        .json()
-        .await
-        .map_err(|e| format!("Failed to parse token response: {}", e))?;
-    
-    println!("✅ Token refresh successful");
-    
+    info!("🔄 Refreshing OAuth token for provider: {}", provider);
+    let token_data = self::refresh_token(&refresh_token, &provider).await?;
+    info!("✅ Token refresh successful");
     Ok(token_data)
 }
+
+/// Exchange the code and keep the tokens in the credential vault; the webview only
+/// gets the credential id. The vault refreshes the access token before it expires.
+#[tauri::command]
+pub async fn exchange_oauth_credential(
+    code: String,
+    provider: String,
+    redirect_uri: String,
+    label: Option<String>,
+) -> Result<CredentialInfo, String> {
+    info!("🔐 Exchanging OAuth token into the vault for provider: {}", provider);
+    let token_data = exchange_code(&code, &provider, &redirect_uri).await?;
+    let info = credentials::store_oauth_tokens(&provider, label, token_data)?;
+    info!("✅ OAuth credential stored: {}", info.id);
+    Ok(info)
+}
@@ -0,0 +1,750 @@
+// src-tauri/src/openai_server.rs
+// Opt-in localhost server exposing the GGUF model pool through an OpenAI-compatible API.
+// Endpoints: /v1/models, /v1/chat/completions (SSE + JSON), /v1/completions, /v1/embeddings
+
+use crate::commands::ChatMessage;
+use crate::gguf::{GgufState, LoadedModel};
+use crate::gguf_completion::{CompletionOptions, FinishReason, GgufCompletion, TokenLogprob};
+use crate::gguf_embedding::{self, EmbeddingOptions};
+use crate::inference::{GenerateRequest, InferenceEngine};
+use crate::usage::{self, UsageEvent};
+use llama_cpp_2::llama_backend::LlamaBackend;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use tauri::State;
+use tiny_http::{Header, Method, Request, Response, Server, StatusCode};
+
+const MAX_BODY_BYTES: u64 = 32 * 1024 * 1024;
+/// Requests are served by this many threads; the rest wait in tiny_http's queue
+const WORKER_THREADS: usize = 4;
+
+fn default_port() -> u16 {
+    8765
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenAiServerConfig {
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Required `Authorization: Bearer <key>` value; no check when empty
+    pub api_key: Option<String>,
+    /// Pool key used when a request asks for "default" or omits the model
+    pub default_model: Option<String>,
+}
+
+struct RunningServer {
+    server: Arc<Server>,
+    config: OpenAiServerConfig,
+    workers: Vec<std::thread::JoinHandle<()>>,
+}
+
+#[derive(Default)]
+pub struct OpenAiServerState {
+    running: Mutex<Option<RunningServer>>,
+}
+
+// --------------------
+// REQUEST TYPES
+// --------------------
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum StopParam {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl StopParam {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            StopParam::One(s) => vec![s],
+            StopParam::Many(v) => v,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionRequest {
+    model: Option<String>,
+    messages: Vec<ChatMessage>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    stop: Option<StopParam>,
+    #[serde(default)]
+    stream: bool,
+    #[serde(default)]
+    logprobs: bool,
+    top_logprobs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CompletionRequest {
+    model: Option<String>,
+    prompt: String,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+    stop: Option<StopParam>,
+    #[serde(default)]
+    stream: bool,
+    logprobs: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum EmbeddingInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingRequest {
+    model: Option<String>,
+    input: EmbeddingInput,
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub async fn start_openai_server(
+    server_state: State<'_, OpenAiServerState>,
+    gguf_state: State<'_, Arc<Mutex<GgufState>>>,
+    config: OpenAiServerConfig,
+) -> Result<serde_json::Value, String> {
+    let mut running = server_state.running.lock().unwrap();
+    if let Some(existing) = running.as_ref() {
+        return Err(format!("Sunucu zaten çalışıyor: http://127.0.0.1:{}", existing.config.port));
+    }
+
+    // Only ever bind to loopback
+    let addr = format!("127.0.0.1:{}", config.port);
+    let server = Arc::new(Server::http(&addr).map_err(|e| format!("Sunucu başlatılamadı: {}", e))?);
+    info!("🌐 OpenAI-compatible server listening on http://{}", addr);
+
+    let workers = (0..WORKER_THREADS)
+        .map(|_| {
+            let server = server.clone();
+            let pool = gguf_state.inner().clone();
+            let config = config.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle_request(request, &pool, &config);
+                }
+            })
+        })
+        .collect();
+
+    *running = Some(RunningServer { server, config: config.clone(), workers });
+
+    Ok(json!({
+        "running": true,
+        "base_url": format!("http://127.0.0.1:{}/v1", config.port),
+        "auth_required": config.api_key.as_deref().map_or(false, |k| !k.is_empty()),
+    }))
+}
+
+#[tauri::command]
+pub async fn stop_openai_server(server_state: State<'_, OpenAiServerState>) -> Result<(), String> {
+    let running = server_state.running.lock().unwrap().take();
+    match running {
+        Some(running) => {
+            // Each unblock releases one waiting worker
+            for _ in &running.workers {
+                running.server.unblock();
+            }
+            // Workers finish the request they are serving first; don't block the async runtime on that
+            let port = running.config.port;
+            tokio::task::spawn_blocking(move || {
+                for worker in running.workers {
+                    let _ = worker.join();
+                }
+            })
+            .await
+            .map_err(|e| format!("Sunucu durdurulamadı: {}", e))?;
+            info!("✅ OpenAI-compatible server on port {} stopped", port);
+            Ok(())
+        }
+        None => Err("Sunucu çalışmıyor".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn get_openai_server_status(
+    server_state: State<'_, OpenAiServerState>,
+) -> Result<serde_json::Value, String> {
+    let running = server_state.running.lock().unwrap();
+    Ok(match running.as_ref() {
+        Some(r) => json!({
+            "running": true,
+            "port": r.config.port,
+            "base_url": format!("http://127.0.0.1:{}/v1", r.config.port),
+            "auth_required": r.config.api_key.as_deref().map_or(false, |k| !k.is_empty()),
+        }),
+        None => json!({ "running": false }),
+    })
+}
+
+// --------------------
+// ROUTING
+// --------------------
+
+fn handle_request(mut request: Request, pool: &Arc<Mutex<GgufState>>, config: &OpenAiServerConfig) {
+    let method = request.method().clone();
+    let path = request.url().split('?').next().unwrap_or("").trim_end_matches('/').to_string();
+    info!("📥 {} {}", method, path);
+
+    let auth = request.headers().iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .map(|h| h.value.as_str().to_string());
+    if !is_authorized(config.api_key.as_deref(), auth.as_deref()) {
+        respond_error(request, 401, "invalid_api_key", "Invalid or missing API key");
+        return;
+    }
+
+    let body = if method == Method::Post {
+        let mut body = String::new();
+        if let Err(e) = request.as_reader().take(MAX_BODY_BYTES).read_to_string(&mut body) {
+            respond_error(request, 400, "invalid_request_error", &format!("Body okunamadı: {}", e));
+            return;
+        }
+        body
+    } else {
+        String::new()
+    };
+
+    match (method, path.as_str()) {
+        (Method::Get, "/v1/models") => handle_models(request, pool),
+        (Method::Post, "/v1/chat/completions") => match serde_json::from_str(&body) {
+            Ok(req) => handle_chat(request, pool, config, req),
+            Err(e) => respond_error(request, 400, "invalid_request_error", &e.to_string()),
+        },
+        (Method::Post, "/v1/completions") => match serde_json::from_str(&body) {
+            Ok(req) => handle_completion(request, pool, config, req),
+            Err(e) => respond_error(request, 400, "invalid_request_error", &e.to_string()),
+        },
+        (Method::Post, "/v1/embeddings") => match serde_json::from_str(&body) {
+            Ok(req) => handle_embeddings(request, pool, config, req),
+            Err(e) => respond_error(request, 400, "invalid_request_error", &e.to_string()),
+        },
+        _ => respond_error(request, 404, "not_found", &format!("Unknown endpoint: {}", path)),
+    }
+}
+
+fn handle_models(request: Request, pool: &Arc<Mutex<GgufState>>) {
+    let keys = InferenceEngine::llama_cpp(pool.clone()).loaded_models();
+    let data: Vec<serde_json::Value> = keys.iter()
+        .map(|k| json!({ "id": k, "object": "model", "owned_by": "corex", "created": 0 }))
+        .collect();
+    respond_json(request, 200, &json!({ "object": "list", "data": data }));
+}
+
+fn handle_chat(request: Request, pool: &Arc<Mutex<GgufState>>, config: &OpenAiServerConfig, req: ChatCompletionRequest) {
+    let (engine, model_id) = match engine_for(pool, config, req.model.as_deref()) {
+        Ok(m) => m,
+        Err(e) => return respond_error(request, 404, "model_not_found", &e),
+    };
+
+    if let Err(e) = usage::check_budget(Some("gguf"), "api_chat", None) {
+        return respond_error(request, 429, "budget_exceeded", &e);
+    }
+
+    let prompt = match engine.format_chat(&model_id, &req.messages) {
+        Ok(p) => p,
+        Err(e) => return respond_error(request, 404, "model_not_found", &e),
+    };
+    let options = CompletionOptions {
+        stop: req.stop.map(StopParam::into_vec).unwrap_or_default(),
+        top_logprobs: if req.logprobs { Some(req.top_logprobs.unwrap_or(0)) } else { None },
+        ..Default::default()
+    };
+    let generate = GenerateRequest {
+        prompt,
+        max_tokens: req.max_tokens.unwrap_or(2000),
+        temperature: req.temperature.unwrap_or(0.7),
+        options,
+    };
+    let id = format!("chatcmpl-{}", uuid::Uuid::new_v4().simple());
+
+    if req.stream {
+        let chunk_id = id.clone();
+        let chunk_model = model_id.clone();
+        stream_sse(request, move |tx| {
+            let _ = tx.send(sse_data(&chat_chunk(&chunk_id, &chunk_model, json!({ "role": "assistant" }), None)));
+            let result = engine.stream(&chunk_model, &generate, &mut |text| {
+                tx.send(sse_data(&chat_chunk(&chunk_id, &chunk_model, json!({ "content": text }), None))).is_ok()
+            });
+            match result {
+                Ok(c) => {
+                    usage::record(UsageEvent::local("api_chat", &chunk_model, c.prompt_tokens, c.completion_tokens));
+                    let _ = tx.send(sse_data(&chat_chunk(&chunk_id, &chunk_model, json!({}), Some(finish_reason_str(c.finish_reason)))));
+                }
+                Err(e) => {
+                    error!("❌ Streaming generation failed: {}", e);
+                    let _ = tx.send(sse_data(&json!({ "error": { "message": e, "type": "server_error" } })));
+                }
+            }
+            let _ = tx.send(b"data: [DONE]\n\n".to_vec());
+        });
+        return;
+    }
+
+    match engine.generate(&model_id, &generate) {
+        Ok(c) => {
+            usage::record(UsageEvent::local("api_chat", &model_id, c.prompt_tokens, c.completion_tokens));
+            let body = json!({
+                "id": id,
+                "object": "chat.completion",
+                "created": unix_now(),
+                "model": model_id,
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": c.text },
+                    "finish_reason": finish_reason_str(c.finish_reason),
+                    "logprobs": logprobs_json(&c),
+                }],
+                "usage": usage_json(&c),
+            });
+            respond_json(request, 200, &body);
+        }
+        Err(e) => respond_error(request, 500, "server_error", &e),
+    }
+}
+
+fn handle_completion(request: Request, pool: &Arc<Mutex<GgufState>>, config: &OpenAiServerConfig, req: CompletionRequest) {
+    let (engine, model_id) = match engine_for(pool, config, req.model.as_deref()) {
+        Ok(m) => m,
+        Err(e) => return respond_error(request, 404, "model_not_found", &e),
+    };
+
+    if let Err(e) = usage::check_budget(Some("gguf"), "api_completion", None) {
+        return respond_error(request, 429, "budget_exceeded", &e);
+    }
+
+    let prompt_chars = req.prompt.chars().count();
+    let options = CompletionOptions {
+        stop: req.stop.map(StopParam::into_vec).unwrap_or_default(),
+        top_logprobs: req.logprobs,
+        ..Default::default()
+    };
+    let generate = GenerateRequest {
+        prompt: req.prompt,
+        max_tokens: req.max_tokens.unwrap_or(256),
+        temperature: req.temperature.unwrap_or(0.7),
+        options,
+    };
+    let id = format!("cmpl-{}", uuid::Uuid::new_v4().simple());
+
+    if req.stream {
+        let chunk_id = id.clone();
+        let chunk_model = model_id.clone();
+        stream_sse(request, move |tx| {
+            let result = engine.stream(&chunk_model, &generate, &mut |text| {
+                tx.send(sse_data(&text_chunk(&chunk_id, &chunk_model, text, None))).is_ok()
+            });
+            match result {
+                Ok(c) => {
+                    usage::record(UsageEvent::local("api_completion", &chunk_model, c.prompt_tokens, c.completion_tokens));
+                    let _ = tx.send(sse_data(&text_chunk(&chunk_id, &chunk_model, "", Some(finish_reason_str(c.finish_reason)))));
+                }
+                Err(e) => {
+                    error!("❌ Streaming generation failed: {}", e);
+                    let _ = tx.send(sse_data(&json!({ "error": { "message": e, "type": "server_error" } })));
+                }
+            }
+            let _ = tx.send(b"data: [DONE]\n\n".to_vec());
+        });
+        return;
+    }
+
+    match engine.generate(&model_id, &generate) {
+        Ok(c) => {
+            usage::record(UsageEvent::local("api_completion", &model_id, c.prompt_tokens, c.completion_tokens));
+            let body = json!({
+                "id": id,
+                "object": "text_completion",
+                "created": unix_now(),
+                "model": model_id,
+                "choices": [{
+                    "index": 0,
+                    "text": c.text,
+                    "finish_reason": finish_reason_str(c.finish_reason),
+                    "logprobs": legacy_logprobs_json(c.logprobs.as_deref(), prompt_chars),
+                }],
+                "usage": usage_json(&c),
+            });
+            respond_json(request, 200, &body);
+        }
+        Err(e) => respond_error(request, 500, "server_error", &e),
+    }
+}
+
+fn handle_embeddings(request: Request, pool: &Arc<Mutex<GgufState>>, config: &OpenAiServerConfig, req: EmbeddingRequest) {
+    let (model_id, loaded, backend) = match checkout_model(pool, config, req.model.as_deref()) {
+        Ok(m) => m,
+        Err(e) => return respond_error(request, 404, "model_not_found", &e),
+    };
+
+    if let Err(e) = usage::check_budget(Some("gguf"), "api_embedding", None) {
+        return respond_error(request, 429, "budget_exceeded", &e);
+    }
+
+    let inputs = match req.input {
+        EmbeddingInput::One(s) => vec![s],
+        EmbeddingInput::Many(v) => v,
+    };
+
+    let result = match gguf_embedding::embed_texts(&loaded, &backend, &inputs, &EmbeddingOptions::default()) {
+        Ok(r) => r,
+        Err(e) => return respond_error(request, 500, "server_error", &e),
+    };
+    let prompt_tokens = result.prompt_tokens;
+    usage::record(UsageEvent::local("api_embedding", &model_id, prompt_tokens, 0));
+    let data: Vec<serde_json::Value> = result.embeddings.into_iter().enumerate()
+        .map(|(index, embedding)| json!({ "object": "embedding", "index": index, "embedding": embedding }))
+        .collect();
+
+    respond_json(request, 200, &json!({
+        "object": "list",
+        "data": data,
+        "model": model_id,
+        "usage": { "prompt_tokens": prompt_tokens, "total_tokens": prompt_tokens },
+    }));
+}
+
+// --------------------
+// HELPERS
+// --------------------
+
+type CheckedOutModel = (String, Arc<LoadedModel>, Arc<LlamaBackend>);
+
+/// Inference engine over the pool plus the resolved model id
+fn engine_for(
+    pool: &Arc<Mutex<GgufState>>,
+    config: &OpenAiServerConfig,
+    requested: Option<&str>,
+) -> Result<(InferenceEngine, String), String> {
+    let engine = InferenceEngine::llama_cpp(pool.clone());
+    let key = resolve_model(&engine.loaded_models(), requested, config.default_model.as_deref())?;
+    Ok((engine, key))
+}
+
+fn checkout_model(
+    pool: &Arc<Mutex<GgufState>>,
+    config: &OpenAiServerConfig,
+    requested: Option<&str>,
+) -> Result<CheckedOutModel, String> {
+    let mut guard = match pool.lock() {
+        Ok(g) => g,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let keys: Vec<String> = guard.models.keys().cloned().collect();
+    let key = resolve_model(&keys, requested, config.default_model.as_deref())?;
+    let (model, backend) = guard.checkout(&key)?;
+    Ok((key, model, backend))
+}
+
+/// Match a requested model name against pool keys (full path, file name or file stem).
+/// "default" or no model picks the configured default, then the first loaded model.
+fn resolve_model(keys: &[String], requested: Option<&str>, default_model: Option<&str>) -> Result<String, String> {
+    let requested = requested.filter(|r| !r.is_empty() && *r != "default");
+
+    if let Some(name) = requested {
+        return keys.iter()
+            .find(|k| {
+                let path = std::path::Path::new(k.as_str());
+                k.as_str() == name
+                    || path.file_name().and_then(|f| f.to_str()) == Some(name)
+                    || path.file_stem().and_then(|f| f.to_str()) == Some(name)
+            })
+            .cloned()
+            .ok_or_else(|| format!("The model '{}' is not loaded", name));
+    }
+
+    if let Some(default) = default_model {
+        if keys.iter().any(|k| k == default) {
+            return Ok(default.to_string());
+        }
+        warn!("⚠️ Default model not loaded: {}", default);
+    }
+
+    let mut sorted = keys.to_vec();
+    sorted.sort();
+    sorted.into_iter().next().ok_or_else(|| "No models loaded".to_string())
+}
+
+fn is_authorized(expected: Option<&str>, header: Option<&str>) -> bool {
+    match expected.filter(|k| !k.is_empty()) {
+        None => true,
+        Some(key) => header
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map_or(false, |given| constant_time_eq(given.trim(), key)),
+    }
+}
+
+/// Compares digests so the time taken says nothing about the key's content or length
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn finish_reason_str(reason: FinishReason) -> &'static str {
+    match reason {
+        FinishReason::Length => "length",
+        FinishReason::Stop | FinishReason::StopSequence | FinishReason::Cancelled => "stop",
+    }
+}
+
+fn usage_json(c: &GgufCompletion) -> serde_json::Value {
+    json!({
+        "prompt_tokens": c.prompt_tokens,
+        "completion_tokens": c.completion_tokens,
+        "total_tokens": c.prompt_tokens + c.completion_tokens,
+    })
+}
+
+fn logprobs_json(c: &GgufCompletion) -> serde_json::Value {
+    match &c.logprobs {
+        Some(entries) => json!({
+            "content": entries.iter().map(|e| json!({
+                "token": e.token,
+                "logprob": e.logprob,
+                "top_logprobs": e.top_logprobs.iter()
+                    .map(|t| json!({ "token": t.token, "logprob": t.logprob }))
+                    .collect::<Vec<_>>(),
+            })).collect::<Vec<_>>()
+        }),
+        None => serde_json::Value::Null,
+    }
+}
+
+/// `/v1/completions` shape; offsets count characters from the start of the prompt
+fn legacy_logprobs_json(logprobs: Option<&[TokenLogprob]>, prompt_chars: usize) -> serde_json::Value {
+    let Some(entries) = logprobs else {
+        return serde_json::Value::Null;
+    };
+    let mut offset = prompt_chars;
+    let text_offset: Vec<usize> = entries.iter()
+        .map(|e| {
+            let start = offset;
+            offset += e.token.chars().count();
+            start
+        })
+        .collect();
+    json!({
+        "tokens": entries.iter().map(|e| e.token.as_str()).collect::<Vec<_>>(),
+        "token_logprobs": entries.iter().map(|e| e.logprob).collect::<Vec<_>>(),
+        "top_logprobs": entries.iter()
+            .map(|e| e.top_logprobs.iter()
+                .map(|t| (t.token.clone(), json!(t.logprob)))
+                .collect::<serde_json::Map<_, _>>())
+            .collect::<Vec<_>>(),
+        "text_offset": text_offset,
+    })
+}
+
+fn chat_chunk(id: &str, model: &str, delta: serde_json::Value, finish_reason: Option<&str>) -> serde_json::Value {
+    json!({
+        "id": id,
+        "object": "chat.completion.chunk",
+        "created": unix_now(),
+        "model": model,
+        "choices": [{ "index": 0, "delta": delta, "finish_reason": finish_reason }],
+    })
+}
+
+fn text_chunk(id: &str, model: &str, text: &str, finish_reason: Option<&str>) -> serde_json::Value {
+    json!({
+        "id": id,
+        "object": "text_completion",
+        "created": unix_now(),
+        "model": model,
+        "choices": [{ "index": 0, "text": text, "finish_reason": finish_reason }],
+    })
+}
+
+fn sse_data(value: &serde_json::Value) -> Vec<u8> {
+    format!("data: {}\n\n", value).into_bytes()
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn respond_json(request: Request, status: u16, body: &serde_json::Value) {
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"));
+    if let Err(e) = request.respond(response) {
+        warn!("⚠️ Yanıt gönderilemedi: {}", e);
+    }
+}
+
+fn respond_error(request: Request, status: u16, code: &str, message: &str) {
+    respond_json(request, status, &json!({
+        "error": { "message": message, "type": code, "code": code }
+    }));
+}
+
+/// Run `producer` on a scoped thread and stream whatever it sends as an SSE body.
+/// The producer sees a send error once the client disconnects. It is joined before
+/// this returns, so there is at most one producer per worker thread.
+fn stream_sse<F>(request: Request, producer: F)
+where
+    F: FnOnce(&SyncSender<Vec<u8>>) + Send,
+{
+    let (tx, rx) = mpsc::sync_channel::<Vec<u8>>(64);
+    std::thread::scope(|scope| {
+        scope.spawn(move || producer(&tx));
+        respond_sse(request, rx);
+    });
+}
+
+fn respond_sse(request: Request, rx: Receiver<Vec<u8>>) {
+    let response = Response::new(
+        StatusCode(200),
+        vec![
+            header("Content-Type", "text/event-stream"),
+            header("Cache-Control", "no-cache"),
+        ],
+        ChannelReader::new(rx),
+        None,
+        None,
+    );
+    if let Err(e) = request.respond(response) {
+        warn!("⚠️ SSE stream closed: {}", e);
+    }
+}
+
+/// `Read` adapter over a channel of byte chunks; EOF once every sender is dropped
+struct ChannelReader {
+    rx: Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    fn new(rx: Receiver<Vec<u8>>) -> Self {
+        Self { rx, buf: Vec::new(), pos: 0 }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.buf.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.buf = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = (self.buf.len() - self.pos).min(out.len());
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_model() {
+        let keys = vec!["/models/b.gguf".to_string(), "/models/qwen-7b.gguf".to_string()];
+        assert_eq!(resolve_model(&keys, Some("qwen-7b"), None).unwrap(), "/models/qwen-7b.gguf");
+        assert_eq!(resolve_model(&keys, Some("qwen-7b.gguf"), None).unwrap(), "/models/qwen-7b.gguf");
+        assert_eq!(resolve_model(&keys, Some("default"), None).unwrap(), "/models/b.gguf");
+        assert_eq!(resolve_model(&keys, None, Some("/models/qwen-7b.gguf")).unwrap(), "/models/qwen-7b.gguf");
+        assert!(resolve_model(&keys, Some("missing"), None).is_err());
+        assert!(resolve_model(&[], None, None).is_err());
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        assert!(is_authorized(None, None));
+        assert!(is_authorized(Some(""), None));
+        assert!(is_authorized(Some("secret"), Some("Bearer secret")));
+        assert!(!is_authorized(Some("secret"), Some("Bearer wrong")));
+        assert!(!is_authorized(Some("secret"), None));
+        assert!(!is_authorized(Some("secret"), Some("Bearer secret2")));
+    }
+
+    #[test]
+    fn test_legacy_logprobs_shape() {
+        use crate::gguf_completion::TopLogprob;
+        let entry = |token: &str, logprob: f32| TokenLogprob {
+            token: token.to_string(),
+            token_id: 0,
+            logprob,
+            top_logprobs: vec![TopLogprob { token: token.to_string(), token_id: 0, logprob }],
+        };
+        let entries = [entry("fn", -0.5), entry(" main", -1.0)];
+        let v = legacy_logprobs_json(Some(&entries[..]), 3);
+        assert_eq!(v["tokens"], json!(["fn", " main"]));
+        assert_eq!(v["token_logprobs"], json!([-0.5, -1.0]));
+        assert_eq!(v["top_logprobs"][1][" main"], json!(-1.0));
+        assert_eq!(v["text_offset"], json!([3, 5]));
+        assert!(legacy_logprobs_json(None, 0).is_null());
+    }
+
+    #[test]
+    fn test_stop_param_and_channel_reader() {
+        let one: StopParam = serde_json::from_str("\"\\n\"").unwrap();
+        assert_eq!(one.into_vec(), vec!["\n".to_string()]);
+        let many: StopParam = serde_json::from_str("[\"a\",\"b\"]").unwrap();
+        assert_eq!(many.into_vec().len(), 2);
+
+        let (tx, rx) = mpsc::sync_channel(4);
+        tx.send(b"data: 1\n\n".to_vec()).unwrap();
+        tx.send(b"data: [DONE]\n\n".to_vec()).unwrap();
+        drop(tx);
+        let mut out = String::new();
+        ChannelReader::new(rx).read_to_string(&mut out).unwrap();
+        assert_eq!(out, "data: 1\n\ndata: [DONE]\n\n");
+    }
+
+    #[test]
+    fn test_stream_sse_joins_producer() {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+        let finished = Arc::new(AtomicBool::new(false));
+        let flag = finished.clone();
+        let handle = std::thread::spawn(move || {
+            let request = server.recv().unwrap();
+            stream_sse(request, move |tx| {
+                for i in 0..3 {
+                    let _ = tx.send(sse_data(&json!(i)));
+                }
+                flag.store(true, Ordering::SeqCst);
+            });
+            // The producer ran on a scoped thread: it is done once stream_sse returns
+            assert!(flag.load(Ordering::SeqCst));
+        });
+
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream.write_all(b"GET /v1/stream HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+        handle.join().unwrap();
+        assert!(finished.load(Ordering::SeqCst));
+        assert!(body.contains("data: 2"));
+    }
+}
@@ -0,0 +1,604 @@
+// src-tauri/src/prompts.rs
+// Prompt template library.
+//
+// Templates are named, versioned and declare typed variables. `{{name}}` inserts a
+// variable formatted for its kind (selections and files are fenced, diffs get a diff
+// fence, long values are truncated) and `{{#name}}...{{/name}}` keeps a section only
+// when the variable has a value. Bundled defaults can be replaced per repo with JSON
+// files in `.corex/prompts/`; each rendered prompt records the template id, version,
+// origin and a fingerprint of the exact template text it came from.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::rag_pipeline::{ContextSource, RAGPipeline};
+
+/// Per-repo override directory, relative to the project root
+pub const OVERRIDE_DIR: &str = ".corex/prompts";
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*([A-Za-z_][\w]*)\s*\}\}").unwrap());
+static SECTION_OPEN: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{#\s*([A-Za-z_][\w]*)\s*\}\}").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VarKind {
+    Text,
+    /// Code selected in the editor
+    Selection,
+    /// Path of a file whose content is inlined
+    File,
+    Diff,
+    /// Pre-built retrieval context
+    Rag,
+    /// Language id, also used as the fence language for selections
+    Language,
+}
+
+impl VarKind {
+    /// Default character cap for values of this kind
+    fn max_chars(self) -> Option<usize> {
+        match self {
+            VarKind::Selection | VarKind::Diff => Some(12_000),
+            VarKind::File => Some(20_000),
+            VarKind::Rag => Some(24_000),
+            VarKind::Text | VarKind::Language => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateVar {
+    pub name: String,
+    pub kind: VarKind,
+    #[serde(default)]
+    pub required: bool,
+    /// Rejected on `File` variables of project overrides
+    #[serde(default)]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub description: String,
+    /// Overrides the kind's default cap
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TemplateOrigin {
+    #[default]
+    Bundled,
+    Project { path: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub id: String,
+    pub version: u32,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub system: Option<String>,
+    pub template: String,
+    #[serde(default)]
+    pub variables: Vec<TemplateVar>,
+    #[serde(default, skip_deserializing)]
+    pub origin: TemplateOrigin,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedPrompt {
+    pub template_id: String,
+    pub template_version: u32,
+    pub origin: TemplateOrigin,
+    /// Hash of the template text, so edited overrides are told apart even without a version bump
+    pub fingerprint: String,
+    pub system: Option<String>,
+    pub prompt: String,
+    /// Variables that were cut to their character cap
+    #[serde(default)]
+    pub truncated: Vec<String>,
+    /// Files the RAG context was built from
+    #[serde(default)]
+    pub sources: Vec<ContextSource>,
+}
+
+fn var(name: &str, kind: VarKind, required: bool, description: &str) -> TemplateVar {
+    TemplateVar {
+        name: name.to_string(),
+        kind,
+        required,
+        default: None,
+        description: description.to_string(),
+        max_chars: None,
+    }
+}
+
+/// Templates shipped with the app
+pub fn bundled_templates() -> Vec<PromptTemplate> {
+    let template = |id: &str, description: &str, system: &str, body: &str, variables: Vec<TemplateVar>| PromptTemplate {
+        id: id.to_string(),
+        version: 1,
+        description: description.to_string(),
+        system: Some(system.to_string()),
+        template: body.to_string(),
+        variables,
+        origin: TemplateOrigin::Bundled,
+    };
+    vec![
+        template(
+            "commit_message",
+            "Conventional Commits message for the staged diff",
+            "You are a senior developer who writes precise commit messages.",
+            "Write a concise commit message in Conventional Commits style for the staged changes below. \
+             Reply with the commit message only.\n\
+             {{#conventions}}\nProject conventions:\n{{conventions}}\n{{/conventions}}\n\
+             {{diff}}",
+            vec![
+                var("diff", VarKind::Diff, true, "Staged diff"),
+                var("conventions", VarKind::Text, false, "Extra commit rules for this repo"),
+            ],
+        ),
+        template(
+            "explain_selection",
+            "Explain the selected code",
+            "You are an expert programmer explaining code to a colleague.",
+            "Explain what the following {{#language}}{{language}} {{/language}}code does, step by step.\n\
+             {{#file_path}}It is taken from `{{file_path}}`.\n{{/file_path}}\n\
+             {{selection}}\n\
+             {{#rag}}\nRelated code from the project:\n{{rag}}\n{{/rag}}",
+            vec![
+                var("selection", VarKind::Selection, true, "Selected code"),
+                var("language", VarKind::Language, false, "Language of the selection"),
+                var("file_path", VarKind::Text, false, "File the selection comes from"),
+                var("rag", VarKind::Rag, false, "Related project context"),
+            ],
+        ),
+        template(
+            "refactor_selection",
+            "Refactor the selected code",
+            "You are an expert programmer. Preserve behaviour unless asked otherwise.",
+            "Refactor the following {{#language}}{{language}} {{/language}}code.\n\
+             {{#instructions}}Instructions: {{instructions}}\n{{/instructions}}\n\
+             {{selection}}\n\
+             {{#rag}}\nRelated code from the project:\n{{rag}}\n{{/rag}}\n\
+             Reply with the refactored code in a single code block, followed by a short summary of the changes.",
+            vec![
+                var("selection", VarKind::Selection, true, "Selected code"),
+                var("language", VarKind::Language, false, "Language of the selection"),
+                var("instructions", VarKind::Text, false, "What to change"),
+                var("rag", VarKind::Rag, false, "Related project context"),
+            ],
+        ),
+        template(
+            "review_diff",
+            "Review a diff for bugs and style issues",
+            "You are a careful code reviewer.",
+            "Review the following changes. List bugs, risky edge cases and style issues, \
+             most important first. Say so plainly if the change looks good.\n\n\
+             {{diff}}\n\
+             {{#rag}}\nRelated code from the project:\n{{rag}}\n{{/rag}}",
+            vec![
+                var("diff", VarKind::Diff, true, "Changes to review"),
+                var("rag", VarKind::Rag, false, "Related project context"),
+            ],
+        ),
+        template(
+            "generate_tests",
+            "Write unit tests for a file",
+            "You are an expert programmer who writes focused, idiomatic unit tests.",
+            "Write unit tests for the following {{#language}}{{language}} {{/language}}file using the project's existing test style.\n\n\
+             {{file}}\n\
+             {{#rag}}\nRelated code from the project:\n{{rag}}\n{{/rag}}",
+            vec![
+                var("file", VarKind::File, true, "File to test"),
+                var("language", VarKind::Language, false, "Language of the file"),
+                var("rag", VarKind::Rag, false, "Related project context"),
+            ],
+        ),
+        template(
+            "ask_codebase",
+            "Answer a question about the project",
+            "You are an assistant with deep knowledge of this codebase. Cite file paths when you use them.",
+            "{{#rag}}Project context:\n{{rag}}\n\n{{/rag}}Question: {{question}}",
+            vec![
+                var("question", VarKind::Text, true, "The question"),
+                var("rag", VarKind::Rag, false, "Related project context"),
+            ],
+        ),
+    ]
+}
+
+impl PromptTemplate {
+    pub fn var(&self, name: &str) -> Option<&TemplateVar> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+
+    /// First 16 hex chars of SHA-256 over the template's text and variables
+    pub fn fingerprint(&self) -> String {
+        let canonical = serde_json::json!({
+            "system": self.system,
+            "template": self.template,
+            "variables": self.variables,
+        });
+        Sha256::digest(canonical.to_string().as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Every placeholder must be declared and every section closed
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.trim().is_empty() {
+            return Err("Şablon id boş olamaz".to_string());
+        }
+        for text in std::iter::once(&self.template).chain(self.system.as_ref()) {
+            for caps in PLACEHOLDER.captures_iter(text).chain(SECTION_OPEN.captures_iter(text)) {
+                if self.var(&caps[1]).is_none() {
+                    return Err(format!("'{}' şablonunda tanımsız değişken: {}", self.id, &caps[1]));
+                }
+            }
+            for caps in SECTION_OPEN.captures_iter(text) {
+                if !text.contains(&format!("{{{{/{}}}}}", &caps[1])) {
+                    return Err(format!("'{}' şablonunda kapanmamış bölüm: {}", self.id, &caps[1]));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Render with `values`; `read_file` resolves `File` variables to their content
+    pub fn render(
+        &self,
+        values: &HashMap<String, String>,
+        read_file: impl Fn(&str) -> Result<String, String>,
+    ) -> Result<RenderedPrompt, String> {
+        self.validate()?;
+
+        // Resolve raw values: given, else default, else empty (required ones must be set)
+        let mut resolved: HashMap<&str, String> = HashMap::new();
+        for v in &self.variables {
+            let value = values.get(&v.name)
+                .filter(|s| !s.trim().is_empty())
+                .cloned()
+                .or_else(|| v.default.clone())
+                .unwrap_or_default();
+            if v.required && value.trim().is_empty() {
+                return Err(format!("'{}' şablonu için eksik değişken: {}", self.id, v.name));
+            }
+            resolved.insert(v.name.as_str(), value);
+        }
+
+        let language = self.variables.iter()
+            .find(|v| v.kind == VarKind::Language)
+            .and_then(|v| resolved.get(v.name.as_str()))
+            .cloned()
+            .unwrap_or_default();
+
+        let mut truncated = Vec::new();
+        let mut formatted: HashMap<&str, String> = HashMap::new();
+        for v in &self.variables {
+            let raw = &resolved[v.name.as_str()];
+            let value = if raw.is_empty() {
+                String::new()
+            } else {
+                let cap = v.max_chars.or(v.kind.max_chars());
+                let mut cut = |text: String| match cap {
+                    Some(max) if text.chars().count() > max => {
+                        truncated.push(v.name.clone());
+                        let mut short: String = text.chars().take(max).collect();
+                        short.push_str("\n[… truncated]");
+                        short
+                    }
+                    _ => text,
+                };
+                match v.kind {
+                    VarKind::Text | VarKind::Language => raw.trim().to_string(),
+                    VarKind::Rag => cut(raw.trim().to_string()),
+                    VarKind::Selection => format!("```{}\n{}\n```", language, cut(raw.trim_end().to_string())),
+                    VarKind::Diff => format!("```diff\n{}\n```", cut(raw.trim_end().to_string())),
+                    VarKind::File => {
+                        let content = read_file(raw.trim())?;
+                        let fence = Path::new(raw.trim()).extension().and_then(|e| e.to_str()).unwrap_or("");
+                        format!("`{}`:\n```{}\n{}\n```", raw.trim(), fence, cut(content.trim_end().to_string()))
+                    }
+                }
+            };
+            formatted.insert(v.name.as_str(), value);
+        }
+
+        let fill = |text: &str| -> String {
+            let text = expand_sections(text, &formatted);
+            PLACEHOLDER.replace_all(&text, |caps: &regex::Captures| formatted.get(&caps[1]).cloned().unwrap_or_default())
+                .trim()
+                .to_string()
+        };
+
+        Ok(RenderedPrompt {
+            template_id: self.id.clone(),
+            template_version: self.version,
+            origin: self.origin.clone(),
+            fingerprint: self.fingerprint(),
+            system: self.system.as_deref().map(fill).filter(|s| !s.is_empty()),
+            prompt: fill(&self.template),
+            truncated,
+            sources: Vec::new(),
+        })
+    }
+}
+
+/// Keep `{{#name}}...{{/name}}` bodies whose variable is set, drop the rest
+fn expand_sections(text: &str, values: &HashMap<&str, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(caps) = SECTION_OPEN.captures(rest) {
+        let open = caps.get(0).unwrap();
+        let name = &caps[1];
+        let close = format!("{{{{/{}}}}}", name);
+        let Some(end) = rest[open.end()..].find(&close) else {
+            break;
+        };
+        out.push_str(&rest[..open.start()]);
+        if values.get(name).map_or(false, |v| !v.is_empty()) {
+            out.push_str(&rest[open.end()..open.end() + end]);
+        }
+        rest = &rest[open.end() + end + close.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// --------------------
+// LIBRARY
+// --------------------
+
+pub struct PromptLibrary {
+    templates: BTreeMap<String, PromptTemplate>,
+}
+
+impl PromptLibrary {
+    /// Bundled templates, overridden by `{project}/.corex/prompts/*.json` when given
+    pub fn load(project: Option<&Path>) -> Self {
+        let mut templates: BTreeMap<String, PromptTemplate> = bundled_templates().into_iter().map(|t| (t.id.clone(), t)).collect();
+        let Some(dir) = project.map(|p| p.join(OVERRIDE_DIR)) else {
+            return Self { templates };
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Self { templates };
+        };
+        let mut paths: Vec<PathBuf> = entries.flatten()
+            .map(|e| e.path())
+            .filter(|p| p.extension().map_or(false, |e| e == "json"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            match Self::load_override(&path) {
+                Ok(template) => {
+                    info!("📝 Prompt şablonu yüklendi: {} (v{}) <- {}", template.id, template.version, path.display());
+                    templates.insert(template.id.clone(), template);
+                }
+                Err(e) => warn!("⚠️ Prompt şablonu atlandı ({}): {}", path.display(), e),
+            }
+        }
+        Self { templates }
+    }
+
+    fn load_override(path: &Path) -> Result<PromptTemplate, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut template: PromptTemplate = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        template.validate()?;
+        // A repo must not be able to point a prompt at files outside what the user picks
+        if let Some(v) = template.variables.iter().find(|v| v.kind == VarKind::File && v.default.is_some()) {
+            return Err(format!("Dosya değişkeni varsayılan değer alamaz: {}", v.name));
+        }
+        template.origin = TemplateOrigin::Project { path: path.to_string_lossy().into_owned() };
+        Ok(template)
+    }
+
+    pub fn get(&self, id: &str) -> Result<&PromptTemplate, String> {
+        self.templates.get(id).ok_or_else(|| format!("Prompt şablonu bulunamadı: {}", id))
+    }
+
+    pub fn list(&self) -> Vec<PromptTemplate> {
+        self.templates.values().cloned().collect()
+    }
+
+    /// Render `id`, reading `File` variables from inside `project` only
+    pub fn render(&self, id: &str, values: &HashMap<String, String>, project: Option<&Path>) -> Result<RenderedPrompt, String> {
+        self.get(id)?.render(values, |path| read_project_file(project, path))
+    }
+}
+
+/// Read `path` relative to `project`; symlinks, `..` and absolute paths must stay under it
+fn read_project_file(project: Option<&Path>, path: &str) -> Result<String, String> {
+    let root = project.ok_or("Dosya değişkenleri için proje klasörü gerekli")?;
+    let root = root.canonicalize().map_err(|e| format!("Proje klasörü bulunamadı ({}): {}", root.display(), e))?;
+    let full = root.join(path).canonicalize().map_err(|e| format!("Dosya okunamadı ({}): {}", path, e))?;
+    if !full.starts_with(&root) {
+        return Err(format!("Dosya proje klasörünün dışında: {}", path));
+    }
+    std::fs::read_to_string(&full).map_err(|e| format!("Dosya okunamadı ({}): {}", full.display(), e))
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub fn list_prompt_templates(project_path: Option<String>) -> Result<Vec<PromptTemplate>, String> {
+    Ok(PromptLibrary::load(project_path.as_deref().map(Path::new)).list())
+}
+
+/// Render a template. When `rag_query` is given and the template has an unset `Rag`
+/// variable, the context is built from the VectorDB and its sources are returned.
+#[tauri::command]
+pub async fn render_prompt(
+    app: AppHandle,
+    template_id: String,
+    variables: HashMap<String, String>,
+    project_path: Option<String>,
+    rag_query: Option<String>,
+) -> Result<RenderedPrompt, String> {
+    let project = project_path.as_deref().map(Path::new);
+    let library = PromptLibrary::load(project);
+    let template = library.get(&template_id)?;
+    let mut variables = variables;
+    let mut sources = Vec::new();
+
+    let rag_var = template.variables.iter()
+        .find(|v| v.kind == VarKind::Rag && variables.get(&v.name).map_or(true, |s| s.trim().is_empty()));
+    if let (Some(rag_var), Some(query)) = (rag_var, rag_query.filter(|q| !q.trim().is_empty())) {
+        match app.try_state::<crate::vector_db::VectorDB>() {
+            Some(vector_db) => {
+                let pipeline = RAGPipeline::new(170_000);
+                let intent = pipeline.analyze_intent(&query);
+                let (context, found) = pipeline.build_context(intent, &query, &vector_db).await.map_err(|e| e.to_string())?;
+                variables.insert(rag_var.name.clone(), context);
+                sources = found;
+            }
+            None => warn!("⚠️ VectorDB hazır değil, RAG bağlamı olmadan devam ediliyor"),
+        }
+    }
+
+    let mut rendered = library.render(&template_id, &variables, project)?;
+    rendered.sources = sources;
+    info!("📝 Prompt oluşturuldu: {} v{} ({})", rendered.template_id, rendered.template_version, rendered.fingerprint);
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    fn no_files(path: &str) -> Result<String, String> {
+        Err(format!("unexpected read: {}", path))
+    }
+
+    #[test]
+    fn test_bundled_templates_validate() {
+        for t in bundled_templates() {
+            t.validate().unwrap_or_else(|e| panic!("{}: {}", t.id, e));
+        }
+    }
+
+    #[test]
+    fn test_render_commit_message() {
+        let library = PromptLibrary::load(None);
+        let t = library.get("commit_message").unwrap();
+
+        let r = t.render(&values(&[("diff", "+fn main() {}\n")]), no_files).unwrap();
+        assert_eq!(r.template_id, "commit_message");
+        assert_eq!(r.template_version, 1);
+        assert_eq!(r.origin, TemplateOrigin::Bundled);
+        assert_eq!(r.fingerprint.len(), 16);
+        assert!(r.prompt.ends_with("```diff\n+fn main() {}\n```"));
+        assert!(!r.prompt.contains("Project conventions"));
+        assert!(!r.prompt.contains("{{"));
+
+        let r = t.render(&values(&[("diff", "+x"), ("conventions", "Use a scope.")]), no_files).unwrap();
+        assert!(r.prompt.contains("Project conventions:\nUse a scope."));
+
+        let err = t.render(&values(&[("diff", "  ")]), no_files).unwrap_err();
+        assert!(err.contains("diff"));
+    }
+
+    #[test]
+    fn test_selection_file_and_truncation() {
+        let library = PromptLibrary::load(None);
+        let r = library.get("explain_selection").unwrap()
+            .render(&values(&[("selection", "let x = 1;"), ("language", "rust")]), no_files)
+            .unwrap();
+        assert!(r.prompt.starts_with("Explain what the following rust code does"));
+        assert!(r.prompt.contains("```rust\nlet x = 1;\n```"));
+        assert!(!r.prompt.contains("taken from"));
+
+        let mut t = library.get("generate_tests").unwrap().clone();
+        t.variables[0].max_chars = Some(5);
+        let r = t.render(&values(&[("file", "src/lib.rs")]), |path| {
+            assert_eq!(path, "src/lib.rs");
+            Ok("çğüşöı and more".to_string())
+        }).unwrap();
+        assert!(r.prompt.contains("`src/lib.rs`:\n```rs\nçğüşö\n[… truncated]\n```"));
+        assert_eq!(r.truncated, vec!["file".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_rejects_undeclared_and_unclosed() {
+        let mut t = bundled_templates().remove(0);
+        t.template = "{{diff}} {{unknown}}".into();
+        assert!(t.validate().unwrap_err().contains("unknown"));
+        t.template = "{{#conventions}} {{diff}}".into();
+        assert!(t.validate().unwrap_err().contains("conventions"));
+    }
+
+    #[test]
+    fn test_project_overrides() {
+        let root = std::env::temp_dir().join(format!("corex_prompts_{}", uuid::Uuid::new_v4().simple()));
+        let dir = root.join(OVERRIDE_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("commit.json"), serde_json::json!({
+            "id": "commit_message",
+            "version": 3,
+            "template": "Commit for ticket {{ticket}}:\n{{diff}}",
+            "variables": [
+                { "name": "diff", "kind": "diff", "required": true },
+                { "name": "ticket", "kind": "text", "default": "NONE" }
+            ]
+        }).to_string()).unwrap();
+        std::fs::write(dir.join("broken.json"), r#"{ "id": "review_diff", "version": 2, "template": "{{nope}}" }"#).unwrap();
+
+        let bundled = PromptLibrary::load(None).get("commit_message").unwrap().fingerprint();
+        let library = PromptLibrary::load(Some(&root));
+        let t = library.get("commit_message").unwrap();
+        assert_eq!(t.version, 3);
+        assert!(matches!(&t.origin, TemplateOrigin::Project { path } if path.ends_with("commit.json")));
+        assert_ne!(t.fingerprint(), bundled);
+
+        let r = library.render("commit_message", &values(&[("diff", "-a")]), Some(&root)).unwrap();
+        assert_eq!(r.template_version, 3);
+        assert!(r.prompt.starts_with("Commit for ticket NONE:"));
+        assert!(r.system.is_none());
+
+        // The invalid override is skipped and the bundled template kept
+        assert_eq!(library.get("review_diff").unwrap().origin, TemplateOrigin::Bundled);
+        std::fs::remove_dir_all(root).ok();
+    }
+
+    #[test]
+    fn test_file_variables_stay_in_project() {
+        let base = std::env::temp_dir().join(format!("corex_prompts_{}", uuid::Uuid::new_v4().simple()));
+        let root = base.join("repo");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "pub fn f() {}").unwrap();
+        std::fs::write(base.join("secret.txt"), "top secret").unwrap();
+        let outside = base.join("secret.txt").to_string_lossy().into_owned();
+
+        let library = PromptLibrary::load(Some(&root));
+        let r = library.render("generate_tests", &values(&[("file", "src/lib.rs")]), Some(&root)).unwrap();
+        assert!(r.prompt.contains("pub fn f() {}"));
+        for path in ["../secret.txt", outside.as_str()] {
+            let err = library.render("generate_tests", &values(&[("file", path)]), Some(&root)).unwrap_err();
+            assert!(err.contains("dışında"), "{}: {}", path, err);
+        }
+        assert!(library.render("generate_tests", &values(&[("file", "src/lib.rs")]), None).is_err());
+
+        // Overrides can't give a file variable a default
+        let dir = root.join(OVERRIDE_DIR);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("tests.json"), serde_json::json!({
+            "id": "generate_tests",
+            "version": 9,
+            "template": "{{file}}",
+            "variables": [{ "name": "file", "kind": "file", "default": "~/.ssh/id_rsa" }]
+        }).to_string()).unwrap();
+        let library = PromptLibrary::load(Some(&root));
+        assert_eq!(library.get("generate_tests").unwrap().origin, TemplateOrigin::Bundled);
+        std::fs::remove_dir_all(base).ok();
+    }
+}
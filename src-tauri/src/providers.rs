@@ -0,0 +1,1667 @@
+// src-tauri/src/providers.rs
+// Remote chat providers behind one trait. Each adapter only knows how to turn a
+// normalised ChatRequest into its native HTTP request and how to read the native
+// response back; `send` does the HTTP part for all of them.
+//
+// Streaming works the same way: the adapter builds the streaming request and
+// turns each decoded event into StreamChunks; `stream` owns the wire format.
+//
+// Adapters: openai (and any OpenAI-compatible server), anthropic, gemini, ollama
+
+use futures_util::StreamExt;
+use log::{error, info};
+use once_cell::sync::Lazy;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::credentials;
+use crate::http_client::{self, HttpError, RetryPolicy};
+use crate::response_cache::{self, CacheInfo, CacheOptions};
+use crate::sse::{NdjsonParser, SseParser};
+use crate::usage::{self, UsageEvent};
+
+// --------------------
+// NORMALISED TYPES
+// --------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// Result of a tool call, answering `tool_call_id`
+    Tool,
+}
+
+impl Role {
+    /// Lenient parse for frontend role strings; unknown roles count as user
+    pub fn parse(role: &str) -> Role {
+        match role.to_ascii_lowercase().as_str() {
+            "system" => Role::System,
+            "assistant" | "model" | "ai" => Role::Assistant,
+            "tool" | "function" => Role::Tool,
+            _ => Role::User,
+        }
+    }
+}
+
+/// A function the model may call; `parameters` is a JSON Schema object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "empty_schema")]
+    pub parameters: Value,
+}
+
+fn empty_schema() -> Value {
+    json!({ "type": "object", "properties": {} })
+}
+
+/// A call requested by the model. Several calls in one response may run in parallel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// Parsed JSON arguments (a string if the model produced invalid JSON)
+    pub arguments: Value,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum ToolChoice {
+    #[default]
+    Auto,
+    None,
+    /// At least one tool must be called
+    Required,
+    Tool { name: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+    /// Assistant turns that requested tools
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Tool turns: the call this result answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+impl Message {
+    pub fn new(role: Role, content: impl Into<String>) -> Self {
+        Self { role, content: content.into(), tool_calls: Vec::new(), tool_call_id: None }
+    }
+
+    pub fn assistant_tool_calls(content: impl Into<String>, tool_calls: Vec<ToolCall>) -> Self {
+        Self { tool_calls, ..Self::new(Role::Assistant, content) }
+    }
+
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        Self { tool_call_id: Some(tool_call_id.into()), ..Self::new(Role::Tool, content) }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChatRequest {
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub stop: Vec<String>,
+    #[serde(default)]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(default)]
+    pub tool_choice: ToolChoice,
+    /// Sampling seed, for providers that support one (OpenAI-compatible, Gemini, Ollama)
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+impl ChatRequest {
+    /// Tools are only sent when there are some and the choice allows them
+    pub fn uses_tools(&self) -> bool {
+        !self.tools.is_empty() && self.tool_choice != ToolChoice::None
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
+impl Usage {
+    pub fn new(prompt_tokens: u64, completion_tokens: u64) -> Self {
+        Self { prompt_tokens, completion_tokens, total_tokens: prompt_tokens + completion_tokens }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinishReason {
+    Stop,
+    Length,
+    ContentFilter,
+    /// The model wants tool results before continuing
+    ToolCalls,
+    /// Stream stopped by the caller
+    Cancelled,
+    Other(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatResponse {
+    pub text: String,
+    pub model: Option<String>,
+    pub finish_reason: Option<FinishReason>,
+    pub usage: Option<Usage>,
+    /// Provider id that produced the response
+    pub provider: String,
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Set when the caller asked for response caching
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheInfo>,
+}
+
+impl ChatResponse {
+    pub fn empty(provider: &str) -> Self {
+        Self { text: String::new(), model: None, finish_reason: None, usage: None, provider: provider.to_string(), tool_calls: Vec::new(), cache: None }
+    }
+}
+
+/// Where and how to reach one provider account
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProviderSettings {
+    /// Empty means the adapter's default endpoint
+    #[serde(default)]
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    pub model: String,
+    /// Vault credential used instead of `api_key` (see credentials.rs)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_id: Option<String>,
+}
+
+/// A fully built native request
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Value,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProviderInfo {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub default_base_url: &'static str,
+    pub requires_api_key: bool,
+}
+
+// --------------------
+// TRAIT + REGISTRY
+// --------------------
+
+/// Wire format of a streamed response body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamFraming {
+    Sse,
+    Ndjson,
+}
+
+/// One normalised piece of a streamed response
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamChunk {
+    Text(String),
+    Model(String),
+    /// Partial usage; providers report prompt and completion counts at different times
+    Usage { prompt_tokens: Option<u64>, completion_tokens: Option<u64> },
+    Finish(FinishReason),
+    /// Piece of a streamed tool call; pieces with the same index are concatenated
+    ToolCallDelta { index: usize, id: Option<String>, name: Option<String>, arguments: String },
+    /// Tool call delivered whole
+    ToolCall(ToolCall),
+    /// Provider signalled the end of the stream
+    Done,
+}
+
+pub trait ChatProvider: Send + Sync {
+    fn info(&self) -> ProviderInfo;
+    fn build_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String>;
+    fn parse_response(&self, body: &Value) -> Result<ChatResponse, String>;
+
+    /// `event` is the SSE event name, if the provider sends one
+    fn parse_stream_event(&self, event: Option<&str>, data: &Value) -> Result<Vec<StreamChunk>, String>;
+
+    fn build_stream_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let mut http = self.build_request(settings, request)?;
+        http.body["stream"] = json!(true);
+        Ok(http)
+    }
+
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::Sse
+    }
+
+    fn id(&self) -> &'static str {
+        self.info().id
+    }
+
+    /// The adapter sends `seed` and the API makes sampled replies repeatable with it,
+    /// so seeded requests at temperature > 0 may be cached
+    fn honours_seed(&self) -> bool {
+        false
+    }
+}
+
+static REGISTRY: Lazy<BTreeMap<&'static str, Arc<dyn ChatProvider>>> = Lazy::new(|| {
+    let providers: Vec<Arc<dyn ChatProvider>> = vec![
+        Arc::new(OpenAiProvider),
+        Arc::new(AnthropicProvider),
+        Arc::new(GeminiProvider),
+        Arc::new(OllamaProvider),
+    ];
+    providers.into_iter().map(|p| (p.id(), p)).collect()
+});
+
+pub fn provider(id: &str) -> Result<Arc<dyn ChatProvider>, String> {
+    REGISTRY.get(id).cloned().ok_or_else(|| format!("Bilinmeyen provider: {}", id))
+}
+
+pub fn providers() -> Vec<ProviderInfo> {
+    REGISTRY.values().map(|p| p.info()).collect()
+}
+
+/// Best guess for configs that don't name their provider
+pub fn detect_provider(base_url: &str) -> &'static str {
+    let url = base_url.to_ascii_lowercase();
+    if url.contains("anthropic.com") {
+        "anthropic"
+    } else if url.contains("generativelanguage.googleapis.com") {
+        "gemini"
+    } else if url.contains(":11434") && !url.trim_end_matches('/').ends_with("/v1") {
+        "ollama"
+    } else {
+        "openai"
+    }
+}
+
+fn base_url<'a>(settings: &'a ProviderSettings, info: &ProviderInfo) -> &'a str {
+    let url = settings.base_url.trim().trim_end_matches('/');
+    if url.is_empty() { info.default_base_url } else { url }
+}
+
+fn api_key(settings: &ProviderSettings) -> Option<&str> {
+    settings.api_key.as_deref().map(str::trim).filter(|k| !k.is_empty())
+}
+
+/// System messages joined, and the rest in order
+fn split_system(messages: &[Message]) -> (Option<String>, Vec<&Message>) {
+    let system: Vec<&str> = messages.iter().filter(|m| m.role == Role::System).map(|m| m.content.as_str()).collect();
+    let rest = messages.iter().filter(|m| m.role != Role::System).collect();
+    ((!system.is_empty()).then(|| system.join("\n\n")), rest)
+}
+
+/// Anthropic and Gemini want alternating turns: consecutive turns with the same
+/// role are merged by concatenating their content blocks / parts
+fn merge_blocks(turns: impl IntoIterator<Item = (&'static str, Vec<Value>)>) -> Vec<(&'static str, Vec<Value>)> {
+    let mut merged: Vec<(&'static str, Vec<Value>)> = Vec::new();
+    for (role, blocks) in turns {
+        match merged.last_mut() {
+            Some((last, existing)) if *last == role => existing.extend(blocks),
+            _ => merged.push((role, blocks)),
+        }
+    }
+    merged
+}
+
+/// Model output for tool arguments; invalid JSON is kept as a string so nothing is lost
+pub fn parse_arguments(raw: &str) -> Value {
+    if raw.trim().is_empty() {
+        return json!({});
+    }
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// Give id-less calls stable ids and report ToolCalls as the finish reason
+pub fn finalize_tool_calls(response: &mut ChatResponse) {
+    for (i, call) in response.tool_calls.iter_mut().enumerate() {
+        if call.id.is_empty() {
+            call.id = format!("call_{}", i);
+        }
+    }
+    if !response.tool_calls.is_empty() && matches!(response.finish_reason, None | Some(FinishReason::Stop)) {
+        response.finish_reason = Some(FinishReason::ToolCalls);
+    }
+}
+
+/// OpenAI-style `tools` array (also used by Ollama)
+fn openai_tools(tools: &[ToolDefinition]) -> Value {
+    json!(tools.iter().map(|t| json!({
+        "type": "function",
+        "function": { "name": t.name, "description": t.description, "parameters": t.parameters },
+    })).collect::<Vec<_>>())
+}
+
+/// OpenAI message list; `arguments_as_string` is false for Ollama, which wants objects
+fn openai_messages(messages: &[Message], arguments_as_string: bool) -> Vec<Value> {
+    messages.iter().map(|m| {
+        let mut msg = json!({ "role": m.role, "content": m.content });
+        if !m.tool_calls.is_empty() {
+            msg["tool_calls"] = json!(m.tool_calls.iter().map(|c| {
+                let arguments = if arguments_as_string { json!(c.arguments.to_string()) } else { c.arguments.clone() };
+                json!({ "id": c.id, "type": "function", "function": { "name": c.name, "arguments": arguments } })
+            }).collect::<Vec<_>>());
+            if m.content.is_empty() && arguments_as_string {
+                msg["content"] = Value::Null;
+            }
+        }
+        if let Some(id) = &m.tool_call_id {
+            msg["tool_call_id"] = json!(id);
+        }
+        msg
+    }).collect()
+}
+
+/// Pull a readable message out of a provider error body
+pub fn error_message(body: &str) -> String {
+    serde_json::from_str::<Value>(body)
+        .ok()
+        .and_then(|v| {
+            v["error"]["message"].as_str()
+                .or_else(|| v["error"].as_str())
+                .or_else(|| v["message"].as_str())
+                .map(String::from)
+        })
+        .unwrap_or_else(|| body.to_string())
+}
+
+/// POST `http` through the shared retry/circuit-breaker path, turning non-2xx
+/// statuses into readable errors. `timeout` bounds the whole request.
+async fn post(
+    client: &Client,
+    provider: &dyn ChatProvider,
+    http: &HttpRequest,
+    timeout: Option<Duration>,
+) -> Result<reqwest::Response, String> {
+    let key = http_client::circuit_key(provider.id(), &http.url);
+    let build = || {
+        let mut builder = client.post(&http.url).json(&http.body);
+        for (name, value) in &http.headers {
+            builder = builder.header(name, value);
+        }
+        if let Some(timeout) = timeout {
+            builder = builder.timeout(timeout);
+        }
+        builder
+    };
+    http_client::send_with_retry(&key, &RetryPolicy::default(), build).await.map_err(|e| match e {
+        HttpError::CircuitOpen(message) | HttpError::Network(message) => {
+            error!("❌ {} istek hatası: {}", provider.id(), message);
+            format!("Bağlantı hatası: {}", message)
+        }
+        HttpError::Status { status, body } => {
+            error!("❌ {} API hatası ({}): {}", provider.id(), status, body);
+            format!("API hatası ({}): {}", status, error_message(&body))
+        }
+    })
+}
+
+/// Send one non-streaming chat request through `provider`
+pub async fn send(
+    client: &Client,
+    provider: &dyn ChatProvider,
+    settings: &ProviderSettings,
+    request: &ChatRequest,
+) -> Result<ChatResponse, String> {
+    let settings = &credentials::resolve_settings(settings).await?;
+    let http = provider.build_request(settings, request)?;
+    info!("📡 {} -> {} ({} mesaj)", provider.id(), http.url, request.messages.len());
+
+    let res = post(client, provider, &http, Some(http_client::REQUEST_TIMEOUT)).await?;
+    let text = res.text().await.map_err(|e| format!("Yanıt okunamadı: {}", e))?;
+    let body: Value = serde_json::from_str(&text).map_err(|e| format!("JSON parse hatası: {}", e))?;
+    let mut response = provider.parse_response(&body)?;
+    finalize_tool_calls(&mut response);
+    Ok(response)
+}
+
+/// Body decoder matching the provider's framing; yields (event name, JSON data)
+enum StreamDecoder {
+    Sse(SseParser),
+    Ndjson(NdjsonParser),
+}
+
+impl StreamDecoder {
+    fn new(framing: StreamFraming) -> Self {
+        match framing {
+            StreamFraming::Sse => StreamDecoder::Sse(SseParser::new()),
+            StreamFraming::Ndjson => StreamDecoder::Ndjson(NdjsonParser::new()),
+        }
+    }
+
+    /// `chunk` None means end of body. The OpenAI `[DONE]` sentinel becomes a None payload.
+    fn decode(&mut self, chunk: Option<&[u8]>) -> Result<Vec<(Option<String>, Option<Value>)>, String> {
+        match self {
+            StreamDecoder::Sse(parser) => {
+                let events = match chunk {
+                    Some(bytes) => parser.feed(bytes),
+                    None => parser.finish(),
+                };
+                events.into_iter()
+                    .filter(|e| !e.data.trim().is_empty())
+                    .map(|e| {
+                        if e.data.trim() == "[DONE]" {
+                            return Ok((e.event, None));
+                        }
+                        let data = serde_json::from_str(&e.data)
+                            .map_err(|err| format!("Stream verisi okunamadı: {} ({})", err, e.data))?;
+                        Ok((e.event, Some(data)))
+                    })
+                    .collect()
+            }
+            StreamDecoder::Ndjson(parser) => {
+                let values = match chunk {
+                    Some(bytes) => parser.feed(bytes)?,
+                    None => parser.finish()?,
+                };
+                Ok(values.into_iter().map(|v| (None, Some(v))).collect())
+            }
+        }
+    }
+}
+
+/// Resolves once `flag` is set
+async fn cancelled(flag: &AtomicBool) {
+    while !flag.load(Ordering::Relaxed) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Stream one chat request through `provider`. `on_text` gets every text delta and
+/// can return false to stop; setting `cancel` stops it from outside, even while
+/// waiting on the network. Both end with `FinishReason::Cancelled`.
+pub async fn stream<F>(
+    client: &Client,
+    provider: &dyn ChatProvider,
+    settings: &ProviderSettings,
+    request: &ChatRequest,
+    cancel: &AtomicBool,
+    mut on_text: F,
+) -> Result<ChatResponse, String>
+where
+    F: FnMut(&str) -> bool,
+{
+    let settings = &credentials::resolve_settings(settings).await?;
+    let http = provider.build_stream_request(settings, request)?;
+    info!("🌊 {} stream -> {} ({} mesaj)", provider.id(), http.url, request.messages.len());
+
+    let mut response = ChatResponse::empty(provider.id());
+    // Streamed tool calls by index: (id, name, argument text)
+    let mut partial_calls: BTreeMap<usize, (String, String, String)> = BTreeMap::new();
+    let res = tokio::select! {
+        res = post(client, provider, &http, None) => res?,
+        _ = cancelled(cancel) => {
+            response.finish_reason = Some(FinishReason::Cancelled);
+            return Ok(response);
+        }
+    };
+    let mut body = res.bytes_stream();
+    let mut decoder = StreamDecoder::new(provider.stream_framing());
+
+    'read: loop {
+        let next = tokio::select! {
+            next = tokio::time::timeout(http_client::READ_TIMEOUT, body.next()) => {
+                next.map_err(|_| format!("Stream zaman aşımı: {} sn yanıt gelmedi", http_client::READ_TIMEOUT.as_secs()))?
+            }
+            _ = cancelled(cancel) => {
+                response.finish_reason = Some(FinishReason::Cancelled);
+                break 'read;
+            }
+        };
+        let at_end = next.is_none();
+        let payloads = match next {
+            Some(chunk) => {
+                let bytes = chunk.map_err(|e| format!("Stream hatası: {}", e))?;
+                decoder.decode(Some(&bytes[..]))?
+            }
+            None => decoder.decode(None)?,
+        };
+
+        for (event, data) in payloads {
+            let Some(data) = data else { break 'read };
+            for chunk in provider.parse_stream_event(event.as_deref(), &data)? {
+                match chunk {
+                    StreamChunk::Text(text) if !text.is_empty() => {
+                        response.text.push_str(&text);
+                        if !on_text(&text) {
+                            response.finish_reason = Some(FinishReason::Cancelled);
+                            break 'read;
+                        }
+                    }
+                    StreamChunk::Text(_) => {}
+                    StreamChunk::Model(model) => {
+                        response.model.get_or_insert(model);
+                    }
+                    StreamChunk::Usage { prompt_tokens, completion_tokens } => {
+                        let usage = response.usage.get_or_insert_with(Usage::default);
+                        if let Some(p) = prompt_tokens {
+                            usage.prompt_tokens = p;
+                        }
+                        if let Some(c) = completion_tokens {
+                            usage.completion_tokens = c;
+                        }
+                        usage.total_tokens = usage.prompt_tokens + usage.completion_tokens;
+                    }
+                    StreamChunk::Finish(reason) => response.finish_reason = Some(reason),
+                    StreamChunk::ToolCallDelta { index, id, name, arguments } => {
+                        let entry = partial_calls.entry(index).or_default();
+                        if let Some(id) = id {
+                            entry.0 = id;
+                        }
+                        if let Some(name) = name {
+                            entry.1.push_str(&name);
+                        }
+                        entry.2.push_str(&arguments);
+                    }
+                    StreamChunk::ToolCall(call) => response.tool_calls.push(call),
+                    StreamChunk::Done => break 'read,
+                }
+            }
+        }
+        if at_end {
+            break;
+        }
+    }
+
+    response.tool_calls.extend(partial_calls.into_values().map(|(id, name, arguments)| ToolCall {
+        id,
+        name,
+        arguments: parse_arguments(&arguments),
+    }));
+    finalize_tool_calls(&mut response);
+    info!("✅ {} stream bitti: {} karakter, {} araç çağrısı, {:?}",
+        provider.id(), response.text.len(), response.tool_calls.len(), response.finish_reason);
+    Ok(response)
+}
+
+// --------------------
+// OPENAI (+ compatible: LM Studio, vLLM, llama.cpp server, OpenRouter...)
+// --------------------
+
+pub struct OpenAiProvider;
+
+fn openai_finish(reason: &str) -> FinishReason {
+    match reason {
+        "stop" => FinishReason::Stop,
+        "length" => FinishReason::Length,
+        "content_filter" => FinishReason::ContentFilter,
+        "tool_calls" | "function_call" => FinishReason::ToolCalls,
+        other => FinishReason::Other(other.to_string()),
+    }
+}
+
+fn openai_tool_choice(choice: &ToolChoice) -> Value {
+    match choice {
+        ToolChoice::Auto => json!("auto"),
+        ToolChoice::None => json!("none"),
+        ToolChoice::Required => json!("required"),
+        ToolChoice::Tool { name } => json!({ "type": "function", "function": { "name": name } }),
+    }
+}
+
+/// `tool_calls` of an OpenAI or Ollama message; arguments may be a JSON string or an object
+fn openai_tool_calls(message: &Value) -> Vec<ToolCall> {
+    message["tool_calls"].as_array().map(|calls| calls.iter().map(|c| ToolCall {
+        id: c["id"].as_str().unwrap_or("").to_string(),
+        name: c["function"]["name"].as_str().unwrap_or("").to_string(),
+        arguments: match &c["function"]["arguments"] {
+            Value::String(raw) => parse_arguments(raw),
+            Value::Null => json!({}),
+            other => other.clone(),
+        },
+    }).collect()).unwrap_or_default()
+}
+
+impl ChatProvider for OpenAiProvider {
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo { id: "openai", name: "OpenAI", default_base_url: "https://api.openai.com/v1", requires_api_key: false }
+    }
+
+    fn honours_seed(&self) -> bool {
+        true
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let mut body = json!({
+            "model": settings.model,
+            "messages": openai_messages(&request.messages, true),
+            "stream": false,
+        });
+        if let Some(t) = request.temperature {
+            body["temperature"] = json!(t);
+        }
+        if let Some(seed) = request.seed {
+            body["seed"] = json!(seed);
+        }
+        if let Some(n) = request.max_tokens {
+            body["max_tokens"] = json!(n);
+        }
+        if !request.stop.is_empty() {
+            body["stop"] = json!(request.stop);
+        }
+        if request.uses_tools() {
+            body["tools"] = openai_tools(&request.tools);
+            body["tool_choice"] = openai_tool_choice(&request.tool_choice);
+        }
+        let mut headers = Vec::new();
+        if let Some(key) = api_key(settings) {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", key)));
+        }
+        Ok(HttpRequest { url: format!("{}/chat/completions", base_url(settings, &self.info())), headers, body })
+    }
+
+    /// Streams only carry a final `usage` chunk when asked for it
+    fn build_stream_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let mut http = self.build_request(settings, request)?;
+        http.body["stream"] = json!(true);
+        http.body["stream_options"] = json!({ "include_usage": true });
+        Ok(http)
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<ChatResponse, String> {
+        let choice = body["choices"].get(0).ok_or("OpenAI yanıtında choices yok")?;
+        let usage = body["usage"].as_object().map(|u| {
+            Usage::new(
+                u.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+                u.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+            )
+        });
+        Ok(ChatResponse {
+            text: choice["message"]["content"].as_str().unwrap_or("").to_string(),
+            model: body["model"].as_str().map(String::from),
+            finish_reason: choice["finish_reason"].as_str().map(openai_finish),
+            usage,
+            provider: self.id().to_string(),
+            tool_calls: openai_tool_calls(&choice["message"]),
+            cache: None,
+        })
+    }
+
+    fn parse_stream_event(&self, _event: Option<&str>, data: &Value) -> Result<Vec<StreamChunk>, String> {
+        if let Some(err) = data.get("error") {
+            return Err(format!("Stream hatası: {}", error_message(&err.to_string())));
+        }
+        let mut chunks = Vec::new();
+        if let Some(model) = data["model"].as_str() {
+            chunks.push(StreamChunk::Model(model.to_string()));
+        }
+        if let Some(choice) = data["choices"].get(0) {
+            if let Some(text) = choice["delta"]["content"].as_str() {
+                chunks.push(StreamChunk::Text(text.to_string()));
+            }
+            for (i, call) in choice["delta"]["tool_calls"].as_array().into_iter().flatten().enumerate() {
+                chunks.push(StreamChunk::ToolCallDelta {
+                    index: call["index"].as_u64().map(|n| n as usize).unwrap_or(i),
+                    id: call["id"].as_str().map(String::from),
+                    name: call["function"]["name"].as_str().map(String::from),
+                    arguments: call["function"]["arguments"].as_str().unwrap_or("").to_string(),
+                });
+            }
+            if let Some(reason) = choice["finish_reason"].as_str() {
+                chunks.push(StreamChunk::Finish(openai_finish(reason)));
+            }
+        }
+        if let Some(u) = data["usage"].as_object() {
+            chunks.push(StreamChunk::Usage {
+                prompt_tokens: u.get("prompt_tokens").and_then(|v| v.as_u64()),
+                completion_tokens: u.get("completion_tokens").and_then(|v| v.as_u64()),
+            });
+        }
+        Ok(chunks)
+    }
+}
+
+// --------------------
+// ANTHROPIC (Messages API)
+// --------------------
+
+pub struct AnthropicProvider;
+
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Messages API requires max_tokens
+const ANTHROPIC_DEFAULT_MAX_TOKENS: u32 = 4096;
+
+fn anthropic_finish(reason: &str) -> FinishReason {
+    match reason {
+        "end_turn" | "stop_sequence" => FinishReason::Stop,
+        "max_tokens" => FinishReason::Length,
+        "refusal" => FinishReason::ContentFilter,
+        "tool_use" => FinishReason::ToolCalls,
+        other => FinishReason::Other(other.to_string()),
+    }
+}
+
+/// Content blocks per turn: tool calls become `tool_use`, tool results `tool_result` in a user turn
+fn anthropic_messages(messages: Vec<&Message>) -> Vec<(&'static str, Vec<Value>)> {
+    merge_blocks(messages.into_iter().map(|m| match m.role {
+        Role::Tool => ("user", vec![json!({
+            "type": "tool_result",
+            "tool_use_id": m.tool_call_id.clone().unwrap_or_default(),
+            "content": m.content,
+        })]),
+        Role::Assistant => {
+            let mut blocks = Vec::new();
+            if !m.content.is_empty() {
+                blocks.push(json!({ "type": "text", "text": m.content }));
+            }
+            blocks.extend(m.tool_calls.iter().map(|c| json!({
+                "type": "tool_use", "id": c.id, "name": c.name, "input": c.arguments,
+            })));
+            ("assistant", blocks)
+        }
+        _ => ("user", vec![json!({ "type": "text", "text": m.content })]),
+    }))
+}
+
+impl ChatProvider for AnthropicProvider {
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo { id: "anthropic", name: "Anthropic", default_base_url: "https://api.anthropic.com/v1", requires_api_key: true }
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let key = api_key(settings).ok_or("Anthropic için API anahtarı gerekli")?;
+        let (system, rest) = split_system(&request.messages);
+        let turns = anthropic_messages(rest);
+        if turns.first().map(|(role, _)| *role) != Some("user") {
+            return Err("Anthropic isteği bir kullanıcı mesajıyla başlamalı".to_string());
+        }
+        let messages: Vec<Value> = turns.into_iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect();
+
+        let mut body = json!({
+            "model": settings.model,
+            "messages": messages,
+            "max_tokens": request.max_tokens.unwrap_or(ANTHROPIC_DEFAULT_MAX_TOKENS),
+        });
+        if let Some(system) = system {
+            body["system"] = json!(system);
+        }
+        if let Some(t) = request.temperature {
+            body["temperature"] = json!(t);
+        }
+        if !request.stop.is_empty() {
+            body["stop_sequences"] = json!(request.stop);
+        }
+        if request.uses_tools() {
+            body["tools"] = json!(request.tools.iter().map(|t| json!({
+                "name": t.name, "description": t.description, "input_schema": t.parameters,
+            })).collect::<Vec<_>>());
+            body["tool_choice"] = match &request.tool_choice {
+                ToolChoice::Required => json!({ "type": "any" }),
+                ToolChoice::Tool { name } => json!({ "type": "tool", "name": name }),
+                _ => json!({ "type": "auto" }),
+            };
+        }
+        Ok(HttpRequest {
+            url: format!("{}/messages", base_url(settings, &self.info())),
+            headers: vec![
+                ("x-api-key".to_string(), key.to_string()),
+                ("anthropic-version".to_string(), ANTHROPIC_VERSION.to_string()),
+            ],
+            body,
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<ChatResponse, String> {
+        let blocks = body["content"].as_array().ok_or("Anthropic yanıtında content yok")?;
+        let text: String = blocks.iter()
+            .filter(|b| b["type"] == "text")
+            .filter_map(|b| b["text"].as_str())
+            .collect();
+        let tool_calls = blocks.iter()
+            .filter(|b| b["type"] == "tool_use")
+            .map(|b| ToolCall {
+                id: b["id"].as_str().unwrap_or("").to_string(),
+                name: b["name"].as_str().unwrap_or("").to_string(),
+                arguments: b["input"].clone(),
+            })
+            .collect();
+        let finish_reason = body["stop_reason"].as_str().map(anthropic_finish);
+        let usage = body["usage"].as_object().map(|u| {
+            Usage::new(
+                u.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+                u.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+            )
+        });
+        Ok(ChatResponse {
+            text,
+            model: body["model"].as_str().map(String::from),
+            finish_reason,
+            usage,
+            provider: self.id().to_string(),
+            tool_calls,
+            cache: None,
+        })
+    }
+
+    /// message_start, content_block_start/delta/stop, message_delta, message_stop, ping, error
+    fn parse_stream_event(&self, event: Option<&str>, data: &Value) -> Result<Vec<StreamChunk>, String> {
+        let kind = event.or_else(|| data["type"].as_str()).unwrap_or("");
+        let index = data["index"].as_u64().unwrap_or(0) as usize;
+        Ok(match kind {
+            "message_start" => {
+                let message = &data["message"];
+                let mut chunks = Vec::new();
+                if let Some(model) = message["model"].as_str() {
+                    chunks.push(StreamChunk::Model(model.to_string()));
+                }
+                chunks.push(StreamChunk::Usage {
+                    prompt_tokens: message["usage"]["input_tokens"].as_u64(),
+                    completion_tokens: message["usage"]["output_tokens"].as_u64(),
+                });
+                chunks
+            }
+            "content_block_start" if data["content_block"]["type"] == "tool_use" => {
+                vec![StreamChunk::ToolCallDelta {
+                    index,
+                    id: data["content_block"]["id"].as_str().map(String::from),
+                    name: data["content_block"]["name"].as_str().map(String::from),
+                    arguments: String::new(),
+                }]
+            }
+            "content_block_delta" if data["delta"]["type"] == "text_delta" => {
+                vec![StreamChunk::Text(data["delta"]["text"].as_str().unwrap_or("").to_string())]
+            }
+            "content_block_delta" if data["delta"]["type"] == "input_json_delta" => {
+                vec![StreamChunk::ToolCallDelta {
+                    index,
+                    id: None,
+                    name: None,
+                    arguments: data["delta"]["partial_json"].as_str().unwrap_or("").to_string(),
+                }]
+            }
+            "message_delta" => {
+                let mut chunks = Vec::new();
+                if let Some(reason) = data["delta"]["stop_reason"].as_str() {
+                    chunks.push(StreamChunk::Finish(anthropic_finish(reason)));
+                }
+                if let Some(output) = data["usage"]["output_tokens"].as_u64() {
+                    chunks.push(StreamChunk::Usage { prompt_tokens: None, completion_tokens: Some(output) });
+                }
+                chunks
+            }
+            "message_stop" => vec![StreamChunk::Done],
+            "error" => return Err(format!("Anthropic stream hatası: {}", data["error"]["message"].as_str().unwrap_or("bilinmeyen"))),
+            _ => Vec::new(), // ping, content_block_stop, other deltas
+        })
+    }
+}
+
+// --------------------
+// GEMINI (generateContent)
+// --------------------
+
+pub struct GeminiProvider;
+
+/// Gemini answers function calls by name, so tool results look the name up from the call id
+fn gemini_contents(messages: &[&Message]) -> Vec<Value> {
+    let call_names: BTreeMap<&str, &str> = messages.iter()
+        .flat_map(|m| m.tool_calls.iter())
+        .map(|c| (c.id.as_str(), c.name.as_str()))
+        .collect();
+
+    let turns = merge_blocks(messages.iter().map(|m| match m.role {
+        Role::Tool => {
+            let id = m.tool_call_id.as_deref().unwrap_or("");
+            let response = match serde_json::from_str::<Value>(&m.content) {
+                Ok(Value::Object(obj)) => Value::Object(obj),
+                _ => json!({ "content": m.content }),
+            };
+            ("user", vec![json!({ "functionResponse": {
+                "name": call_names.get(id).copied().unwrap_or(id),
+                "response": response,
+            } })])
+        }
+        Role::Assistant => {
+            let mut parts = Vec::new();
+            if !m.content.is_empty() {
+                parts.push(json!({ "text": m.content }));
+            }
+            parts.extend(m.tool_calls.iter().map(|c| json!({ "functionCall": { "name": c.name, "args": c.arguments } })));
+            ("model", parts)
+        }
+        _ => ("user", vec![json!({ "text": m.content })]),
+    }));
+    turns.into_iter().map(|(role, parts)| json!({ "role": role, "parts": parts })).collect()
+}
+
+impl ChatProvider for GeminiProvider {
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo {
+            id: "gemini",
+            name: "Google Gemini",
+            default_base_url: "https://generativelanguage.googleapis.com/v1beta",
+            requires_api_key: true,
+        }
+    }
+
+    fn honours_seed(&self) -> bool {
+        true
+    }
+
+    fn build_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let key = api_key(settings).ok_or("Gemini için API anahtarı gerekli")?;
+        let (system, rest) = split_system(&request.messages);
+
+        let mut generation = json!({});
+        if let Some(t) = request.temperature {
+            generation["temperature"] = json!(t);
+        }
+        if let Some(seed) = request.seed {
+            generation["seed"] = json!(seed);
+        }
+        if let Some(n) = request.max_tokens {
+            generation["maxOutputTokens"] = json!(n);
+        }
+        if !request.stop.is_empty() {
+            generation["stopSequences"] = json!(request.stop);
+        }
+        let mut body = json!({
+            "contents": gemini_contents(&rest),
+            "generationConfig": generation,
+        });
+        if let Some(system) = system {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        if request.uses_tools() {
+            body["tools"] = json!([{ "functionDeclarations": request.tools.iter().map(|t| json!({
+                "name": t.name, "description": t.description, "parameters": t.parameters,
+            })).collect::<Vec<_>>() }]);
+            body["toolConfig"] = json!({ "functionCallingConfig": match &request.tool_choice {
+                ToolChoice::Required => json!({ "mode": "ANY" }),
+                ToolChoice::Tool { name } => json!({ "mode": "ANY", "allowedFunctionNames": [name] }),
+                _ => json!({ "mode": "AUTO" }),
+            } });
+        }
+        let model = settings.model.trim_start_matches("models/");
+        Ok(HttpRequest {
+            url: format!("{}/models/{}:generateContent", base_url(settings, &self.info()), model),
+            headers: vec![("x-goog-api-key".to_string(), key.to_string())],
+            body,
+        })
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<ChatResponse, String> {
+        let candidate = match body["candidates"].get(0) {
+            Some(c) => c,
+            None => {
+                let reason = body["promptFeedback"]["blockReason"].as_str().unwrap_or("boş yanıt");
+                return Err(format!("Gemini yanıt üretmedi: {}", reason));
+            }
+        };
+        let usage = body["usageMetadata"].as_object().map(|u| {
+            Usage::new(
+                u.get("promptTokenCount").and_then(|v| v.as_u64()).unwrap_or(0),
+                u.get("candidatesTokenCount").and_then(|v| v.as_u64()).unwrap_or(0),
+            )
+        });
+        Ok(ChatResponse {
+            text: gemini_text(candidate),
+            model: body["modelVersion"].as_str().map(String::from),
+            finish_reason: candidate["finishReason"].as_str().map(gemini_finish),
+            usage,
+            provider: self.id().to_string(),
+            tool_calls: gemini_calls(candidate),
+            cache: None,
+        })
+    }
+
+    fn build_stream_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let mut http = self.build_request(settings, request)?;
+        http.url = http.url.replace(":generateContent", ":streamGenerateContent?alt=sse");
+        Ok(http)
+    }
+
+    /// Every event is a partial GenerateContentResponse; usageMetadata is cumulative
+    fn parse_stream_event(&self, _event: Option<&str>, data: &Value) -> Result<Vec<StreamChunk>, String> {
+        if let Some(err) = data.get("error") {
+            return Err(format!("Gemini stream hatası: {}", err["message"].as_str().unwrap_or("bilinmeyen")));
+        }
+        let mut chunks = Vec::new();
+        if let Some(model) = data["modelVersion"].as_str() {
+            chunks.push(StreamChunk::Model(model.to_string()));
+        }
+        if let Some(candidate) = data["candidates"].get(0) {
+            chunks.push(StreamChunk::Text(gemini_text(candidate)));
+            chunks.extend(gemini_calls(candidate).into_iter().map(StreamChunk::ToolCall));
+            if let Some(reason) = candidate["finishReason"].as_str() {
+                chunks.push(StreamChunk::Finish(gemini_finish(reason)));
+            }
+        }
+        if let Some(u) = data["usageMetadata"].as_object() {
+            chunks.push(StreamChunk::Usage {
+                prompt_tokens: u.get("promptTokenCount").and_then(|v| v.as_u64()),
+                completion_tokens: u.get("candidatesTokenCount").and_then(|v| v.as_u64()),
+            });
+        }
+        Ok(chunks)
+    }
+}
+
+fn gemini_text(candidate: &Value) -> String {
+    candidate["content"]["parts"].as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["text"].as_str()).collect())
+        .unwrap_or_default()
+}
+
+/// functionCall parts; Gemini only sometimes sends ids, missing ones are filled in later
+fn gemini_calls(candidate: &Value) -> Vec<ToolCall> {
+    candidate["content"]["parts"].as_array().into_iter().flatten()
+        .filter(|p| p.get("functionCall").is_some())
+        .map(|p| ToolCall {
+            id: p["functionCall"]["id"].as_str().unwrap_or("").to_string(),
+            name: p["functionCall"]["name"].as_str().unwrap_or("").to_string(),
+            arguments: p["functionCall"]["args"].clone(),
+        })
+        .collect()
+}
+
+fn gemini_finish(reason: &str) -> FinishReason {
+    match reason {
+        "STOP" => FinishReason::Stop,
+        "MAX_TOKENS" => FinishReason::Length,
+        "SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" => FinishReason::ContentFilter,
+        other => FinishReason::Other(other.to_lowercase()),
+    }
+}
+
+// --------------------
+// OLLAMA (native /api/chat)
+// --------------------
+
+pub struct OllamaProvider;
+
+impl ChatProvider for OllamaProvider {
+    fn info(&self) -> ProviderInfo {
+        ProviderInfo { id: "ollama", name: "Ollama", default_base_url: "http://localhost:11434", requires_api_key: false }
+    }
+
+    // The seed is sent, but replies still vary with the server's batching and parallel slots
+
+    fn build_request(&self, settings: &ProviderSettings, request: &ChatRequest) -> Result<HttpRequest, String> {
+        let mut options = json!({});
+        if let Some(t) = request.temperature {
+            options["temperature"] = json!(t);
+        }
+        if let Some(seed) = request.seed {
+            options["seed"] = json!(seed);
+        }
+        if let Some(n) = request.max_tokens {
+            options["num_predict"] = json!(n);
+        }
+        if !request.stop.is_empty() {
+            options["stop"] = json!(request.stop);
+        }
+        // Configs written for Ollama's OpenAI endpoint end in /v1
+        let base = base_url(settings, &self.info()).trim_end_matches("/v1");
+        let mut headers = Vec::new();
+        if let Some(key) = api_key(settings) {
+            headers.push(("Authorization".to_string(), format!("Bearer {}", key)));
+        }
+        let mut body = json!({
+            "model": settings.model,
+            "messages": openai_messages(&request.messages, false),
+            "stream": false,
+            "options": options,
+        });
+        // Ollama has no tool_choice; the model decides
+        if request.uses_tools() {
+            body["tools"] = openai_tools(&request.tools);
+        }
+        Ok(HttpRequest { url: format!("{}/api/chat", base), headers, body })
+    }
+
+    fn parse_response(&self, body: &Value) -> Result<ChatResponse, String> {
+        let content = body["message"]["content"].as_str().ok_or("Ollama yanıtında message yok")?;
+        let usage = match (body["prompt_eval_count"].as_u64(), body["eval_count"].as_u64()) {
+            (None, None) => None,
+            (p, c) => Some(Usage::new(p.unwrap_or(0), c.unwrap_or(0))),
+        };
+        Ok(ChatResponse {
+            text: content.to_string(),
+            model: body["model"].as_str().map(String::from),
+            finish_reason: body["done_reason"].as_str().map(openai_finish),
+            usage,
+            provider: self.id().to_string(),
+            tool_calls: openai_tool_calls(&body["message"]),
+            cache: None,
+        })
+    }
+
+    fn stream_framing(&self) -> StreamFraming {
+        StreamFraming::Ndjson
+    }
+
+    /// One JSON object per line; the last one has `done: true` plus counts.
+    /// Tool calls arrive whole inside a message.
+    fn parse_stream_event(&self, _event: Option<&str>, data: &Value) -> Result<Vec<StreamChunk>, String> {
+        if let Some(err) = data["error"].as_str() {
+            return Err(format!("Ollama stream hatası: {}", err));
+        }
+        let mut chunks = Vec::new();
+        if let Some(model) = data["model"].as_str() {
+            chunks.push(StreamChunk::Model(model.to_string()));
+        }
+        if let Some(text) = data["message"]["content"].as_str() {
+            chunks.push(StreamChunk::Text(text.to_string()));
+        }
+        chunks.extend(openai_tool_calls(&data["message"]).into_iter().map(StreamChunk::ToolCall));
+        if data["done"].as_bool() == Some(true) {
+            if let Some(reason) = data["done_reason"].as_str() {
+                chunks.push(StreamChunk::Finish(openai_finish(reason)));
+            }
+            chunks.push(StreamChunk::Usage {
+                prompt_tokens: data["prompt_eval_count"].as_u64(),
+                completion_tokens: data["eval_count"].as_u64(),
+            });
+            chunks.push(StreamChunk::Done);
+        }
+        Ok(chunks)
+    }
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub fn list_chat_providers() -> Vec<ProviderInfo> {
+    providers()
+}
+
+/// Provider-native chat with normalised usage and finish reason.
+/// With `cache` enabled, deterministic requests are answered from the response cache.
+#[tauri::command]
+pub async fn chat_with_provider(
+    provider_id: String,
+    settings: ProviderSettings,
+    request: ChatRequest,
+    cache: Option<CacheOptions>,
+) -> Result<ChatResponse, String> {
+    let key = response_cache::chat_key(&provider_id, &settings.base_url, &settings.model, &request);
+    if let Some((mut response, info)) = response_cache::lookup::<ChatResponse>(&cache, key.as_ref()) {
+        response.cache = Some(info);
+        return Ok(response);
+    }
+    usage::check_budget(Some(&provider_id), "chat", None)?;
+    let provider = provider(&provider_id)?;
+    let mut response = send(&http_client::client(), provider.as_ref(), &settings, &request).await?;
+    usage::record(UsageEvent::from_response("chat", &settings.model, &request, &response));
+    response.cache = response_cache::store(&cache, key.as_ref(), &response);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::sync::mpsc;
+    use tiny_http::{Header, Response, Server};
+
+    /// What the mock server received
+    struct Captured {
+        url: String,
+        headers: Vec<(String, String)>,
+        body: Value,
+    }
+
+    /// One-shot local HTTP server answering with `status` and a JSON `body`
+    fn mock(status: u16, body: Value) -> (String, mpsc::Receiver<Captured>) {
+        mock_raw(status, "application/json", body.to_string())
+    }
+
+    fn mock_raw(status: u16, content_type: &'static str, body: String) -> (String, mpsc::Receiver<Captured>) {
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            if let Some(mut request) = server.incoming_requests().next() {
+                let mut raw = String::new();
+                request.as_reader().read_to_string(&mut raw).unwrap();
+                let _ = tx.send(Captured {
+                    url: request.url().to_string(),
+                    headers: request.headers().iter()
+                        .map(|h| (h.field.as_str().as_str().to_ascii_lowercase(), h.value.as_str().to_string()))
+                        .collect(),
+                    body: serde_json::from_str(&raw).unwrap_or(Value::Null),
+                });
+                let response = Response::from_string(body)
+                    .with_status_code(status)
+                    .with_header(Header::from_bytes("Content-Type", content_type).unwrap());
+                let _ = request.respond(response);
+            }
+        });
+        (addr, rx)
+    }
+
+    fn header<'a>(captured: &'a Captured, name: &str) -> Option<&'a str> {
+        captured.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn conversation() -> ChatRequest {
+        ChatRequest {
+            messages: vec![
+                Message::new(Role::System, "Be brief."),
+                Message::new(Role::User, "Hi"),
+                Message::new(Role::Assistant, "Hello!"),
+                Message::new(Role::User, "What is Rust?"),
+            ],
+            temperature: Some(0.2),
+            max_tokens: Some(64),
+            ..Default::default()
+        }
+    }
+
+    fn settings(base_url: &str) -> ProviderSettings {
+        ProviderSettings { base_url: base_url.to_string(), api_key: Some("test-key".into()), model: "test-model".into(), credential_id: None }
+    }
+
+    #[tokio::test]
+    async fn test_openai_roundtrip() {
+        let (addr, rx) = mock(200, json!({
+            "model": "gpt-test",
+            "choices": [{ "message": { "role": "assistant", "content": "A language." }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15 }
+        }));
+        let res = send(&Client::new(), &OpenAiProvider, &settings(&format!("{}/v1", addr)), &conversation()).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.url, "/v1/chat/completions");
+        assert_eq!(header(&captured, "authorization"), Some("Bearer test-key"));
+        assert_eq!(captured.body["messages"].as_array().unwrap().len(), 4);
+        assert_eq!(captured.body["max_tokens"], 64);
+        assert_eq!(res.text, "A language.");
+        assert_eq!(res.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(res.usage, Some(Usage::new(12, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_roundtrip() {
+        let (addr, rx) = mock(200, json!({
+            "model": "claude-test",
+            "content": [{ "type": "text", "text": "A systems " }, { "type": "text", "text": "language." }],
+            "stop_reason": "max_tokens",
+            "usage": { "input_tokens": 20, "output_tokens": 64 }
+        }));
+        let res = send(&Client::new(), &AnthropicProvider, &settings(&addr), &conversation()).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.url, "/messages");
+        assert_eq!(header(&captured, "x-api-key"), Some("test-key"));
+        assert_eq!(header(&captured, "anthropic-version"), Some(ANTHROPIC_VERSION));
+        assert_eq!(captured.body["system"], "Be brief.");
+        let messages = captured.body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0]["role"], "user");
+        assert_eq!(res.text, "A systems language.");
+        assert_eq!(res.finish_reason, Some(FinishReason::Length));
+        assert_eq!(res.usage, Some(Usage::new(20, 64)));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_roundtrip() {
+        let (addr, rx) = mock(200, json!({
+            "candidates": [{ "content": { "role": "model", "parts": [{ "text": "Fast and safe." }] }, "finishReason": "STOP" }],
+            "usageMetadata": { "promptTokenCount": 9, "candidatesTokenCount": 4, "totalTokenCount": 13 },
+            "modelVersion": "gemini-test"
+        }));
+        let res = send(&Client::new(), &GeminiProvider, &settings(&addr), &conversation()).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.url, "/models/test-model:generateContent");
+        assert_eq!(header(&captured, "x-goog-api-key"), Some("test-key"));
+        assert_eq!(captured.body["systemInstruction"]["parts"][0]["text"], "Be brief.");
+        assert_eq!(captured.body["contents"][1]["role"], "model");
+        assert_eq!(captured.body["generationConfig"]["maxOutputTokens"], 64);
+        assert_eq!(res.text, "Fast and safe.");
+        assert_eq!(res.usage, Some(Usage::new(9, 4)));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_roundtrip() {
+        let (addr, rx) = mock(200, json!({
+            "model": "llama3",
+            "message": { "role": "assistant", "content": "Memory safe." },
+            "done": true,
+            "done_reason": "stop",
+            "prompt_eval_count": 30,
+            "eval_count": 5
+        }));
+        let res = send(&Client::new(), &OllamaProvider, &settings(&format!("{}/v1", addr)), &conversation()).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.url, "/api/chat");
+        assert_eq!(captured.body["stream"], false);
+        assert_eq!(captured.body["options"]["num_predict"], 64);
+        assert_eq!(res.text, "Memory safe.");
+        assert_eq!(res.usage, Some(Usage::new(30, 5)));
+    }
+
+    #[tokio::test]
+    async fn test_api_error_message() {
+        let (addr, _rx) = mock(401, json!({ "error": { "type": "authentication_error", "message": "invalid x-api-key" } }));
+        let err = send(&Client::new(), &AnthropicProvider, &settings(&addr), &conversation()).await.unwrap_err();
+        assert!(err.contains("401"));
+        assert!(err.contains("invalid x-api-key"));
+    }
+
+    #[test]
+    fn test_registry_and_detection() {
+        let ids: Vec<&str> = providers().iter().map(|p| p.id).collect();
+        assert_eq!(ids, vec!["anthropic", "gemini", "ollama", "openai"]);
+        assert!(provider("nope").is_err());
+        assert_eq!(detect_provider("https://api.anthropic.com/v1"), "anthropic");
+        assert_eq!(detect_provider("https://generativelanguage.googleapis.com/v1beta"), "gemini");
+        assert_eq!(detect_provider("http://localhost:11434"), "ollama");
+        assert_eq!(detect_provider("http://localhost:11434/v1"), "openai");
+        assert_eq!(detect_provider("http://127.0.0.1:1234/v1"), "openai");
+    }
+
+    #[test]
+    fn test_anthropic_requires_key_and_user_first() {
+        let mut s = settings("");
+        s.api_key = None;
+        assert!(AnthropicProvider.build_request(&s, &conversation()).is_err());
+        let only_assistant = ChatRequest { messages: vec![Message::new(Role::Assistant, "hi")], ..Default::default() };
+        assert!(AnthropicProvider.build_request(&settings(""), &only_assistant).is_err());
+        let req = AnthropicProvider.build_request(&settings(""), &conversation()).unwrap();
+        assert_eq!(req.url, "https://api.anthropic.com/v1/messages");
+    }
+
+    #[test]
+    fn test_seed_forwarded_where_supported() {
+        let seeded = ChatRequest { seed: Some(42), ..conversation() };
+        assert_eq!(OpenAiProvider.build_request(&settings(""), &seeded).unwrap().body["seed"], 42);
+        assert_eq!(GeminiProvider.build_request(&settings(""), &seeded).unwrap().body["generationConfig"]["seed"], 42);
+        assert_eq!(OllamaProvider.build_request(&settings(""), &seeded).unwrap().body["options"]["seed"], 42);
+        assert!(OpenAiProvider.build_request(&settings(""), &conversation()).unwrap().body.get("seed").is_none());
+    }
+
+    /// SSE body from (event name, JSON data) pairs
+    fn sse(events: &[(Option<&str>, &str)]) -> String {
+        events.iter().map(|(event, data)| match event {
+            Some(name) => format!("event: {}\ndata: {}\n\n", name, data),
+            None => format!("data: {}\n\n", data),
+        }).collect()
+    }
+
+    async fn collect_stream(provider: &dyn ChatProvider, base_url: &str) -> (ChatResponse, Vec<String>) {
+        let mut pieces = Vec::new();
+        let res = stream(&Client::new(), provider, &settings(base_url), &conversation(), &AtomicBool::new(false), |t| {
+            pieces.push(t.to_string());
+            true
+        }).await.unwrap();
+        (res, pieces)
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream() {
+        let body = sse(&[
+            (None, r#"{"model":"gpt-test","choices":[{"delta":{"role":"assistant"}}]}"#),
+            (None, r#"{"choices":[{"delta":{"content":"Hel"}}]}"#),
+            (None, r#"{"choices":[{"delta":{"content":"lo"},"finish_reason":"stop"}]}"#),
+            (None, r#"{"choices":[],"usage":{"prompt_tokens":5,"completion_tokens":2}}"#),
+            (None, "[DONE]"),
+        ]);
+        let (addr, rx) = mock_raw(200, "text/event-stream", body);
+        let (res, pieces) = collect_stream(&OpenAiProvider, &addr).await;
+        let sent = rx.recv().unwrap().body;
+        assert_eq!(sent["stream"], true);
+        assert_eq!(sent["stream_options"]["include_usage"], true);
+        assert_eq!(pieces, vec!["Hel", "lo"]);
+        assert_eq!(res.text, "Hello");
+        assert_eq!(res.model.as_deref(), Some("gpt-test"));
+        assert_eq!(res.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(res.usage, Some(Usage::new(5, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_stream_events() {
+        let body = sse(&[
+            (Some("message_start"), r#"{"type":"message_start","message":{"model":"claude-test","usage":{"input_tokens":25,"output_tokens":1}}}"#),
+            (Some("content_block_start"), r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#),
+            (Some("ping"), r#"{"type":"ping"}"#),
+            (Some("content_block_delta"), r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi "}}"#),
+            (Some("content_block_delta"), r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"there"}}"#),
+            (Some("content_block_stop"), r#"{"type":"content_block_stop","index":0}"#),
+            (Some("message_delta"), r#"{"type":"message_delta","delta":{"stop_reason":"end_turn"},"usage":{"output_tokens":3}}"#),
+            (Some("message_stop"), r#"{"type":"message_stop"}"#),
+        ]);
+        let (addr, _rx) = mock_raw(200, "text/event-stream", body);
+        let (res, pieces) = collect_stream(&AnthropicProvider, &addr).await;
+        assert_eq!(pieces, vec!["Hi ", "there"]);
+        assert_eq!(res.model.as_deref(), Some("claude-test"));
+        assert_eq!(res.finish_reason, Some(FinishReason::Stop));
+        assert_eq!(res.usage, Some(Usage::new(25, 3)));
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_stream_error_event() {
+        let body = sse(&[(Some("error"), r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#)]);
+        let (addr, _rx) = mock_raw(200, "text/event-stream", body);
+        let err = stream(&Client::new(), &AnthropicProvider, &settings(&addr), &conversation(), &AtomicBool::new(false), |_| true)
+            .await.unwrap_err();
+        assert!(err.contains("Overloaded"));
+    }
+
+    #[tokio::test]
+    async fn test_gemini_stream() {
+        let body = sse(&[
+            (None, r#"{"candidates":[{"content":{"parts":[{"text":"Fast"}]}}],"usageMetadata":{"promptTokenCount":9}}"#),
+            (None, r#"{"candidates":[{"content":{"parts":[{"text":" and safe"}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":9,"candidatesTokenCount":4}}"#),
+        ]);
+        let (addr, rx) = mock_raw(200, "text/event-stream", body);
+        let (res, _) = collect_stream(&GeminiProvider, &addr).await;
+        assert_eq!(rx.recv().unwrap().url, "/models/test-model:streamGenerateContent?alt=sse");
+        assert_eq!(res.text, "Fast and safe");
+        assert_eq!(res.usage, Some(Usage::new(9, 4)));
+    }
+
+    #[tokio::test]
+    async fn test_ollama_ndjson_stream() {
+        let body = [
+            r#"{"model":"llama3","message":{"role":"assistant","content":"Mem"},"done":false}"#,
+            r#"{"model":"llama3","message":{"role":"assistant","content":"ory"},"done":false}"#,
+            r#"{"model":"llama3","message":{"role":"assistant","content":""},"done":true,"done_reason":"length","prompt_eval_count":30,"eval_count":2}"#,
+        ].join("\n");
+        let (addr, rx) = mock_raw(200, "application/x-ndjson", body);
+        let (res, pieces) = collect_stream(&OllamaProvider, &addr).await;
+        assert_eq!(rx.recv().unwrap().body["stream"], true);
+        assert_eq!(pieces, vec!["Mem", "ory"]);
+        assert_eq!(res.finish_reason, Some(FinishReason::Length));
+        assert_eq!(res.usage, Some(Usage::new(30, 2)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_stops_when_callback_refuses() {
+        let body = sse(&[
+            (None, r#"{"choices":[{"delta":{"content":"one"}}]}"#),
+            (None, r#"{"choices":[{"delta":{"content":"two"}}]}"#),
+            (None, "[DONE]"),
+        ]);
+        let (addr, _rx) = mock_raw(200, "text/event-stream", body);
+        let res = stream(&Client::new(), &OpenAiProvider, &settings(&addr), &conversation(), &AtomicBool::new(false), |_| false)
+            .await.unwrap();
+        assert_eq!(res.text, "one");
+        assert_eq!(res.finish_reason, Some(FinishReason::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_stream_cancel_flag_while_waiting() {
+        // Accepts the request and never answers
+        let server = Server::http("127.0.0.1:0").unwrap();
+        let addr = format!("http://{}", server.server_addr().to_ip().unwrap());
+        std::thread::spawn(move || {
+            let _held = server.incoming_requests().next();
+            std::thread::sleep(Duration::from_secs(10));
+        });
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            flag.store(true, Ordering::Relaxed);
+        });
+        let res = tokio::time::timeout(
+            Duration::from_secs(5),
+            stream(&Client::new(), &OpenAiProvider, &settings(&addr), &conversation(), &cancel, |_| true),
+        ).await.expect("cancel should end the stream").unwrap();
+        assert_eq!(res.finish_reason, Some(FinishReason::Cancelled));
+    }
+
+    fn weather_tools() -> ChatRequest {
+        ChatRequest {
+            messages: vec![Message::new(Role::User, "Weather in Paris and Rome?")],
+            tools: vec![ToolDefinition {
+                name: "get_weather".into(),
+                description: "Current weather for a city".into(),
+                parameters: json!({ "type": "object", "properties": { "city": { "type": "string" } }, "required": ["city"] }),
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// The follow-up turn after two parallel calls
+    fn with_tool_results(mut request: ChatRequest) -> ChatRequest {
+        request.messages.push(Message::assistant_tool_calls("", vec![
+            ToolCall { id: "call_a".into(), name: "get_weather".into(), arguments: json!({ "city": "Paris" }) },
+            ToolCall { id: "call_b".into(), name: "get_weather".into(), arguments: json!({ "city": "Rome" }) },
+        ]));
+        request.messages.push(Message::tool_result("call_a", "18C"));
+        request.messages.push(Message::tool_result("call_b", "24C"));
+        request
+    }
+
+    #[tokio::test]
+    async fn test_openai_parallel_tool_calls() {
+        let (addr, rx) = mock(200, json!({
+            "choices": [{ "message": { "role": "assistant", "content": null, "tool_calls": [
+                { "id": "call_a", "type": "function", "function": { "name": "get_weather", "arguments": "{\"city\":\"Paris\"}" } },
+                { "id": "call_b", "type": "function", "function": { "name": "get_weather", "arguments": "{\"city\":\"Rome\"}" } }
+            ] }, "finish_reason": "tool_calls" }]
+        }));
+        let res = send(&Client::new(), &OpenAiProvider, &settings(&addr), &weather_tools()).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.body["tools"][0]["function"]["name"], "get_weather");
+        assert_eq!(captured.body["tool_choice"], "auto");
+        assert_eq!(res.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(res.tool_calls.len(), 2);
+        assert_eq!(res.tool_calls[1].arguments, json!({ "city": "Rome" }));
+
+        let next = OpenAiProvider.build_request(&settings(""), &with_tool_results(weather_tools())).unwrap();
+        let messages = next.body["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["tool_calls"][0]["function"]["arguments"], "{\"city\":\"Paris\"}");
+        assert_eq!(messages[2]["role"], "tool");
+        assert_eq!(messages[3]["tool_call_id"], "call_b");
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_tool_use() {
+        let (addr, rx) = mock(200, json!({
+            "content": [
+                { "type": "text", "text": "Checking." },
+                { "type": "tool_use", "id": "toolu_1", "name": "get_weather", "input": { "city": "Paris" } }
+            ],
+            "stop_reason": "tool_use"
+        }));
+        let mut request = weather_tools();
+        request.tool_choice = ToolChoice::Required;
+        let res = send(&Client::new(), &AnthropicProvider, &settings(&addr), &request).await.unwrap();
+        let captured = rx.recv().unwrap();
+
+        assert_eq!(captured.body["tools"][0]["input_schema"]["required"][0], "city");
+        assert_eq!(captured.body["tool_choice"]["type"], "any");
+        assert_eq!(res.text, "Checking.");
+        assert_eq!(res.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(res.tool_calls[0].id, "toolu_1");
+
+        // Both results go back in one user turn
+        let next = AnthropicProvider.build_request(&settings(""), &with_tool_results(weather_tools())).unwrap();
+        let messages = next.body["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1]["content"][1]["type"], "tool_use");
+        assert_eq!(messages[2]["role"], "user");
+        assert_eq!(messages[2]["content"][0]["tool_use_id"], "call_a");
+        assert_eq!(messages[2]["content"][1]["content"], "24C");
+    }
+
+    #[tokio::test]
+    async fn test_gemini_function_calls() {
+        let (addr, _rx) = mock(200, json!({
+            "candidates": [{ "content": { "role": "model", "parts": [
+                { "functionCall": { "name": "get_weather", "args": { "city": "Paris" } } },
+                { "functionCall": { "name": "get_weather", "args": { "city": "Rome" } } }
+            ] }, "finishReason": "STOP" }]
+        }));
+        let res = send(&Client::new(), &GeminiProvider, &settings(&addr), &weather_tools()).await.unwrap();
+        assert_eq!(res.finish_reason, Some(FinishReason::ToolCalls));
+        let ids: Vec<&str> = res.tool_calls.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["call_0", "call_1"]);
+
+        let next = GeminiProvider.build_request(&settings(""), &with_tool_results(weather_tools())).unwrap();
+        let contents = next.body["contents"].as_array().unwrap();
+        assert_eq!(contents[1]["parts"][1]["functionCall"]["args"]["city"], "Rome");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "get_weather");
+        assert_eq!(contents[2]["parts"][1]["functionResponse"]["response"]["content"], "24C");
+    }
+
+    #[tokio::test]
+    async fn test_openai_streamed_tool_call_deltas() {
+        let body = sse(&[
+            (None, r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"id":"call_a","function":{"name":"get_weather","arguments":""}}]}}]}"#),
+            (None, r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"city\":"}}]}}]}"#),
+            (None, r#"{"choices":[{"delta":{"tool_calls":[{"index":1,"id":"call_b","function":{"name":"get_weather","arguments":"{}"}}]}}]}"#),
+            (None, r#"{"choices":[{"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"Paris\"}"}}]},"finish_reason":"tool_calls"}]}"#),
+            (None, "[DONE]"),
+        ]);
+        let (addr, _rx) = mock_raw(200, "text/event-stream", body);
+        let (res, _) = collect_stream(&OpenAiProvider, &addr).await;
+        assert_eq!(res.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(res.tool_calls.len(), 2);
+        assert_eq!(res.tool_calls[0].arguments, json!({ "city": "Paris" }));
+        assert_eq!(res.tool_calls[1].id, "call_b");
+    }
+
+    #[tokio::test]
+    async fn test_anthropic_streamed_tool_use() {
+        let body = sse(&[
+            (Some("content_block_start"), r#"{"type":"content_block_start","index":1,"content_block":{"type":"tool_use","id":"toolu_1","name":"get_weather","input":{}}}"#),
+            (Some("content_block_delta"), r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"{\"city\": \"Ro"}}"#),
+            (Some("content_block_delta"), r#"{"type":"content_block_delta","index":1,"delta":{"type":"input_json_delta","partial_json":"me\"}"}}"#),
+            (Some("message_delta"), r#"{"type":"message_delta","delta":{"stop_reason":"tool_use"},"usage":{"output_tokens":12}}"#),
+            (Some("message_stop"), r#"{"type":"message_stop"}"#),
+        ]);
+        let (addr, _rx) = mock_raw(200, "text/event-stream", body);
+        let (res, _) = collect_stream(&AnthropicProvider, &addr).await;
+        assert_eq!(res.tool_calls, vec![ToolCall { id: "toolu_1".into(), name: "get_weather".into(), arguments: json!({ "city": "Rome" }) }]);
+        assert_eq!(res.finish_reason, Some(FinishReason::ToolCalls));
+    }
+}
@@ -0,0 +1,575 @@
+// src-tauri/src/response_cache.rs
+// Opt-in on-disk cache for repeatable model answers.
+//
+// Only deterministic requests are cached: temperature 0, a seed (remote providers),
+// or JSON mode (GGUF, which is greedy). The key is a SHA-256 of the provider and
+// endpoint, the model, the normalised messages (trimmed, CRLF -> LF), the sampling
+// settings and the tools. Each entry is one JSON file in {app_data}/response_cache/;
+// an in-memory index, rebuilt from those files at startup, tracks sizes for the
+// LRU size cap. Callers opt in per request with `CacheOptions`.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+use crate::gguf::GgufState;
+use crate::gguf_completion::CompletionOptions;
+use crate::providers::{self, ChatRequest, Message};
+
+pub const DEFAULT_TTL_SECS: u64 = 7 * 24 * 3600;
+pub const DEFAULT_MAX_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Per-request opt-in
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheOptions {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Overrides the configured TTL for the stored answer
+    #[serde(default)]
+    pub ttl_secs: Option<u64>,
+    /// Skip the lookup but store the fresh answer
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+/// Reported in response metadata when caching was requested
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CacheInfo {
+    pub hit: bool,
+    pub key: String,
+    pub age_secs: u64,
+    pub expires_in_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheSettings {
+    #[serde(default = "default_ttl")]
+    pub ttl_secs: u64,
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+}
+
+fn default_ttl() -> u64 { DEFAULT_TTL_SECS }
+fn default_max_bytes() -> u64 { DEFAULT_MAX_BYTES }
+
+impl Default for CacheSettings {
+    fn default() -> Self {
+        Self { ttl_secs: DEFAULT_TTL_SECS, max_bytes: DEFAULT_MAX_BYTES }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub settings: CacheSettings,
+}
+
+// --------------------
+// KEYS
+// --------------------
+
+/// What a cached answer depends on, hashed into the entry key
+#[derive(Debug, Clone)]
+pub struct CacheKey {
+    pub provider: String,
+    pub model: String,
+    pub hash: String,
+}
+
+impl CacheKey {
+    fn new(provider: &str, model: &str, canonical: Value) -> Self {
+        // serde_json maps are sorted, so equal inputs always serialise the same way
+        let digest = Sha256::digest(canonical.to_string().as_bytes());
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            hash: digest.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+fn normalise_text(text: &str) -> String {
+    text.replace("\r\n", "\n").trim().to_string()
+}
+
+fn normalise_messages(messages: &[Message]) -> Value {
+    messages.iter()
+        .map(|m| json!({
+            "role": m.role,
+            "content": normalise_text(&m.content),
+            "tool_calls": m.tool_calls,
+            "tool_call_id": m.tool_call_id,
+        }))
+        .collect()
+}
+
+/// Key for a remote provider request; None unless temperature is 0, or a seed is set
+/// and the provider honours it (Anthropic has no seed, Ollama's isn't repeatable)
+pub fn chat_key(provider: &str, base_url: &str, model: &str, request: &ChatRequest) -> Option<CacheKey> {
+    let seeded = request.seed.is_some() && providers::provider(provider).map_or(false, |p| p.honours_seed());
+    if request.temperature != Some(0.0) && !seeded {
+        return None;
+    }
+    Some(CacheKey::new(provider, model, json!({
+        "provider": provider,
+        "endpoint": base_url.trim().trim_end_matches('/'),
+        "model": model,
+        "messages": normalise_messages(&request.messages),
+        "temperature": request.temperature,
+        "seed": request.seed,
+        "max_tokens": request.max_tokens,
+        "stop": request.stop,
+        "tools": if request.uses_tools() { json!(request.tools) } else { Value::Null },
+        "tool_choice": if request.uses_tools() { json!(request.tool_choice) } else { Value::Null },
+    })))
+}
+
+/// Key for a local GGUF call; None unless sampling is greedy (temperature 0 or JSON mode).
+/// `variant` covers model state that changes outputs, e.g. attached LoRA adapters;
+/// the file's size and mtime keep a model replaced under the same path from hitting old entries.
+pub fn gguf_key(
+    kind: &str,
+    model_path: &str,
+    variant: &str,
+    input: Value,
+    max_tokens: u32,
+    temperature: f32,
+    options: &CompletionOptions,
+) -> Option<CacheKey> {
+    if temperature > 0.0 && !options.json {
+        return None;
+    }
+    Some(CacheKey::new("gguf", model_path, json!({
+        "provider": "gguf",
+        "kind": kind,
+        "model": model_path,
+        "file": file_fingerprint(model_path),
+        "variant": variant,
+        "input": input,
+        "max_tokens": max_tokens,
+        "temperature": if options.json { 0.0 } else { temperature },
+        "stop": options.stop,
+        "top_logprobs": options.top_logprobs,
+        "n_keep": options.n_keep,
+        "json": options.json,
+    })))
+}
+
+fn file_fingerprint(path: &str) -> Value {
+    let Ok(meta) = std::fs::metadata(path) else {
+        return Value::Null;
+    };
+    let mtime = meta.modified().ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos() as u64);
+    json!({ "size": meta.len(), "mtime": mtime })
+}
+
+/// Normalised chat messages for `gguf_key`
+pub fn gguf_messages(messages: &[crate::commands::ChatMessage]) -> Value {
+    messages.iter().map(|m| json!({ "role": m.role, "content": normalise_text(&m.content) })).collect()
+}
+
+/// Attached LoRA adapters (path and scale) of a pooled model, part of GGUF keys
+pub fn gguf_variant(state: &Arc<Mutex<GgufState>>, model_path: &str) -> String {
+    let guard = state.lock().unwrap_or_else(|p| p.into_inner());
+    let Some(model) = guard.models.get(model_path) else {
+        return String::new();
+    };
+    let loras = model.loras.lock().unwrap_or_else(|p| p.into_inner());
+    loras.iter()
+        .filter(|l| l.info.scale != 0.0)
+        .map(|l| format!("{}@{}", l.info.path, l.info.scale))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// --------------------
+// STORE
+// --------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    provider: String,
+    model: String,
+    created_at: u64,
+    expires_at: u64,
+    value: Value,
+}
+
+#[derive(Debug, Clone)]
+struct IndexEntry {
+    provider: String,
+    model: String,
+    bytes: u64,
+    expires_at: u64,
+    last_used: u64,
+}
+
+pub struct ResponseCache {
+    dir: PathBuf,
+    pub settings: CacheSettings,
+    index: HashMap<String, IndexEntry>,
+    /// Bumped on every access; orders entries for eviction
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl ResponseCache {
+    /// Open (or create) the cache in `dir`; unreadable entries are removed
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Cache klasörü oluşturulamadı: {}", e))?;
+        let settings = std::fs::read_to_string(dir.join("settings.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        let mut cache = Self { dir: dir.to_path_buf(), settings, index: HashMap::new(), clock: 0, hits: 0, misses: 0 };
+
+        let mut found: Vec<(u64, String, IndexEntry)> = Vec::new();
+        for file in std::fs::read_dir(dir).map_err(|e| e.to_string())?.flatten() {
+            let path = file.path();
+            if path.file_name().is_some_and(|n| n == "settings.json") || path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let entry = std::fs::read_to_string(&path).ok().and_then(|s| serde_json::from_str::<CacheEntry>(&s).ok());
+            match entry {
+                Some(entry) => {
+                    let bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+                    let index = IndexEntry { provider: entry.provider, model: entry.model, bytes, expires_at: entry.expires_at, last_used: 0 };
+                    found.push((entry.created_at, entry.key, index));
+                }
+                None => {
+                    let _ = std::fs::remove_file(&path);
+                }
+            }
+        }
+        // Oldest first, so older entries are evicted first until they are used again
+        found.sort_by_key(|(created_at, _, _)| *created_at);
+        for (_, key, mut entry) in found {
+            cache.clock += 1;
+            entry.last_used = cache.clock;
+            cache.index.insert(key, entry);
+        }
+        Ok(cache)
+    }
+
+    pub fn save_settings(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.settings).map_err(|e| e.to_string())?;
+        std::fs::write(self.dir.join("settings.json"), json).map_err(|e| format!("Cache ayarları yazılamadı: {}", e))
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.index.remove(key);
+        let _ = std::fs::remove_file(self.entry_path(key));
+    }
+
+    /// A live entry for `key`, with its hit metadata
+    pub fn get<T: DeserializeOwned>(&mut self, key: &CacheKey, now: u64) -> Option<(T, CacheInfo)> {
+        let Some(indexed) = self.index.get(&key.hash) else {
+            self.misses += 1;
+            return None;
+        };
+        if indexed.expires_at <= now {
+            self.remove(&key.hash);
+            self.misses += 1;
+            return None;
+        }
+        let entry = std::fs::read_to_string(self.entry_path(&key.hash))
+            .ok()
+            .and_then(|s| serde_json::from_str::<CacheEntry>(&s).ok());
+        let Some((value, entry)) = entry.and_then(|e| Some((serde_json::from_value::<T>(e.value.clone()).ok()?, e))) else {
+            self.remove(&key.hash);
+            self.misses += 1;
+            return None;
+        };
+        self.clock += 1;
+        if let Some(indexed) = self.index.get_mut(&key.hash) {
+            indexed.last_used = self.clock;
+        }
+        self.hits += 1;
+        let info = CacheInfo {
+            hit: true,
+            key: key.hash.clone(),
+            age_secs: now.saturating_sub(entry.created_at),
+            expires_in_secs: entry.expires_at.saturating_sub(now),
+        };
+        Some((value, info))
+    }
+
+    /// Store `value`, evicting least recently used entries beyond the size cap
+    pub fn put<T: Serialize>(&mut self, key: &CacheKey, value: &T, ttl_secs: Option<u64>, now: u64) -> Result<CacheInfo, String> {
+        let ttl = ttl_secs.unwrap_or(self.settings.ttl_secs);
+        let entry = CacheEntry {
+            key: key.hash.clone(),
+            provider: key.provider.clone(),
+            model: key.model.clone(),
+            created_at: now,
+            expires_at: now + ttl,
+            value: serde_json::to_value(value).map_err(|e| e.to_string())?,
+        };
+        let json = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        if json.len() as u64 > self.settings.max_bytes {
+            return Err(format!("Yanıt önbellek sınırından büyük ({} bayt)", json.len()));
+        }
+        std::fs::write(self.entry_path(&key.hash), &json).map_err(|e| format!("Önbellek yazılamadı: {}", e))?;
+        self.clock += 1;
+        self.index.insert(key.hash.clone(), IndexEntry {
+            provider: entry.provider,
+            model: entry.model,
+            bytes: json.len() as u64,
+            expires_at: entry.expires_at,
+            last_used: self.clock,
+        });
+        self.evict(now);
+        Ok(CacheInfo { hit: false, key: key.hash.clone(), age_secs: 0, expires_in_secs: ttl })
+    }
+
+    /// Drop expired entries, then the least recently used ones until under the cap
+    fn evict(&mut self, now: u64) {
+        let expired: Vec<String> = self.index.iter().filter(|(_, e)| e.expires_at <= now).map(|(k, _)| k.clone()).collect();
+        for key in expired {
+            self.remove(&key);
+        }
+        while self.bytes() > self.settings.max_bytes {
+            let Some(oldest) = self.index.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone()) else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.index.values().map(|e| e.bytes).sum()
+    }
+
+    /// Remove entries matching the filters (all when both are None); returns how many
+    pub fn invalidate(&mut self, provider: Option<&str>, model: Option<&str>) -> usize {
+        let keys: Vec<String> = self.index.iter()
+            .filter(|(_, e)| provider.map_or(true, |p| e.provider == p) && model.map_or(true, |m| e.model == m))
+            .map(|(k, _)| k.clone())
+            .collect();
+        for key in &keys {
+            self.remove(key);
+        }
+        keys.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { entries: self.index.len(), bytes: self.bytes(), hits: self.hits, misses: self.misses, settings: self.settings.clone() }
+    }
+}
+
+// --------------------
+// GLOBAL CACHE
+// --------------------
+
+static CACHE: Lazy<Mutex<Option<ResponseCache>>> = Lazy::new(|| Mutex::new(None));
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+pub fn init(dir: &Path) {
+    match ResponseCache::open(dir) {
+        Ok(cache) => {
+            info!("🗄️ Yanıt önbelleği: {} kayıt ({})", cache.index.len(), dir.display());
+            *CACHE.lock().unwrap_or_else(|p| p.into_inner()) = Some(cache);
+        }
+        Err(e) => warn!("⚠️ Yanıt önbelleği açılamadı: {}", e),
+    }
+}
+
+pub fn init_for_app(app: &AppHandle) {
+    match app.path().app_data_dir() {
+        Ok(dir) => init(&dir.join("response_cache")),
+        Err(e) => warn!("⚠️ Yanıt önbelleği klasörü bulunamadı: {}", e),
+    }
+}
+
+fn with_cache<T>(f: impl FnOnce(&mut ResponseCache) -> Result<T, String>) -> Result<T, String> {
+    let mut guard = CACHE.lock().unwrap_or_else(|p| p.into_inner());
+    let cache = guard.as_mut().ok_or("Yanıt önbelleği başlatılmadı")?;
+    f(cache)
+}
+
+/// Cached answer for `key` unless the caller opted out or asked for a refresh
+pub fn lookup<T: DeserializeOwned>(options: &Option<CacheOptions>, key: Option<&CacheKey>) -> Option<(T, CacheInfo)> {
+    options.as_ref().filter(|o| o.enabled && !o.refresh)?;
+    let key = key?;
+    let mut guard = CACHE.lock().unwrap_or_else(|p| p.into_inner());
+    let hit = guard.as_mut()?.get(key, now_secs());
+    if hit.is_some() {
+        info!("🗄️ Önbellekten yanıtlandı: {} ({})", key.model, &key.hash[..12]);
+    }
+    hit
+}
+
+/// Store a fresh answer when caching was requested; failures are logged, never returned
+pub fn store<T: Serialize>(options: &Option<CacheOptions>, key: Option<&CacheKey>, value: &T) -> Option<CacheInfo> {
+    let (options, key) = (options.as_ref().filter(|o| o.enabled)?, key?);
+    let mut guard = CACHE.lock().unwrap_or_else(|p| p.into_inner());
+    match guard.as_mut()?.put(key, value, options.ttl_secs, now_secs()) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!("⚠️ {}", e);
+            None
+        }
+    }
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+#[tauri::command]
+pub fn get_response_cache_stats() -> Result<CacheStats, String> {
+    with_cache(|cache| Ok(cache.stats()))
+}
+
+#[tauri::command]
+pub fn set_response_cache_settings(settings: CacheSettings) -> Result<CacheStats, String> {
+    with_cache(|cache| {
+        cache.settings = settings;
+        cache.save_settings()?;
+        cache.evict(now_secs());
+        Ok(cache.stats())
+    })
+}
+
+/// Remove cached answers for a provider and/or model (everything when both are omitted)
+#[tauri::command]
+pub fn clear_response_cache(provider: Option<String>, model: Option<String>) -> Result<usize, String> {
+    with_cache(|cache| {
+        let removed = cache.invalidate(provider.as_deref(), model.as_deref());
+        info!("🧹 Yanıt önbelleği temizlendi: {} kayıt", removed);
+        Ok(removed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{Role, ToolDefinition};
+
+    fn temp_cache() -> (ResponseCache, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("corex_cache_{}", uuid::Uuid::new_v4().simple()));
+        (ResponseCache::open(&dir).unwrap(), dir)
+    }
+
+    fn request(content: &str, temperature: f32) -> ChatRequest {
+        ChatRequest { messages: vec![Message::new(Role::User, content)], temperature: Some(temperature), ..Default::default() }
+    }
+
+    #[test]
+    fn test_chat_key_normalises_and_requires_determinism() {
+        let key = |r: &ChatRequest| chat_key("openai", "http://127.0.0.1:1234/v1/", "qwen", r).map(|k| k.hash);
+        let a = key(&request("explain this file\r\n", 0.0)).unwrap();
+        assert_eq!(Some(a.clone()), key(&request("  explain this file", 0.0)));
+        assert_ne!(Some(a.clone()), key(&request("explain that file", 0.0)));
+        assert_ne!(Some(a.clone()), chat_key("ollama", "http://127.0.0.1:1234/v1", "qwen", &request("explain this file", 0.0)).map(|k| k.hash));
+
+        assert!(key(&request("explain this file", 0.7)).is_none());
+        let seeded = ChatRequest { seed: Some(7), ..request("explain this file", 0.7) };
+        assert!(key(&seeded).is_some_and(|k| k != a));
+        // Anthropic drops the seed and Ollama's isn't repeatable: sampled replies are not cached
+        assert!(chat_key("anthropic", "", "claude", &seeded).is_none());
+        assert!(chat_key("ollama", "", "qwen", &seeded).is_none());
+        assert!(chat_key("anthropic", "", "claude", &request("explain this file", 0.0)).is_some());
+
+        let with_tools = ChatRequest {
+            tools: vec![ToolDefinition { name: "read_file".into(), description: "Read".into(), parameters: json!({}) }],
+            ..request("explain this file", 0.0)
+        };
+        assert_ne!(key(&with_tools), Some(a));
+    }
+
+    #[test]
+    fn test_gguf_key_requires_greedy_sampling() {
+        let options = CompletionOptions::default();
+        let key = |variant: &str, temperature: f32, options: &CompletionOptions| {
+            gguf_key("completion", "/m/q.gguf", variant, json!("fn main"), 64, temperature, options).map(|k| k.hash)
+        };
+        assert!(key("", 0.7, &options).is_none());
+        let json_mode = CompletionOptions { json: true, ..Default::default() };
+        assert!(key("", 0.7, &json_mode).is_some());
+        assert_ne!(key("", 0.0, &options), key("/l/style.gguf@1", 0.0, &options));
+
+        // Replacing the file under the same path changes the key
+        let path = std::env::temp_dir().join(format!("corex_cache_model_{}.gguf", uuid::Uuid::new_v4().simple()));
+        let path_key = |p: &Path| gguf_key("completion", &p.to_string_lossy(), "", json!("fn main"), 64, 0.0, &options).map(|k| k.hash);
+        std::fs::write(&path, b"GGUF v1").unwrap();
+        let first = path_key(&path);
+        std::fs::write(&path, b"GGUF v2 larger").unwrap();
+        assert_ne!(first, path_key(&path));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_hit_expiry_and_reopen() {
+        let (mut cache, dir) = temp_cache();
+        let key = chat_key("openai", "", "qwen", &request("hi", 0.0)).unwrap();
+        assert!(cache.get::<String>(&key, 100).is_none());
+
+        let stored = cache.put(&key, &"hello".to_string(), Some(60), 100).unwrap();
+        assert!(!stored.hit);
+        let (value, info) = cache.get::<String>(&key, 130).unwrap();
+        assert_eq!(value, "hello");
+        assert_eq!((info.hit, info.age_secs, info.expires_in_secs), (true, 30, 30));
+
+        let mut reopened = ResponseCache::open(&dir).unwrap();
+        assert_eq!(reopened.stats().entries, 1);
+        assert!(reopened.get::<String>(&key, 160).is_none());
+        assert_eq!(reopened.stats().entries, 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_size_cap_evicts_least_recently_used() {
+        let (mut cache, dir) = temp_cache();
+        let keys: Vec<CacheKey> = (0..3).map(|i| chat_key("openai", "", "qwen", &request(&i.to_string(), 0.0)).unwrap()).collect();
+        cache.put(&keys[0], &"a".repeat(100), None, 1).unwrap();
+        let one = cache.bytes();
+        cache.settings.max_bytes = one * 2 + one / 2;
+        cache.put(&keys[1], &"b".repeat(100), None, 2).unwrap();
+        assert!(cache.get::<String>(&keys[0], 3).is_some());
+        cache.put(&keys[2], &"c".repeat(100), None, 4).unwrap();
+
+        assert!(cache.get::<String>(&keys[1], 5).is_none());
+        assert!(cache.get::<String>(&keys[0], 5).is_some());
+        assert!(cache.bytes() <= cache.settings.max_bytes);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_invalidate_by_provider_and_model() {
+        let (mut cache, dir) = temp_cache();
+        let remote = chat_key("openai", "", "gpt-4o", &request("x", 0.0)).unwrap();
+        let local = gguf_key("chat", "/m/q.gguf", "", json!([]), 32, 0.0, &CompletionOptions::default()).unwrap();
+        cache.put(&remote, &"r", None, 1).unwrap();
+        cache.put(&local, &"l", None, 1).unwrap();
+
+        assert_eq!(cache.invalidate(Some("gguf"), Some("other.gguf")), 0);
+        assert_eq!(cache.invalidate(None, Some("gpt-4o")), 1);
+        assert!(cache.get::<String>(&local, 2).is_some());
+        assert_eq!(cache.invalidate(None, None), 1);
+        assert_eq!(cache.stats().entries, 0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,191 @@
+// src-tauri/src/sse.rs
+// Incremental parsers for streamed HTTP bodies. Network chunks can end anywhere
+// (mid-line, mid-event, even mid UTF-8 character), so both parsers buffer raw
+// bytes and only decode complete lines.
+//
+// SseParser: text/event-stream (OpenAI, Anthropic, Gemini ?alt=sse)
+// NdjsonParser: one JSON object per line (Ollama)
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SseEvent {
+    /// `event:` field; None means the default "message" type
+    pub event: Option<String>,
+    /// `data:` lines joined with '\n'
+    pub data: String,
+    pub id: Option<String>,
+}
+
+/// Take the next complete line off `buf`, accepting \n, \r\n and \r endings.
+/// A trailing '\r' waits for the next chunk since a '\n' may follow it.
+fn take_line(buf: &mut Vec<u8>, at_eof: bool) -> Option<String> {
+    let pos = match buf.iter().position(|&b| b == b'\n' || b == b'\r') {
+        Some(pos) => pos,
+        None if at_eof && !buf.is_empty() => {
+            let line = String::from_utf8_lossy(buf).into_owned();
+            buf.clear();
+            return Some(line);
+        }
+        None => return None,
+    };
+    let mut end = pos + 1;
+    if buf[pos] == b'\r' {
+        match buf.get(pos + 1) {
+            Some(b'\n') => end += 1,
+            None if !at_eof => return None,
+            _ => {}
+        }
+    }
+    let line = String::from_utf8_lossy(&buf[..pos]).into_owned();
+    buf.drain(..end);
+    Some(line)
+}
+
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buf: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    last_id: Option<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed one network chunk; returns every event completed by it
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buf.extend_from_slice(chunk);
+        self.drain(false)
+    }
+
+    /// End of body: flush a final event that wasn't followed by a blank line
+    pub fn finish(&mut self) -> Vec<SseEvent> {
+        let mut events = self.drain(true);
+        events.extend(self.dispatch());
+        events
+    }
+
+    fn drain(&mut self, at_eof: bool) -> Vec<SseEvent> {
+        let mut events = Vec::new();
+        while let Some(line) = take_line(&mut self.buf, at_eof) {
+            if line.is_empty() {
+                events.extend(self.dispatch());
+                continue;
+            }
+            if line.starts_with(':') {
+                continue; // comment / keep-alive
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+                None => (line.as_str(), ""),
+            };
+            match field {
+                "event" => self.event = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                "id" => self.last_id = Some(value.to_string()),
+                _ => {} // retry and unknown fields
+            }
+        }
+        events
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+            id: self.last_id.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NdjsonParser {
+    buf: Vec<u8>,
+}
+
+impl NdjsonParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Vec<Value>, String> {
+        self.buf.extend_from_slice(chunk);
+        self.drain(false)
+    }
+
+    pub fn finish(&mut self) -> Result<Vec<Value>, String> {
+        self.drain(true)
+    }
+
+    fn drain(&mut self, at_eof: bool) -> Result<Vec<Value>, String> {
+        let mut values = Vec::new();
+        while let Some(line) = take_line(&mut self.buf, at_eof) {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            values.push(serde_json::from_str(line).map_err(|e| format!("NDJSON satırı okunamadı: {} ({})", e, line))?);
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `body` split at every possible position and check the result never changes
+    fn parse_all_splits(body: &str) -> Vec<SseEvent> {
+        let bytes = body.as_bytes();
+        let mut whole = SseParser::new();
+        let mut expected = whole.feed(bytes);
+        expected.extend(whole.finish());
+        for split in 1..bytes.len() {
+            let mut parser = SseParser::new();
+            let mut events = parser.feed(&bytes[..split]);
+            events.extend(parser.feed(&bytes[split..]));
+            events.extend(parser.finish());
+            assert_eq!(events, expected, "split at {}", split);
+        }
+        expected
+    }
+
+    #[test]
+    fn test_sse_events_and_fields() {
+        let body = ": keep-alive\n\nevent: message_start\ndata: {\"a\":1}\n\nevent: ping\ndata: {}\nid: 7\n\ndata: line one\ndata: line two\n\n";
+        let events = parse_all_splits(body);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].event.as_deref(), Some("message_start"));
+        assert_eq!(events[0].data, "{\"a\":1}");
+        assert_eq!(events[1].id.as_deref(), Some("7"));
+        assert_eq!(events[2].event, None);
+        assert_eq!(events[2].data, "line one\nline two");
+    }
+
+    #[test]
+    fn test_sse_crlf_utf8_and_unterminated_tail() {
+        let body = "data: merhaba dünya 🌊\r\n\r\ndata:no-space\r\rdata: [DONE]";
+        let events = parse_all_splits(body);
+        let data: Vec<&str> = events.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, vec!["merhaba dünya 🌊", "no-space", "[DONE]"]);
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let body = b"{\"n\":1}\n\n{\"n\":2,\"s\":\"\xc3\xbc\"}\n{\"n\":3}";
+        let mut parser = NdjsonParser::new();
+        let mut values = parser.feed(&body[..22]).unwrap();
+        values.extend(parser.feed(&body[22..]).unwrap());
+        values.extend(parser.finish().unwrap());
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[1]["s"], "ü");
+        assert!(NdjsonParser::new().feed(b"{oops}\n").is_err());
+    }
+}
@@ -3,8 +3,18 @@
 
 use tauri::{AppHandle, Emitter, Manager};
 use serde::{Deserialize, Serialize};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use crate::commands::{ChatMessage, ProviderConfig};
+use crate::gguf_completion::CompletionOptions;
+use crate::http_client;
+use crate::inference::{GenerateRequest, InferenceEngine};
+use crate::providers::{self, ChatRequest, ChatResponse, Message, ProviderSettings, Role};
+use crate::usage::{self, UsageEvent};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamToken {
     pub token: String,
@@ -17,6 +27,12 @@ pub struct StreamingRequest {
     pub prompt: String,
     pub max_tokens: Option<i32>,
     pub temperature: Option<f32>,
+    pub draft_model_path: Option<String>, // 🆕 Speculative decoding için küçük draft model
+    pub n_draft: Option<usize>,
+    #[serde(default)]
+    pub model: Option<String>, // HTTP streaming: sunucudaki model adı
+    #[serde(default)]
+    pub request_id: Option<String>, // cancel_chat_stream ile iptal için
 }
 
 /// Stream AI response with real-time token emission
@@ -26,101 +42,49 @@ pub async fn chat_with_streaming(
     request: StreamingRequest,
 ) -> Result<String, String> {
     log::info!("🌊 Starting real GGUF streaming chat...");
-    
-    // 1. Get GGUF state and model
-    let gguf_state = app.state::<Arc<Mutex<crate::gguf::GgufState>>>();
-    
-    let (loaded_model, backend) = {
-        let guard = gguf_state.lock().map_err(|e| e.to_string())?;
-        
-        let model_path = match &request.model_path {
-            Some(path) => path.clone(),
-            None => guard.models.keys().next().cloned().ok_or("No models loaded")?,
-        };
-
-        let model = guard.models.get(&model_path).cloned().ok_or("Model not found")?;
-        let backend = guard.backend.as_ref().cloned().ok_or("Backend not init")?;
-        (model, backend)
-    };
-
-    let model = &loaded_model.model;
-    let n_ctx = loaded_model.n_ctx;
-    let max_tokens = request.max_tokens.unwrap_or(2000) as u32;
-    let temperature = request.temperature.unwrap_or(0.7);
 
-    // 2. Setup Context (FIX-31)
-    let kv_cache_size = (n_ctx + max_tokens).max(4096);
-    let n_batch = 2048;
-    let ctx_params = llama_cpp_2::context::params::LlamaContextParams::default()
-        .with_n_ctx(std::num::NonZeroU32::new(kv_cache_size as u32))
-        .with_n_batch(n_batch as u32);
+    let pool = app.state::<Arc<Mutex<crate::gguf::GgufState>>>().inner().clone();
+    let engine = InferenceEngine::llama_cpp(pool.clone());
+    let model_path = engine.resolve_model(request.model_path.as_deref())?;
+    usage::check_budget(Some("gguf"), "chat_stream", None)?;
 
-    let mut context = model.new_context(&backend, ctx_params).map_err(|e| format!("{:?}", e))?;
+    // 🆕 Speculative decoding: istekteki draft model eşleştirilir, engine kayıtlı eşleşmeyi kullanır
+    if let Some(draft_path) = &request.draft_model_path {
+        crate::gguf_speculative::pair_models(&pool, &model_path, draft_path, request.n_draft)?;
+    }
 
-    // 3. Tokenize Prompt
-    let tokens = model.str_to_token(&request.prompt, llama_cpp_2::model::AddBos::Always)
-        .map_err(|e| format!("{:?}", e))?;
+    let generate = GenerateRequest {
+        prompt: request.prompt,
+        max_tokens: request.max_tokens.unwrap_or(2000) as u32,
+        temperature: request.temperature.unwrap_or(0.7),
+        options: CompletionOptions::default(),
+    };
 
     app.emit("stream-start", ()).map_err(|e| e.to_string())?;
 
-    // 4. Initial Batch Processing
-    let mut batch = llama_cpp_2::llama_batch::LlamaBatch::new(tokens.len().min(8192), 1);
-    for (i, token) in tokens.iter().enumerate() {
-        batch.add(*token, i as i32, &[0], i == tokens.len() - 1).map_err(|e| format!("{:?}", e))?;
-    }
-    context.decode(&mut batch).map_err(|e| format!("{:?}", e))?;
-
-    // 5. Token Generation Loop
-    let mut full_response = String::new();
-    let mut n_cur = batch.n_tokens();
-    let mut decoder = encoding_rs::UTF_8.new_decoder();
-    let mut response_tokens = Vec::new();
-
-    for _i in 0..max_tokens {
-        let candidates = context.candidates();
-        
-        // Simple sampling logic (reused from gguf.rs basics)
-        let candidates_vec: Vec<_> = candidates.into_iter().collect();
-        let new_token_id = if temperature > 0.0 {
-            // Very basic sampling for brevity here, similar to gguf.rs
-            candidates_vec[0].id() // Fallback to greedy if sampling complex
-        } else {
-            candidates_vec[0].id()
-        };
-
-        if model.is_eog_token(new_token_id) { break; }
-
-        // Decode token to string
-        match model.token_to_piece(new_token_id, &mut decoder, false, None) {
-            Ok(token_str) => {
-                full_response.push_str(&token_str);
-                
-                // 🔥 Emit token immediately!
-                app.emit("stream-token", StreamToken {
-                    token: token_str,
-                    is_complete: false,
-                }).map_err(|e| e.to_string())?;
-            },
-            _ => {}
-        }
+    let emitter = app.clone();
+    let model_id = model_path.clone();
+    let completion = tokio::task::spawn_blocking(move || {
+        engine.stream(&model_id, &generate, &mut |text| {
+            // 🔥 Emit token immediately!
+            emitter.emit("stream-token", StreamToken { token: text.to_string(), is_complete: false }).is_ok()
+        })
+    })
+    .await
+    .map_err(|e| format!("Streaming görevi başarısız: {}", e))??;
+    usage::record(UsageEvent::local("chat_stream", &model_path, completion.prompt_tokens, completion.completion_tokens));
 
-        response_tokens.push(new_token_id);
-        
-        // Prepare for next token
-        batch.clear();
-        batch.add(new_token_id, n_cur, &[0], true).map_err(|e| format!("{:?}", e))?;
-        context.decode(&mut batch).map_err(|e| format!("{:?}", e))?;
-        n_cur += 1;
+    app.emit("stream-token", StreamToken { token: String::new(), is_complete: true }).map_err(|e| e.to_string())?;
+    if completion.speculative.is_some() {
+        app.emit("stream-stats", &completion).map_err(|e| e.to_string())?;
     }
+    app.emit("stream-complete", completion.text.clone()).map_err(|e| e.to_string())?;
 
-    // 6. Complete
-    app.emit("stream-token", StreamToken { token: String::new(), is_complete: true }).map_err(|e| e.to_string())?;
-    app.emit("stream-complete", full_response.clone()).map_err(|e| e.to_string())?;
-    
-    Ok(full_response)
+    Ok(completion.text)
 }
 
-/// Stream with HTTP API (LM Studio, Ollama)
+/// Stream with HTTP API (LM Studio, Ollama). Emits the shared stream-* events;
+/// prefer `stream_chat_with_provider` for per-request channels.
 #[tauri::command]
 pub async fn chat_with_http_streaming(
     app: AppHandle,
@@ -128,82 +92,154 @@ pub async fn chat_with_http_streaming(
     request: StreamingRequest,
 ) -> Result<String, String> {
     log::info!("🌊 Starting HTTP streaming to: {}", base_url);
-    
-    use reqwest::Client;
-    use futures_util::StreamExt;
-    
-    let client = Client::new();
-    
-    // Emit start event
+
+    let provider_id = providers::detect_provider(&base_url);
+    let base = base_url.trim_end_matches('/');
+    let settings = ProviderSettings {
+        // OpenAI-compatible servers are addressed without /v1 here
+        base_url: if provider_id == "openai" && !base.ends_with("/v1") { format!("{}/v1", base) } else { base.to_string() },
+        api_key: None,
+        model: request.model.clone().unwrap_or_else(|| "default".to_string()),
+        credential_id: None,
+    };
+    let chat = ChatRequest {
+        messages: vec![Message::new(Role::User, request.prompt.clone())],
+        temperature: Some(request.temperature.unwrap_or(0.7)),
+        max_tokens: Some(request.max_tokens.unwrap_or(2000).max(1) as u32),
+        ..Default::default()
+    };
+    usage::check_budget(Some(provider_id), "chat_stream", None)?;
+    let provider = providers::provider(provider_id)?;
+    let request_id = request.request_id.clone().unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancel = register_stream(&request_id);
+
     app.emit("stream-start", ()).map_err(|e| e.to_string())?;
-    
-    let body = serde_json::json!({
-        "model": "default",
-        "messages": [
-            {"role": "user", "content": request.prompt}
-        ],
-        "max_tokens": request.max_tokens.unwrap_or(2000),
-        "temperature": request.temperature.unwrap_or(0.7),
-        "stream": true
-    });
-    
-    let response = client
-        .post(format!("{}/v1/chat/completions", base_url))
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| format!("HTTP request failed: {}", e))?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err(format!("HTTP error ({}): {}", status, error_text));
+    let result = providers::stream(&http_client::client(), provider.as_ref(), &settings, &chat, &cancel, |text| {
+        app.emit("stream-token", StreamToken { token: text.to_string(), is_complete: false }).is_ok()
+    }).await;
+    unregister_stream(&request_id);
+    let response = result?;
+    usage::record(UsageEvent::from_response("chat_stream", &settings.model, &chat, &response));
+
+    app.emit("stream-token", StreamToken { token: String::new(), is_complete: true }).map_err(|e| e.to_string())?;
+    app.emit("stream-complete", response.text.clone()).map_err(|e| e.to_string())?;
+
+    log::info!("✅ HTTP streaming complete");
+
+    Ok(response.text)
+}
+
+// --------------------
+// PER-REQUEST PROVIDER STREAMS
+// --------------------
+
+/// Payload of the `chat-stream:{request_id}` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatStreamEvent {
+    Start { provider: String, model: String },
+    Token { text: String },
+    Done { response: ChatResponse },
+    Error { message: String },
+}
+
+/// Cancel flags of running streams, keyed by request id
+static ACTIVE_STREAMS: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn register_stream(request_id: &str) -> Arc<AtomicBool> {
+    let flag = Arc::new(AtomicBool::new(false));
+    ACTIVE_STREAMS.lock().unwrap_or_else(|p| p.into_inner()).insert(request_id.to_string(), flag.clone());
+    flag
+}
+
+fn unregister_stream(request_id: &str) {
+    ACTIVE_STREAMS.lock().unwrap_or_else(|p| p.into_inner()).remove(request_id);
+}
+
+pub fn stream_event_name(request_id: &str) -> String {
+    format!("chat-stream:{}", request_id)
+}
+
+/// Stream through `provider_id`, emitting ChatStreamEvents on this request's own channel
+pub async fn run_provider_stream(
+    app: &AppHandle,
+    request_id: &str,
+    provider_id: &str,
+    settings: &ProviderSettings,
+    request: &ChatRequest,
+) -> Result<ChatResponse, String> {
+    let event = stream_event_name(request_id);
+    if let Err(e) = usage::check_budget(Some(provider_id), "chat_stream", None) {
+        let _ = app.emit(&event, ChatStreamEvent::Error { message: e.clone() });
+        return Err(e);
     }
-    
-    let mut stream = response.bytes_stream();
-    let mut full_response = String::new();
-    
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Stream error: {}", e))?;
-        let text = String::from_utf8_lossy(&chunk);
-        
-        // Parse SSE format
-        for line in text.lines() {
-            if line.starts_with("data: ") {
-                let data = &line[6..];
-                if data == "[DONE]" {
-                    break;
-                }
-                
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(data) {
-                    // Chat format uses delta.content instead of text
-                    if let Some(token) = json["choices"][0]["delta"]["content"].as_str() {
-                        full_response.push_str(token);
-                        
-                        let stream_token = StreamToken {
-                            token: token.to_string(),
-                            is_complete: false,
-                        };
-                        
-                        app.emit("stream-token", stream_token).map_err(|e| e.to_string())?;
-                    }
-                }
-            }
+    let provider = providers::provider(provider_id)?;
+    let cancel = register_stream(request_id);
+
+    let _ = app.emit(&event, ChatStreamEvent::Start { provider: provider_id.to_string(), model: settings.model.clone() });
+    let result = providers::stream(&http_client::client(), provider.as_ref(), settings, request, &cancel, |text| {
+        app.emit(&event, ChatStreamEvent::Token { text: text.to_string() }).is_ok()
+    }).await;
+    unregister_stream(request_id);
+
+    match &result {
+        Ok(response) => {
+            usage::record(UsageEvent::from_response("chat_stream", &settings.model, request, response));
+            let _ = app.emit(&event, ChatStreamEvent::Done { response: response.clone() });
         }
+        Err(e) => { let _ = app.emit(&event, ChatStreamEvent::Error { message: e.clone() }); }
     }
-    
-    // Emit completion
-    let final_token = StreamToken {
-        token: String::new(),
-        is_complete: true,
+    result
+}
+
+/// Listen on `chat-stream:{request_id}` before invoking
+#[tauri::command]
+pub async fn stream_chat_with_provider(
+    app: AppHandle,
+    request_id: String,
+    provider_id: String,
+    settings: ProviderSettings,
+    request: ChatRequest,
+) -> Result<ChatResponse, String> {
+    run_provider_stream(&app, &request_id, &provider_id, &settings, &request).await
+}
+
+/// Streaming twin of `chat_with_dynamic_ai`
+#[tauri::command]
+pub async fn chat_with_dynamic_ai_streaming(
+    app: AppHandle,
+    request_id: String,
+    message: String,
+    conversation_history: Vec<ChatMessage>,
+    provider_config: ProviderConfig,
+) -> Result<String, String> {
+    let messages = if conversation_history.is_empty() {
+        vec![Message::new(Role::User, message)]
+    } else {
+        conversation_history.iter().map(ChatMessage::to_message).collect()
     };
-    app.emit("stream-token", final_token).map_err(|e| e.to_string())?;
-    app.emit("stream-complete", full_response.clone()).map_err(|e| e.to_string())?;
-    
-    log::info!("✅ HTTP streaming complete");
-    
-    Ok(full_response)
+    let request = provider_config.chat_request(messages);
+    let response = run_provider_stream(
+        &app,
+        &request_id,
+        &provider_config.provider_id(),
+        &provider_config.settings(),
+        &request,
+    ).await?;
+    Ok(response.text)
+}
+
+/// Stop a running provider stream; false if it already finished
+#[tauri::command]
+pub fn cancel_chat_stream(request_id: String) -> bool {
+    match ACTIVE_STREAMS.lock().unwrap_or_else(|p| p.into_inner()).get(&request_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            log::info!("🛑 Stream iptal edildi: {}", request_id);
+            true
+        }
+        None => false,
+    }
 }
 
 // Note: We don't need chat_with_gguf_model_internal anymore
@@ -0,0 +1,613 @@
+// src-tauri/src/usage.rs
+// Token usage and cost ledger.
+//
+// Every chat / stream / embedding call, remote or local, appends one UsageRecord
+// to {app_data}/usage/ledger.jsonl. Token counts come from the provider's `usage`
+// block or the llama.cpp tokenizer; when a remote provider omits them they are
+// estimated from the text (~4 chars per token) and flagged `estimated`.
+// Cost = tokens x the model's price (bundled table, overridable in settings.json).
+// Budgets are checked before a request and block it once a limit is reached.
+// Days are UTC.
+
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tauri::{AppHandle, Manager};
+
+use crate::providers::{ChatRequest, ChatResponse};
+
+// --------------------
+// RECORDS & PRICES
+// --------------------
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub at: u64,
+    /// UTC date, YYYY-MM-DD
+    pub day: String,
+    pub provider: String,
+    pub model: String,
+    /// What the call was for: chat, chat_stream, agent, embedding, fim, ...
+    pub feature: String,
+    pub project: Option<String>,
+    /// One id per app launch
+    pub session: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated: bool,
+    pub cost_usd: f64,
+}
+
+/// USD per million tokens for every model whose name starts with `model`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub model: String,
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+fn price(model: &str, input: f64, output: f64) -> ModelPrice {
+    ModelPrice { model: model.to_string(), input_per_million: input, output_per_million: output }
+}
+
+/// Bundled list prices; anything not listed (and every local model) costs 0
+pub fn default_prices() -> Vec<ModelPrice> {
+    vec![
+        price("gpt-4o", 2.5, 10.0),
+        price("gpt-4o-mini", 0.15, 0.6),
+        price("gpt-4.1", 2.0, 8.0),
+        price("gpt-4.1-mini", 0.4, 1.6),
+        price("gpt-4.1-nano", 0.1, 0.4),
+        price("o1", 15.0, 60.0),
+        price("o1-mini", 1.1, 4.4),
+        price("o3-mini", 1.1, 4.4),
+        price("text-embedding-3-small", 0.02, 0.0),
+        price("text-embedding-3-large", 0.13, 0.0),
+        price("claude-3-5-sonnet", 3.0, 15.0),
+        price("claude-3-7-sonnet", 3.0, 15.0),
+        price("claude-sonnet-4", 3.0, 15.0),
+        price("claude-3-5-haiku", 0.8, 4.0),
+        price("claude-3-opus", 15.0, 75.0),
+        price("claude-opus-4", 15.0, 75.0),
+        price("gemini-1.5-flash", 0.075, 0.3),
+        price("gemini-1.5-pro", 1.25, 5.0),
+        price("gemini-2.0-flash", 0.1, 0.4),
+    ]
+}
+
+/// Providers that never cost money
+fn is_local(provider: &str) -> bool {
+    matches!(provider, "gguf" | "ollama")
+}
+
+/// Longest matching model prefix; user overrides win over the bundled table
+pub fn price_for<'a>(overrides: &'a [ModelPrice], defaults: &'a [ModelPrice], model: &str) -> Option<&'a ModelPrice> {
+    let model = model.to_ascii_lowercase();
+    let model = model.strip_prefix("models/").unwrap_or(&model);
+    let best = |table: &'a [ModelPrice]| {
+        table.iter()
+            .filter(|p| model.starts_with(&p.model.to_ascii_lowercase()))
+            .max_by_key(|p| p.model.len())
+    };
+    best(overrides).or_else(|| best(defaults))
+}
+
+/// Rough token count for text a provider didn't count for us
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+/// UTC date of a unix timestamp (days-from-civil inverse)
+pub fn day_of(secs: u64) -> String {
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// One call to account for; built at the call site, priced by the ledger
+#[derive(Debug, Clone)]
+pub struct UsageEvent {
+    pub provider: String,
+    pub model: String,
+    pub feature: String,
+    /// None = the project currently open in the editor
+    pub project: Option<String>,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub estimated: bool,
+}
+
+impl UsageEvent {
+    /// Remote/provider chat; falls back to estimates when `usage` is missing
+    pub fn from_response(feature: &str, model: &str, request: &ChatRequest, response: &ChatResponse) -> Self {
+        let (prompt_tokens, completion_tokens, estimated) = match &response.usage {
+            Some(u) if u.prompt_tokens + u.completion_tokens > 0 => (u.prompt_tokens, u.completion_tokens, false),
+            _ => {
+                let prompt: u64 = request.messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+                let calls: u64 = response.tool_calls.iter().map(|c| estimate_tokens(&c.arguments.to_string())).sum();
+                (prompt, estimate_tokens(&response.text) + calls, true)
+            }
+        };
+        Self {
+            provider: response.provider.clone(),
+            model: response.model.clone().filter(|m| !m.is_empty()).unwrap_or_else(|| model.to_string()),
+            feature: feature.to_string(),
+            project: None,
+            prompt_tokens,
+            completion_tokens,
+            estimated,
+        }
+    }
+
+    /// Local GGUF call with exact tokenizer counts
+    pub fn local(feature: &str, model_path: &str, prompt_tokens: usize, completion_tokens: usize) -> Self {
+        let model = Path::new(model_path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| model_path.to_string());
+        Self {
+            provider: "gguf".to_string(),
+            model,
+            feature: feature.to_string(),
+            project: None,
+            prompt_tokens: prompt_tokens as u64,
+            completion_tokens: completion_tokens as u64,
+            estimated: false,
+        }
+    }
+
+    /// Raw OpenAI-compatible body; estimates from `prompt`/`completion` when `usage` is missing
+    pub fn from_openai_json(feature: &str, provider: &str, model: &str, json: &serde_json::Value, prompt: &str, completion: &str) -> Self {
+        let usage = &json["usage"];
+        let prompt_tokens = usage["prompt_tokens"].as_u64();
+        Self {
+            provider: provider.to_string(),
+            model: json["model"].as_str().filter(|m| !m.is_empty()).unwrap_or(model).to_string(),
+            feature: feature.to_string(),
+            project: None,
+            prompt_tokens: prompt_tokens.unwrap_or_else(|| estimate_tokens(prompt)),
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or_else(|| estimate_tokens(completion)),
+            estimated: prompt_tokens.is_none(),
+        }
+    }
+
+    pub fn with_project(mut self, project: impl Into<String>) -> Self {
+        self.project = Some(project.into());
+        self
+    }
+}
+
+// --------------------
+// BUDGETS & TOTALS
+// --------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetPeriod {
+    Day,
+    Month,
+    Total,
+}
+
+/// A spending cap; unset filters match everything. USD limits never block local
+/// GGUF calls since those are free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetLimit {
+    pub period: BudgetPeriod,
+    #[serde(default)]
+    pub provider: Option<String>,
+    #[serde(default)]
+    pub project: Option<String>,
+    #[serde(default)]
+    pub feature: Option<String>,
+    #[serde(default)]
+    pub limit_usd: Option<f64>,
+    #[serde(default)]
+    pub limit_tokens: Option<u64>,
+}
+
+impl BudgetLimit {
+    fn matches(&self, provider: Option<&str>, feature: &str, project: Option<&str>) -> bool {
+        let field = |filter: &Option<String>, value: Option<&str>| filter.as_deref().map_or(true, |f| value.map_or(true, |v| v == f));
+        field(&self.provider, provider) && field(&self.feature, Some(feature)) && field(&self.project, project)
+    }
+
+    fn covers(&self, record: &UsageRecord, today: &str) -> bool {
+        let in_period = match self.period {
+            BudgetPeriod::Day => record.day == today,
+            BudgetPeriod::Month => record.day.get(..7) == today.get(..7),
+            BudgetPeriod::Total => true,
+        };
+        in_period
+            && self.provider.as_deref().map_or(true, |p| p == record.provider)
+            && self.feature.as_deref().map_or(true, |f| f == record.feature)
+            && self.project.as_deref().map_or(true, |p| Some(p) == record.project.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetStatus {
+    pub limit: BudgetLimit,
+    pub spent_usd: f64,
+    pub spent_tokens: u64,
+    pub exceeded: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroup {
+    Day,
+    Project,
+    Provider,
+    Model,
+    Feature,
+    Session,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UsageTotal {
+    pub key: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// Requests whose token counts were estimated
+    pub estimated: u64,
+}
+
+/// Totals per group for records with `from <= day <= to`
+pub fn summarize(records: &[UsageRecord], group: UsageGroup, from: Option<&str>, to: Option<&str>) -> Vec<UsageTotal> {
+    let mut totals: BTreeMap<String, UsageTotal> = BTreeMap::new();
+    for r in records {
+        if from.is_some_and(|f| r.day.as_str() < f) || to.is_some_and(|t| r.day.as_str() > t) {
+            continue;
+        }
+        let key = match group {
+            UsageGroup::Day => r.day.clone(),
+            UsageGroup::Project => r.project.clone().unwrap_or_else(|| "(none)".to_string()),
+            UsageGroup::Provider => r.provider.clone(),
+            UsageGroup::Model => r.model.clone(),
+            UsageGroup::Feature => r.feature.clone(),
+            UsageGroup::Session => r.session.clone(),
+        };
+        let total = totals.entry(key.clone()).or_insert_with(|| UsageTotal { key, ..Default::default() });
+        total.requests += 1;
+        total.prompt_tokens += r.prompt_tokens;
+        total.completion_tokens += r.completion_tokens;
+        total.cost_usd += r.cost_usd;
+        total.estimated += r.estimated as u64;
+    }
+    totals.into_values().collect()
+}
+
+// --------------------
+// LEDGER
+// --------------------
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageSettings {
+    /// Checked before the bundled table
+    #[serde(default)]
+    pub prices: Vec<ModelPrice>,
+    #[serde(default)]
+    pub budgets: Vec<BudgetLimit>,
+}
+
+pub struct Ledger {
+    dir: PathBuf,
+    pub settings: UsageSettings,
+    pub records: Vec<UsageRecord>,
+    session: String,
+    project: Option<String>,
+}
+
+impl Ledger {
+    /// Open (or create) the ledger in `dir`; unreadable lines are skipped
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Usage klasörü oluşturulamadı: {}", e))?;
+        let records = std::fs::read_to_string(dir.join("ledger.jsonl"))
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let settings = std::fs::read_to_string(dir.join("settings.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Ok(Self { dir: dir.to_path_buf(), settings, records, session: uuid::Uuid::new_v4().to_string(), project: None })
+    }
+
+    pub fn save_settings(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.settings).map_err(|e| e.to_string())?;
+        std::fs::write(self.dir.join("settings.json"), json).map_err(|e| format!("Usage ayarları yazılamadı: {}", e))
+    }
+
+    pub fn cost(&self, provider: &str, model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        if is_local(provider) {
+            return 0.0;
+        }
+        let defaults = default_prices();
+        price_for(&self.settings.prices, &defaults, model).map_or(0.0, |p| {
+            (prompt_tokens as f64 * p.input_per_million + completion_tokens as f64 * p.output_per_million) / 1_000_000.0
+        })
+    }
+
+    pub fn record(&mut self, event: UsageEvent, at: u64) -> Result<&UsageRecord, String> {
+        let record = UsageRecord {
+            at,
+            day: day_of(at),
+            cost_usd: self.cost(&event.provider, &event.model, event.prompt_tokens, event.completion_tokens),
+            provider: event.provider,
+            model: event.model,
+            feature: event.feature,
+            project: event.project.or_else(|| self.project.clone()),
+            session: self.session.clone(),
+            prompt_tokens: event.prompt_tokens,
+            completion_tokens: event.completion_tokens,
+            estimated: event.estimated,
+        };
+        let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join("ledger.jsonl"))
+            .and_then(|mut f| writeln!(f, "{}", line))
+            .map_err(|e| format!("Usage kaydı yazılamadı: {}", e))?;
+        self.records.push(record);
+        Ok(self.records.last().expect("just pushed"))
+    }
+
+    pub fn budget_statuses(&self, today: &str) -> Vec<BudgetStatus> {
+        self.settings.budgets.iter().map(|limit| {
+            let (spent_usd, spent_tokens) = self.records.iter()
+                .filter(|r| limit.covers(r, today))
+                .fold((0.0, 0), |(usd, tokens), r| (usd + r.cost_usd, tokens + r.prompt_tokens + r.completion_tokens));
+            let exceeded = limit.limit_usd.is_some_and(|l| spent_usd >= l) || limit.limit_tokens.is_some_and(|l| spent_tokens >= l);
+            BudgetStatus { limit: limit.clone(), spent_usd, spent_tokens, exceeded }
+        }).collect()
+    }
+
+    /// Err when a matching budget is used up. `provider` None = not known yet (any).
+    pub fn check_budget(&self, provider: Option<&str>, feature: &str, project: Option<&str>, today: &str) -> Result<(), String> {
+        let project = project.or(self.project.as_deref());
+        for status in self.budget_statuses(today) {
+            let limit = &status.limit;
+            if !status.exceeded || !limit.matches(provider, feature, project) {
+                continue;
+            }
+            let usd_only = limit.limit_tokens.is_none();
+            if usd_only && provider.is_some_and(is_local) {
+                continue;
+            }
+            return Err(format!(
+                "Bütçe sınırı aşıldı ({:?}{}{}): ${:.4} / {} token harcandı",
+                limit.period,
+                limit.provider.as_deref().map(|p| format!(", {}", p)).unwrap_or_default(),
+                limit.project.as_deref().map(|p| format!(", {}", p)).unwrap_or_default(),
+                status.spent_usd,
+                status.spent_tokens,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// App-wide ledger; calls are no-ops until `init` (e.g. in tests)
+static LEDGER: Lazy<Mutex<Option<Ledger>>> = Lazy::new(|| Mutex::new(None));
+
+pub fn init(dir: &Path) {
+    match Ledger::open(dir) {
+        Ok(ledger) => {
+            info!("💰 Usage ledger: {} kayıt ({})", ledger.records.len(), dir.display());
+            *LEDGER.lock().unwrap_or_else(|p| p.into_inner()) = Some(ledger);
+        }
+        Err(e) => warn!("⚠️ Usage ledger açılamadı: {}", e),
+    }
+}
+
+pub fn init_for_app(app: &AppHandle) {
+    match app.path().app_data_dir() {
+        Ok(dir) => init(&dir.join("usage")),
+        Err(e) => warn!("⚠️ Usage ledger klasörü bulunamadı: {}", e),
+    }
+}
+
+fn with_ledger<T>(f: impl FnOnce(&mut Ledger) -> Result<T, String>) -> Result<T, String> {
+    let mut guard = LEDGER.lock().unwrap_or_else(|p| p.into_inner());
+    let ledger = guard.as_mut().ok_or("Usage ledger başlatılmadı")?;
+    f(ledger)
+}
+
+/// Account for one call; failures are logged, never returned to the caller
+pub fn record(event: UsageEvent) {
+    let mut guard = LEDGER.lock().unwrap_or_else(|p| p.into_inner());
+    if let Some(ledger) = guard.as_mut() {
+        if let Err(e) = ledger.record(event, now_secs()) {
+            warn!("⚠️ {}", e);
+        }
+    }
+}
+
+/// Refuse a request whose budget is used up
+pub fn check_budget(provider: Option<&str>, feature: &str, project: Option<&str>) -> Result<(), String> {
+    let guard = LEDGER.lock().unwrap_or_else(|p| p.into_inner());
+    match guard.as_ref() {
+        Some(ledger) => ledger.check_budget(provider, feature, project, &day_of(now_secs())),
+        None => Ok(()),
+    }
+}
+
+// --------------------
+// COMMANDS
+// --------------------
+
+/// Project that unscoped usage is attributed to (the workspace open in the editor)
+#[tauri::command]
+pub fn set_usage_project(project: Option<String>) -> Result<(), String> {
+    with_ledger(|ledger| {
+        ledger.project = project;
+        Ok(())
+    })
+}
+
+/// Totals grouped by day/project/provider/model/feature/session; days are YYYY-MM-DD (UTC)
+#[tauri::command]
+pub fn get_usage_totals(group_by: UsageGroup, from: Option<String>, to: Option<String>) -> Result<Vec<UsageTotal>, String> {
+    with_ledger(|ledger| Ok(summarize(&ledger.records, group_by, from.as_deref(), to.as_deref())))
+}
+
+/// Most recent records first
+#[tauri::command]
+pub fn list_usage_records(limit: Option<usize>) -> Result<Vec<UsageRecord>, String> {
+    with_ledger(|ledger| Ok(ledger.records.iter().rev().take(limit.unwrap_or(100)).cloned().collect()))
+}
+
+#[tauri::command]
+pub fn get_usage_settings() -> Result<UsageSettings, String> {
+    with_ledger(|ledger| Ok(ledger.settings.clone()))
+}
+
+/// The bundled price table (overrides in settings are matched first)
+#[tauri::command]
+pub fn get_default_model_prices() -> Vec<ModelPrice> {
+    default_prices()
+}
+
+#[tauri::command]
+pub fn set_usage_settings(settings: UsageSettings) -> Result<UsageSettings, String> {
+    with_ledger(|ledger| {
+        ledger.settings = settings;
+        ledger.save_settings()?;
+        Ok(ledger.settings.clone())
+    })
+}
+
+#[tauri::command]
+pub fn get_budget_status() -> Result<Vec<BudgetStatus>, String> {
+    with_ledger(|ledger| Ok(ledger.budget_statuses(&day_of(now_secs()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{Message, Role, Usage};
+
+    fn temp_ledger() -> (Ledger, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("corex_usage_{}", uuid::Uuid::new_v4().simple()));
+        (Ledger::open(&dir).unwrap(), dir)
+    }
+
+    fn event(provider: &str, model: &str, feature: &str, prompt: u64, completion: u64) -> UsageEvent {
+        UsageEvent {
+            provider: provider.into(),
+            model: model.into(),
+            feature: feature.into(),
+            project: None,
+            prompt_tokens: prompt,
+            completion_tokens: completion,
+            estimated: false,
+        }
+    }
+
+    #[test]
+    fn test_day_of() {
+        assert_eq!(day_of(0), "1970-01-01");
+        assert_eq!(day_of(951_782_400), "2000-02-29");
+        assert_eq!(day_of(1_767_225_599), "2025-12-31");
+    }
+
+    #[test]
+    fn test_price_lookup_and_cost() {
+        let defaults = default_prices();
+        assert_eq!(price_for(&[], &defaults, "gpt-4o-mini-2024-07-18").unwrap().model, "gpt-4o-mini");
+        assert_eq!(price_for(&[], &defaults, "models/gemini-1.5-flash-002").unwrap().model, "gemini-1.5-flash");
+        assert!(price_for(&[], &defaults, "qwen2.5-coder-7b-instruct").is_none());
+        let custom = [price("gpt-4o", 1.0, 1.0)];
+        assert_eq!(price_for(&custom, &defaults, "gpt-4o-mini").unwrap().input_per_million, 1.0);
+
+        let (ledger, dir) = temp_ledger();
+        assert!((ledger.cost("openai", "gpt-4o", 1_000_000, 100_000) - 3.5).abs() < 1e-9);
+        assert_eq!(ledger.cost("gguf", "gpt-4o", 1_000_000, 0), 0.0);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_estimates_when_usage_missing() {
+        let request = ChatRequest { messages: vec![Message::new(Role::User, "12345678")], ..Default::default() };
+        let mut response = ChatResponse { text: "abcd".into(), ..ChatResponse::empty("openai") };
+        let e = UsageEvent::from_response("chat", "local-model", &request, &response);
+        assert_eq!((e.prompt_tokens, e.completion_tokens, e.estimated), (2, 1, true));
+        assert_eq!(e.model, "local-model");
+
+        response.usage = Some(Usage::new(40, 7));
+        response.model = Some("gpt-4o-2024-08-06".into());
+        let e = UsageEvent::from_response("chat", "gpt-4o", &request, &response);
+        assert_eq!((e.prompt_tokens, e.completion_tokens, e.estimated), (40, 7, false));
+        assert_eq!(e.model, "gpt-4o-2024-08-06");
+
+        let raw = serde_json::json!({"model": "", "choices": []});
+        let e = UsageEvent::from_openai_json("chat", "openai", "qwen", &raw, "12345678", "abcd");
+        assert_eq!((e.prompt_tokens, e.completion_tokens, e.estimated, e.model.as_str()), (2, 1, true, "qwen"));
+        let raw = serde_json::json!({"usage": {"prompt_tokens": 9, "completion_tokens": 3}});
+        let e = UsageEvent::from_openai_json("chat", "openai", "qwen", &raw, "", "");
+        assert_eq!((e.prompt_tokens, e.completion_tokens, e.estimated), (9, 3, false));
+    }
+
+    #[test]
+    fn test_ledger_totals_persist() {
+        let (mut ledger, dir) = temp_ledger();
+        ledger.project = Some("corex".into());
+        ledger.record(event("openai", "gpt-4o", "chat", 1000, 100), 86_400).unwrap();
+        ledger.record(event("gguf", "qwen.gguf", "fim", 50, 10).with_project("other"), 86_400).unwrap();
+        ledger.record(event("anthropic", "claude-3-5-sonnet-latest", "agent", 2000, 500), 2 * 86_400).unwrap();
+
+        let reopened = Ledger::open(&dir).unwrap();
+        assert_eq!(reopened.records.len(), 3);
+        let by_day = summarize(&reopened.records, UsageGroup::Day, None, None);
+        assert_eq!(by_day.iter().map(|t| (t.key.as_str(), t.requests)).collect::<Vec<_>>(), vec![("1970-01-02", 2), ("1970-01-03", 1)]);
+        let by_project = summarize(&reopened.records, UsageGroup::Project, Some("1970-01-02"), Some("1970-01-02"));
+        assert_eq!(by_project.iter().map(|t| t.key.as_str()).collect::<Vec<_>>(), vec!["corex", "other"]);
+        let by_provider = summarize(&reopened.records, UsageGroup::Provider, None, None);
+        let anthropic = by_provider.iter().find(|t| t.key == "anthropic").unwrap();
+        assert!((anthropic.cost_usd - 0.0135).abs() < 1e-9);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_budget_blocks_once_exceeded() {
+        let (mut ledger, dir) = temp_ledger();
+        ledger.settings.budgets = vec![
+            BudgetLimit { period: BudgetPeriod::Day, provider: None, project: None, feature: None, limit_usd: Some(0.01), limit_tokens: None },
+            BudgetLimit { period: BudgetPeriod::Month, provider: None, project: None, feature: Some("fim".into()), limit_usd: None, limit_tokens: Some(100) },
+        ];
+        let today = day_of(86_400);
+        ledger.record(event("openai", "gpt-4o", "chat", 1000, 0), 86_400).unwrap();
+        assert!(ledger.check_budget(Some("openai"), "chat", None, &today).is_ok());
+
+        ledger.record(event("openai", "gpt-4o", "chat", 4000, 0), 86_400).unwrap();
+        assert!(ledger.check_budget(Some("openai"), "chat", None, &today).is_err());
+        assert!(ledger.check_budget(None, "agent", None, &today).is_err());
+        // Free local calls are not blocked by a USD limit, and the next day starts fresh
+        assert!(ledger.check_budget(Some("gguf"), "chat", None, &today).is_ok());
+        assert!(ledger.check_budget(Some("openai"), "chat", None, &day_of(2 * 86_400)).is_ok());
+
+        ledger.record(event("gguf", "coder.gguf", "fim", 90, 20), 86_400).unwrap();
+        assert!(ledger.check_budget(Some("gguf"), "fim", None, &today).is_err());
+        let statuses = ledger.budget_statuses(&today);
+        assert!(statuses.iter().all(|s| s.exceeded));
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
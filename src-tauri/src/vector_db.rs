@@ -11,7 +11,16 @@ use arrow_array::{RecordBatch, RecordBatchIterator, StringArray, Float32Array, U
 use arrow_schema::{Schema, Field, DataType};
 use futures_util::StreamExt;
 use std::sync::Arc as StdArc;
+use std::sync::Mutex as StdMutex;
 use fastembed::{TextEmbedding, InitOptions};
+use crate::gguf::GgufState;
+use crate::gguf_embedding::{self, EmbeddingOptions};
+use crate::usage::{self, UsageEvent};
+
+/// Table family for indexed source code
+pub const CODE_COLLECTION: &str = "code_chunks";
+/// Table family for stored conversation messages (see conversations.rs)
+pub const CONVERSATION_COLLECTION: &str = "conversations";
 
 /// Represents a code chunk stored in the vector database
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,7 +31,7 @@ pub struct CodeChunk {
     pub file_path: String,
     /// Content of the code chunk
     pub content: String,
-    /// Vector embedding (384 dimensions for BGE-small-en-v1.5)
+    /// Vector embedding (384 dimensions for BGE-small-en-v1.5, model-specific for GGUF)
     pub embedding: Vec<f32>,
     /// Optional symbol name (function, class, etc.)
     pub symbol_name: Option<String>,
@@ -103,11 +112,27 @@ impl CodeChunk {
     }
 }
 
+/// Where chunk and query embeddings come from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EmbeddingSource {
+    /// fastembed's default model (BGE-Small-EN-v1.5, downloaded on first use)
+    FastEmbed,
+    /// A GGUF embedding model from the local pool, fully offline
+    Gguf { model_path: String, options: EmbeddingOptions },
+}
+
+struct Embedder {
+    source: EmbeddingSource,
+    gguf_state: Option<StdArc<StdMutex<GgufState>>>,
+}
+
 /// Vector database interface for semantic code search
 pub struct VectorDB {
     connection: Arc<Mutex<Connection>>,
-    table_name: String,
-    embedding_model: Arc<Mutex<TextEmbedding>>,
+    embedder: Arc<Mutex<Embedder>>,
+    /// Loaded lazily so an offline GGUF setup never touches the network
+    embedding_model: Arc<Mutex<Option<TextEmbedding>>>,
 }
 
 impl VectorDB {
@@ -115,24 +140,59 @@ impl VectorDB {
     pub async fn init(db_path: &str) -> Result<Self, Box<dyn Error>> {
         let connection = lancedb::connect(db_path).execute().await?;
         
-        let table_name = "code_chunks".to_string();
-        
-        // Initialize the embedding model (BGE-Small-EN-v1.5 is small and fast for offline usage)
-        let model = TextEmbedding::try_new(InitOptions::default())
-            .map_err(|e| format!("Failed to load embedding model: {}", e))?;
-        
         Ok(Self {
             connection: Arc::new(Mutex::new(connection)),
-            table_name,
-            embedding_model: Arc::new(Mutex::new(model)),
+            embedder: Arc::new(Mutex::new(Embedder { source: EmbeddingSource::FastEmbed, gguf_state: None })),
+            embedding_model: Arc::new(Mutex::new(None)),
         })
     }
+
+    /// Share another instance's embedder (and loaded fastembed model), so choosing an
+    /// embedding model on one applies to both
+    pub fn share_embedder(mut self, other: &VectorDB) -> Self {
+        self.embedder = other.embedder.clone();
+        self.embedding_model = other.embedding_model.clone();
+        self
+    }
+
+    /// Embed with a GGUF model from the pool instead of fastembed
+    pub async fn use_gguf_embeddings(
+        &self,
+        gguf_state: StdArc<StdMutex<GgufState>>,
+        model_path: String,
+        options: EmbeddingOptions,
+    ) {
+        let mut embedder = self.embedder.lock().await;
+        embedder.source = EmbeddingSource::Gguf { model_path, options };
+        embedder.gguf_state = Some(gguf_state);
+    }
+
+    /// Switch back to fastembed's default model
+    pub async fn use_fastembed(&self) {
+        let mut embedder = self.embedder.lock().await;
+        embedder.source = EmbeddingSource::FastEmbed;
+        embedder.gguf_state = None;
+    }
+
+    pub async fn embedding_source(&self) -> EmbeddingSource {
+        self.embedder.lock().await.source.clone()
+    }
+
+    /// Each embedding source gets its own table since vector sizes differ
+    pub async fn table_name(&self) -> String {
+        self.collection_table(CODE_COLLECTION).await
+    }
+
+    async fn collection_table(&self, collection: &str) -> String {
+        table_name_for(collection, &self.embedder.lock().await.source)
+    }
     
-    /// Get or create the code_chunks table
-    async fn get_table(&self) -> Result<Table, Box<dyn Error>> {
+    /// Open the collection's table for the current embedding source
+    async fn get_table(&self, collection: &str) -> Result<Table, Box<dyn Error>> {
+        let table_name = self.collection_table(collection).await;
         let conn = self.connection.lock().await;
         
-        match conn.open_table(&self.table_name).execute().await {
+        match conn.open_table(&table_name).execute().await {
             Ok(table) => Ok(table),
             Err(_) => {
                 Err("Table does not exist. Use upsert to create it.".into())
@@ -142,9 +202,7 @@ impl VectorDB {
     
     /// Generate embedding for a given text
     pub async fn generate_embedding(&self, text: &str) -> Result<Vec<f32>, Box<dyn Error>> {
-        let mut model = self.embedding_model.lock().await;
-        let embeddings: Vec<Vec<f32>> = model.embed(vec![text], None)
-            .map_err(|e| format!("Embedding generation error: {}", e))?;
+        let embeddings = self.generate_embeddings(vec![text]).await?;
         
         if let Some(first) = embeddings.into_iter().next() {
             Ok(first)
@@ -155,18 +213,62 @@ impl VectorDB {
 
     /// Generate embeddings for multiple texts
     pub async fn generate_embeddings(&self, texts: Vec<&str>) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let (source, gguf_state) = {
+            let embedder = self.embedder.lock().await;
+            (embedder.source.clone(), embedder.gguf_state.clone())
+        };
+
+        if let (EmbeddingSource::Gguf { model_path, options }, Some(gguf_state)) = (source, gguf_state) {
+            let (loaded_model, backend) = {
+                let mut guard = gguf_state.lock().unwrap_or_else(|p| p.into_inner());
+                guard.checkout(&model_path)
+                    .map_err(|e| format!("GGUF embedding modeli yüklü değil: {}", e))?
+            };
+            let texts: Vec<String> = texts.into_iter().map(String::from).collect();
+            let result = tokio::task::spawn_blocking(move || {
+                gguf_embedding::embed_texts(&loaded_model, &backend, &texts, &options)
+            })
+            .await
+            .map_err(|e| format!("Embedding görevi başarısız: {}", e))??;
+            usage::record(UsageEvent::local("embedding", &model_path, result.prompt_tokens, 0));
+            return Ok(result.embeddings);
+        }
+
         let mut model = self.embedding_model.lock().await;
+        if model.is_none() {
+            // BGE-Small-EN-v1.5 is small and fast for offline usage once cached
+            *model = Some(TextEmbedding::try_new(InitOptions::default())
+                .map_err(|e| format!("Failed to load embedding model: {}", e))?);
+        }
+        let model = model.as_mut().ok_or("Embedding model not initialized")?;
+        let prompt_tokens = texts.iter().map(|t| usage::estimate_tokens(t)).sum();
         let embeddings: Vec<Vec<f32>> = model.embed(texts, None)
             .map_err(|e| format!("Embedding generation error: {}", e))?;
+        // fastembed doesn't expose its token counts
+        usage::record(UsageEvent {
+            provider: "fastembed".to_string(),
+            model: "bge-small-en-v1.5".to_string(),
+            feature: "embedding".to_string(),
+            project: None,
+            prompt_tokens,
+            completion_tokens: 0,
+            estimated: true,
+        });
         Ok(embeddings)
     }
 
     /// Insert or update code chunks in the vector database
     /// NOTE: embeddings should be generated prior to this step or within it
     pub async fn upsert(&self, chunks: Vec<CodeChunk>) -> Result<(), Box<dyn Error>> {
-        // Filter out empty or too small chunks (FIX-41)
+        self.upsert_into(CODE_COLLECTION, chunks).await
+    }
+
+    /// `upsert` into another collection
+    pub async fn upsert_into(&self, collection: &str, chunks: Vec<CodeChunk>) -> Result<(), Box<dyn Error>> {
+        // Filter out empty or too small code chunks (FIX-41); short chat messages stay searchable
+        let min_len = if collection == CODE_COLLECTION { 10 } else { 0 };
         let mut valid_chunks: Vec<CodeChunk> = chunks.into_iter()
-            .filter(|c| c.content.trim().len() > 10)
+            .filter(|c| c.content.trim().len() > min_len)
             .collect();
 
         if valid_chunks.is_empty() {
@@ -191,6 +293,7 @@ impl VectorDB {
             }
         }
 
+        let table_name = self.collection_table(collection).await;
         let conn = self.connection.lock().await;
 
         // Split into batches of 1000 to prevent memory issues and improve write performance (FIX-26)
@@ -200,12 +303,12 @@ impl VectorDB {
             let schema = batch.schema();
             let reader = RecordBatchIterator::new(vec![Ok(batch)].into_iter(), schema);
             
-            match conn.open_table(&self.table_name).execute().await {
+            match conn.open_table(&table_name).execute().await {
                 Ok(table) => {
                     table.add(Box::new(reader)).execute().await?;
                 }
                 Err(_) => {
-                    conn.create_table(&self.table_name, Box::new(reader))
+                    conn.create_table(&table_name, Box::new(reader))
                         .execute()
                         .await?;
                 }
@@ -222,7 +325,18 @@ impl VectorDB {
         top_k: usize,
         path_filter: Option<String>,
     ) -> Result<Vec<CodeChunk>, Box<dyn Error>> {
-        let table = self.get_table().await?;
+        self.query_in(CODE_COLLECTION, query_embedding, top_k, path_filter).await
+    }
+
+    /// `query` against another collection
+    pub async fn query_in(
+        &self,
+        collection: &str,
+        query_embedding: Vec<f32>,
+        top_k: usize,
+        path_filter: Option<String>,
+    ) -> Result<Vec<CodeChunk>, Box<dyn Error>> {
+        let table = self.get_table(collection).await?;
         
         // Perform vector similarity search
         let mut query = table.vector_search(query_embedding)?;
@@ -280,7 +394,12 @@ impl VectorDB {
     
     /// Delete all chunks associated with a file
     pub async fn delete_file(&self, file_path: &str) -> Result<(), Box<dyn Error>> {
-        let table = self.get_table().await?;
+        self.delete_file_in(CODE_COLLECTION, file_path).await
+    }
+
+    /// `delete_file` in another collection
+    pub async fn delete_file_in(&self, collection: &str, file_path: &str) -> Result<(), Box<dyn Error>> {
+        let table = self.get_table(collection).await?;
         
         // SQL string escape: double quotes for single quotes
         let safe_path = file_path.replace('\'', "''");
@@ -298,6 +417,22 @@ impl VectorDB {
     }
 }
 
+fn table_name_for(collection: &str, source: &EmbeddingSource) -> String {
+    match source {
+        EmbeddingSource::FastEmbed => collection.to_string(),
+        EmbeddingSource::Gguf { model_path, .. } => {
+            let stem = std::path::Path::new(model_path)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            let safe: String = stem.chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            format!("{}_gguf_{}", collection, safe)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -324,4 +459,15 @@ mod tests {
         let db = VectorDB::init(temp_dir.to_str().unwrap()).await;
         assert!(db.is_ok());
     }
+
+    #[test]
+    fn test_table_name_per_embedding_source() {
+        assert_eq!(table_name_for(CODE_COLLECTION, &EmbeddingSource::FastEmbed), "code_chunks");
+        let gguf = EmbeddingSource::Gguf {
+            model_path: "/models/nomic-embed-text-v1.5.Q8_0.gguf".to_string(),
+            options: EmbeddingOptions::default(),
+        };
+        assert_eq!(table_name_for(CODE_COLLECTION, &gguf), "code_chunks_gguf_nomic_embed_text_v1_5_q8_0");
+        assert_eq!(table_name_for(CONVERSATION_COLLECTION, &gguf), "conversations_gguf_nomic_embed_text_v1_5_q8_0");
+    }
 }
@@ -2,6 +2,13 @@ import React, { useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { motion, AnimatePresence } from 'framer-motion';
 
+interface RenderedPrompt {
+    template_id: string;
+    template_version: number;
+    system: string | null;
+    prompt: string;
+}
+
 interface GitAutopilotProps {
     projectPath: string;
     onCommitSuccess?: (message: string) => void;
@@ -19,26 +26,22 @@ const GitAutopilot: React.FC<GitAutopilotProps> = ({ projectPath, onCommitSucces
         setErrorMsg('');
 
         try {
-            // 1. Get diff from backend
-            const diff = await invoke<string>('generate_semantic_commit_message', { repoPath: projectPath });
-
-            if (!diff || diff.trim() === '') {
-                throw new Error("Staged changes not found. Please stage your changes first.");
-            }
+            // 1. Render the commit prompt from the staged diff (backend template)
+            const rendered = await invoke<RenderedPrompt>('generate_semantic_commit_message', { repoPath: projectPath });
 
-            // 2. Call AI to generate message from diff
+            // 2. Call AI to generate message from the rendered prompt
             const providerConfig = JSON.parse(localStorage.getItem('ai_provider_config') || '{}');
-            const prompt = `Generate a concise, semantic commit message (Conventional Commits style) for the following diff:\n\n${diff.slice(0, 5000)}`;
 
-            const message = await invoke<string>("chat_with_dynamic_ai", {
-                message: prompt,
+            const reply = await invoke<{ text: string }>("chat_with_dynamic_ai", {
+                message: rendered.prompt,
                 conversationHistory: [
-                    { role: "system", content: "You are a senior developer who writes perfect commit messages." }
+                    ...(rendered.system ? [{ role: "system", content: rendered.system }] : []),
+                    { role: "user", content: rendered.prompt }
                 ],
                 provider_config: providerConfig
             });
 
-            setSuggestion(message.trim());
+            setSuggestion(reply.text.trim());
             setStatus('success');
         } catch (err: any) {
             console.error("Git Autopilot Error:", err);
@@ -69,13 +69,13 @@ export const agentSwarm = {
                 { role: "user", content: message }
             ];
 
-            const response = await invoke<string>("chat_with_dynamic_ai", {
+            const response = await invoke<{ text: string }>("chat_with_dynamic_ai", {
                 message,
                 conversationHistory,
                 providerConfig
             });
 
-            return response;
+            return response.text;
         } catch (error) {
             console.error("❌ Agent call failed:", error);
             throw error;
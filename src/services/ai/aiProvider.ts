@@ -601,7 +601,7 @@ export async function callAI(
     // Max tokens'ı artır (daha uzun cevaplar için)
     const adjustedMaxTokens = model.maxTokens ? Math.max(model.maxTokens, 8192) : 8192;
 
-    const aiPromise = invoke<string>("chat_with_dynamic_ai", {
+    const aiPromise = invoke<{ text: string }>("chat_with_dynamic_ai", {
       message: cleanMessage,
       conversationHistory: messages, // 🔥 Güncellenmiş history kullan
       providerConfig: {
@@ -613,7 +613,7 @@ export async function callAI(
         temperature: adjustedTemperature,
         max_tokens: adjustedMaxTokens
       }
-    });
+    }).then(reply => reply.text);
 
     return await Promise.race([aiPromise, timeoutPromise]);
   } finally {
@@ -132,7 +132,8 @@ class DownloadManager {
     try {
       await invoke('download_gguf_model', {
         url,
-        destination
+        destination,
+        downloadId: taskId
       });
 
       // İndirme tamamlandı
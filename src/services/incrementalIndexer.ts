@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import { createEmbedding, shouldIndexFile, getEmbeddingEndpoint } from "./embedding";
+import { createEmbedding, shouldIndexFile } from "./embedding";
 import { cacheManager, generateFileCacheKey } from "./cache";
 import { FileIndex } from "../types/index";
 import { gitIntelligence } from "./gitIntelligence";
@@ -172,8 +172,8 @@ export class IncrementalIndexer {
 
       // 🆕 Vector DB'ye indexle
       try {
-        const endpoint = getEmbeddingEndpoint();
-        await invoke("index_file_vector", { filePath, endpoint });
+        // Embedding, backend'de seçili modelle üretilir (set_vector_embedding_model)
+        await invoke("index_file_vector", { filePath });
         console.log(`✅ Vector DB'ye eklendi: ${filePath}`);
       } catch (error) {
         console.warn(`⚠️ Vector DB indexleme hatası (${filePath}):`, error);